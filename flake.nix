@@ -37,6 +37,7 @@
             cp index.html dist/
             cp logotex.png dist/
             cp -r pkg dist/
+            if [ -d swiftlatex ]; then cp -r swiftlatex dist/; fi
           '';
 
           installPhase = ''
@@ -0,0 +1,39 @@
+// Proxy that holds the vendor API keys for the browser app
+//
+// Usage: cargo run --bin llm-proxy CONFIG.json
+// Serve it on the app's origin (e.g. behind the same reverse proxy) and set
+// "LLM Proxy URL" in the profile panel to its path. See src/proxy.rs for
+// the config format.
+
+use wasm_pdf_generator::proxy::ProxyConfig;
+use wasm_pdf_generator::proxy_server::ProxyServer;
+
+fn main() {
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("Usage: llm-proxy CONFIG.json");
+        std::process::exit(2);
+    };
+
+    let config = std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|json| ProxyConfig::from_json(&json));
+    let config = match config {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Invalid proxy config {}: {}", path, e);
+            std::process::exit(1);
+        }
+    };
+
+    let listen = config.listen.clone();
+    let server = match ProxyServer::start(config) {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("Failed to start LLM proxy on {}: {}", listen, e);
+            std::process::exit(1);
+        }
+    };
+
+    println!("LLM proxy listening on {}", server.url());
+    server.join();
+}
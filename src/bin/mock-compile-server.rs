@@ -0,0 +1,21 @@
+// Local stand-in for a LaTeX compile service
+//
+// Usage: cargo run --bin mock-compile-server [ADDR]
+// Then set "Compile Service URL" in the profile panel to the printed URL.
+
+use wasm_pdf_generator::mock_compile_server::MockCompileServer;
+
+fn main() {
+    let addr = std::env::args().nth(1).unwrap_or_else(|| "127.0.0.1:8787".to_string());
+
+    let server = match MockCompileServer::start(&addr) {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("Failed to start mock compile server on {}: {}", addr, e);
+            std::process::exit(1);
+        }
+    };
+
+    println!("Mock compile server listening on {}", server.url());
+    server.join();
+}
@@ -0,0 +1,24 @@
+// Local stand-in for an OpenAI-compatible model server
+//
+// Usage: cargo run --bin mock-llm-server [ADDR] [API_KEY]
+// Then pick "Custom endpoint" in the app and set its base URL to the printed
+// URL, with any model name.
+
+use wasm_pdf_generator::mock_llm_server::MockLlmServer;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let addr = args.next().unwrap_or_else(|| "127.0.0.1:8788".to_string());
+    let api_key = args.next();
+
+    let server = match MockLlmServer::start_with_key(&addr, api_key.as_deref()) {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("Failed to start mock LLM server on {}: {}", addr, e);
+            std::process::exit(1);
+        }
+    };
+
+    println!("Mock LLM server listening on {}", server.base_url());
+    server.join();
+}
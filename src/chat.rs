@@ -0,0 +1,222 @@
+// Conversation turns sent to the AI providers
+//
+// `GeneratedContent::chat_history` keeps the conversation as (role, text)
+// pairs. Besides the user's requests and the model's documents it records the
+// auto-fix rounds: the compile errors sent back to the model and the diff of
+// the revision it answered with, and the diffs of section rewrites.
+//
+// Follow-up requests replay that history as alternating user/assistant
+// messages. Earlier documents are replaced by a short placeholder and only the
+// current document is sent in full, with the new request. In edit mode the
+// request asks for search/replace edits (see `patch`) instead of a new copy
+// of the document.
+
+use serde::{Deserialize, Serialize};
+
+use crate::compiler::CompileError;
+use crate::length::LengthTarget;
+use crate::lint::LintIssue;
+use crate::texlog::{Diagnostic, Severity};
+
+// Roles used in `GeneratedContent::chat_history`
+pub const USER: &str = "user";
+pub const AI: &str = "ai";
+pub const COMPILE_ERRORS: &str = "compile-errors";
+pub const FIX_DIFF: &str = "fix-diff";
+pub const SECTION_DIFF: &str = "section-diff";
+
+// How many errors to send back per auto-fix round, or lint issues per
+// request
+const MAX_REPORTED_ERRORS: usize = 10;
+// How much of the raw log to send when no errors could be parsed from it
+const LOG_TAIL_LINES: usize = 30;
+
+// System instructions sent with every request. Whether to reply with the
+// whole document, edits or one section is left to each mode's prompt.
+pub const SYSTEM_PROMPT: &str = "You are an expert LaTeX author. You write documents that compile with pdflatex and revise them on request. Reply in the form each request asks for.";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ChatMessage {
+    pub role: Role,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn user(content: impl Into<String>) -> Self {
+        ChatMessage { role: Role::User, content: content.into() }
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        ChatMessage { role: Role::Assistant, content: content.into() }
+    }
+}
+
+// Document class and packages of a template option
+pub fn template_setup(template: &str) -> (&'static str, &'static str) {
+    match template {
+        "IEEEtran" => ("IEEEtran", r#"\usepackage[utf8]{inputenc}\usepackage{amsmath}\usepackage{graphicx}\usepackage{hyperref}\usepackage{cite}\usepackage{amsfonts}\usepackage{amssymb}\usepackage{url}"#),
+        "Report" => ("report", r#"\usepackage[utf8]{inputenc}\usepackage{amsmath}\usepackage{graphicx}\usepackage{hyperref}\usepackage{titlesec}"#),
+        "Book" => ("book", r#"\usepackage[utf8]{inputenc}\usepackage{amsmath}\usepackage{graphicx}\usepackage{hyperref}\usepackage{fancyhdr}"#),
+        "Letter" => ("letter", r#"\usepackage[utf8]{inputenc}\usepackage{hyperref}\usepackage{geometry}"#),
+        _ => ("article", r#"\usepackage[utf8]{inputenc}\usepackage{amsmath}\usepackage{graphicx}\usepackage{hyperref}"#)
+    }
+}
+
+// Prompt asking for a new document about `topic` of the `length` picked in
+// the size option
+pub fn generation_prompt(topic: &str, template: &str, length: &LengthTarget) -> String {
+    let (doc_class, additional_packages) = template_setup(template);
+    
+    format!(
+        "Generate a comprehensive LaTeX document about '{}' using the '{}' document class. Include appropriate sections, equations, and references. Format it as a complete LaTeX document, from \\documentclass to \\end{{document}}, that can be compiled directly. Use these packages:\n\n{}\n\nMake sure to include:\n\n1. A title section\n2. {} content sections\n3. At least one equation\n4. Proper document structure with begin/end document\n5. All necessary template-specific elements for {}\n\n{}",
+        topic, doc_class, additional_packages, length.sections, doc_class, length.instructions()
+    )
+}
+
+// Follow-up message carrying the current document and the change to make
+pub fn revision_prompt(latex: &str, request: &str) -> String {
+    format!(
+        "This is the current LaTeX document:\n\n```latex\n{}\n```\n\nRevise it as follows: {}\n\nReply with the complete revised LaTeX document. Keep everything the request does not ask to change.",
+        latex.trim_end(),
+        request
+    )
+}
+
+// Follow-up message asking for the change as search/replace edits to the
+// current document rather than a new copy of it
+pub fn edit_prompt(latex: &str, request: &str) -> String {
+    format!(
+        "This is the current LaTeX document:\n\n```latex\n{}\n```\n\nChange it as follows: {}\n\nDo not repeat the whole document. Reply only with search/replace blocks, one per change, in this format:\n\n<<<<<<< SEARCH\nlines copied exactly from the current document\n=======\nthe lines that replace them\n>>>>>>> REPLACE\n\nEach SEARCH part must match the current document exactly, including whitespace, and occur only once in it; include enough surrounding lines to make it unique.",
+        latex.trim_end(),
+        request
+    )
+}
+
+// Messages for a follow-up request: the earlier turns of `history`, then
+// `prompt` carrying the current document and the new request
+pub fn conversation(history: &[(String, String)], template: &str, length: &LengthTarget, prompt: String) -> Vec<ChatMessage> {
+    let mut messages = Vec::new();
+    let mut revision = 0;
+
+    for (index, (role, text)) in history.iter().enumerate() {
+        match role.as_str() {
+            // The first request is the topic of the document
+            USER if index == 0 => messages.push(ChatMessage::user(generation_prompt(text, template, length))),
+            USER => messages.push(ChatMessage::user(text.clone())),
+            COMPILE_ERRORS => messages.push(ChatMessage::user(fix_prompt(text))),
+            AI => {
+                revision += 1;
+                messages.push(ChatMessage::assistant(format!(
+                    "[Revision {} of the LaTeX document, superseded by the current document in the next message]",
+                    revision
+                )));
+            },
+            // Diffs are only shown to the user
+            _ => {}
+        }
+    }
+
+    messages.push(ChatMessage::user(prompt));
+    messages
+}
+
+// Merge consecutive messages from the same side and drop leading assistant
+// messages, since the providers expect turns to alternate starting with the
+// user
+pub fn alternating(messages: &[ChatMessage]) -> Vec<ChatMessage> {
+    let mut merged: Vec<ChatMessage> = Vec::new();
+    for message in messages {
+        match merged.last_mut() {
+            Some(last) if last.role == message.role => {
+                last.content.push_str("\n\n");
+                last.content.push_str(&message.content);
+            },
+            None if message.role == Role::Assistant => {},
+            _ => merged.push(message.clone()),
+        }
+    }
+    merged
+}
+
+// The compile errors of a failed build as plain text, one per line with its
+// source context; falls back to the end of the raw log
+pub fn error_report(err: &CompileError, diagnostics: &[Diagnostic]) -> String {
+    let errors: Vec<&Diagnostic> = diagnostics.iter()
+        .filter(|d| d.severity == Severity::Error)
+        .take(MAX_REPORTED_ERRORS)
+        .collect();
+
+    if errors.is_empty() {
+        let lines: Vec<&str> = err.log.lines().collect();
+        let tail = lines[lines.len().saturating_sub(LOG_TAIL_LINES)..].join("\n");
+        return if tail.trim().is_empty() {
+            err.message.clone()
+        } else {
+            format!("{}\n\n{}", err.message, tail)
+        };
+    }
+
+    let mut report = String::new();
+    for error in errors {
+        if let Some(location) = error.location() {
+            report.push_str(&location);
+            report.push_str(": ");
+        }
+        report.push_str(&error.message);
+        report.push('\n');
+        if let Some(context) = &error.context {
+            for line in context.lines() {
+                report.push_str("    ");
+                report.push_str(line);
+                report.push('\n');
+            }
+        }
+    }
+    report
+}
+
+// Message asking for one section to be rewritten, given the summary of the
+// rest of the document from `rewrite::document_summary`
+pub fn section_prompt(summary: &str, section: &str, request: &str) -> String {
+    format!(
+        "You are revising one section of a longer LaTeX document. This is an outline of the whole document:\n\n{}\nThis is the section to rewrite:\n\n```latex\n{}\n```\n\nRewrite it as follows: {}\n\nReply only with the rewritten section in a single ```latex block, starting with its \\section-level heading. Do not include a preamble, \\begin{{document}} or any other section. Keep the heading command, and keep labels that other parts of the document may refer to.",
+        summary,
+        section.trim_end(),
+        request
+    )
+}
+
+// How a section rewrite is recorded in the chat history
+pub fn section_request(title: &str, request: &str) -> String {
+    format!("Rewrite only the section \"{}\": {}", title, request)
+}
+
+// Lint issues to send with a follow-up request, or `None` if there are none
+pub fn lint_report(issues: &[LintIssue]) -> Option<String> {
+    if issues.is_empty() {
+        return None;
+    }
+    let mut report = String::from("A static check of the current document found these problems; fix them too:\n");
+    for issue in issues.iter().take(MAX_REPORTED_ERRORS) {
+        report.push_str(&format!("- line {}:{}: {}\n", issue.line, issue.column, issue.message));
+    }
+    if issues.len() > MAX_REPORTED_ERRORS {
+        report.push_str(&format!("- and {} more\n", issues.len() - MAX_REPORTED_ERRORS));
+    }
+    Some(report)
+}
+
+// Follow-up message asking the model to repair its last document
+pub fn fix_prompt(error_report: &str) -> String {
+    format!(
+        "The LaTeX document you wrote fails to compile with pdflatex. The compiler reported:\n\n{}\nFix these errors and reply with the complete corrected LaTeX document, from \\documentclass to \\end{{document}}. Keep everything that is not related to the errors unchanged.",
+        error_report
+    )
+}
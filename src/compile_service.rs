@@ -0,0 +1,190 @@
+// Compile service protocol
+//
+// The app talks to a LaTeX compile server with a single JSON request:
+//
+//     POST <compile service URL>
+//     Content-Type: application/json
+//
+//     {
+//       "version": 1,
+//       "engine": "pdflatex",
+//       "main": "main.tex",
+//       "files": [
+//         { "path": "main.tex", "content": "\\documentclass{article}...", "encoding": "utf8" },
+//         { "path": "figure.png", "content": "iVBORw0KGgo...", "encoding": "base64" }
+//       ]
+//     }
+//
+// `main` names the file to run the engine on and must be one of `files`.
+// Text files may be sent as "utf8", binary assets are sent as "base64".
+//
+// A successful compile answers 200 with either the raw PDF
+// (`Content-Type: application/pdf`) or a JSON body carrying the PDF and the
+// TeX log:
+//
+//     { "status": "success", "pdf": "<base64 PDF>", "log": "This is pdfTeX..." }
+//
+// A failed compile answers with a 4xx/5xx status (422 when the document
+// itself is broken, 400 when the request is malformed) and a JSON error:
+//
+//     {
+//       "status": "error",
+//       "message": "LaTeX Error: Environment foo undefined.",
+//       "log": "This is pdfTeX...",
+//       "errors": [ { "file": "main.tex", "line": 12, "message": "LaTeX Error: ..." } ]
+//     }
+//
+// `log` and `errors` are optional. Servers must answer CORS preflight
+// requests, since the app calls them straight from the browser.
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use crate::compiler::{Asset, CompileError, CompileOutput};
+
+pub const PROTOCOL_VERSION: u32 = 1;
+pub const MAIN_FILE: &str = "main.tex";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CompileRequest {
+    pub version: u32,
+    pub engine: String,
+    pub main: String,
+    pub files: Vec<SourceFile>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SourceFile {
+    pub path: String,
+    pub content: String,
+    #[serde(default)]
+    pub encoding: Encoding,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Base64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum CompileResponse {
+    Success {
+        pdf: String,
+        #[serde(default)]
+        log: String,
+    },
+    Error {
+        message: String,
+        #[serde(default)]
+        log: String,
+        #[serde(default)]
+        errors: Vec<ServiceError>,
+    },
+}
+
+// An error location reported by the server
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ServiceError {
+    #[serde(default)]
+    pub file: Option<String>,
+    #[serde(default)]
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl CompileRequest {
+    // Request compiling `latex` as main.tex together with its assets
+    pub fn new(latex: &str, assets: &[Asset]) -> Self {
+        let mut files = vec![SourceFile {
+            path: MAIN_FILE.to_string(),
+            content: latex.to_string(),
+            encoding: Encoding::Utf8,
+        }];
+
+        for asset in assets {
+            files.push(SourceFile {
+                path: asset.path.clone(),
+                content: BASE64.encode(&asset.data),
+                encoding: Encoding::Base64,
+            });
+        }
+
+        CompileRequest {
+            version: PROTOCOL_VERSION,
+            engine: "pdflatex".to_string(),
+            main: MAIN_FILE.to_string(),
+            files,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl SourceFile {
+    pub fn bytes(&self) -> Result<Vec<u8>, String> {
+        match self.encoding {
+            Encoding::Utf8 => Ok(self.content.as_bytes().to_vec()),
+            Encoding::Base64 => BASE64.decode(&self.content)
+                .map_err(|e| format!("{} is not valid base64: {}", self.path, e)),
+        }
+    }
+}
+
+impl CompileResponse {
+    pub fn success(pdf: &[u8], log: &str) -> Self {
+        CompileResponse::Success {
+            pdf: BASE64.encode(pdf),
+            log: log.to_string(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+// Turn a compile service HTTP response into a compile result
+pub fn decode_response(status: u16, content_type: &str, body: &[u8]) -> Result<CompileOutput, CompileError> {
+    let is_success = (200..300).contains(&status);
+
+    if is_success && content_type.starts_with("application/pdf") {
+        return Ok(CompileOutput {
+            pdf: body.to_vec(),
+            log: String::new(),
+        });
+    }
+
+    match serde_json::from_slice::<CompileResponse>(body) {
+        Ok(CompileResponse::Success { pdf, log }) if is_success => {
+            let pdf = BASE64.decode(pdf.as_bytes())
+                .map_err(|e| CompileError::new(format!("Compile service sent an invalid PDF: {}", e)))?;
+            Ok(CompileOutput { pdf, log })
+        },
+        Ok(CompileResponse::Success { .. }) => {
+            Err(CompileError::new(format!("Compile service returned status {} with a success body", status)))
+        },
+        Ok(CompileResponse::Error { message, log, errors }) => {
+            let mut message = message;
+            if let Some(first) = errors.first() {
+                if !message.contains(&first.message) {
+                    message = format!("{}: {}", message, first.message);
+                }
+            }
+            Err(CompileError { message, log })
+        },
+        Err(_) => {
+            let text = String::from_utf8_lossy(body).into_owned();
+            Err(CompileError {
+                message: format!("Compile service returned status {} with an unexpected response", status),
+                log: text,
+            })
+        }
+    }
+}
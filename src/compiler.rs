@@ -0,0 +1,254 @@
+// LaTeX compiler backends
+//
+// Every backend turns a LaTeX source into PDF bytes plus the TeX log. The
+// in-browser backend drives a SwiftLaTeX pdfTeX build loaded next to the app,
+// the service backend posts the source to a compile server using the protocol
+// described in compile_service.rs.
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use js_sys::{Promise, Reflect, Uint8Array};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Headers, RequestInit, Response};
+
+use crate::compile_service::{self, CompileRequest};
+
+pub type CompileFuture<'a> = Pin<Box<dyn Future<Output = Result<CompileOutput, CompileError>> + 'a>>;
+
+// A file the document needs next to main.tex (figures, .bib, .sty, ...)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Asset {
+    pub path: String,
+    // Saved as base64 rather than an array of numbers
+    #[serde(serialize_with = "to_base64", deserialize_with = "from_base64")]
+    pub data: Vec<u8>,
+}
+
+fn to_base64<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&BASE64.encode(data))
+}
+
+fn from_base64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    BASE64.decode(text).map_err(serde::de::Error::custom)
+}
+
+// Successful compilation result
+pub struct CompileOutput {
+    pub pdf: Vec<u8>,
+    pub log: String,
+}
+
+// Failed compilation, with whatever log the engine produced
+#[derive(Debug)]
+pub struct CompileError {
+    pub message: String,
+    pub log: String,
+}
+
+impl CompileError {
+    pub fn new(message: impl Into<String>) -> Self {
+        CompileError { message: message.into(), log: String::new() }
+    }
+
+    fn from_js(context: &str, err: JsValue) -> Self {
+        let detail = err.as_string()
+            .or_else(|| Reflect::get(&err, &JsValue::from_str("message")).ok().and_then(|m| m.as_string()))
+            .unwrap_or_else(|| format!("{:?}", err));
+        CompileError::new(format!("{}: {}", context, detail))
+    }
+}
+
+// A backend that can turn a LaTeX document into a PDF
+pub trait Compiler {
+    fn name(&self) -> &'static str;
+    fn compile<'a>(&'a self, latex: &'a str, assets: &'a [Asset]) -> CompileFuture<'a>;
+}
+
+// Backends selectable in the profile panel
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CompilerBackend {
+    Browser,
+    Service,
+}
+
+impl CompilerBackend {
+    pub const ALL: [CompilerBackend; 2] = [CompilerBackend::Browser, CompilerBackend::Service];
+
+    pub fn id(self) -> &'static str {
+        match self {
+            CompilerBackend::Browser => "browser",
+            CompilerBackend::Service => "service",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            CompilerBackend::Browser => "In-browser (SwiftLaTeX)",
+            CompilerBackend::Service => "Compile service",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<CompilerBackend> {
+        CompilerBackend::ALL.into_iter().find(|backend| backend.id() == id)
+    }
+}
+
+// Build the compiler configured in localStorage
+pub fn compiler_from_settings() -> Box<dyn Compiler> {
+    let storage = web_sys::window().unwrap().local_storage().ok().flatten();
+    let get = |key: &str| storage.as_ref().and_then(|s| s.get_item(key).ok().flatten());
+
+    let backend = get("compiler_backend")
+        .and_then(|id| CompilerBackend::from_id(&id))
+        .unwrap_or(CompilerBackend::Browser);
+
+    match backend {
+        CompilerBackend::Browser => Box::new(SwiftLatexCompiler),
+        CompilerBackend::Service => Box::new(HttpCompiler {
+            url: get("compile_service_url").unwrap_or_default(),
+        }),
+    }
+}
+
+#[wasm_bindgen]
+extern "C" {
+    // SwiftLaTeX's PdfTeXEngine, loaded from swiftlatex/PdfTeXEngine.js
+    #[derive(Clone)]
+    type PdfTeXEngine;
+
+    #[wasm_bindgen(constructor, catch)]
+    fn new() -> Result<PdfTeXEngine, JsValue>;
+
+    #[wasm_bindgen(method, catch, js_name = loadEngine)]
+    fn load_engine(this: &PdfTeXEngine) -> Result<Promise, JsValue>;
+
+    #[wasm_bindgen(method, catch, js_name = writeMemFSFile)]
+    fn write_mem_fs_file(this: &PdfTeXEngine, name: &str, content: &JsValue) -> Result<(), JsValue>;
+
+    #[wasm_bindgen(method, catch, js_name = setEngineMainFile)]
+    fn set_engine_main_file(this: &PdfTeXEngine, name: &str) -> Result<(), JsValue>;
+
+    #[wasm_bindgen(method, catch, js_name = compileLaTeX)]
+    fn compile_latex(this: &PdfTeXEngine) -> Result<Promise, JsValue>;
+}
+
+thread_local! {
+    // Loading the engine fetches the whole TeX format, so keep it around
+    static ENGINE: RefCell<Option<PdfTeXEngine>> = const { RefCell::new(None) };
+}
+
+// pdfTeX compiled to WebAssembly, running in a worker in this page
+pub struct SwiftLatexCompiler;
+
+impl SwiftLatexCompiler {
+    async fn engine() -> Result<PdfTeXEngine, CompileError> {
+        if let Some(engine) = ENGINE.with(|e| e.borrow().clone()) {
+            return Ok(engine);
+        }
+
+        let engine = PdfTeXEngine::new().map_err(|_| {
+            CompileError::new("In-browser TeX engine is not available. Put SwiftLaTeX's PdfTeXEngine.js in swiftlatex/ or switch to a compile service in the profile settings.")
+        })?;
+        let loading = engine.load_engine().map_err(|e| CompileError::from_js("Failed to start TeX engine", e))?;
+        JsFuture::from(loading).await.map_err(|e| CompileError::from_js("Failed to load TeX engine", e))?;
+
+        ENGINE.with(|e| *e.borrow_mut() = Some(engine.clone()));
+        Ok(engine)
+    }
+}
+
+impl Compiler for SwiftLatexCompiler {
+    fn name(&self) -> &'static str {
+        "SwiftLaTeX"
+    }
+
+    fn compile<'a>(&'a self, latex: &'a str, assets: &'a [Asset]) -> CompileFuture<'a> {
+        Box::pin(async move {
+            let engine = SwiftLatexCompiler::engine().await?;
+
+            for asset in assets {
+                engine.write_mem_fs_file(&asset.path, &Uint8Array::from(&asset.data[..]))
+                    .map_err(|e| CompileError::from_js(&format!("Failed to write {}", asset.path), e))?;
+            }
+            engine.write_mem_fs_file(compile_service::MAIN_FILE, &JsValue::from_str(latex))
+                .map_err(|e| CompileError::from_js("Failed to write main.tex", e))?;
+            engine.set_engine_main_file(compile_service::MAIN_FILE)
+                .map_err(|e| CompileError::from_js("Failed to set main file", e))?;
+
+            let compiling = engine.compile_latex().map_err(|e| CompileError::from_js("Failed to start compilation", e))?;
+            let result = JsFuture::from(compiling).await
+                .map_err(|e| CompileError::from_js("Compilation crashed", e))?;
+
+            let status = Reflect::get(&result, &JsValue::from_str("status"))
+                .ok()
+                .and_then(|s| s.as_f64())
+                .unwrap_or(-1.0);
+            let log = Reflect::get(&result, &JsValue::from_str("log"))
+                .ok()
+                .and_then(|l| l.as_string())
+                .unwrap_or_default();
+            let pdf = Reflect::get(&result, &JsValue::from_str("pdf")).unwrap_or(JsValue::UNDEFINED);
+
+            if status != 0.0 || pdf.is_undefined() || pdf.is_null() {
+                return Err(CompileError {
+                    message: format!("pdfTeX exited with status {}", status),
+                    log,
+                });
+            }
+
+            Ok(CompileOutput {
+                pdf: Uint8Array::new(&pdf).to_vec(),
+                log,
+            })
+        })
+    }
+}
+
+// Remote compile server speaking the compile service protocol
+pub struct HttpCompiler {
+    pub url: String,
+}
+
+impl Compiler for HttpCompiler {
+    fn name(&self) -> &'static str {
+        "Compile service"
+    }
+
+    fn compile<'a>(&'a self, latex: &'a str, assets: &'a [Asset]) -> CompileFuture<'a> {
+        Box::pin(async move {
+            if self.url.is_empty() {
+                return Err(CompileError::new("No compile service URL configured. Set one in the profile settings."));
+            }
+
+            let window = web_sys::window().unwrap();
+
+            let headers = Headers::new().unwrap();
+            headers.append("content-type", "application/json").unwrap();
+            headers.append("accept", "application/json, application/pdf").unwrap();
+
+            let request_init = RequestInit::new();
+            request_init.set_method("POST");
+            request_init.set_headers(&headers);
+            request_init.set_body(&JsValue::from_str(&CompileRequest::new(latex, assets).to_json()));
+
+            let request = web_sys::Request::new_with_str_and_init(&self.url, &request_init)
+                .map_err(|e| CompileError::from_js("Invalid compile service URL", e))?;
+            let response = JsFuture::from(window.fetch_with_request(&request)).await
+                .map_err(|e| CompileError::from_js("Compile service unreachable", e))?;
+            let response = response.dyn_into::<Response>().unwrap();
+
+            let content_type = response.headers().get("content-type").ok().flatten().unwrap_or_default();
+            let buffer = JsFuture::from(response.array_buffer().unwrap()).await
+                .map_err(|e| CompileError::from_js("Failed to read compile service response", e))?;
+
+            compile_service::decode_response(response.status(), &content_type, &Uint8Array::new(&buffer).to_vec())
+        })
+    }
+}
@@ -0,0 +1,196 @@
+// Line-based diffs between LaTeX revisions
+
+// Above this many cells the LCS table gets too big for the browser, and the
+// changed region is reported as a single replacement instead
+const MAX_TABLE_CELLS: usize = 4_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffOp<'a> {
+    Equal(&'a str),
+    Delete(&'a str),
+    Insert(&'a str),
+}
+
+// Diff two texts line by line
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<DiffOp<'a>> {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+
+    // Most edits touch a small region, so strip the common ends first
+    let prefix = old_lines.iter().zip(&new_lines).take_while(|(a, b)| a == b).count();
+    let suffix = old_lines[prefix..].iter().rev()
+        .zip(new_lines[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let old_middle = &old_lines[prefix..old_lines.len() - suffix];
+    let new_middle = &new_lines[prefix..new_lines.len() - suffix];
+
+    let mut ops: Vec<DiffOp> = old_lines[..prefix].iter().map(|l| DiffOp::Equal(l)).collect();
+    ops.extend(diff_middle(old_middle, new_middle));
+    ops.extend(old_lines[old_lines.len() - suffix..].iter().map(|l| DiffOp::Equal(l)));
+    ops
+}
+
+fn diff_middle<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<DiffOp<'a>> {
+    if old.len().saturating_mul(new.len()) > MAX_TABLE_CELLS {
+        let mut ops: Vec<DiffOp> = old.iter().map(|l| DiffOp::Delete(l)).collect();
+        ops.extend(new.iter().map(|l| DiffOp::Insert(l)));
+        return ops;
+    }
+
+    // lcs[i][j] is the LCS length of old[i..] and new[j..]
+    let width = new.len() + 1;
+    let mut lcs = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i * width + j] = if old[i] == new[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::with_capacity(old.len() + new.len());
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            ops.push(DiffOp::Equal(old[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[(i + 1) * width + j] >= lcs[i * width + j + 1] {
+            ops.push(DiffOp::Delete(old[i]));
+            i += 1;
+        } else {
+            ops.push(DiffOp::Insert(new[j]));
+            j += 1;
+        }
+    }
+    ops.extend(old[i..].iter().map(|l| DiffOp::Delete(l)));
+    ops.extend(new[j..].iter().map(|l| DiffOp::Insert(l)));
+    ops
+}
+
+// Number of inserted and deleted lines
+pub fn change_counts(ops: &[DiffOp]) -> (usize, usize) {
+    let inserted = ops.iter().filter(|op| matches!(op, DiffOp::Insert(_))).count();
+    let deleted = ops.iter().filter(|op| matches!(op, DiffOp::Delete(_))).count();
+    (inserted, deleted)
+}
+
+// A row of a side-by-side diff: a line of the old text and of the new one,
+// with their 1-based line numbers. Changed lines are paired up in order; a
+// side with nothing to pair is `None`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SideBySideRow<'a> {
+    pub old: Option<(usize, &'a str)>,
+    pub new: Option<(usize, &'a str)>,
+    pub changed: bool,
+}
+
+// Lay `ops` out as side-by-side rows
+pub fn side_by_side<'a>(ops: &[DiffOp<'a>]) -> Vec<SideBySideRow<'a>> {
+    let mut rows = Vec::with_capacity(ops.len());
+    let (mut old_line, mut new_line) = (0, 0);
+    let mut deleted: Vec<(usize, &str)> = Vec::new();
+    let mut inserted: Vec<(usize, &str)> = Vec::new();
+
+    let flush = |rows: &mut Vec<SideBySideRow<'a>>, deleted: &mut Vec<(usize, &'a str)>, inserted: &mut Vec<(usize, &'a str)>| {
+        for i in 0..deleted.len().max(inserted.len()) {
+            rows.push(SideBySideRow { old: deleted.get(i).copied(), new: inserted.get(i).copied(), changed: true });
+        }
+        deleted.clear();
+        inserted.clear();
+    };
+
+    for op in ops {
+        match *op {
+            DiffOp::Equal(line) => {
+                flush(&mut rows, &mut deleted, &mut inserted);
+                old_line += 1;
+                new_line += 1;
+                rows.push(SideBySideRow { old: Some((old_line, line)), new: Some((new_line, line)), changed: false });
+            },
+            DiffOp::Delete(line) => {
+                old_line += 1;
+                deleted.push((old_line, line));
+            },
+            DiffOp::Insert(line) => {
+                new_line += 1;
+                inserted.push((new_line, line));
+            },
+        }
+    }
+    flush(&mut rows, &mut deleted, &mut inserted);
+    rows
+}
+
+// Unified diff with `context` lines around each change; empty if the texts
+// have the same lines
+pub fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str, context: usize) -> String {
+    let ops = diff_lines(old, new);
+    if ops.iter().all(|op| matches!(op, DiffOp::Equal(_))) {
+        return String::new();
+    }
+
+    // Line numbers (0-based) of each op in the old and new text
+    let mut positions = Vec::with_capacity(ops.len());
+    let (mut old_line, mut new_line) = (0, 0);
+    for op in &ops {
+        positions.push((old_line, new_line));
+        match op {
+            DiffOp::Equal(_) => { old_line += 1; new_line += 1; },
+            DiffOp::Delete(_) => old_line += 1,
+            DiffOp::Insert(_) => new_line += 1,
+        }
+    }
+
+    // Group changes that are within 2 * context lines of each other
+    let changed: Vec<usize> = ops.iter().enumerate()
+        .filter(|(_, op)| !matches!(op, DiffOp::Equal(_)))
+        .map(|(i, _)| i)
+        .collect();
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &index in &changed {
+        let start = index.saturating_sub(context);
+        let end = (index + context + 1).min(ops.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", old_name, new_name);
+    for (start, end) in hunks {
+        let slice = &ops[start..end];
+        let old_count = slice.iter().filter(|op| !matches!(op, DiffOp::Insert(_))).count();
+        let new_count = slice.iter().filter(|op| !matches!(op, DiffOp::Delete(_))).count();
+        let (old_start, new_start) = positions[start];
+
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(old_start, old_count),
+            hunk_range(new_start, new_count)
+        ));
+        for op in slice {
+            match op {
+                DiffOp::Equal(line) => { out.push(' '); out.push_str(line); },
+                DiffOp::Delete(line) => { out.push('-'); out.push_str(line); },
+                DiffOp::Insert(line) => { out.push('+'); out.push_str(line); },
+            }
+            out.push('\n');
+        }
+    }
+    out
+}
+
+// "start,count" in unified diff notation (1-based; an empty range names the
+// line before it)
+fn hunk_range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, count),
+    }
+}
@@ -0,0 +1,368 @@
+// Document structure of a LaTeX source
+//
+// `parse` walks the tokens from `lexer` and records what the rest of the app
+// needs to know about a document without compiling it: the class and its
+// options, loaded packages, where the preamble and body are, the sectioning
+// commands with the range of text each one covers, environments with their
+// nesting, captions, and the labels, references and citations. Unknown
+// commands are skipped, and unbalanced input produces a partial tree rather
+// than an error.
+
+use std::ops::Range;
+
+use crate::lexer::{self, Position, Token, TokenKind};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DocumentTree {
+    pub class: Option<DocumentClass>,
+    pub packages: Vec<Package>,
+    pub title: Option<String>,
+    // From the start of the source to \begin{document}, or all of it if
+    // there is no document environment
+    pub preamble: Range<usize>,
+    // Between \begin{document} and \end{document}
+    pub body: Option<Range<usize>>,
+    pub sections: Vec<Section>,
+    pub environments: Vec<Environment>,
+    // \end commands with no open environment of that name
+    pub unmatched_ends: Vec<EnvironmentEnd>,
+    pub labels: Vec<Label>,
+    pub captions: Vec<Caption>,
+    pub references: Vec<Reference>,
+    pub citations: Vec<Citation>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DocumentClass {
+    pub name: String,
+    pub options: Vec<String>,
+    pub position: Position,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Package {
+    pub name: String,
+    pub options: Vec<String>,
+    pub position: Position,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Section {
+    // \part is 0, \chapter 1, \section 2, down to \subparagraph at 6
+    pub level: u8,
+    pub command: String,
+    pub starred: bool,
+    // The title as written, markup included
+    pub title: String,
+    pub position: Position,
+    // From the command up to the next section at the same or a higher level,
+    // or the end of the body
+    pub range: Range<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Environment {
+    pub name: String,
+    pub begin: Position,
+    // Where its \end is; `None` if it is never closed
+    pub end: Option<Position>,
+    // From \begin to the end of \end{...}, or of the source if unclosed
+    pub range: Range<usize>,
+    // Index of the enclosing environment in `DocumentTree::environments`
+    pub parent: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EnvironmentEnd {
+    pub name: String,
+    pub position: Position,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Label {
+    pub name: String,
+    pub position: Position,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Caption {
+    // The caption as written, without the short form for lists of figures
+    pub text: String,
+    pub position: Position,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reference {
+    // \ref, \eqref, \cref, ...
+    pub command: String,
+    pub name: String,
+    pub position: Position,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Citation {
+    // \cite, \citep, \parencite, ...
+    pub command: String,
+    pub keys: Vec<String>,
+    pub position: Position,
+}
+
+// Sectioning commands by level
+const SECTION_COMMANDS: [&str; 7] = ["part", "chapter", "section", "subsection", "subsubsection", "paragraph", "subparagraph"];
+
+const REFERENCE_COMMANDS: &[&str] = &[
+    "ref", "eqref", "pageref", "autoref", "nameref", "vref", "cref", "Cref", "cpageref", "Cpageref",
+];
+
+impl DocumentTree {
+    // Sections nested in section `index`, directly or deeper
+    pub fn subsections(&self, index: usize) -> impl Iterator<Item = (usize, &Section)> {
+        let level = self.sections[index].level;
+        self.sections.iter().enumerate()
+            .skip(index + 1)
+            .take_while(move |(_, section)| section.level > level)
+    }
+
+    // Environments with the given name
+    pub fn environments_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Environment> {
+        self.environments.iter().filter(move |environment| environment.name == name)
+    }
+
+    pub fn has_package(&self, name: &str) -> bool {
+        self.packages.iter().any(|package| package.name == name)
+    }
+}
+
+pub fn is_citation_command(name: &str) -> bool {
+    name.starts_with("cite") || name.ends_with("cite") || name.starts_with("Cite")
+}
+
+pub fn parse(text: &str) -> DocumentTree {
+    let tokens = lexer::tokenize(text);
+    let mut tree = DocumentTree { preamble: 0..text.len(), ..DocumentTree::default() };
+    // Open environments, innermost last
+    let mut open: Vec<usize> = Vec::new();
+
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = &tokens[i];
+        let Some(name) = token.command_name() else {
+            i += 1;
+            continue;
+        };
+        let position = token.start;
+
+        match name {
+            "documentclass" => {
+                let args = arguments(text, &tokens, i + 1, 1, 1);
+                if let Some(class) = args.required.first() {
+                    tree.class = Some(DocumentClass {
+                        name: class.trim().to_string(),
+                        options: args.optional.first().map(|o| split_list(o)).unwrap_or_default(),
+                        position,
+                    });
+                }
+                i = args.next;
+            },
+            "usepackage" | "RequirePackage" => {
+                let args = arguments(text, &tokens, i + 1, 1, 1);
+                let options = args.optional.first().map(|o| split_list(o)).unwrap_or_default();
+                for name in args.required.first().map(|names| split_list(names)).unwrap_or_default() {
+                    tree.packages.push(Package { name, options: options.clone(), position });
+                }
+                i = args.next;
+            },
+            "title" => {
+                let args = arguments(text, &tokens, i + 1, 1, 1);
+                tree.title = args.required.first().map(|title| title.trim().to_string());
+                i = args.next;
+            },
+            "begin" => {
+                let args = arguments(text, &tokens, i + 1, 0, 1);
+                let Some(env) = args.required.first().map(|env| env.trim().to_string()) else {
+                    i = args.next;
+                    continue;
+                };
+                if env == "document" {
+                    tree.preamble = 0..position.offset;
+                    tree.body = Some(tokens[args.next - 1].end()..text.len());
+                }
+                tree.environments.push(Environment {
+                    name: env,
+                    begin: position,
+                    end: None,
+                    range: position.offset..text.len(),
+                    parent: open.last().copied(),
+                });
+                open.push(tree.environments.len() - 1);
+                i = args.next;
+            },
+            "end" => {
+                let args = arguments(text, &tokens, i + 1, 0, 1);
+                let env = args.required.first().map(|env| env.trim()).unwrap_or("");
+                // Close the innermost environment of that name, and with it
+                // any left open inside it
+                if let Some(depth) = open.iter().rposition(|&index| tree.environments[index].name == env) {
+                    let index = open[depth];
+                    open.truncate(depth);
+                    let environment = &mut tree.environments[index];
+                    environment.end = Some(position);
+                    environment.range.end = tokens[args.next - 1].end();
+                    if env == "document" {
+                        if let Some(body) = &mut tree.body {
+                            body.end = position.offset;
+                        }
+                    }
+                } else {
+                    tree.unmatched_ends.push(EnvironmentEnd { name: env.to_string(), position });
+                }
+                i = args.next;
+            },
+            "label" => {
+                let args = arguments(text, &tokens, i + 1, 0, 1);
+                if let Some(label) = args.required.first() {
+                    tree.labels.push(Label { name: label.trim().to_string(), position });
+                }
+                i = args.next;
+            },
+            "caption" => {
+                let args = arguments(text, &tokens, i + 1, 1, 1);
+                if let Some(caption) = args.required.first() {
+                    tree.captions.push(Caption { text: caption.trim().to_string(), position });
+                }
+                i = args.next;
+            },
+            _ if REFERENCE_COMMANDS.contains(&name) => {
+                let args = arguments(text, &tokens, i + 1, 0, 1);
+                for label in args.required.first().map(|names| split_list(names)).unwrap_or_default() {
+                    tree.references.push(Reference { command: name.to_string(), name: label, position });
+                }
+                i = args.next;
+            },
+            _ if is_citation_command(name) => {
+                // Up to two optional notes before the keys
+                let args = arguments(text, &tokens, i + 1, 2, 1);
+                if let Some(keys) = args.required.first() {
+                    tree.citations.push(Citation { command: name.to_string(), keys: split_list(keys), position });
+                }
+                i = args.next;
+            },
+            _ => match SECTION_COMMANDS.iter().position(|&command| command == name) {
+                Some(level) => {
+                    let args = arguments(text, &tokens, i + 1, 1, 1);
+                    if let Some(title) = args.required.first() {
+                        tree.sections.push(Section {
+                            level: level as u8,
+                            command: name.to_string(),
+                            starred: args.starred,
+                            title: title.trim().to_string(),
+                            position,
+                            range: position.offset..text.len(),
+                        });
+                    }
+                    i = args.next;
+                },
+                None => i += 1,
+            },
+        }
+    }
+
+    // Each section runs to the next one at its level or above
+    let body_end = tree.body.as_ref().map_or(text.len(), |body| body.end);
+    for index in 0..tree.sections.len() {
+        let level = tree.sections[index].level;
+        let end = tree.sections[index + 1..].iter()
+            .find(|section| section.level <= level)
+            .map_or(body_end, |section| section.position.offset);
+        tree.sections[index].range.end = end.max(tree.sections[index].range.start);
+    }
+
+    tree
+}
+
+struct Arguments {
+    starred: bool,
+    optional: Vec<String>,
+    required: Vec<String>,
+    // Index of the first token after the arguments
+    next: usize,
+}
+
+// Read a command's arguments starting at token `i`: an optional star, up to
+// `max_optional` bracketed arguments, then up to `required` braced ones.
+// Whitespace and comments between them are skipped.
+fn arguments(text: &str, tokens: &[Token], mut i: usize, max_optional: usize, required: usize) -> Arguments {
+    let mut args = Arguments { starred: false, optional: Vec::new(), required: Vec::new(), next: i };
+
+    let skip_space = |mut i: usize| {
+        while i < tokens.len() && matches!(tokens[i].kind, TokenKind::Whitespace | TokenKind::Comment) {
+            i += 1;
+        }
+        i
+    };
+
+    if i < tokens.len() && tokens[i].kind == TokenKind::Text && tokens[i].text == "*" {
+        args.starred = true;
+        i += 1;
+        args.next = i;
+    }
+
+    while args.optional.len() < max_optional {
+        let j = skip_space(i);
+        match tokens.get(j) {
+            Some(token) if token.kind == TokenKind::OpenBracket => match closing(tokens, j, TokenKind::CloseBracket) {
+                Some(close) => {
+                    args.optional.push(text[token.end()..tokens[close].start.offset].to_string());
+                    i = close + 1;
+                    args.next = i;
+                },
+                None => break,
+            },
+            _ => break,
+        }
+    }
+
+    while args.required.len() < required {
+        let j = skip_space(i);
+        match tokens.get(j) {
+            Some(token) if token.kind == TokenKind::BeginGroup => match closing(tokens, j, TokenKind::EndGroup) {
+                Some(close) => {
+                    args.required.push(text[token.end()..tokens[close].start.offset].to_string());
+                    i = close + 1;
+                    args.next = i;
+                },
+                None => break,
+            },
+            _ => break,
+        }
+    }
+
+    args
+}
+
+// Index of the token closing the group or bracket opened at `open`, at the
+// same brace depth
+fn closing(tokens: &[Token], open: usize, close: TokenKind) -> Option<usize> {
+    let mut depth = 0;
+    for (index, token) in tokens.iter().enumerate().skip(open + 1) {
+        match token.kind {
+            TokenKind::BeginGroup => depth += 1,
+            TokenKind::EndGroup if depth > 0 => depth -= 1,
+            // An unmatched } ends the enclosing group, so the argument does
+            // not close
+            TokenKind::EndGroup if close != TokenKind::EndGroup => return None,
+            kind if kind == close && depth == 0 => return Some(index),
+            _ => {},
+        }
+    }
+    None
+}
+
+// "a, b ,c" as ["a", "b", "c"]
+fn split_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
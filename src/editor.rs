@@ -0,0 +1,222 @@
+// LaTeX source editor
+//
+// The LaTeX view is a textarea laid over a highlighted copy of the source, so
+// the browser handles typing, selection and scrolling while the copy shows
+// the colours, line numbers and matching brackets. This module has the parts
+// that do not need the DOM: the highlight class of each piece of a line,
+// bracket matching, conversion between the textarea's UTF-16 offsets and
+// byte offsets, and the undo history.
+
+use std::ops::Range;
+
+use crate::lexer::{self, TokenKind};
+
+// Typing within this many milliseconds of the last edit is undone together
+const COALESCE_MS: f64 = 1000.0;
+// Oldest undo steps are dropped beyond this
+const MAX_UNDO_STEPS: usize = 200;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Segment {
+    // Highlight class, or `None` for plain text
+    pub class: Option<&'static str>,
+    pub range: Range<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct EditHistory {
+    current: String,
+    undo: Vec<String>,
+    redo: Vec<String>,
+    // When the last edit was made, for coalescing
+    last_edit: Option<f64>,
+}
+
+// Each line of `text` as highlighted segments. Lines are split at '\n', so
+// a trailing newline gives a last, empty line as in a textarea.
+pub fn highlight_lines(text: &str) -> Vec<Vec<Segment>> {
+    let mut pieces: Vec<Segment> = Vec::new();
+    let mut in_math = false;
+    // Set after \begin or \end until the environment name
+    let mut environment_name = false;
+
+    for token in lexer::tokenize(text) {
+        let class = match token.kind {
+            TokenKind::Command => match token.text {
+                "\\(" | "\\[" => {
+                    in_math = true;
+                    Some("tex-math")
+                },
+                "\\)" | "\\]" => {
+                    in_math = false;
+                    Some("tex-math")
+                },
+                "\\begin" | "\\end" => {
+                    environment_name = true;
+                    Some("tex-command")
+                },
+                _ => Some("tex-command"),
+            },
+            TokenKind::MathShift => {
+                in_math = !in_math;
+                Some("tex-math")
+            },
+            TokenKind::BeginGroup | TokenKind::EndGroup => Some("tex-brace"),
+            TokenKind::OpenBracket | TokenKind::CloseBracket => Some("tex-bracket"),
+            TokenKind::Comment => Some("tex-comment"),
+            TokenKind::Verbatim => Some("tex-verbatim"),
+            TokenKind::Text if environment_name => {
+                environment_name = false;
+                Some("tex-environment")
+            },
+            TokenKind::Text if in_math => Some("tex-math"),
+            TokenKind::Text | TokenKind::Whitespace => None,
+        };
+        // A paragraph break ends math that was left open
+        if token.kind == TokenKind::Whitespace && token.text.matches('\n').count() > 1 {
+            in_math = false;
+        }
+        pieces.push(Segment { class, range: token.start.offset..token.end() });
+    }
+
+    // Cut the pieces at line breaks
+    let mut lines = vec![Vec::new()];
+    for piece in pieces {
+        let mut start = piece.range.start;
+        for (index, _) in text[piece.range.clone()].match_indices('\n') {
+            let newline = piece.range.start + index;
+            if newline > start {
+                lines.last_mut().unwrap().push(Segment { class: piece.class, range: start..newline });
+            }
+            lines.push(Vec::new());
+            start = newline + 1;
+        }
+        if piece.range.end > start {
+            lines.last_mut().unwrap().push(Segment { class: piece.class, range: start..piece.range.end });
+        }
+    }
+    lines
+}
+
+// Byte offsets of the bracket next to the cursor at byte offset `cursor` and
+// the one matching it. The bracket after the cursor is tried first. Braces
+// and square brackets are matched separately, and brackets in comments and
+// verbatim text are skipped.
+pub fn matching_bracket(text: &str, cursor: usize) -> Option<(usize, usize)> {
+    let tokens: Vec<_> = lexer::tokenize(text).into_iter()
+        .filter(|token| matches!(
+            token.kind,
+            TokenKind::BeginGroup | TokenKind::EndGroup | TokenKind::OpenBracket | TokenKind::CloseBracket
+        ))
+        .collect();
+    let at = tokens.iter().position(|token| token.start.offset == cursor)
+        .or_else(|| tokens.iter().position(|token| token.end() == cursor))?;
+
+    // Brackets of the same kind that nest deeper and shallower when walking
+    // away from the one at the cursor
+    let (forward, deeper, shallower) = match tokens[at].kind {
+        TokenKind::BeginGroup => (true, TokenKind::BeginGroup, TokenKind::EndGroup),
+        TokenKind::EndGroup => (false, TokenKind::EndGroup, TokenKind::BeginGroup),
+        TokenKind::OpenBracket => (true, TokenKind::OpenBracket, TokenKind::CloseBracket),
+        _ => (false, TokenKind::CloseBracket, TokenKind::OpenBracket),
+    };
+    let candidates: Box<dyn Iterator<Item = _>> = if forward {
+        Box::new(tokens[at + 1..].iter())
+    } else {
+        Box::new(tokens[..at].iter().rev())
+    };
+    let mut depth = 0;
+    for token in candidates {
+        if token.kind == deeper {
+            depth += 1;
+        } else if token.kind == shallower {
+            if depth == 0 {
+                let (a, b) = (tokens[at].start.offset, token.start.offset);
+                return Some((a.min(b), a.max(b)));
+            }
+            depth -= 1;
+        }
+    }
+    None
+}
+
+// Byte offset of the UTF-16 offset `index` into `text`, as reported by a
+// textarea's selection
+pub fn byte_offset(text: &str, index: usize) -> usize {
+    let mut units = 0;
+    for (offset, c) in text.char_indices() {
+        if units >= index {
+            return offset;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+// UTF-16 offset of the byte offset `offset` into `text`
+pub fn utf16_offset(text: &str, offset: usize) -> usize {
+    text[..offset.min(text.len())].chars().map(char::len_utf16).sum()
+}
+
+// Byte offset in `new` just past the part that differs from `old`, where the
+// cursor goes after an undo or redo
+pub fn change_end(old: &str, new: &str) -> usize {
+    let prefix: usize = old.chars().zip(new.chars())
+        .take_while(|(a, b)| a == b)
+        .map(|(c, _)| c.len_utf8())
+        .sum();
+    let suffix: usize = old[prefix..].chars().rev().zip(new[prefix..].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(c, _)| c.len_utf8())
+        .sum();
+    new.len() - suffix
+}
+
+impl EditHistory {
+    pub fn new(text: &str) -> Self {
+        EditHistory {
+            current: text.to_string(),
+            undo: Vec::new(),
+            redo: Vec::new(),
+            last_edit: None,
+        }
+    }
+
+    // The text the history ends at
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+
+    // The text was changed to `text` at `time` in milliseconds. Quick
+    // successive edits make one undo step.
+    pub fn edit(&mut self, text: &str, time: f64) {
+        if text == self.current {
+            return;
+        }
+        let coalesce = self.last_edit.is_some_and(|last| time - last < COALESCE_MS) && !self.undo.is_empty();
+        let previous = std::mem::replace(&mut self.current, text.to_string());
+        if !coalesce {
+            self.undo.push(previous);
+            if self.undo.len() > MAX_UNDO_STEPS {
+                self.undo.remove(0);
+            }
+        }
+        self.redo.clear();
+        self.last_edit = Some(time);
+    }
+
+    // Step back, returning the text to show
+    pub fn undo(&mut self) -> Option<&str> {
+        let previous = self.undo.pop()?;
+        self.redo.push(std::mem::replace(&mut self.current, previous));
+        self.last_edit = None;
+        Some(&self.current)
+    }
+
+    pub fn redo(&mut self) -> Option<&str> {
+        let next = self.redo.pop()?;
+        self.undo.push(std::mem::replace(&mut self.current, next));
+        self.last_edit = None;
+        Some(&self.current)
+    }
+}
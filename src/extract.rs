@@ -0,0 +1,569 @@
+// LaTeX extraction from model replies
+//
+// A reply mixes explanation with one or more code fences. The document may be
+// complete, split over several fences, cut off by the token limit, or only a
+// body to go into the template. `extract_latex` finds the document and ends it
+// at the `\end{document}` that actually closes it, skipping comments,
+// `\verb` and verbatim-like environments. `filecontents` blocks and BibTeX
+// fences become separate files, and everything else the reply contained is
+// reported as discarded.
+
+use std::fmt;
+
+use crate::chat;
+use crate::doctree;
+use crate::lexer::VERBATIM_ENVIRONMENTS;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Extraction {
+    pub latex: String,
+    pub kind: ExtractionKind,
+    // Files written by `filecontents` blocks or sent as BibTeX fences, to be
+    // compiled alongside the document
+    pub files: Vec<ExtractedFile>,
+    pub discarded: Vec<Discarded>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExtractionKind {
+    // From \documentclass to its \end{document}
+    Complete,
+    // A document cut off before \end{document}; its open environments were
+    // closed
+    Unterminated,
+    // A body, or a document without \documentclass, put into the template's
+    // preamble
+    Wrapped,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExtractedFile {
+    pub path: String,
+    pub contents: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Discarded {
+    // Explanation around the document
+    Prose(String),
+    // A code block that is not part of the document
+    CodeBlock { language: String, text: String },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExtractError {
+    Empty,
+    NoLatex,
+    // A section rewrite without a heading at the rewritten section's level
+    NoSection,
+}
+
+impl fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtractError::Empty => write!(f, "the reply is empty"),
+            ExtractError::NoLatex => write!(f, "the reply contains no LaTeX"),
+            ExtractError::NoSection => write!(f, "the reply contains no section heading"),
+        }
+    }
+}
+
+impl Extraction {
+    // A document that needs no extraction, such as one built by applying edits
+    pub fn document(latex: String) -> Self {
+        Extraction { latex, kind: ExtractionKind::Complete, files: Vec::new(), discarded: Vec::new() }
+    }
+
+    // One line on what was done besides taking the document as is, or `None`
+    pub fn summary(&self) -> Option<String> {
+        let mut notes = Vec::new();
+        match self.kind {
+            ExtractionKind::Complete => {},
+            ExtractionKind::Unterminated => notes.push("closed the cut-off document".to_string()),
+            ExtractionKind::Wrapped => notes.push("wrapped the reply in the template preamble".to_string()),
+        }
+        if !self.files.is_empty() {
+            let paths: Vec<&str> = self.files.iter().map(|file| file.path.as_str()).collect();
+            notes.push(format!("added {}", paths.join(", ")));
+        }
+
+        let prose_lines: usize = self.discarded.iter()
+            .map(|discarded| match discarded {
+                Discarded::Prose(text) => text.lines().filter(|line| !line.trim().is_empty()).count(),
+                Discarded::CodeBlock { .. } => 0,
+            })
+            .sum();
+        if prose_lines > 0 {
+            notes.push(format!("dropped {} line{} of explanation", prose_lines, if prose_lines == 1 { "" } else { "s" }));
+        }
+        let blocks = self.discarded.iter().filter(|discarded| matches!(discarded, Discarded::CodeBlock { .. })).count();
+        if blocks > 0 {
+            notes.push(format!("dropped {} other code block{}", blocks, if blocks == 1 { "" } else { "s" }));
+        }
+
+        if notes.is_empty() {
+            return None;
+        }
+        let summary = notes.join("; ");
+        let mut chars = summary.chars();
+        chars.next().map(|first| first.to_uppercase().chain(chars).collect())
+    }
+}
+
+// A piece of the reply: text outside fences, or a fenced block
+#[derive(Clone, Debug)]
+enum Segment {
+    Prose(String),
+    Code { language: String, text: String },
+}
+
+// Find the LaTeX document in a model reply. Fragments are wrapped in the
+// preamble of `template`.
+pub fn extract_latex(reply: &str, template: &str) -> Result<Extraction, ExtractError> {
+    if reply.trim().is_empty() {
+        return Err(ExtractError::Empty);
+    }
+
+    let mut discarded = Vec::new();
+    let mut files = Vec::new();
+    let mut candidates = Vec::new();
+
+    let segments = split_fences(reply);
+    let fenced = segments.iter().any(|segment| matches!(segment, Segment::Code { .. }));
+    for segment in segments {
+        match segment {
+            Segment::Prose(text) if fenced => {
+                if !text.trim().is_empty() {
+                    discarded.push(Discarded::Prose(text.trim().to_string()));
+                }
+            },
+            Segment::Prose(text) => candidates.push((String::new(), text)),
+            Segment::Code { language, text } => match language.as_str() {
+                "bibtex" | "bib" | "biblatex" => files.push(ExtractedFile { path: String::new(), contents: text }),
+                "latex" | "tex" | "context" => candidates.push((language, text)),
+                // Unlabelled or mislabelled blocks count if they hold LaTeX
+                _ if find_command(&text, "\\documentclass").is_some() || (language.is_empty() && has_commands(&text)) => {
+                    candidates.push((language, text))
+                },
+                _ => discarded.push(Discarded::CodeBlock { language, text }),
+            },
+        }
+    }
+
+    let (text, others) = choose_document(candidates);
+    for (language, text) in others {
+        discarded.push(Discarded::CodeBlock { language, text });
+    }
+    let Some(text) = text else {
+        return Err(ExtractError::NoLatex);
+    };
+
+    let (text, embedded) = take_filecontents(&text);
+    let (mut latex, kind) = assemble(&text, template, !fenced, &mut discarded)?;
+
+    // BibTeX fences are named after the bibliography the document loads
+    let bib_name = bibliography_name(&latex);
+    for file in files.iter_mut() {
+        file.path = format!("{}.bib", bib_name.as_deref().unwrap_or("references"));
+    }
+    files.extend(embedded);
+    files.dedup_by(|a, b| a.path == b.path);
+
+    if !latex.ends_with('\n') {
+        latex.push('\n');
+    }
+    Ok(Extraction { latex, kind, files, discarded })
+}
+
+// Find a rewritten section in a model reply: in the LaTeX blocks, or the
+// whole reply if it has no fences, the text from the sectioning command at
+// `level` (see `doctree::Section`) up to the next one at that level or above,
+// or to \end{document}. A heading still titled `title` is preferred, in case
+// the model sent back more than the one section.
+pub fn extract_section(reply: &str, level: u8, title: &str) -> Result<String, ExtractError> {
+    if reply.trim().is_empty() {
+        return Err(ExtractError::Empty);
+    }
+
+    let segments = split_fences(reply);
+    let fenced = segments.iter().any(|segment| matches!(segment, Segment::Code { .. }));
+    let mut found_latex = false;
+    for segment in segments {
+        let text = match segment {
+            Segment::Code { language, text } if matches!(language.as_str(), "latex" | "tex" | "") => text,
+            Segment::Prose(text) if !fenced => text,
+            _ => continue,
+        };
+        found_latex |= has_commands(&text);
+
+        let tree = doctree::parse(&text);
+        let mut headings = tree.sections.iter().filter(|section| section.level == level);
+        let Some(first) = headings.next() else {
+            continue;
+        };
+        let section = std::iter::once(first).chain(headings).find(|section| section.title == title).unwrap_or(first);
+        let start = section.position.offset;
+        let end = find_command(&text[start..], "\\end{document}").map_or(section.range.end, |n| section.range.end.min(start + n));
+        return Ok(format!("{}\n", text[start..end].trim_end()));
+    }
+
+    Err(if found_latex { ExtractError::NoSection } else { ExtractError::NoLatex })
+}
+
+// Split the reply at ``` and ~~~ fences; an unterminated last fence (a reply
+// cut off mid-block) runs to the end
+fn split_fences(reply: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut prose = String::new();
+    let mut lines = reply.lines();
+
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim_start();
+        let fence_char = match trimmed.chars().next() {
+            Some(c @ ('`' | '~')) => c,
+            _ => {
+                prose.push_str(line);
+                prose.push('\n');
+                continue;
+            }
+        };
+        let fence_len = trimmed.chars().take_while(|&c| c == fence_char).count();
+        if fence_len < 3 {
+            prose.push_str(line);
+            prose.push('\n');
+            continue;
+        }
+
+        let language = trimmed[fence_len..].split_whitespace().next().unwrap_or("").to_lowercase();
+        let mut text = String::new();
+        for line in lines.by_ref() {
+            let trimmed = line.trim();
+            if trimmed.len() >= fence_len && trimmed.chars().all(|c| c == fence_char) {
+                break;
+            }
+            text.push_str(line);
+            text.push('\n');
+        }
+
+        if !prose.is_empty() {
+            segments.push(Segment::Prose(std::mem::take(&mut prose)));
+        }
+        segments.push(Segment::Code { language, text });
+    }
+
+    if !prose.is_empty() {
+        segments.push(Segment::Prose(prose));
+    }
+    segments
+}
+
+// Pick the document among the LaTeX blocks: a document may continue over the
+// blocks after its \documentclass until one holds its \end{document}. The
+// largest complete document wins; without one, the largest candidate. Returns
+// the document and the blocks left over.
+fn choose_document(candidates: Vec<(String, String)>) -> (Option<String>, Vec<(String, String)>) {
+    if candidates.is_empty() {
+        return (None, Vec::new());
+    }
+
+    let mut spans = Vec::new();
+    for start in 0..candidates.len() {
+        if find_command(&candidates[start].1, "\\documentclass").is_none() {
+            continue;
+        }
+        let mut text = candidates[start].1.clone();
+        let mut end = start;
+        while find_document_end(&text).is_none()
+            && end + 1 < candidates.len()
+            && find_command(&candidates[end + 1].1, "\\documentclass").is_none()
+        {
+            end += 1;
+            text.push_str(&candidates[end].1);
+        }
+        let complete = find_document_end(&text).is_some();
+        spans.push((start, end, complete, text));
+    }
+
+    let best = spans.into_iter().max_by_key(|(_, _, complete, text)| (*complete, text.len()));
+    let (start, end, text) = match best {
+        Some((start, end, _, text)) => (start, end, text),
+        None => {
+            // No \documentclass anywhere: prefer a block with \begin{document},
+            // then the largest one
+            let index = (0..candidates.len())
+                .max_by_key(|&i| (find_command(&candidates[i].1, "\\begin{document}").is_some(), candidates[i].1.trim().len()))
+                .unwrap();
+            (index, index, candidates[index].1.clone())
+        },
+    };
+
+    let others = candidates.into_iter().enumerate()
+        .filter(|(index, (_, text))| (*index < start || *index > end) && !text.trim().is_empty())
+        .map(|(_, candidate)| candidate)
+        .collect();
+    (Some(text), others)
+}
+
+// Turn the chosen text into a complete document
+fn assemble(text: &str, template: &str, unfenced: bool, discarded: &mut Vec<Discarded>) -> Result<(String, ExtractionKind), ExtractError> {
+    let (class, packages) = chat::template_setup(template);
+
+    let (start, mut kind, mut latex) = match find_command(text, "\\documentclass") {
+        Some(start) => (start, ExtractionKind::Complete, String::new()),
+        None => match find_command(text, "\\begin{document}") {
+            // A preamble without its class, or only the document body
+            Some(start) => (preamble_start(text, start), ExtractionKind::Wrapped, format!("\\documentclass{{{}}}\n", class)),
+            None => return wrap_fragment(text, class, packages, unfenced, discarded).map(|latex| (latex, ExtractionKind::Wrapped)),
+        },
+    };
+
+    // Keep leading comments (e.g. "% main.tex"), drop anything else
+    let before = &text[..start];
+    if before.lines().all(|line| line.trim().is_empty() || line.trim_start().starts_with('%')) {
+        latex.insert_str(0, before.trim_start());
+    } else {
+        discarded.push(Discarded::Prose(before.trim().to_string()));
+    }
+
+    let document = &text[start..];
+    match find_document_end(document) {
+        Some(end) => {
+            latex.push_str(&document[..end]);
+            let after = document[end..].trim();
+            if !after.is_empty() {
+                discarded.push(Discarded::Prose(after.to_string()));
+            }
+        },
+        None => {
+            latex.push_str(document.trim_end());
+            latex.push('\n');
+            let open = open_environments(document);
+            if !open.iter().any(|env| env == "document") {
+                latex.push_str("\\begin{document}\n");
+            }
+            for env in open.iter().rev().filter(|env| *env != "document") {
+                latex.push_str(&format!("\\end{{{}}}\n", env));
+            }
+            latex.push_str("\\end{document}");
+            if kind == ExtractionKind::Complete {
+                kind = ExtractionKind::Unterminated;
+            }
+        },
+    }
+
+    Ok((latex, kind))
+}
+
+// Put a body fragment into the template's preamble, moving any \usepackage
+// lines it carries into the preamble
+fn wrap_fragment(text: &str, class: &str, packages: &str, unfenced: bool, discarded: &mut Vec<Discarded>) -> Result<String, ExtractError> {
+    let mut lines: Vec<&str> = text.lines().collect();
+
+    // Without fences, the explanation before the first command is prose
+    if unfenced {
+        let first = lines.iter().position(|line| {
+            let line = line.trim_start();
+            line.starts_with('\\') || line.starts_with('%')
+        });
+        let Some(first) = first else {
+            return Err(ExtractError::NoLatex);
+        };
+        let prose = lines[..first].join("\n");
+        if !prose.trim().is_empty() {
+            discarded.push(Discarded::Prose(prose.trim().to_string()));
+        }
+        lines.drain(..first);
+    }
+
+    let body: Vec<&str> = lines.iter().copied()
+        .filter(|line| !line.trim_start().starts_with("\\usepackage") && line.trim() != "\\end{document}")
+        .collect();
+    let extra_packages: Vec<&str> = lines.iter()
+        .map(|line| line.trim())
+        .filter(|line| line.starts_with("\\usepackage") && !packages.contains(*line))
+        .collect();
+    let body = body.join("\n");
+    if !has_commands(&body) && extra_packages.is_empty() {
+        return Err(ExtractError::NoLatex);
+    }
+
+    let mut latex = format!("\\documentclass{{{}}}\n", class);
+    for package in packages.split("\\usepackage").filter(|p| !p.is_empty()) {
+        latex.push_str(&format!("\\usepackage{}\n", package));
+    }
+    for package in extra_packages {
+        latex.push_str(package);
+        latex.push('\n');
+    }
+    latex.push_str(&format!("\n\\begin{{document}}\n\n{}\n\n\\end{{document}}", body.trim()));
+    Ok(latex)
+}
+
+// Move `filecontents` blocks out of the text into files
+fn take_filecontents(text: &str) -> (String, Vec<ExtractedFile>) {
+    let mut rest = text.to_string();
+    let mut files = Vec::new();
+
+    while let Some(start) = find_command(&rest, "\\begin{filecontents") {
+        let after = &rest[start + "\\begin{filecontents".len()..];
+        let (env, after) = match after.strip_prefix("*}") {
+            Some(after) => ("filecontents*", after),
+            None => match after.strip_prefix('}') {
+                Some(after) => ("filecontents", after),
+                None => break,
+            },
+        };
+        // Options such as [overwrite, nosearch]
+        let after = match after.trim_start().strip_prefix('[') {
+            Some(options) => options.split_once(']').map_or("", |(_, after)| after),
+            None => after,
+        };
+        let Some((name, body)) = after.trim_start().strip_prefix('{').and_then(|a| a.split_once('}')) else {
+            break;
+        };
+        let end_marker = format!("\\end{{{}}}", env);
+        let Some(body_end) = body.find(&end_marker) else {
+            break;
+        };
+
+        let contents = body[..body_end].strip_prefix('\n').unwrap_or(&body[..body_end]).to_string();
+        files.push(ExtractedFile { path: name.trim().to_string(), contents });
+
+        let block_end = rest.len() - body.len() + body_end + end_marker.len();
+        let block_end = block_end + rest[block_end..].find('\n').map_or(rest.len() - block_end, |i| i + 1);
+        rest.replace_range(line_start(&rest, start)..block_end, "");
+    }
+
+    (rest, files)
+}
+
+// Byte offset just past the \end{document} that closes the document, if any
+pub fn find_document_end(text: &str) -> Option<usize> {
+    let mut end = None;
+    scan(text, |event, index| {
+        if let ScanEvent::End("document") = event {
+            end = Some(index);
+            return false;
+        }
+        true
+    });
+    end
+}
+
+// Environments begun and not ended by the end of `text`, outermost first
+fn open_environments(text: &str) -> Vec<String> {
+    let mut stack: Vec<String> = Vec::new();
+    scan(text, |event, _| {
+        match event {
+            ScanEvent::Begin(env) => stack.push(env.to_string()),
+            ScanEvent::End(env) => {
+                if let Some(position) = stack.iter().rposition(|open| open == env) {
+                    stack.truncate(position);
+                }
+            },
+        }
+        true
+    });
+    stack
+}
+
+enum ScanEvent<'a> {
+    Begin(&'a str),
+    End(&'a str),
+}
+
+// Walk the \begin and \end commands of `text` outside comments, \verb and
+// verbatim-like environments, calling `visit` with each and the byte offset
+// just past it until it returns false
+fn scan<'a>(text: &'a str, mut visit: impl FnMut(ScanEvent<'a>, usize) -> bool) {
+    let bytes = text.as_bytes();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => i = text[i..].find('\n').map_or(bytes.len(), |n| i + n + 1),
+            b'\\' => {
+                let name_len = bytes[i + 1..].iter().take_while(|b| b.is_ascii_alphabetic()).count();
+                let name = &text[i + 1..i + 1 + name_len];
+                i += 1 + name_len.max(1);
+                match name {
+                    "verb" => {
+                        // \verb|...| or \verb*|...|
+                        let mut chars = text[i.min(text.len())..].char_indices();
+                        let mut delimiter = chars.next();
+                        if let Some((_, '*')) = delimiter {
+                            delimiter = chars.next();
+                        }
+                        if let Some((offset, d)) = delimiter {
+                            let start = i + offset + d.len_utf8();
+                            i = text[start..].find([d, '\n']).map_or(bytes.len(), |n| start + n + 1);
+                        }
+                    },
+                    "begin" | "end" => {
+                        let Some(env) = text[i..].strip_prefix('{').and_then(|rest| rest.split_once('}')).map(|(env, _)| env) else {
+                            continue;
+                        };
+                        let after = i + env.len() + 2;
+                        if name == "begin" && VERBATIM_ENVIRONMENTS.contains(&env) {
+                            let end_marker = format!("\\end{{{}}}", env);
+                            i = text[after..].find(&end_marker).map_or(bytes.len(), |n| after + n + end_marker.len());
+                            continue;
+                        }
+                        let event = if name == "begin" { ScanEvent::Begin(env) } else { ScanEvent::End(env) };
+                        if !visit(event, after) {
+                            return;
+                        }
+                        i = after;
+                    },
+                    _ => {},
+                }
+            },
+            _ => i += 1,
+        }
+    }
+}
+
+// Offset of the first `command` that is not inside a comment
+fn find_command(text: &str, command: &str) -> Option<usize> {
+    text.match_indices(command)
+        .map(|(index, _)| index)
+        .find(|&index| {
+            let line = &text[line_start(text, index)..index];
+            !line.replace("\\%", "").contains('%')
+        })
+}
+
+fn line_start(text: &str, index: usize) -> usize {
+    text[..index].rfind('\n').map_or(0, |n| n + 1)
+}
+
+// Where a preamble without \documentclass begins: the first of the lines
+// before \begin{document} that start with a command, after any explanation
+fn preamble_start(text: &str, begin: usize) -> usize {
+    let mut start = line_start(text, begin);
+    while start > 0 {
+        let previous = line_start(text, start - 1);
+        let line = text[previous..start].trim_start();
+        if !(line.is_empty() || line.starts_with('\\') || line.starts_with('%')) {
+            break;
+        }
+        start = previous;
+    }
+    start
+}
+
+fn has_commands(text: &str) -> bool {
+    text.as_bytes().windows(2).any(|pair| pair[0] == b'\\' && pair[1].is_ascii_alphabetic())
+}
+
+// The .bib name loaded by \bibliography{name} or \addbibresource{name.bib}
+fn bibliography_name(latex: &str) -> Option<String> {
+    for command in ["\\bibliography{", "\\addbibresource{"] {
+        if let Some(start) = find_command(latex, command) {
+            let rest = &latex[start + command.len()..];
+            let name = rest.split(['}', ',']).next()?.trim();
+            return Some(name.trim_end_matches(".bib").to_string());
+        }
+    }
+    None
+}
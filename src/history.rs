@@ -0,0 +1,275 @@
+// Conversation history records
+//
+// Each entry in the history panel is a `Conversation`: the topic it started
+// from, the provider, template and size it was generated with, every chat
+// turn and every LaTeX revision, under a stable id. history_store.rs keeps
+// them in IndexedDB.
+//
+// Older versions kept history in localStorage as a JSON array under
+// "chat_history_data", one entry per generation:
+//
+//     [{ "date": "10/17/2026", "time": "3:04:05 PM", "topic": "...",
+//        "content": "<latex>", "template": "Article", "ai_provider": "Claude",
+//        "pdf_size": "Medium" }, ...]
+//
+// `migrate_legacy` turns those into conversations.
+//
+// Every change to a conversation's document is kept as a `Revision`, tagged
+// with what made it, so the editor's timeline can compare and restore them.
+//
+// The panel lists pinned conversations first, then the rest by the date they
+// were started, and can be narrowed to those matching a search.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::chat;
+use crate::compiler::Asset;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Conversation {
+    pub id: String,
+    // Milliseconds since the Unix epoch
+    pub created: f64,
+    pub updated: f64,
+    pub topic: String,
+    // Name given in the panel; the topic is shown until there is one
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub pinned: bool,
+    pub provider: String,
+    pub template: String,
+    pub pdf_size: String,
+    pub turns: Vec<Turn>,
+    // Oldest first; the last one is the current document
+    pub revisions: Vec<Revision>,
+    // Figures and other files the document was compiled with
+    #[serde(default)]
+    pub assets: Vec<Asset>,
+}
+
+// A chat turn; `role` is one of the role names in chat.rs
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Turn {
+    pub role: String,
+    pub content: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Revision {
+    pub created: f64,
+    pub latex: String,
+    // Revisions saved before kinds were recorded all came from generating
+    #[serde(default)]
+    pub kind: RevisionKind,
+}
+
+// What made a revision
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RevisionKind {
+    #[default]
+    Generation,
+    Upload,
+    Edit,
+    Fix,
+    Restore,
+}
+
+impl RevisionKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            RevisionKind::Generation => "Generated",
+            RevisionKind::Upload => "Uploaded",
+            RevisionKind::Edit => "Edited",
+            RevisionKind::Fix => "Auto-fixed",
+            RevisionKind::Restore => "Restored",
+        }
+    }
+}
+
+impl Conversation {
+    pub fn new(id: String, created: f64, topic: &str, provider: &str, template: &str, pdf_size: &str) -> Self {
+        Conversation {
+            id,
+            created,
+            updated: created,
+            topic: topic.to_string(),
+            title: None,
+            pinned: false,
+            provider: provider.to_string(),
+            template: template.to_string(),
+            pdf_size: pdf_size.to_string(),
+            turns: Vec::new(),
+            revisions: Vec::new(),
+            assets: Vec::new(),
+        }
+    }
+
+    // Name shown in the history panel
+    pub fn title(&self) -> &str {
+        self.title.as_deref().unwrap_or(&self.topic)
+    }
+
+    // Name the conversation; an empty name goes back to the topic
+    pub fn rename(&mut self, title: &str, time: f64) {
+        let title = title.trim();
+        self.title = (!title.is_empty() && title != self.topic).then(|| title.to_string());
+        self.updated = time;
+    }
+
+    // Whether every word of `query` is in the name, topic or current document,
+    // ignoring case
+    pub fn matches(&self, query: &str) -> bool {
+        let fields = [self.title(), &self.topic, self.latex()].map(str::to_lowercase);
+        query.to_lowercase().split_whitespace()
+            .all(|word| fields.iter().any(|field| field.contains(word)))
+    }
+
+    // The current document
+    pub fn latex(&self) -> &str {
+        self.revisions.last().map_or("", |revision| &revision.latex)
+    }
+
+    // Replace the turns with the document's chat history
+    pub fn set_turns(&mut self, turns: &[(String, String)], time: f64) {
+        self.turns = turns.iter().map(|(role, content)| Turn { role: role.clone(), content: content.clone() }).collect();
+        self.updated = time;
+    }
+
+    // Keep `latex` as a new revision, unless it is the current one already
+    pub fn add_revision(&mut self, kind: RevisionKind, latex: &str, time: f64) {
+        if self.revisions.last().is_some_and(|revision| revision.latex == latex) {
+            return;
+        }
+        self.revisions.push(Revision { created: time, latex: latex.to_string(), kind });
+        self.updated = time;
+    }
+
+    // Typing in the editor: a run of edits is one revision, changed in place
+    pub fn edit_latex(&mut self, latex: &str, time: f64) {
+        match self.revisions.last_mut() {
+            Some(revision) if revision.kind == RevisionKind::Edit => {
+                revision.latex = latex.to_string();
+                revision.created = time;
+                self.updated = time;
+            },
+            _ => self.add_revision(RevisionKind::Edit, latex, time),
+        }
+    }
+
+    // Make revision `index` the current document again, as a new revision so
+    // nothing after it is lost. Returns the restored document.
+    pub fn restore(&mut self, index: usize, time: f64) -> Option<&str> {
+        let latex = self.revisions.get(index)?.latex.clone();
+        self.add_revision(RevisionKind::Restore, &latex, time);
+        Some(self.latex())
+    }
+
+    // The turns as (role, content) pairs, as `GeneratedContent` keeps them
+    pub fn chat_history(&self) -> Vec<(String, String)> {
+        self.turns.iter().map(|turn| (turn.role.clone(), turn.content.clone())).collect()
+    }
+}
+
+// The conversation with `id`
+pub fn find<'a>(history: &'a [Conversation], id: &str) -> Option<&'a Conversation> {
+    history.iter().find(|conversation| conversation.id == id)
+}
+
+// Heading of the group of pinned conversations
+pub const PINNED: &str = "Pinned";
+
+// The history panel's groups: the pinned conversations matching `query`,
+// then the others by the date `date` gives for their start time, newest
+// first
+pub fn date_groups<'a>(history: &'a [Conversation], query: &str, date: impl Fn(f64) -> String) -> Vec<(String, Vec<&'a Conversation>)> {
+    let mut newest_first: Vec<&Conversation> = history.iter()
+        .filter(|conversation| conversation.matches(query))
+        .collect();
+    newest_first.sort_by(|a, b| b.pinned.cmp(&a.pinned).then(b.created.total_cmp(&a.created)));
+
+    let mut groups: Vec<(String, Vec<&Conversation>)> = Vec::new();
+    for conversation in newest_first {
+        let label = if conversation.pinned { PINNED.to_string() } else { date(conversation.created) };
+        match groups.last_mut() {
+            Some((last, entries)) if *last == label => entries.push(conversation),
+            _ => groups.push((label, vec![conversation])),
+        }
+    }
+    groups
+}
+
+#[derive(Deserialize)]
+struct LegacyEntry {
+    #[serde(default)]
+    date: String,
+    #[serde(default)]
+    time: String,
+    #[serde(default)]
+    topic: String,
+    #[serde(default)]
+    content: String,
+    #[serde(default = "legacy_template")]
+    template: String,
+    #[serde(default = "legacy_provider")]
+    ai_provider: String,
+    #[serde(default = "legacy_size")]
+    pdf_size: String,
+}
+
+// What restoring an entry assumed when a field was missing
+fn legacy_template() -> String {
+    "Article".to_string()
+}
+
+fn legacy_provider() -> String {
+    "Claude".to_string()
+}
+
+fn legacy_size() -> String {
+    "Medium".to_string()
+}
+
+// 16 bytes of the entry's SHA-256 as hex
+fn legacy_id(index: usize, entry: &LegacyEntry) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(index.to_le_bytes());
+    for field in [&entry.date, &entry.time, &entry.topic, &entry.content, &entry.template, &entry.ai_provider, &entry.pdf_size] {
+        // Length-prefixed so fields cannot run into each other
+        hasher.update((field.len() as u64).to_le_bytes());
+        hasher.update(field.as_bytes());
+    }
+    hasher.finalize()[..16].iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Conversations from the old localStorage array. `timestamp` reads an
+// entry's date and time; entries it cannot read get `now`. Ids hash each
+// entry and its position, in the same form as new ones, so migrating the same
+// data twice gives the same records while entries from other browsers do not
+// take each other's ids when workspaces are merged.
+pub fn migrate_legacy(
+    json: &str,
+    now: f64,
+    timestamp: impl Fn(&str, &str) -> Option<f64>,
+) -> Result<Vec<Conversation>, String> {
+    let entries: Vec<LegacyEntry> = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    Ok(entries.into_iter().enumerate().map(|(index, entry)| {
+        let created = timestamp(&entry.date, &entry.time).unwrap_or(now);
+        let mut conversation = Conversation::new(
+            legacy_id(index, &entry),
+            created,
+            &entry.topic,
+            &entry.ai_provider,
+            &entry.template,
+            &entry.pdf_size,
+        );
+        conversation.set_turns(&[
+            (chat::USER.to_string(), entry.topic.clone()),
+            (chat::AI.to_string(), entry.content.clone()),
+        ], created);
+        conversation.add_revision(RevisionKind::Generation, &entry.content, created);
+        conversation
+    }).collect())
+}
@@ -0,0 +1,176 @@
+// IndexedDB store for the conversation history
+//
+// Conversations are kept as `history::Conversation` records, keyed by id, in
+// the "conversations" object store of the "ladfx" database. Unlike the old
+// localStorage blobs this is not capped at a few megabytes, and saving one
+// conversation does not rewrite all the others.
+//
+// The first time the database is opened, history an older version left in
+// localStorage is moved over and the localStorage item removed.
+
+use std::cell::RefCell;
+
+use js_sys::{Array, Promise};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{IdbDatabase, IdbObjectStoreParameters, IdbRequest, IdbTransaction, IdbTransactionMode};
+
+use crate::history::{self, Conversation};
+
+const DATABASE_NAME: &str = "ladfx";
+const DATABASE_VERSION: u32 = 1;
+const CONVERSATIONS: &str = "conversations";
+
+// Where older versions kept the history, see history.rs
+pub const LEGACY_STORAGE_KEY: &str = "chat_history_data";
+
+thread_local! {
+    static DATABASE: RefCell<Option<IdbDatabase>> = const { RefCell::new(None) };
+}
+
+// Every saved conversation, oldest first
+pub async fn load() -> Result<Vec<Conversation>, JsValue> {
+    let database = database().await?;
+    let transaction = database.transaction_with_str_and_mode(CONVERSATIONS, IdbTransactionMode::Readonly)?;
+    let records = request(&transaction.object_store(CONVERSATIONS)?.get_all()?).await?;
+
+    let mut conversations = Vec::new();
+    for record in Array::from(&records).iter() {
+        match serde_wasm_bindgen::from_value::<Conversation>(record) {
+            Ok(conversation) => conversations.push(conversation),
+            Err(e) => web_sys::console::error_1(&format!("Skipping unreadable history record: {}", e).into()),
+        }
+    }
+    conversations.sort_by(|a, b| a.created.total_cmp(&b.created));
+    Ok(conversations)
+}
+
+// Add or replace conversations, by id
+pub async fn save(conversations: &[Conversation]) -> Result<(), JsValue> {
+    put(&database().await?, conversations).await
+}
+
+async fn put(database: &IdbDatabase, conversations: &[Conversation]) -> Result<(), JsValue> {
+    let transaction = database.transaction_with_str_and_mode(CONVERSATIONS, IdbTransactionMode::Readwrite)?;
+    let store = transaction.object_store(CONVERSATIONS)?;
+    for conversation in conversations {
+        let record = serde_wasm_bindgen::to_value(conversation).map_err(JsValue::from)?;
+        store.put(&record)?;
+    }
+    complete(&transaction).await
+}
+
+// Save in the background, reporting failures on the console
+pub fn save_later(conversation: Conversation) {
+    wasm_bindgen_futures::spawn_local(async move {
+        if let Err(e) = save(&[conversation]).await {
+            web_sys::console::error_2(&"Failed to save history:".into(), &e);
+        }
+    });
+}
+
+// Remove conversations, by id
+pub async fn delete(ids: &[String]) -> Result<(), JsValue> {
+    let database = database().await?;
+    let transaction = database.transaction_with_str_and_mode(CONVERSATIONS, IdbTransactionMode::Readwrite)?;
+    let store = transaction.object_store(CONVERSATIONS)?;
+    for id in ids {
+        store.delete(&JsValue::from_str(id))?;
+    }
+    complete(&transaction).await
+}
+
+// Delete in the background, reporting failures on the console
+pub fn delete_later(ids: Vec<String>) {
+    wasm_bindgen_futures::spawn_local(async move {
+        if let Err(e) = delete(&ids).await {
+            web_sys::console::error_2(&"Failed to delete history:".into(), &e);
+        }
+    });
+}
+
+async fn database() -> Result<IdbDatabase, JsValue> {
+    if let Some(database) = DATABASE.with(|d| d.borrow().clone()) {
+        return Ok(database);
+    }
+
+    let factory = web_sys::window().unwrap().indexed_db()?
+        .ok_or_else(|| JsValue::from_str("IndexedDB is not available"))?;
+    let opening = factory.open_with_u32(DATABASE_NAME, DATABASE_VERSION)?;
+    let upgrade = Closure::once_into_js({
+        let opening = opening.clone();
+        move || {
+            let database: IdbDatabase = opening.result().unwrap().unchecked_into();
+            if !database.object_store_names().contains(CONVERSATIONS) {
+                let parameters = IdbObjectStoreParameters::new();
+                parameters.set_key_path(&JsValue::from_str("id"));
+                database.create_object_store_with_optional_parameters(CONVERSATIONS, &parameters).unwrap();
+            }
+        }
+    });
+    opening.set_onupgradeneeded(Some(upgrade.unchecked_ref()));
+    let database: IdbDatabase = request(&opening).await?.unchecked_into();
+
+    DATABASE.with(|d| *d.borrow_mut() = Some(database.clone()));
+    migrate_local_storage(&database).await;
+    Ok(database)
+}
+
+// Move history out of localStorage. The item is only removed once the
+// records are saved, and ids are stable, so an interrupted migration just
+// runs again.
+async fn migrate_local_storage(database: &IdbDatabase) {
+    let Some(storage) = web_sys::window().unwrap().local_storage().ok().flatten() else {
+        return;
+    };
+    let Some(json) = storage.get_item(LEGACY_STORAGE_KEY).ok().flatten() else {
+        return;
+    };
+
+    let now = js_sys::Date::now();
+    let timestamp = |date: &str, time: &str| {
+        let parsed = js_sys::Date::parse(&format!("{} {}", date, time));
+        (!parsed.is_nan()).then_some(parsed)
+    };
+    let conversations = match history::migrate_legacy(&json, now, timestamp) {
+        Ok(conversations) => conversations,
+        Err(e) => {
+            web_sys::console::error_1(&format!("Could not read the saved history: {}", e).into());
+            return;
+        }
+    };
+    match put(database, &conversations).await {
+        Ok(()) => {
+            let _ = storage.remove_item(LEGACY_STORAGE_KEY);
+        },
+        Err(e) => web_sys::console::error_2(&"Failed to move history to IndexedDB:".into(), &e),
+    }
+}
+
+// Wait for `request` and return its result
+async fn request(request: &IdbRequest) -> Result<JsValue, JsValue> {
+    let done = Promise::new(&mut |resolve, reject| {
+        request.set_onsuccess(Some(&resolve));
+        request.set_onerror(Some(&reject));
+    });
+    match JsFuture::from(done).await {
+        Ok(_) => request.result(),
+        Err(_) => Err(failure(request.error().ok().flatten())),
+    }
+}
+
+// Wait for `transaction` to commit
+async fn complete(transaction: &IdbTransaction) -> Result<(), JsValue> {
+    let done = Promise::new(&mut |resolve, reject| {
+        transaction.set_oncomplete(Some(&resolve));
+        transaction.set_onerror(Some(&reject));
+        transaction.set_onabort(Some(&reject));
+    });
+    JsFuture::from(done).await
+        .map(|_| ())
+        .map_err(|_| failure(transaction.error()))
+}
+
+fn failure(error: Option<web_sys::DomException>) -> JsValue {
+    error.map_or_else(|| JsValue::from_str("IndexedDB request failed"), JsValue::from)
+}
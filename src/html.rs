@@ -0,0 +1,88 @@
+// Safe HTML for the DOM
+//
+// Markup only reaches `set_inner_html` as `Html`, which is either a fixed
+// template written in this crate or text that has been escaped. Model
+// replies, uploaded files, saved history and error messages can hold `<` and
+// `&`, or a `<script>` after the API keys in localStorage; through here they
+// always show as text.
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Html(String);
+
+// A value filled into a template: text is escaped, `Html` is kept as it is
+pub trait ToHtml {
+    fn to_html(&self) -> Html;
+}
+
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl Html {
+    // Markup written in this crate
+    pub fn from_static(markup: &'static str) -> Html {
+        Html(markup.to_string())
+    }
+
+    pub fn text(text: &str) -> Html {
+        Html(escape(text))
+    }
+
+    // Text with its line breaks kept as `<br>`
+    pub fn lines(text: &str) -> Html {
+        Html(escape(text).replace('\n', "<br>"))
+    }
+
+    // `template` with each `{}` replaced by the next of `args`. The number of
+    // placeholders is fixed by the template, so a mismatch is a bug.
+    pub fn fill(template: &'static str, args: &[&dyn ToHtml]) -> Html {
+        let pieces: Vec<&str> = template.split("{}").collect();
+        assert_eq!(pieces.len() - 1, args.len(), "wrong number of values for template");
+
+        let mut markup = String::from(pieces[0]);
+        for (arg, piece) in args.iter().zip(&pieces[1..]) {
+            markup.push_str(arg.to_html().as_str());
+            markup.push_str(piece);
+        }
+        Html(markup)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl ToHtml for Html {
+    fn to_html(&self) -> Html {
+        self.clone()
+    }
+}
+
+impl ToHtml for String {
+    fn to_html(&self) -> Html {
+        Html::text(self)
+    }
+}
+
+impl ToHtml for &str {
+    fn to_html(&self) -> Html {
+        Html::text(self)
+    }
+}
+
+impl ToHtml for usize {
+    fn to_html(&self) -> Html {
+        Html(self.to_string())
+    }
+}
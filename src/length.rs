@@ -0,0 +1,134 @@
+// Document length targets for the "PDF Size" option
+//
+// Each size is a page range plus the section count and word budget that
+// usually fill it. The targets go into the generation prompt, and their
+// `max_tokens` is sent unless the provider settings name a limit. After a
+// compile the real page count from the TeX log (or, without a log, from the
+// PDF itself) is checked against the range, and a document that misses it can
+// be revised with `adjustment_request`.
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PdfSize {
+    #[default]
+    Small,
+    Medium,
+    Large,
+    ExtraLarge,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LengthTarget {
+    pub min_pages: usize,
+    pub max_pages: usize,
+    pub sections: usize,
+    pub words: usize,
+    pub max_tokens: u32,
+}
+
+// How a compiled document compares with its target
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PageCheck {
+    OnTarget,
+    TooShort,
+    TooLong,
+}
+
+impl PdfSize {
+    // In the order of the size dropdown
+    pub const ALL: [PdfSize; 4] = [PdfSize::Small, PdfSize::Medium, PdfSize::Large, PdfSize::ExtraLarge];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PdfSize::Small => "Small",
+            PdfSize::Medium => "Medium",
+            PdfSize::Large => "Large",
+            PdfSize::ExtraLarge => "Extra Large",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<PdfSize> {
+        PdfSize::ALL.into_iter().find(|size| size.label() == label)
+    }
+
+    pub fn target(self) -> LengthTarget {
+        // About 400 words per page with headings and equations, and roughly
+        // two tokens per word once the LaTeX markup is counted
+        let (min_pages, max_pages, sections, words, max_tokens) = match self {
+            PdfSize::Small => (1, 2, 3, 600, 3000),
+            PdfSize::Medium => (3, 5, 5, 1500, 6000),
+            PdfSize::Large => (6, 10, 7, 3500, 10000),
+            PdfSize::ExtraLarge => (12, 20, 10, 7000, 16000),
+        };
+        LengthTarget { min_pages, max_pages, sections, words, max_tokens }
+    }
+}
+
+impl LengthTarget {
+    pub fn pages(&self) -> String {
+        if self.min_pages == self.max_pages {
+            format!("{} page{}", self.max_pages, if self.max_pages == 1 { "" } else { "s" })
+        } else {
+            format!("{}-{} pages", self.min_pages, self.max_pages)
+        }
+    }
+
+    // Length instructions for the generation prompt
+    pub fn instructions(&self) -> String {
+        format!(
+            "The compiled PDF must be {} long: write about {} words of body text in {} content sections, scaling the depth of each section to fit.",
+            self.pages(),
+            self.words,
+            self.sections
+        )
+    }
+
+    pub fn check(&self, pages: usize) -> PageCheck {
+        if pages < self.min_pages {
+            PageCheck::TooShort
+        } else if pages > self.max_pages {
+            PageCheck::TooLong
+        } else {
+            PageCheck::OnTarget
+        }
+    }
+
+    // Revision request bringing a document of `pages` pages into the target
+    // range, or `None` if it is already in it
+    pub fn adjustment_request(&self, pages: usize) -> Option<String> {
+        match self.check(pages) {
+            PageCheck::OnTarget => None,
+            PageCheck::TooShort => Some(format!(
+                "The document compiles to {} page{} but should be {} long. Expand it to about {} words: deepen the existing sections with more explanation, examples and equations, and add sections up to {} in total.",
+                pages,
+                if pages == 1 { "" } else { "s" },
+                self.pages(),
+                self.words,
+                self.sections
+            )),
+            PageCheck::TooLong => Some(format!(
+                "The document compiles to {} pages but should be {} long. Condense it to about {} words: tighten the prose, drop repetition and minor details, and merge sections down to {} while keeping the key content.",
+                pages,
+                self.pages(),
+                self.words,
+                self.sections
+            )),
+        }
+    }
+}
+
+// Number of pages in a PDF, counted from its "/Type /Page" objects. Page
+// objects packed in compressed object streams are not seen, so a PDF where
+// none are found gives `None`.
+pub fn pdf_page_count(pdf: &[u8]) -> Option<usize> {
+    let mut pages = 0;
+    let mut rest = pdf;
+    while let Some(index) = rest.windows(5).position(|window| window == b"/Type") {
+        rest = &rest[index + 5..];
+        let name = rest.iter().position(|&b| !b.is_ascii_whitespace()).map_or(&[][..], |start| &rest[start..]);
+        // "/Page" but not "/Pages" or any other name starting with it
+        if name.starts_with(b"/Page") && name.get(5).is_none_or(|&b| !b.is_ascii_alphanumeric()) {
+            pages += 1;
+        }
+    }
+    (pages > 0).then_some(pages)
+}
@@ -0,0 +1,238 @@
+// LaTeX tokenizer
+//
+// Splits a source into commands, braces, brackets, math shifts, comments,
+// whitespace and text runs, each with its position. It does not expand
+// macros; it only knows enough TeX to keep the structure right: `\verb` and
+// the bodies of verbatim-like environments are single `Verbatim` tokens, so
+// braces and `\end{document}` inside them are not seen as markup.
+
+// Environments whose bodies are taken literally
+pub const VERBATIM_ENVIRONMENTS: &[&str] = &[
+    "verbatim", "verbatim*", "Verbatim", "Verbatim*", "BVerbatim", "lstlisting", "minted", "comment",
+    "filecontents", "filecontents*",
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Position {
+    // Byte offset into the source
+    pub offset: usize,
+    // 1-based line and column; columns count characters
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    // `\name`, or a backslash and one other character such as `\%` or `\\`
+    Command,
+    BeginGroup,
+    EndGroup,
+    OpenBracket,
+    CloseBracket,
+    // `$` or `$$`
+    MathShift,
+    // From `%` to the end of the line
+    Comment,
+    Whitespace,
+    Text,
+    // A `\verb` command with its argument, or the body of a verbatim-like
+    // environment
+    Verbatim,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    pub start: Position,
+}
+
+impl<'a> Token<'a> {
+    // Name of a command without its backslash
+    pub fn command_name(&self) -> Option<&'a str> {
+        match self.kind {
+            TokenKind::Command => Some(&self.text[1..]),
+            _ => None,
+        }
+    }
+
+    // Byte offset just past the token
+    pub fn end(&self) -> usize {
+        self.start.offset + self.text.len()
+    }
+}
+
+pub fn tokenize(text: &str) -> Vec<Token<'_>> {
+    let mut lexer = Lexer { text, offset: 0, line: 1, column: 1, tokens: Vec::new() };
+    lexer.run();
+    lexer.tokens
+}
+
+struct Lexer<'a> {
+    text: &'a str,
+    offset: usize,
+    line: usize,
+    column: usize,
+    tokens: Vec<Token<'a>>,
+}
+
+impl<'a> Lexer<'a> {
+    fn run(&mut self) {
+        while let Some(c) = self.peek() {
+            let start = self.offset;
+            let kind = match c {
+                '\\' => {
+                    self.bump();
+                    match self.peek() {
+                        Some(c) if c.is_ascii_alphabetic() => {
+                            self.bump_while(|c| c.is_ascii_alphabetic());
+                        },
+                        Some(_) => self.bump(),
+                        None => {},
+                    }
+                    match &self.text[start..self.offset] {
+                        "\\verb" => self.verb(),
+                        "\\begin" => {
+                            self.push(TokenKind::Command, start);
+                            self.begin_environment();
+                            continue;
+                        },
+                        "\\" => TokenKind::Text,
+                        _ => TokenKind::Command,
+                    }
+                },
+                '{' => {
+                    self.bump();
+                    TokenKind::BeginGroup
+                },
+                '}' => {
+                    self.bump();
+                    TokenKind::EndGroup
+                },
+                '[' => {
+                    self.bump();
+                    TokenKind::OpenBracket
+                },
+                ']' => {
+                    self.bump();
+                    TokenKind::CloseBracket
+                },
+                '$' => {
+                    self.bump();
+                    if self.peek() == Some('$') {
+                        self.bump();
+                    }
+                    TokenKind::MathShift
+                },
+                '%' => {
+                    self.bump_while(|c| c != '\n');
+                    TokenKind::Comment
+                },
+                c if c.is_whitespace() => {
+                    self.bump_while(char::is_whitespace);
+                    TokenKind::Whitespace
+                },
+                _ => {
+                    self.bump_while(|c| !matches!(c, '\\' | '{' | '}' | '[' | ']' | '$' | '%') && !c.is_whitespace());
+                    TokenKind::Text
+                },
+            };
+            self.push(kind, start);
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.offset..].chars().next()
+    }
+
+    fn bump(&mut self) {
+        if let Some(c) = self.peek() {
+            self.offset += c.len_utf8();
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+    }
+
+    fn bump_while(&mut self, mut keep: impl FnMut(char) -> bool) {
+        while self.peek().is_some_and(&mut keep) {
+            self.bump();
+        }
+    }
+
+    // Advance to byte offset `end`, keeping the line and column
+    fn bump_to(&mut self, end: usize) {
+        while self.offset < end {
+            self.bump();
+        }
+    }
+
+    fn push(&mut self, kind: TokenKind, start: usize) {
+        let position = self.position_of(start);
+        self.tokens.push(Token { kind, text: &self.text[start..self.offset], start: position });
+    }
+
+    // Position of `offset`, which must be on the current line or earlier in
+    // the token being pushed
+    fn position_of(&self, offset: usize) -> Position {
+        let consumed = &self.text[offset..self.offset];
+        let newlines = consumed.matches('\n').count();
+        let column = if newlines == 0 {
+            self.column - consumed.chars().count()
+        } else {
+            self.text[..offset].rsplit('\n').next().unwrap_or("").chars().count() + 1
+        };
+        Position { offset, line: self.line - newlines, column }
+    }
+
+    // After `\verb`: an optional star, a delimiter and the text up to the
+    // next delimiter or the end of the line
+    fn verb(&mut self) -> TokenKind {
+        if self.peek() == Some('*') {
+            self.bump();
+        }
+        match self.peek() {
+            Some(delimiter) if !delimiter.is_whitespace() && !delimiter.is_ascii_alphabetic() => {
+                self.bump();
+                self.bump_while(|c| c != delimiter && c != '\n');
+                if self.peek() == Some(delimiter) {
+                    self.bump();
+                }
+                TokenKind::Verbatim
+            },
+            _ => TokenKind::Command,
+        }
+    }
+
+    // After `\begin`: lex the environment name, and take the body of a
+    // verbatim-like environment up to its `\end` as one token
+    fn begin_environment(&mut self) {
+        let rest = &self.text[self.offset..];
+        let Some(name) = rest.strip_prefix('{').and_then(|rest| rest.split_once('}')).map(|(name, _)| name) else {
+            return;
+        };
+        if !VERBATIM_ENVIRONMENTS.contains(&name) {
+            return;
+        }
+
+        for kind in [TokenKind::BeginGroup, TokenKind::Text, TokenKind::EndGroup] {
+            let start = self.offset;
+            match kind {
+                TokenKind::Text => self.bump_to(start + name.len()),
+                _ => self.bump(),
+            }
+            self.push(kind, start);
+        }
+
+        let end_marker = format!("\\end{{{}}}", name);
+        let start = self.offset;
+        let body_end = self.text[start..].find(&end_marker).map_or(self.text.len(), |n| start + n);
+        self.bump_to(body_end);
+        if body_end > start {
+            self.push(TokenKind::Verbatim, start);
+        }
+    }
+}
@@ -1,31 +1,117 @@
 use wasm_bindgen::prelude::*;
 use web_sys::{
     window, Document, HtmlElement, HtmlTextAreaElement, HtmlSelectElement, HtmlInputElement, 
-    Element, Headers, Blob, Url, console, RequestInit, Response, Node
+    Element, Headers, Blob, BlobPropertyBag, Url, console, RequestInit, Response, Node,
+    AbortController, ReadableStream, ReadableStreamDefaultReader
 };
-use js_sys::{Array, JsString, Uint8Array, Reflect, JSON};
+use js_sys::{Array, JsString, Uint8Array, Reflect};
+use std::collections::{BTreeMap, HashMap};
 use std::rc::Rc;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use wasm_bindgen_futures::JsFuture;
 use web_sys::FileList;
-use serde_json;
+
+pub mod chat;
+pub mod compile_service;
+pub mod compiler;
+pub mod diff;
+pub mod doctree;
+pub mod editor;
+pub mod extract;
+pub mod history;
+pub mod history_store;
+pub mod html;
+pub mod length;
+pub mod lexer;
+pub mod lint;
+pub mod outline;
+pub mod patch;
+pub mod providers;
+pub mod proxy;
+pub mod rewrite;
+pub mod streaming;
+pub mod texlog;
+pub mod vault;
+pub mod workspace;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod mock_compile_server;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod mock_llm_server;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod proxy_server;
+
+use chat::ChatMessage;
+use compiler::{compiler_from_settings, Asset, CompileError, CompilerBackend};
+use editor::EditHistory;
+use extract::{ExtractedFile, Extraction};
+use history::{Conversation, RevisionKind};
+use html::Html;
+use length::{PageCheck, PdfSize};
+use lint::LintIssue;
+use outline::{EntryKind, Outline, OutlineEntry};
+use providers::{LlmProvider, ProviderSettings};
+use streaming::{SseParser, StreamFormat, StreamUpdate};
+use texlog::{Diagnostic, DiagnosticKind, Severity};
+use vault::{Vault, VaultError};
+use workspace::Workspace;
 
 // Structure to store generated content
 struct GeneratedContent {
     latex: String,
     pdf_blob: Option<Blob>,
     pdf_url: Option<String>,
+    // Pages in the compiled PDF, once there is one
+    page_count: Option<usize>,
+    assets: Vec<Asset>,
+    diagnostics: Vec<Diagnostic>,
     chat_history: Vec<(String, String)>,
     pdf_size: String,
     template: String,
     ai_provider: String,
+    // Id of the history conversation the document is saved in
+    conversation_id: Option<String>,
+}
+
+// The editable LaTeX view. Edits, undo and redo go into the current
+// document, and once typing pauses into its history entry.
+struct SourceEditor {
+    generated_content: Rc<RefCell<Option<GeneratedContent>>>,
+    chat_history_state: Rc<RefCell<Vec<Conversation>>>,
+    history: RefCell<EditHistory>,
+    // Pending refresh of the rest of the LaTeX view
+    refresh_timer: Cell<Option<i32>>,
 }
 
-// Structure to store API keys
+// Milliseconds without an edit before the outline and lint issues refresh
+const EDIT_REFRESH_MS: i32 = 500;
+
+// A reply shown in an AI chat message while it streams in, and optionally
+// in the LaTeX view, with a button that cancels the request
+struct ReplyView {
+    document: Document,
+    text: Element,
+    preview: bool,
+    controller: AbortController,
+}
+
+// Structure to store API keys, by provider id
+#[derive(Default)]
 struct ApiKeys {
-    claude: String,
-    perplexity: String,
-    mistral: String,
+    keys: HashMap<String, String>,
+    // Passphrase of the key vault while it is unlocked
+    vault_passphrase: Option<String>,
+}
+
+// localStorage item holding the sealed `Vault`
+const VAULT_STORAGE_KEY: &str = "api_key_vault";
+// localStorage item holding the LLM proxy URL, empty when calling vendors
+// directly
+const LLM_PROXY_STORAGE_KEY: &str = "llm_proxy_url";
+
+impl ApiKeys {
+    fn get(&self, provider_id: &str) -> String {
+        self.keys.get(provider_id).cloned().unwrap_or_default()
+    }
 }
 
 // Initialize console error panic hook for better debugging
@@ -46,6 +132,17 @@ fn create_element_with_class(tag: &str, class_name: &str) -> HtmlElement {
     element.dyn_into::<HtmlElement>().unwrap()
 }
 
+// Markup is only set from `Html`, so text in it is always escaped
+trait SetHtml {
+    fn set_html(&self, html: &Html);
+}
+
+impl SetHtml for Element {
+    fn set_html(&self, html: &Html) {
+        self.set_inner_html(html.as_str());
+    }
+}
+
 // Alert function
 #[wasm_bindgen]
 extern "C" {
@@ -83,7 +180,7 @@ pub fn main() -> Result<(), JsValue> {
     // History toggle button (moved to left)
     let history_toggle = create_element_with_class("button", "header-btn");
     history_toggle.set_id("history-toggle");
-    history_toggle.set_inner_html(r#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="20" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"><path d="M12 19V5M5 12l7-7 7 7"/></svg>"#);
+    history_toggle.set_html(&Html::from_static(r#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="20" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"><path d="M12 19V5M5 12l7-7 7 7"/></svg>"#));
     
     logo_container.append_child(&history_toggle)?;
     header.append_child(&logo_container)?;
@@ -94,27 +191,27 @@ pub fn main() -> Result<(), JsValue> {
     // Profile button
     let profile_toggle = create_element_with_class("button", "header-btn");
     profile_toggle.set_id("profile-toggle");
-    profile_toggle.set_inner_html(r#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="20" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"><path d="M19 21v-2a4 4 0 0 0-4-4H9a4 4 0 0 0-4 4v2"/><circle cx="12" cy="7" r="4"/></svg>"#);
+    profile_toggle.set_html(&Html::from_static(r#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="20" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"><path d="M19 21v-2a4 4 0 0 0-4-4H9a4 4 0 0 0-4 4v2"/><circle cx="12" cy="7" r="4"/></svg>"#));
     
     // Theme toggle with dropdown
     let theme_container = create_element_with_class("div", "theme-container");
-    theme_container.set_attribute("style", "position: relative;");
+    theme_container.set_attribute("style", "position: relative;")?;
     
     let theme_toggle = create_element_with_class("button", "header-btn");
     theme_toggle.set_id("theme-toggle");
-    theme_toggle.set_inner_html(r#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="20" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"><circle cx="12" cy="12" r="5"/><path d="M12 1v2M12 21v2M4.22 4.22l1.42 1.42M18.36 18.36l1.42 1.42M1 12h2M21 12h2M4.22 19.78l1.42-1.42M18.36 5.64l1.42-1.42"/></svg>"#);
+    theme_toggle.set_html(&Html::from_static(r#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="20" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"><circle cx="12" cy="12" r="5"/><path d="M12 1v2M12 21v2M4.22 4.22l1.42 1.42M18.36 18.36l1.42 1.42M1 12h2M21 12h2M4.22 19.78l1.42-1.42M18.36 5.64l1.42-1.42"/></svg>"#));
     
     let theme_dropdown = create_element_with_class("div", "theme-dropdown");
-    theme_dropdown.set_attribute("style", "position: absolute; top: 100%; right: 0; background: hsl(var(--card)); border: 1px solid hsl(var(--border)); border-radius: 0.5rem; padding: 0.5rem; z-index: 100; display: none;");
+    theme_dropdown.set_attribute("style", "position: absolute; top: 100%; right: 0; background: hsl(var(--card)); border: 1px solid hsl(var(--border)); border-radius: 0.5rem; padding: 0.5rem; z-index: 100; display: none;")?;
     
     let light_option = create_element_with_class("button", "theme-option");
-    light_option.set_inner_html(r#"<svg xmlns="http://www.w3.org/2000/svg" width="16" height="16" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"><circle cx="12" cy="12" r="5"/><path d="M12 1v2M12 21v2M4.22 4.22l1.42 1.42M18.36 18.36l1.42 1.42M1 12h2M21 12h2M4.22 19.78l1.42-1.42M18.36 5.64l1.42-1.42"/></svg> Light"#);
+    light_option.set_html(&Html::from_static(r#"<svg xmlns="http://www.w3.org/2000/svg" width="16" height="16" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"><circle cx="12" cy="12" r="5"/><path d="M12 1v2M12 21v2M4.22 4.22l1.42 1.42M18.36 18.36l1.42 1.42M1 12h2M21 12h2M4.22 19.78l1.42-1.42M18.36 5.64l1.42-1.42"/></svg> Light"#));
     
     let dark_option = create_element_with_class("button", "theme-option");
-    dark_option.set_inner_html(r#"<svg xmlns="http://www.w3.org/2000/svg" width="16" height="16" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"><path d="M21 12.79A9 9 0 1 1 11.21 3 7 7 0 0 0 21 12.79z"/></svg> Dark"#);
+    dark_option.set_html(&Html::from_static(r#"<svg xmlns="http://www.w3.org/2000/svg" width="16" height="16" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"><path d="M21 12.79A9 9 0 1 1 11.21 3 7 7 0 0 0 21 12.79z"/></svg> Dark"#));
     
     let system_option = create_element_with_class("button", "theme-option");
-    system_option.set_inner_html(r#"<svg xmlns="http://www.w3.org/2000/svg" width="16" height="16" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"><rect x="2" y="3" width="20" height="14" rx="2" ry="2"/><path d="M8 21h8M12 17v4"/></svg> System"#);
+    system_option.set_html(&Html::from_static(r#"<svg xmlns="http://www.w3.org/2000/svg" width="16" height="16" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"><rect x="2" y="3" width="20" height="14" rx="2" ry="2"/><path d="M8 21h8M12 17v4"/></svg> System"#));
     
     theme_dropdown.append_child(&light_option)?;
     theme_dropdown.append_child(&dark_option)?;
@@ -136,7 +233,13 @@ pub fn main() -> Result<(), JsValue> {
     history_panel.set_id("history-panel");
     
     let history_header = create_element_with_class("div", "history-header");
-    history_header.set_inner_html("<h3>Chat History</h3>");
+    history_header.set_html(&Html::from_static("<h3>Chat History</h3>"));
+    
+    let history_clear_btn = create_element_with_class("button", "history-clear-btn");
+    history_clear_btn.set_id("history-clear-btn");
+    history_clear_btn.set_attribute("title", "Delete all conversations that are not pinned")?;
+    history_clear_btn.set_text_content(Some("Clear"));
+    history_header.append_child(&history_clear_btn)?;
     
     let new_chat_btn = create_element_with_class("button", "btn-primary");
     new_chat_btn.set_id("new-chat-btn");
@@ -145,24 +248,23 @@ pub fn main() -> Result<(), JsValue> {
     let history_list = create_element_with_class("div", "history-list");
     history_list.set_id("history-list");
     
-    // Load saved history
+    // Saved history is loaded from IndexedDB once the page is up; until
+    // then, and when there is none, the list shows the empty state
     if let Ok(Some(storage)) = web_sys::window().unwrap().local_storage() {
-        if let Ok(Some(history)) = storage.get_item("chat_history") {
-            history_list.set_inner_html(&history);
-        } else {
-            history_list.set_inner_html(r#"
-                <div class="empty-state">
-                    <svg xmlns="http://www.w3.org/2000/svg" width="24" height="24" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round">
-                        <path d="M21 15a2 2 0 0 1-2 2H7l-4 4V5a2 2 0 0 1-2-2V5a2 2 0 0 0 2 2h14a2 2 0 0 0 2-2V5a2 2 0 0 1-2-2z"></path>
-                    </svg>
-                    <p>No chat history yet</p>
-                </div>
-            "#);
-        }
+        let _ = storage.remove_item("chat_history");
     }
+    history_list.append_child(&history_empty_state(&document, true))?;
+    
+    // Search over the names, topics and documents of the conversations
+    let history_search = document.create_element("input")?.dyn_into::<HtmlInputElement>()?;
+    history_search.set_class_name("form-input history-search");
+    history_search.set_id("history-search");
+    history_search.set_attribute("type", "search")?;
+    history_search.set_attribute("placeholder", "Search conversations")?;
     
     history_panel.append_child(&history_header)?;
     history_panel.append_child(&new_chat_btn)?;
+    history_panel.append_child(&history_search)?;
     history_panel.append_child(&history_list)?;
     
     // Profile panel
@@ -170,61 +272,246 @@ pub fn main() -> Result<(), JsValue> {
     profile_panel.set_id("profile-panel");
     
     let profile_header = create_element_with_class("div", "profile-header");
-    profile_header.set_inner_html("<h3>Profile Settings</h3>");
+    profile_header.set_html(&Html::from_static("<h3>Profile Settings</h3>"));
     
     let api_keys_form = create_element_with_class("div", "api-keys-form");
     
-    // Claude API Key
-    let claude_group = create_element_with_class("div", "form-group");
-    let claude_label = create_element_with_class("label", "form-label");
-    claude_label.set_text_content(Some("Claude API Key"));
+    // Key vault: the keys encrypted under a passphrase and unlocked for the
+    // session
+    let vault_group = create_element_with_class("div", "form-group vault-group");
+    let vault_label = create_element_with_class("label", "form-label");
+    vault_label.set_text_content(Some("Key Vault Passphrase"));
+    
+    let vault_passphrase = document.create_element("input")?;
+    vault_passphrase.set_class_name("form-input");
+    vault_passphrase.set_id("vault-passphrase");
+    vault_passphrase.set_attribute("type", "password")?;
+    vault_passphrase.set_attribute("autocomplete", "off")?;
+    vault_passphrase.set_attribute("placeholder", "Passphrase to encrypt the API keys")?;
+    
+    let vault_status = create_element_with_class("p", "vault-status");
+    vault_status.set_id("vault-status");
+    
+    let vault_actions = create_element_with_class("div", "vault-actions");
+    for (id, label) in [("vault-unlock-btn", "Unlock"), ("vault-lock-btn", "Lock"), ("vault-forget-btn", "Forget keys")] {
+        let button = create_element_with_class("button", "btn-secondary");
+        button.set_id(id);
+        button.set_text_content(Some(label));
+        vault_actions.append_child(&button)?;
+    }
+    
+    vault_group.append_child(&vault_label)?;
+    vault_group.append_child(&vault_passphrase)?;
+    vault_group.append_child(&vault_status)?;
+    vault_group.append_child(&vault_actions)?;
+    
+    // One collapsible section per provider with its key and generation
+    // settings; the selected provider's section starts open
+    let saved_provider = web_sys::window().unwrap().local_storage().ok().flatten()
+        .and_then(|storage| storage.get_item("api_provider").ok().flatten());
+    let mut key_groups = Vec::new();
+    for provider in providers::PROVIDERS {
+        let section = create_element_with_class("details", "provider-settings");
+        if saved_provider.as_deref().unwrap_or(providers::PROVIDERS[0].name()) == provider.name() {
+            section.set_attribute("open", "")?;
+        }
+        let summary = create_element_with_class("summary", "provider-settings-title");
+        summary.set_text_content(Some(provider.name()));
+        section.append_child(&summary)?;
+        
+        let key_group = create_element_with_class("div", "form-group");
+        let key_label = create_element_with_class("label", "form-label");
+        key_label.set_text_content(Some("API Key"));
+        
+        let key_input = document.create_element("input")?;
+        key_input.set_class_name("form-input");
+        key_input.set_id(&format!("{}-key", provider.id()));
+        key_input.set_attribute("type", "password")?;
+        key_input.set_attribute("placeholder", provider.key_placeholder())?;
+        
+        key_group.append_child(&key_label)?;
+        key_group.append_child(&key_input)?;
+        section.append_child(&key_group)?;
+        
+        let default_max_tokens = provider.default_max_tokens().map_or("Provider default".to_string(), |tokens| tokens.to_string());
+        let default_temperature = provider.default_temperature().map_or("Provider default".to_string(), |temperature| temperature.to_string());
+        let model_placeholder = match provider.default_model() {
+            "" => "llama3.1",
+            model => model,
+        };
+        let fields = [
+            ("base_url", "Base URL", "url", "http://localhost:11434/v1"),
+            ("model", "Model", "text", model_placeholder),
+            ("max_tokens", "Max tokens", "number", &default_max_tokens),
+            ("temperature", "Temperature", "number", &default_temperature),
+            ("top_p", "Top-p", "number", "Provider default"),
+            ("system_prompt", "System prompt", "textarea", chat::SYSTEM_PROMPT),
+        ];
+        for (name, label, input_type, placeholder) in fields {
+            // Only self-hosted servers need their address
+            if name == "base_url" && !provider.has_custom_endpoint() {
+                continue;
+            }
+            
+            let field_group = create_element_with_class("div", "form-group");
+            let field_label = create_element_with_class("label", "form-label");
+            field_label.set_text_content(Some(label));
+            
+            let field_input = if input_type == "textarea" {
+                let textarea = create_element_with_class("textarea", "form-input form-textarea");
+                textarea.set_attribute("rows", "4")?;
+                textarea
+            } else {
+                let input = create_element_with_class("input", "form-input");
+                input.set_attribute("type", input_type)?;
+                input
+            };
+            field_input.set_id(&format!("{}-{}", provider.id(), name.replace('_', "-")));
+            field_input.set_attribute("placeholder", placeholder)?;
+            match name {
+                "max_tokens" => field_input.set_attribute("min", "1")?,
+                "temperature" => {
+                    field_input.set_attribute("min", "0")?;
+                    field_input.set_attribute("max", "2")?;
+                    field_input.set_attribute("step", "0.1")?;
+                },
+                "top_p" => {
+                    field_input.set_attribute("min", "0")?;
+                    field_input.set_attribute("max", "1")?;
+                    field_input.set_attribute("step", "0.05")?;
+                },
+                _ => {}
+            }
+            
+            // Suggest current models without limiting the input to them
+            if name == "model" && !provider.suggested_models().is_empty() {
+                let list_id = format!("{}-models", provider.id());
+                let datalist = document.create_element("datalist")?;
+                datalist.set_id(&list_id);
+                for model in provider.suggested_models() {
+                    let option = document.create_element("option")?;
+                    option.set_attribute("value", model)?;
+                    datalist.append_child(&option)?;
+                }
+                field_input.set_attribute("list", &list_id)?;
+                field_group.append_child(&datalist)?;
+            }
+            
+            if let Ok(Some(storage)) = web_sys::window().unwrap().local_storage() {
+                if let Ok(Some(value)) = storage.get_item(&format!("{}_{}", provider.id(), name)) {
+                    set_form_value(&field_input, &value);
+                }
+            }
+            
+            field_group.append_child(&field_label)?;
+            field_group.append_child(&field_input)?;
+            section.append_child(&field_group)?;
+        }
+        
+        key_groups.push(section);
+    }
+    
+    // LaTeX compiler backend
+    let compiler_group = create_element_with_class("div", "form-group");
+    let compiler_label = create_element_with_class("label", "form-label");
+    compiler_label.set_text_content(Some("LaTeX Compiler"));
+    
+    let compiler_select = document.create_element("select")?.dyn_into::<HtmlSelectElement>()?;
+    compiler_select.set_class_name("form-select");
+    compiler_select.set_id("compiler-backend");
     
-    let claude_input = document.create_element("input")?;
-    claude_input.set_class_name("form-input");
-    claude_input.set_id("claude-key");
-    claude_input.set_attribute("type", "password")?;
-    claude_input.set_attribute("placeholder", "sk-ant-...")?;
+    for backend in CompilerBackend::ALL.iter() {
+        let option = document.create_element("option")?;
+        option.set_attribute("value", backend.id())?;
+        option.set_text_content(Some(backend.label()));
+        compiler_select.append_child(&option)?;
+    }
     
-    claude_group.append_child(&claude_label)?;
-    claude_group.append_child(&claude_input)?;
+    compiler_group.append_child(&compiler_label)?;
+    compiler_group.append_child(&compiler_select)?;
     
-    // Perplexity API Key
-    let perplexity_group = create_element_with_class("div", "form-group");
-    let perplexity_label = create_element_with_class("label", "form-label");
-    perplexity_label.set_text_content(Some("Perplexity API Key"));
+    // Compile service URL
+    let compile_url_group = create_element_with_class("div", "form-group");
+    let compile_url_label = create_element_with_class("label", "form-label");
+    compile_url_label.set_text_content(Some("Compile Service URL"));
     
-    let perplexity_input = document.create_element("input")?;
-    perplexity_input.set_class_name("form-input");
-    perplexity_input.set_id("perplexity-key");
-    perplexity_input.set_attribute("type", "password")?;
-    perplexity_input.set_attribute("placeholder", "pplx-...")?;
+    let compile_url_input = document.create_element("input")?.dyn_into::<HtmlInputElement>()?;
+    compile_url_input.set_class_name("form-input");
+    compile_url_input.set_id("compile-service-url");
+    compile_url_input.set_attribute("type", "url")?;
+    compile_url_input.set_attribute("placeholder", "https://latex.example.com/compile")?;
     
-    perplexity_group.append_child(&perplexity_label)?;
-    perplexity_group.append_child(&perplexity_input)?;
+    compile_url_group.append_child(&compile_url_label)?;
+    compile_url_group.append_child(&compile_url_input)?;
+    
+    // Load saved compiler settings
+    if let Ok(Some(storage)) = web_sys::window().unwrap().local_storage() {
+        if let Ok(Some(backend)) = storage.get_item("compiler_backend") {
+            compiler_select.set_value(&backend);
+        }
+        if let Ok(Some(url)) = storage.get_item("compile_service_url") {
+            compile_url_input.set_value(&url);
+        }
+    }
     
-    // Mistral API Key
-    let mistral_group = create_element_with_class("div", "form-group");
-    let mistral_label = create_element_with_class("label", "form-label");
-    mistral_label.set_text_content(Some("Mistral API Key"));
+    // LLM proxy URL; when set, requests go through the proxy, which holds
+    // the API keys
+    let proxy_url_group = create_element_with_class("div", "form-group");
+    let proxy_url_label = create_element_with_class("label", "form-label");
+    proxy_url_label.set_text_content(Some("LLM Proxy URL"));
     
-    let mistral_input = document.create_element("input")?;
-    mistral_input.set_class_name("form-input");
-    mistral_input.set_id("mistral-key");
-    mistral_input.set_attribute("type", "password")?;
-    mistral_input.set_attribute("placeholder", "sk-...")?;
+    let proxy_url_input = document.create_element("input")?.dyn_into::<HtmlInputElement>()?;
+    proxy_url_input.set_class_name("form-input");
+    proxy_url_input.set_id("llm-proxy-url");
+    proxy_url_input.set_attribute("type", "text")?;
+    proxy_url_input.set_attribute("placeholder", proxy::DEFAULT_PATH)?;
+    proxy_url_input.set_value(&llm_proxy_url().unwrap_or_default());
     
-    mistral_group.append_child(&mistral_label)?;
-    mistral_group.append_child(&mistral_input)?;
+    proxy_url_group.append_child(&proxy_url_label)?;
+    proxy_url_group.append_child(&proxy_url_input)?;
     
     // Save button
     let save_btn = create_element_with_class("button", "btn-primary");
     save_btn.set_id("save-keys-btn");
-    save_btn.set_text_content(Some("Save API Keys"));
+    save_btn.set_text_content(Some("Save Settings"));
+    
+    // Workspace export and import: conversations and settings, without the
+    // API keys, as one file
+    let workspace_group = create_element_with_class("div", "form-group workspace-group");
+    let workspace_label = create_element_with_class("label", "form-label");
+    workspace_label.set_text_content(Some("Workspace"));
+    
+    let workspace_status = create_element_with_class("p", "workspace-status");
+    workspace_status.set_text_content(Some("Back up or move your conversations and settings. API keys are not included."));
+    
+    let workspace_actions = create_element_with_class("div", "workspace-actions");
+    for (id, label) in [("workspace-export-btn", "Export"), ("workspace-import-btn", "Import")] {
+        let button = create_element_with_class("button", "btn-secondary");
+        button.set_id(id);
+        button.set_text_content(Some(label));
+        workspace_actions.append_child(&button)?;
+    }
     
-    api_keys_form.append_child(&claude_group)?;
-    api_keys_form.append_child(&perplexity_group)?;
-    api_keys_form.append_child(&mistral_group)?;
+    let workspace_input = document.create_element("input")?;
+    workspace_input.set_id("workspace-file");
+    workspace_input.set_attribute("type", "file")?;
+    workspace_input.set_attribute("accept", ".json,application/json")?;
+    workspace_input.set_attribute("style", "display: none")?;
+    
+    workspace_group.append_child(&workspace_label)?;
+    workspace_group.append_child(&workspace_status)?;
+    workspace_group.append_child(&workspace_actions)?;
+    workspace_group.append_child(&workspace_input)?;
+    
+    api_keys_form.append_child(&proxy_url_group)?;
+    api_keys_form.append_child(&vault_group)?;
+    for key_group in &key_groups {
+        api_keys_form.append_child(key_group)?;
+    }
+    api_keys_form.append_child(&compiler_group)?;
+    api_keys_form.append_child(&compile_url_group)?;
     api_keys_form.append_child(&save_btn)?;
+    api_keys_form.append_child(&workspace_group)?;
     
     profile_panel.append_child(&profile_header)?;
     profile_panel.append_child(&api_keys_form)?;
@@ -236,14 +523,14 @@ pub fn main() -> Result<(), JsValue> {
     // Chat history
     let chat_history = create_element_with_class("div", "chat-history");
     chat_history.set_id("chat-history");
-    chat_history.set_inner_html(r#"
+    chat_history.set_html(&Html::from_static(r#"
         <div class="empty-state">
             <svg xmlns="http://www.w3.org/2000/svg" width="24" height="24" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round">
                 <path d="M21 15a2 2 0 0 1-2 2H7l-4 4V5a2 2 0 0 1-2-2V5a2 2 0 0 0 2 2h14a2 2 0 0 0 2-2V5a2 2 0 0 1-2-2z"></path>
             </svg>
             <p style="font-size: 1rem; max-width: 80%; text-align: center; color: hsl(var(--muted-foreground));">Your chat history will appear here</p>
         </div>
-    "#);
+    "#));
     
     // Chat input container
     let chat_input_container = create_element_with_class("div", "chat-input-container");
@@ -264,33 +551,33 @@ pub fn main() -> Result<(), JsValue> {
     
     // Attach button
     let attach_btn = create_element_with_class("button", "attach-btn");
-    attach_btn.set_inner_html(r#"
+    attach_btn.set_html(&Html::from_static(r#"
         <svg xmlns="http://www.w3.org/2000/svg" width="20" height="20" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round">
             <path d="M21 15v4a2 2 0 0 1-2 2H5a2 2 0 0 1-2-2v-4"></path>
             <polyline points="17 8 12 3 7 8"></polyline>
             <line x1="12" y1="3" x2="12" y2="15"></line>
         </svg>
-    "#);
+    "#));
     
     let file_input = document.create_element("input")?;
     file_input.set_id("file-upload");
     file_input.set_attribute("type", "file")?;
-    file_input.set_attribute("accept", ".tex")?;
+    file_input.set_attribute("accept", ".tex,.bib,.sty,.cls,.png,.jpg,.jpeg,.pdf,.eps")?;
     file_input.set_attribute("style", "display: none")?;
     
     // More options button
     let more_options_btn = create_element_with_class("button", "more-options-btn");
-    more_options_btn.set_inner_html(r#"
+    more_options_btn.set_html(&Html::from_static(r#"
         <svg xmlns="http://www.w3.org/2000/svg" width="20" height="20" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round">
             <circle cx="12" cy="12" r="1"></circle>
             <circle cx="12" cy="5" r="1"></circle>
             <circle cx="12" cy="19" r="1"></circle>
         </svg>
-    "#);
+    "#));
     
     // More options dropdown
     let more_options_dropdown = create_element_with_class("div", "more-options-dropdown");
-    more_options_dropdown.set_attribute("style", "display: none;");
+    more_options_dropdown.set_attribute("style", "display: none;")?;
     
     // Options row container
     let options_row = create_element_with_class("div", "options-row");
@@ -323,10 +610,9 @@ pub fn main() -> Result<(), JsValue> {
     api_select.set_class_name("form-select");
     api_select.set_id("api-provider");
     
-    let providers = ["Claude", "Perplexity", "Mistral"];
-    for provider in providers.iter() {
+    for provider in providers::PROVIDERS {
         let option = document.create_element("option")?;
-        option.set_text_content(Some(provider));
+        option.set_text_content(Some(provider.name()));
         api_select.append_child(&option)?;
     }
     
@@ -349,10 +635,10 @@ pub fn main() -> Result<(), JsValue> {
     pdf_size_select.set_class_name("form-select");
     pdf_size_select.set_id("pdf-size-select");
     
-    let sizes = ["Small", "Medium", "Large", "Extra Large"];
-    for size in sizes.iter() {
+    for size in PdfSize::ALL {
         let option = document.create_element("option")?;
-        option.set_text_content(Some(size));
+        option.set_attribute("value", size.label())?;
+        option.set_text_content(Some(&format!("{} ({})", size.label(), size.target().pages())));
         pdf_size_select.append_child(&option)?;
     }
     
@@ -364,7 +650,68 @@ pub fn main() -> Result<(), JsValue> {
     options_row.append_child(&api_form_group)?;
     options_row.append_child(&pdf_size_group)?;
     
+    // Generation modes: auto-fix compiles each answer and sends the errors
+    // back to the model; edit mode asks for edits instead of whole documents
+    let modes_row = create_element_with_class("div", "options-row modes-row");
+    
+    let edit_mode_label = create_element_with_class("label", "form-label checkbox-label");
+    let edit_mode_toggle = document.create_element("input")?.dyn_into::<HtmlInputElement>()?;
+    edit_mode_toggle.set_id("edit-mode-toggle");
+    edit_mode_toggle.set_type("checkbox");
+    edit_mode_label.append_child(&edit_mode_toggle)?;
+    edit_mode_label.append_child(&document.create_text_node("Reply with edits on follow-ups"))?;
+    
+    let auto_fix_label = create_element_with_class("label", "form-label checkbox-label");
+    let auto_fix_toggle = document.create_element("input")?.dyn_into::<HtmlInputElement>()?;
+    auto_fix_toggle.set_id("auto-fix-toggle");
+    auto_fix_toggle.set_type("checkbox");
+    auto_fix_label.append_child(&auto_fix_toggle)?;
+    auto_fix_label.append_child(&document.create_text_node("Auto-fix compile errors"))?;
+    
+    let lint_label = create_element_with_class("label", "form-label checkbox-label");
+    let lint_toggle = document.create_element("input")?.dyn_into::<HtmlInputElement>()?;
+    lint_toggle.set_id("lint-toggle");
+    lint_toggle.set_type("checkbox");
+    lint_label.append_child(&lint_toggle)?;
+    lint_label.append_child(&document.create_text_node("Include lint issues in follow-ups"))?;
+    
+    let auto_fix_rounds_group = create_element_with_class("div", "form-group");
+    let auto_fix_rounds_label = create_element_with_class("label", "form-label");
+    auto_fix_rounds_label.set_text_content(Some("Max fix rounds"));
+    
+    let auto_fix_rounds = document.create_element("input")?.dyn_into::<HtmlInputElement>()?;
+    auto_fix_rounds.set_class_name("form-input");
+    auto_fix_rounds.set_id("auto-fix-rounds");
+    auto_fix_rounds.set_type("number");
+    auto_fix_rounds.set_min("1");
+    auto_fix_rounds.set_max("10");
+    auto_fix_rounds.set_value("3");
+    
+    // Load saved generation modes
+    if let Ok(Some(storage)) = web_sys::window().unwrap().local_storage() {
+        if let Ok(Some(enabled)) = storage.get_item("edit_mode") {
+            edit_mode_toggle.set_checked(enabled == "true");
+        }
+        if let Ok(Some(enabled)) = storage.get_item("auto_fix") {
+            auto_fix_toggle.set_checked(enabled == "true");
+        }
+        if let Ok(Some(rounds)) = storage.get_item("auto_fix_rounds") {
+            auto_fix_rounds.set_value(&rounds);
+        }
+        if let Ok(Some(enabled)) = storage.get_item("lint_in_prompt") {
+            lint_toggle.set_checked(enabled == "true");
+        }
+    }
+    
+    auto_fix_rounds_group.append_child(&auto_fix_rounds_label)?;
+    auto_fix_rounds_group.append_child(&auto_fix_rounds)?;
+    modes_row.append_child(&edit_mode_label)?;
+    modes_row.append_child(&auto_fix_label)?;
+    modes_row.append_child(&auto_fix_rounds_group)?;
+    modes_row.append_child(&lint_label)?;
+    
     more_options_dropdown.append_child(&options_row)?;
+    more_options_dropdown.append_child(&modes_row)?;
     
     attachment_container.append_child(&attach_btn)?;
     attachment_container.append_child(&file_input)?;
@@ -374,18 +721,30 @@ pub fn main() -> Result<(), JsValue> {
     // Send button
     let send_btn = create_element_with_class("button", "send-btn");
     send_btn.set_id("send-btn");
-    send_btn.set_inner_html(r#"
+    send_btn.set_html(&Html::from_static(r#"
         <svg xmlns="http://www.w3.org/2000/svg" width="20" height="20" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round">
             <line x1="22" y1="2" x2="11" y2="13"></line>
             <polygon points="22 2 15 22 11 13 2 9 22 2"></polygon>
         </svg>
-    "#);
+    "#));
+    
+    // Set from the outline: the next request rewrites only this section
+    let section_scope = create_element_with_class("div", "section-scope");
+    section_scope.set_id("section-scope");
+    section_scope.set_attribute("hidden", "")?;
+    let section_scope_label = create_element_with_class("span", "section-scope-label");
+    let section_scope_clear = create_element_with_class("button", "section-scope-clear");
+    section_scope_clear.set_attribute("title", "Revise the whole document")?;
+    section_scope_clear.set_text_content(Some("×"));
+    section_scope.append_child(&section_scope_label)?;
+    section_scope.append_child(&section_scope_clear)?;
     
     let input_row = create_element_with_class("div", "input-row");
     input_row.append_child(&attachment_container)?;
-    input_row.append_child(&chat_textarea.unchecked_ref())?;
+    input_row.append_child(chat_textarea.unchecked_ref())?;
     input_row.append_child(&send_btn)?;
     
+    chat_controls.append_child(&section_scope)?;
     chat_controls.append_child(&input_row)?;
     chat_controls.append_child(&more_options_dropdown)?;
     
@@ -424,12 +783,12 @@ pub fn main() -> Result<(), JsValue> {
     let preview_content = create_element_with_class("div", "preview-content");
     preview_content.set_id("preview-content");
     preview_content.set_attribute("style", "flex: 1; overflow-y: auto; padding: 1rem;")?;
-    preview_content.set_inner_html(r#"<div class="empty-state"><svg xmlns="http://www.w3.org/2000/svg" width="24" height="24" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"><path d="M14 2H6a2 2 0 0 0-2 2v16a2 2 0 0 0 2 2h12a2 2 0 0 0 2-2V8z"></path><polyline points="14 2 14 8 20 8"></polyline><line x1="16" y1="13" x2="8" y2="13"></line><line x1="16" y1="17" x2="8" y2="17"></line><polyline points="10 9 9 9 8 9"></polyline></svg><p>Generated content will appear here</p></div>"#);
+    preview_content.set_html(&Html::from_static(r#"<div class="empty-state"><svg xmlns="http://www.w3.org/2000/svg" width="24" height="24" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"><path d="M14 2H6a2 2 0 0 0-2 2v16a2 2 0 0 0 2 2h12a2 2 0 0 0 2-2V8z"></path><polyline points="14 2 14 8 20 8"></polyline><line x1="16" y1="13" x2="8" y2="13"></line><line x1="16" y1="17" x2="8" y2="17"></line><polyline points="10 9 9 9 8 9"></polyline></svg><p>Generated content will appear here</p></div>"#));
     
     // Download button (small pill in corner)
     let download_btn = create_element_with_class("button", "download-pill");
     download_btn.set_id("download-btn");
-    download_btn.set_inner_html(r#"<svg xmlns="http://www.w3.org/2000/svg" width="16" height="16" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"><path d="M21 15v4a2 2 0 0 1-2 2H5a2 2 0 0 1-2-2v-4"></path><polyline points="7 10 12 15 17 10"></polyline><line x1="12" y1="15" x2="12" y2="3"></line></svg>"#);
+    download_btn.set_html(&Html::from_static(r#"<svg xmlns="http://www.w3.org/2000/svg" width="16" height="16" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"><path d="M21 15v4a2 2 0 0 1-2 2H5a2 2 0 0 1-2-2v-4"></path><polyline points="7 10 12 15 17 10"></polyline><line x1="12" y1="15" x2="12" y2="3"></line></svg>"#));
     download_btn.set_attribute("disabled", "true")?;
     download_btn.set_attribute("style", "position: absolute; bottom: 1rem; right: 1rem; z-index: 10;")?;
     
@@ -452,43 +811,47 @@ pub fn main() -> Result<(), JsValue> {
     let document_rc = Rc::new(RefCell::new(document.clone()));
     
     // Store generated content
-    let generated_content = Rc::new(RefCell::new(None));
+    let generated_content: Rc<RefCell<Option<GeneratedContent>>> = Rc::new(RefCell::new(None));
     
     // Store chat history
-    let chat_history_state = Rc::new(RefCell::new(Vec::new()));
+    let chat_history_state: Rc<RefCell<Vec<Conversation>>> = Rc::new(RefCell::new(Vec::new()));
+    
+    // Load saved history
+    wasm_bindgen_futures::spawn_local({
+        let document = document.clone();
+        let chat_history_state = chat_history_state.clone();
+        async move {
+            match history_store::load().await {
+                Ok(conversations) if !conversations.is_empty() => {
+                    // Anything generated while loading goes after the saved entries
+                    let mut history = chat_history_state.borrow_mut();
+                    let added = std::mem::replace(&mut *history, conversations);
+                    history.extend(added);
+                    update_history_panel(&document, &history);
+                },
+                Ok(_) => {},
+                Err(e) => console::error_2(&"Failed to load history:".into(), &e),
+            }
+        }
+    });
     
     // Store API keys
-    let api_keys = Rc::new(RefCell::new(ApiKeys {
-        claude: String::new(),
-        perplexity: String::new(),
-        mistral: String::new(),
-    }));
+    let api_keys = Rc::new(RefCell::new(ApiKeys::default()));
     
-    // Load saved API keys
-    {
+    // Load saved API keys; keys in the vault wait until it is unlocked
+    if saved_vault().is_none() {
         if let Ok(Some(storage)) = web_sys::window().unwrap().local_storage() {
-            if let Ok(Some(claude_key)) = storage.get_item("claude_api_key") {
-                document.get_element_by_id("claude-key").unwrap()
-                    .dyn_into::<HtmlInputElement>().unwrap()
-                    .set_value(&claude_key);
-                api_keys.borrow_mut().claude = claude_key;
-            }
-            
-            if let Ok(Some(perplexity_key)) = storage.get_item("perplexity_api_key") {
-                document.get_element_by_id("perplexity-key").unwrap()
-                    .dyn_into::<HtmlInputElement>().unwrap()
-                    .set_value(&perplexity_key);
-                api_keys.borrow_mut().perplexity = perplexity_key;
-            }
-            
-            if let Ok(Some(mistral_key)) = storage.get_item("mistral_api_key") {
-                document.get_element_by_id("mistral-key").unwrap()
-                    .dyn_into::<HtmlInputElement>().unwrap()
-                    .set_value(&mistral_key);
-                api_keys.borrow_mut().mistral = mistral_key;
+            for provider in providers::PROVIDERS {
+                if let Ok(Some(key)) = storage.get_item(&format!("{}_api_key", provider.id())) {
+                    document.get_element_by_id(&format!("{}-key", provider.id())).unwrap()
+                        .dyn_into::<HtmlInputElement>().unwrap()
+                        .set_value(&key);
+                    api_keys.borrow_mut().keys.insert(provider.id().to_string(), key);
+                }
             }
         }
     }
+    update_vault_status(&document, &api_keys.borrow());
     
     // Get elements for event listeners
     let send_btn = document.get_element_by_id("send-btn").unwrap();
@@ -601,13 +964,11 @@ pub fn main() -> Result<(), JsValue> {
     
     // Close dropdown when clicking outside
     {
-        let document_clone = document_rc.clone();
         let theme_dropdown_element = theme_dropdown_element.clone();
         let theme_toggle_element = theme_toggle_element.clone();
         let click_callback = Closure::wrap(Box::new(move |event: web_sys::MouseEvent| {
             if let Some(target) = event.target() {
                 let target_node = target.dyn_into::<Node>().unwrap();
-                let document = document_clone.borrow();
                 
                 if !theme_toggle_element.contains(Some(&target_node)) && 
                    !theme_dropdown_element.contains(Some(&target_node)) {
@@ -698,43 +1059,367 @@ pub fn main() -> Result<(), JsValue> {
         let api_select = api_select.clone();
         let save_keys_callback = Closure::wrap(Box::new(move || {
             let document = get_document();
-            let claude_key = document.get_element_by_id("claude-key").unwrap()
-                .dyn_into::<HtmlInputElement>().unwrap()
+            let mut keys: Vec<(String, String)> = providers::PROVIDERS.iter()
+                .map(|provider| {
+                    let key = document.get_element_by_id(&format!("{}-key", provider.id())).unwrap()
+                        .dyn_into::<HtmlInputElement>().unwrap()
+                        .value();
+                    (provider.id().to_string(), key)
+                })
+                .collect();
+            
+            let compiler_backend = document.get_element_by_id("compiler-backend").unwrap()
+                .dyn_into::<HtmlSelectElement>().unwrap()
                 .value();
             
-            let perplexity_key = document.get_element_by_id("perplexity-key").unwrap()
+            let compile_service_url = document.get_element_by_id("compile-service-url").unwrap()
                 .dyn_into::<HtmlInputElement>().unwrap()
                 .value();
             
-            let mistral_key = document.get_element_by_id("mistral-key").unwrap()
+            let llm_proxy_url = document.get_element_by_id("llm-proxy-url").unwrap()
                 .dyn_into::<HtmlInputElement>().unwrap()
                 .value();
             
             let api_provider = api_select.value();
             
+            // With a passphrase, new or from unlocking, the keys are sealed in
+            // the vault instead of being saved as they are
+            let passphrase_input = document.get_element_by_id("vault-passphrase").unwrap()
+                .dyn_into::<HtmlInputElement>().unwrap();
+            let typed_passphrase = Some(passphrase_input.value()).filter(|p| !p.is_empty());
+            let unlocked_passphrase = api_keys.borrow().vault_passphrase.clone();
+            let passphrase = typed_passphrase.clone().or_else(|| unlocked_passphrase.clone());
+            let locked = passphrase.is_none() && saved_vault().is_some();
+            if locked && keys.iter().any(|(_, key)| !key.is_empty()) {
+                alert("Unlock the key vault, or forget the saved keys, before saving new keys.");
+                return;
+            }
+            
+            // The key inputs are empty while the vault is locked, so sealing
+            // them alone would lose the saved keys: open it with the typed
+            // passphrase and keep its keys where none was typed
+            if let (None, Some(typed), Some(saved)) = (&unlocked_passphrase, &typed_passphrase, saved_vault()) {
+                match saved.and_then(|vault| vault.merged_keys(typed, &keys)) {
+                    Ok(merged) => {
+                        for (id, key) in &mut keys {
+                            *key = merged.get(id).cloned().unwrap_or_default();
+                        }
+                    },
+                    Err(e) => {
+                        alert(&format!("Could not open the saved key vault with this passphrase ({}). Nothing was saved; unlock the vault or forget the saved keys first.", e));
+                        return;
+                    }
+                }
+            }
+            let sealed = match &passphrase {
+                Some(passphrase) => {
+                    let saved: BTreeMap<String, String> = keys.iter().filter(|(_, key)| !key.is_empty()).cloned().collect();
+                    match Vault::seal(&saved, passphrase, random_bytes(), random_bytes(), vault::ITERATIONS) {
+                        Ok(vault) => Some(vault),
+                        Err(e) => {
+                            alert(&format!("Could not encrypt the keys: {}", e));
+                            return;
+                        }
+                    }
+                },
+                None => None,
+            };
+            
+            // Check every provider's settings before saving any of them
+            let mut settings = Vec::new();
+            for provider in providers::PROVIDERS {
+                let fields: Vec<(&str, String)> = providers::PROVIDER_SETTINGS.iter()
+                    .filter_map(|&name| {
+                        let element = document.get_element_by_id(&format!("{}-{}", provider.id(), name.replace('_', "-")))?;
+                        Some((name, form_value(&element).trim().to_string()))
+                    })
+                    .collect();
+                let field = |name: &str| fields.iter().find(|(n, _)| *n == name).map(|(_, value)| value.clone()).unwrap_or_default();
+                if let Err(e) = ProviderSettings::parse("", field) {
+                    alert(&format!("{} settings: {}", provider.name(), e));
+                    return;
+                }
+                settings.push((provider, fields));
+            }
+            
             if let Ok(Some(storage)) = web_sys::window().unwrap().local_storage() {
-                let _ = storage.set_item("claude_api_key", &claude_key);
-                let _ = storage.set_item("perplexity_api_key", &perplexity_key);
-                let _ = storage.set_item("mistral_api_key", &mistral_key);
+                match &sealed {
+                    Some(vault) => {
+                        let _ = storage.set_item(VAULT_STORAGE_KEY, &vault.to_json());
+                        for (id, _) in &keys {
+                            let _ = storage.remove_item(&format!("{}_api_key", id));
+                        }
+                    },
+                    // The locked vault keeps the keys
+                    None if locked => {},
+                    None => {
+                        for (id, key) in &keys {
+                            let _ = storage.set_item(&format!("{}_api_key", id), key);
+                        }
+                    },
+                }
+                for (provider, fields) in &settings {
+                    for (name, value) in fields {
+                        let _ = storage.set_item(&format!("{}_{}", provider.id(), name), value);
+                    }
+                }
                 let _ = storage.set_item("api_provider", &api_provider);
+                let _ = storage.set_item("compiler_backend", &compiler_backend);
+                let _ = storage.set_item("compile_service_url", compile_service_url.trim());
+                let _ = storage.set_item(LLM_PROXY_STORAGE_KEY, llm_proxy_url.trim());
             }
             
-            let mut keys = api_keys.borrow_mut();
-            keys.claude = claude_key;
-            keys.perplexity = perplexity_key;
-            keys.mistral = mistral_key;
+            {
+                let mut api_keys = api_keys.borrow_mut();
+                api_keys.keys.extend(keys);
+                if sealed.is_some() {
+                    api_keys.vault_passphrase = passphrase;
+                    passphrase_input.set_value("");
+                    show_api_keys(&document, &api_keys);
+                }
+                update_vault_status(&document, &api_keys);
+            }
             
-            alert("API keys and provider saved successfully!");
+            alert("Settings saved successfully!");
         }) as Box<dyn FnMut()>);
         
         save_keys_btn.add_event_listener_with_callback("click", save_keys_callback.as_ref().unchecked_ref())?;
         save_keys_callback.forget();
     }
     
+    // Key vault: unlock for the session, lock again, or forget the keys
+    {
+        let api_keys = api_keys.clone();
+        let unlock_callback = Closure::wrap(Box::new(move || {
+            let document = get_document();
+            let passphrase_input = document.get_element_by_id("vault-passphrase").unwrap()
+                .dyn_into::<HtmlInputElement>().unwrap();
+            let vault = match saved_vault() {
+                Some(Ok(vault)) => vault,
+                Some(Err(e)) => {
+                    alert(&format!("Could not read the key vault: {}", e));
+                    return;
+                },
+                None => return,
+            };
+            let passphrase = passphrase_input.value();
+            match vault.open(&passphrase) {
+                Ok(keys) => {
+                    let mut api_keys = api_keys.borrow_mut();
+                    api_keys.keys = keys.into_iter().collect();
+                    api_keys.vault_passphrase = Some(passphrase);
+                    passphrase_input.set_value("");
+                    show_api_keys(&document, &api_keys);
+                    update_vault_status(&document, &api_keys);
+                },
+                Err(e) => alert(&format!("Could not unlock the key vault: {}", e)),
+            }
+        }) as Box<dyn FnMut()>);
+        
+        document.get_element_by_id("vault-unlock-btn").unwrap()
+            .add_event_listener_with_callback("click", unlock_callback.as_ref().unchecked_ref())?;
+        unlock_callback.forget();
+    }
+    
+    {
+        let api_keys = api_keys.clone();
+        let lock_callback = Closure::wrap(Box::new(move || {
+            let document = get_document();
+            let mut api_keys = api_keys.borrow_mut();
+            api_keys.keys.clear();
+            api_keys.vault_passphrase = None;
+            show_api_keys(&document, &api_keys);
+            update_vault_status(&document, &api_keys);
+        }) as Box<dyn FnMut()>);
+        
+        document.get_element_by_id("vault-lock-btn").unwrap()
+            .add_event_listener_with_callback("click", lock_callback.as_ref().unchecked_ref())?;
+        lock_callback.forget();
+    }
+    
+    {
+        let api_keys = api_keys.clone();
+        let forget_callback = Closure::wrap(Box::new(move || {
+            let window = web_sys::window().unwrap();
+            if !window.confirm_with_message("Remove all saved API keys from this browser?").unwrap_or(false) {
+                return;
+            }
+            if let Ok(Some(storage)) = window.local_storage() {
+                let _ = storage.remove_item(VAULT_STORAGE_KEY);
+                for provider in providers::PROVIDERS {
+                    let _ = storage.remove_item(&format!("{}_api_key", provider.id()));
+                }
+            }
+            
+            let document = get_document();
+            let mut api_keys = api_keys.borrow_mut();
+            api_keys.keys.clear();
+            api_keys.vault_passphrase = None;
+            show_api_keys(&document, &api_keys);
+            update_vault_status(&document, &api_keys);
+        }) as Box<dyn FnMut()>);
+        
+        document.get_element_by_id("vault-forget-btn").unwrap()
+            .add_event_listener_with_callback("click", forget_callback.as_ref().unchecked_ref())?;
+        forget_callback.forget();
+    }
+    
+    // Workspace export: every conversation and the settings, without the
+    // API keys, downloaded as one JSON file
+    {
+        let chat_history_state = chat_history_state.clone();
+        let export_callback = Closure::wrap(Box::new(move || {
+            let mut items = Vec::new();
+            if let Ok(Some(storage)) = web_sys::window().unwrap().local_storage() {
+                for index in 0..storage.length().unwrap_or(0) {
+                    if let Ok(Some(key)) = storage.key(index) {
+                        if let Ok(Some(value)) = storage.get_item(&key) {
+                            items.push((key, value));
+                        }
+                    }
+                }
+            }
+            let now = js_sys::Date::now();
+            let workspace = Workspace::new(now, chat_history_state.borrow().clone(), items);
+            
+            let blob_parts = Array::new();
+            blob_parts.push(&JsValue::from_str(&workspace.to_json()));
+            let options = BlobPropertyBag::new();
+            options.set_type("application/json");
+            let blob = Blob::new_with_str_sequence_and_options(&blob_parts, &options).unwrap();
+            let url = Url::create_object_url_with_blob(&blob).unwrap();
+            
+            let document = get_document();
+            let date: String = js_sys::Date::new_0().to_iso_string().as_string().unwrap_or_default().chars().take(10).collect();
+            let a = document.create_element("a").unwrap().dyn_into::<HtmlElement>().unwrap();
+            a.set_attribute("href", &url).unwrap();
+            a.set_attribute("download", &format!("ladfx-workspace-{}.json", date)).unwrap();
+            a.set_attribute("style", "display: none").unwrap();
+            document.body().unwrap().append_child(&a).unwrap();
+            a.click();
+            document.body().unwrap().remove_child(&a).unwrap();
+            
+            // Give the download time to start before dropping the file
+            let revoke = Closure::once_into_js(move || {
+                Url::revoke_object_url(&url).ok();
+            });
+            web_sys::window().unwrap()
+                .set_timeout_with_callback_and_timeout_and_arguments_0(revoke.unchecked_ref(), 1000)
+                .unwrap();
+        }) as Box<dyn FnMut()>);
+        
+        document.get_element_by_id("workspace-export-btn").unwrap()
+            .add_event_listener_with_callback("click", export_callback.as_ref().unchecked_ref())?;
+        export_callback.forget();
+    }
+    
+    // Workspace import: merge a workspace file's conversations into the
+    // history and take its settings
+    {
+        let workspace_input = document.get_element_by_id("workspace-file").unwrap()
+            .dyn_into::<HtmlInputElement>()?;
+        
+        let import_callback = {
+            let workspace_input = workspace_input.clone();
+            Closure::wrap(Box::new(move || {
+                workspace_input.click();
+            }) as Box<dyn FnMut()>)
+        };
+        document.get_element_by_id("workspace-import-btn").unwrap()
+            .add_event_listener_with_callback("click", import_callback.as_ref().unchecked_ref())?;
+        import_callback.forget();
+        
+        let chat_history_state = chat_history_state.clone();
+        let file_callback = Closure::wrap(Box::new(move |event: web_sys::Event| {
+            let input = event.target().unwrap().dyn_into::<HtmlInputElement>().unwrap();
+            let Some(file) = input.files().and_then(|files| files.get(0)) else {
+                return;
+            };
+            input.set_value("");
+            
+            let chat_history_state = chat_history_state.clone();
+            let name = file.name();
+            let reader = web_sys::FileReader::new().unwrap();
+            let onload = Closure::wrap(Box::new(move |e: web_sys::ProgressEvent| {
+                let json = e.target().unwrap()
+                    .dyn_into::<web_sys::FileReader>().unwrap()
+                    .result().unwrap()
+                    .as_string().unwrap_or_default();
+                let mut workspace = match Workspace::from_json(&json) {
+                    Ok(workspace) => workspace,
+                    Err(e) => {
+                        alert(&format!("Could not import {}: {}", name, e));
+                        return;
+                    }
+                };
+                
+                let (merged, changed) = {
+                    let mut history = chat_history_state.borrow_mut();
+                    let merged = workspace::merge(&mut history, std::mem::take(&mut workspace.conversations));
+                    let changed: Vec<Conversation> = history.iter()
+                        .filter(|conversation| merged.added.contains(&conversation.id) || merged.updated.contains(&conversation.id))
+                        .cloned()
+                        .collect();
+                    (merged, changed)
+                };
+                wasm_bindgen_futures::spawn_local(async move {
+                    if let Err(e) = history_store::save(&changed).await {
+                        web_sys::console::error_2(&"Failed to save imported history:".into(), &e);
+                        alert("The imported conversations could not be saved in this browser.");
+                    }
+                });
+                update_history_panel(&get_document(), &chat_history_state.borrow());
+                
+                let window = web_sys::window().unwrap();
+                if let Ok(Some(storage)) = window.local_storage() {
+                    // Requests go wherever these point, so ask before changing them
+                    let endpoints = workspace.changed_endpoints(|key| storage.get_item(key).ok().flatten());
+                    let apply_endpoints = endpoints.is_empty() || {
+                        let list: Vec<String> = endpoints.iter()
+                            .map(|(key, value)| format!("  {}: {}", key, if value.is_empty() { "(none)" } else { value }))
+                            .collect();
+                        window.confirm_with_message(&format!(
+                            "The workspace file changes where documents and prompts are sent:\n\n{}\n\nUse these addresses? Cancel keeps the current ones.",
+                            list.join("\n")
+                        )).unwrap_or(false)
+                    };
+                    for (key, value) in &workspace.settings {
+                        if apply_endpoints || !workspace::is_endpoint(key) {
+                            let _ = storage.set_item(key, value);
+                        }
+                    }
+                }
+                
+                let message = format!(
+                    "Imported {} new and {} updated conversation(s); {} already up to date.",
+                    merged.added.len(),
+                    merged.updated.len(),
+                    merged.unchanged
+                );
+                if workspace.settings.is_empty() {
+                    alert(&message);
+                    return;
+                }
+                // The settings are read as the page loads
+                if window.confirm_with_message(&format!("{}\n\nReload now to use the imported settings?", message)).unwrap_or(false) {
+                    let _ = window.location().reload();
+                }
+            }) as Box<dyn FnMut(_)>);
+            
+            reader.set_onload(Some(onload.as_ref().unchecked_ref()));
+            onload.forget();
+            reader.read_as_text(&file).unwrap();
+        }) as Box<dyn FnMut(_)>);
+        
+        workspace_input.add_event_listener_with_callback("change", file_callback.as_ref().unchecked_ref())?;
+        file_callback.forget();
+    }
+    
     // File upload handler
     {
         let document_rc = document_rc.clone();
         let generated_content = generated_content.clone();
+        let chat_history_state = chat_history_state.clone();
         
         let upload_callback = Closure::wrap(Box::new(move |event: web_sys::Event| {
             let input = event.target().unwrap().dyn_into::<web_sys::HtmlInputElement>().unwrap();
@@ -744,8 +1429,53 @@ pub fn main() -> Result<(), JsValue> {
                     let file = file_list.get(0).unwrap();
                     let reader = web_sys::FileReader::new().unwrap();
                     
+                    // Anything that is not a .tex file is an asset for the current document
+                    if !file.name().ends_with(".tex") {
+                        if generated_content.borrow().is_none() {
+                            alert("Upload or generate a LaTeX document before adding figures or other files.");
+                            return;
+                        }
+                        
+                        let generated_content = generated_content.clone();
+                        let chat_history_state = chat_history_state.clone();
+                        let name = file.name();
+                        let onload = Closure::wrap(Box::new(move |e: web_sys::ProgressEvent| {
+                            let buffer = e.target().unwrap()
+                                .dyn_into::<web_sys::FileReader>().unwrap()
+                                .result().unwrap();
+                            let data = Uint8Array::new(&buffer).to_vec();
+                            
+                            if let Some(content) = &mut *generated_content.borrow_mut() {
+                                content.assets.retain(|asset| asset.path != name);
+                                content.assets.push(Asset { path: name.clone(), data });
+                                
+                                // The cached PDF was built without this file
+                                if let Some(url) = content.pdf_url.take() {
+                                    Url::revoke_object_url(&url).ok();
+                                }
+                                content.pdf_blob = None;
+                                content.page_count = None;
+                                
+                                if let Some(id) = &content.conversation_id {
+                                    save_history_assets(&chat_history_state, id, &content.assets);
+                                }
+                            }
+                            
+                            alert(&format!("Added {} to the document files.", name));
+                        }) as Box<dyn FnMut(_)>);
+                        
+                        reader.set_onload(Some(onload.as_ref().unchecked_ref()));
+                        onload.forget();
+                        
+                        reader.read_as_array_buffer(&file).unwrap();
+                        input.set_value("");
+                        return;
+                    }
+                    
                     let document_rc = document_rc.clone();
                     let generated_content = generated_content.clone();
+                    let chat_history_state = chat_history_state.clone();
+                    let name = file.name();
                     
                     let onload = Closure::wrap(Box::new(move |e: web_sys::ProgressEvent| {
                         let content = e.target().unwrap()
@@ -755,8 +1485,7 @@ pub fn main() -> Result<(), JsValue> {
                         
                         // Update preview with LaTeX content
                         let document = document_rc.borrow();
-                        let preview_content = document.get_element_by_id("preview-content").unwrap();
-                        preview_content.set_inner_html(&format!("<pre class='latex-content'>{}</pre>", content));
+                        render_latex_preview(&document, &content, &[]);
                         
                         // Store the uploaded content
                         let pdf_size = document.get_element_by_id("pdf-size-select").unwrap()
@@ -771,15 +1500,23 @@ pub fn main() -> Result<(), JsValue> {
                             .dyn_into::<HtmlSelectElement>().unwrap()
                             .value();
                         
-                        *generated_content.borrow_mut() = Some(GeneratedContent {
+                        // An uploaded document starts its own conversation
+                        let mut uploaded = GeneratedContent {
                             latex: content,
                             pdf_blob: None,
                             pdf_url: None,
+                            page_count: None,
+                            assets: Vec::new(),
+                            diagnostics: Vec::new(),
                             chat_history: Vec::new(),
                             pdf_size,
                             template,
                             ai_provider,
-                        });
+                            conversation_id: None,
+                        };
+                        record_revision(&chat_history_state, &mut uploaded, &name, RevisionKind::Upload);
+                        *generated_content.borrow_mut() = Some(uploaded);
+                        update_history_panel(&document, &chat_history_state.borrow());
                         
                         // Enable download button
                         document.get_element_by_id("download-btn").unwrap()
@@ -796,6 +1533,7 @@ pub fn main() -> Result<(), JsValue> {
                     onload.forget();
                     
                     reader.read_as_text(&file).unwrap();
+                    input.set_value("");
                 }
             }
         }) as Box<dyn FnMut(_)>);
@@ -856,31 +1594,62 @@ pub fn main() -> Result<(), JsValue> {
         click_callback.forget();
     }
 
+    // Save generation modes when they change
+    {
+        let edit_mode = edit_mode_toggle.clone();
+        let auto_fix = auto_fix_toggle.clone();
+        let rounds = auto_fix_rounds.clone();
+        let lint = lint_toggle.clone();
+        let modes_callback = Closure::wrap(Box::new(move || {
+            if let Ok(Some(storage)) = web_sys::window().unwrap().local_storage() {
+                let _ = storage.set_item("edit_mode", &edit_mode.checked().to_string());
+                let _ = storage.set_item("auto_fix", &auto_fix.checked().to_string());
+                let _ = storage.set_item("auto_fix_rounds", &rounds.value());
+                let _ = storage.set_item("lint_in_prompt", &lint.checked().to_string());
+            }
+        }) as Box<dyn FnMut()>);
+        
+        edit_mode_toggle.add_event_listener_with_callback("change", modes_callback.as_ref().unchecked_ref())?;
+        auto_fix_toggle.add_event_listener_with_callback("change", modes_callback.as_ref().unchecked_ref())?;
+        auto_fix_rounds.add_event_listener_with_callback("change", modes_callback.as_ref().unchecked_ref())?;
+        lint_toggle.add_event_listener_with_callback("change", modes_callback.as_ref().unchecked_ref())?;
+        modes_callback.forget();
+    }
+
     // New chat button
     {
         let document_rc = document_rc.clone();
+        let generated_content = generated_content.clone();
         let new_chat_callback = Closure::wrap(Box::new(move || {
             let document = document_rc.borrow();
+            
+            // Forget the current document so the next message starts over
+            if let Some(content) = generated_content.borrow_mut().take() {
+                if let Some(url) = content.pdf_url {
+                    Url::revoke_object_url(&url).ok();
+                }
+            }
         
             // Clear chat input
             document.get_element_by_id("chat-input").unwrap()
                 .dyn_into::<HtmlTextAreaElement>().unwrap()
                 .set_value("");
+            set_section_scope(&document, None);
             
             // Clear chat history display
             document.get_element_by_id("chat-history").unwrap()
-                .set_inner_html(r#"
+                .set_html(&Html::from_static(r#"
                     <div class="empty-state">
                         <svg xmlns="http://www.w3.org/2000/svg" width="24" height="24" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round">
                             <path d="M21 15a2 2 0 0 1-2 2H7l-4 4V5a2 2 0 0 1-2-2V5a2 2 0 0 0 2 2h14a2 2 0 0 0 2-2V5a2 2 0 0 1-2-2z"></path>
                         </svg>
                         <p>Start a new conversation</p>
                     </div>
-                "#);
+                "#));
             
             // Clear preview
             document.get_element_by_id("preview-content").unwrap()
-                .set_inner_html(r#"
+                .set_html(&Html::from_static(r#"
                     <div class="empty-state">
                         <svg xmlns="http://www.w3.org/2000/svg" width="24" height="24" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round">
                             <path d="M14 2H6a2 2 0 0 0-2 2v16a2 2 0 0 0 2 2h12a2 2 0 0 0 2-2V8z"></path>
@@ -891,7 +1660,7 @@ pub fn main() -> Result<(), JsValue> {
                         </svg>
                         <p>Generated content will appear here</p>
                     </div>
-                "#);
+                "#));
             
             // Disable download button
             document.get_element_by_id("download-btn").unwrap()
@@ -918,12 +1687,21 @@ pub fn main() -> Result<(), JsValue> {
         let api_select = api_select.clone();
         let pdf_size_select = pdf_size_select.clone();
         let template_select = template_select.clone();
+        let edit_mode_toggle = edit_mode_toggle.clone();
+        let auto_fix_toggle = auto_fix_toggle.clone();
+        let auto_fix_rounds = auto_fix_rounds.clone();
+        let lint_toggle = lint_toggle.clone();
         
         let send_callback = Closure::wrap(Box::new(move || {
             let document = document_rc.borrow();
             let api_provider = api_select.value();
             let template = template_select.value();
             let pdf_size = pdf_size_select.value();
+            let max_fix_rounds = if auto_fix_toggle.checked() {
+                auto_fix_rounds.value().parse::<u32>().unwrap_or(3).clamp(1, 10)
+            } else {
+                0
+            };
             
             let topic = document.get_element_by_id("chat-input").unwrap()
                 .dyn_into::<HtmlTextAreaElement>().unwrap()
@@ -934,15 +1712,40 @@ pub fn main() -> Result<(), JsValue> {
                 return;
             }
             
-            let api_key = match api_provider.as_str() {
-                "Claude" => api_keys.borrow().claude.clone(),
-                "Perplexity" => api_keys.borrow().perplexity.clone(),
-                "Mistral" => api_keys.borrow().mistral.clone(),
-                _ => String::new()
+            let Some(provider) = providers::find(&api_provider) else {
+                alert("Please select an AI provider");
+                return;
+            };
+            let mut settings = match provider_settings(provider, &api_keys.borrow()) {
+                Ok(settings) => settings,
+                Err(e) => {
+                    alert(&format!("Invalid {} settings: {}", api_provider, e));
+                    return;
+                }
             };
             
-            if api_key.is_empty() {
-                alert(&format!("Please enter your {} API key in the profile settings", api_provider));
+            // The size option sets the length asked for and, unless the
+            // provider settings set one, the token limit
+            let length = PdfSize::from_label(&pdf_size).unwrap_or_default().target();
+            settings.max_tokens = settings.max_tokens.or(Some(length.max_tokens));
+            
+            // Through the proxy, the keys and the custom endpoint are the
+            // server's business
+            let proxied = llm_proxy_url().is_some();
+            if provider.requires_key() && settings.api_key.is_empty() && !proxied {
+                if saved_vault().is_some() && api_keys.borrow().vault_passphrase.is_none() {
+                    alert("Please unlock the key vault in the profile settings to use your saved API keys");
+                } else {
+                    alert(&format!("Please enter your {} API key in the profile settings", api_provider));
+                }
+                return;
+            }
+            if provider.has_custom_endpoint() && settings.model.is_empty() {
+                alert(&format!("Please enter the {} model in the profile settings", api_provider));
+                return;
+            }
+            if provider.has_custom_endpoint() && settings.base_url.is_empty() && !proxied {
+                alert(&format!("Please enter the {} base URL in the profile settings", api_provider));
                 return;
             }
             
@@ -960,20 +1763,20 @@ pub fn main() -> Result<(), JsValue> {
             // Add user message to chat history
             let user_message = document.create_element("div").unwrap();
             user_message.set_class_name("chat-message user-message");
-            user_message.set_inner_html(&format!(
+            user_message.set_html(&Html::fill(
                 r#"<div class="message-content">{}</div>"#,
-                topic.replace("\n", "<br>")
+                &[&Html::lines(&topic)]
             ));
             chat_history.append_child(&user_message).unwrap();
             
             // Add loading indicator for AI response
             let ai_message = document.create_element("div").unwrap();
             ai_message.set_class_name("chat-message ai-message");
-            ai_message.set_inner_html(r#"
+            ai_message.set_html(&Html::from_static(r#"
                 <div class="message-content">
                     <div class="loader-spinner small"></div>
                 </div>
-            "#);
+            "#));
             chat_history.append_child(&ai_message).unwrap();
             
             // Scroll to bottom of chat
@@ -981,7 +1784,7 @@ pub fn main() -> Result<(), JsValue> {
             
             // Update preview to show loading state
             document.get_element_by_id("preview-content").unwrap()
-                .set_inner_html(r#"<div class="loader"><div class="loader-spinner"></div><p>Generating document...</p></div>"#);
+                .set_html(&Html::from_static(r#"<div class="loader"><div class="loader-spinner"></div><p>Generating document...</p></div>"#));
             
             // Hide more options dropdown
             document.get_element_by_id("more-options-dropdown").unwrap()
@@ -990,68 +1793,171 @@ pub fn main() -> Result<(), JsValue> {
                 .set_property("display", "none")
                 .unwrap();
     
+            // Follow-ups revise the current document, carrying the earlier turns;
+            // in edit mode they first ask for edits and fall back to the whole
+            // document if those do not apply
+            let reply_template = generated_content.borrow().as_ref().map_or(template.clone(), |content| content.template.clone());
+            // A section picked in the outline limits the request to it
+            let scope = generated_content.borrow().as_ref().and(picked_section(&document));
+            // With the lint option on, the issues found in the current
+            // document are sent along with the request
+            let (messages, edit_messages, previous_latex) = match generated_content.borrow().as_ref() {
+                Some(content) => {
+                    let lint_report = lint_toggle.checked().then(|| chat::lint_report(&lint::lint(&content.latex))).flatten();
+                    let request = match lint_report {
+                        Some(report) => format!("{}\n\n{}", topic, report),
+                        None => topic.clone(),
+                    };
+                    (
+                        chat::conversation(&content.chat_history, &content.template, &length, chat::revision_prompt(&content.latex, &request)),
+                        edit_mode_toggle.checked().then(|| {
+                            chat::conversation(&content.chat_history, &content.template, &length, chat::edit_prompt(&content.latex, &request))
+                        }),
+                        Some(content.latex.clone()),
+                    )
+                },
+                None => (vec![ChatMessage::user(chat::generation_prompt(&topic, &template, &length))], None, None),
+            };
+    
             wasm_bindgen_futures::spawn_local({
                 let document_rc = document_rc.clone();
                 let generated_content = generated_content.clone();
                 let chat_history_state = chat_history_state.clone();
                 let api_provider = api_provider.clone();
-                let api_key = api_key.clone();
+                let settings = settings.clone();
                 let template = template.clone();
                 let topic = topic.clone();
                 let pdf_size = pdf_size.clone();
+                let ai_message = ai_message.clone();
                 
                 async move {
-                    match generate_latex_content(&api_provider, &api_key, &topic, &template).await {
-                        Ok(content) => {
+                    let document = document_rc.borrow().clone();
+                    let message = ai_message.first_element_child().unwrap();
+                    let result = match (&scope, edit_messages, &previous_latex) {
+                        (Some((index, title)), _, Some(previous)) => {
+                            rewrite_section(&message, provider, &settings, previous, *index, title, &topic).await
+                        },
+                        (None, Some(edit_messages), Some(previous)) => {
+                            request_edits(&message, provider, &settings, &reply_template, &edit_messages, &messages, previous).await
+                        },
+                        _ => {
+                            let view = ReplyView::new(&document, &message, &format!("Writing with {}...", api_provider), true);
+                            generate_latex_content(provider, &settings, &reply_template, &messages, &view).await
+                        },
+                    };
+                    match result {
+                        Ok(extraction) => {
+                            let content = extraction.latex.clone();
+                            
                             // Store the generated content
-                            let mut chat_history = Vec::new();
-                            chat_history.push(("user".to_string(), topic.clone()));
-                            chat_history.push(("ai".to_string(), content.clone()));
+                            let request = match &scope {
+                                Some((_, title)) => chat::section_request(title, &topic),
+                                None => topic.clone(),
+                            };
+                            let turns = [
+                                (chat::USER.to_string(), request),
+                                (chat::AI.to_string(), content.clone()),
+                            ];
                             
-                            *generated_content.borrow_mut() = Some(GeneratedContent {
-                                latex: content.clone(),
-                                pdf_blob: None,
-                                pdf_url: None,
-                                chat_history,
-                                pdf_size: pdf_size.clone(),
-                                template: template.clone(),
-                                ai_provider: api_provider.clone(),
-                            });
+                            let mut revised = None;
+                            {
+                                let mut current = generated_content.borrow_mut();
+                                match (current.as_mut(), &previous_latex) {
+                                    // Only revise if the document was not replaced while waiting
+                                    (Some(existing), Some(previous)) if &existing.latex == previous => {
+                                        let diff = diff::unified_diff(previous, &content, "a/main.tex", "b/main.tex", 3);
+                                        existing.latex = content.clone();
+                                        existing.pdf_blob = None;
+                                        existing.page_count = None;
+                                        if let Some(url) = existing.pdf_url.take() {
+                                            Url::revoke_object_url(&url).ok();
+                                        }
+                                        add_extracted_files(&mut existing.assets, &extraction.files);
+                                        existing.diagnostics.clear();
+                                        existing.chat_history.extend(turns);
+                                        if scope.is_some() {
+                                            existing.chat_history.push((chat::SECTION_DIFF.to_string(), diff.clone()));
+                                        }
+                                        revised = Some(diff);
+                                        existing.ai_provider = api_provider.clone();
+                                        existing.pdf_size = pdf_size.clone();
+                                    },
+                                    _ => {
+                                        let mut assets = Vec::new();
+                                        add_extracted_files(&mut assets, &extraction.files);
+                                        *current = Some(GeneratedContent {
+                                            latex: content.clone(),
+                                            pdf_blob: None,
+                                            pdf_url: None,
+                                            page_count: None,
+                                            assets,
+                                            diagnostics: Vec::new(),
+                                            chat_history: turns.to_vec(),
+                                            pdf_size: pdf_size.clone(),
+                                            template: template.clone(),
+                                            ai_provider: api_provider.clone(),
+                                            conversation_id: None,
+                                        });
+                                    }
+                                }
+                            }
                             
                             // Update preview with LaTeX content
-                            let preview_content = document_rc.borrow().get_element_by_id("preview-content").unwrap();
-                            preview_content.set_inner_html(&format!("<pre class='latex-content'>{}</pre>", content));
+                            render_latex_preview(&document_rc.borrow(), &content, &[]);
+                            set_section_scope(&document_rc.borrow(), None);
                             
                             // Update AI message in chat
                             let chat_history_element = document_rc.borrow().get_element_by_id("chat-history").unwrap();
                             if let Some(last_message) = chat_history_element.last_child() {
                                 let last_message = last_message.dyn_into::<Element>().unwrap();
-                                last_message.set_inner_html(&format!(
+                                let summary = match &revised {
+                                    Some(_) => {
+                                        let (inserted, deleted) = diff::change_counts(&diff::diff_lines(previous_latex.as_deref().unwrap_or_default(), &content));
+                                        match &scope {
+                                            Some((_, title)) => format!("Rewrote section \"{}\" (+{} / -{} lines)", title, inserted, deleted),
+                                            None => format!("Revised LaTeX document (+{} / -{} lines)", inserted, deleted),
+                                        }
+                                    },
+                                    None => format!("Generated LaTeX document with {} sections", section_count(&content)),
+                                };
+                                last_message.set_html(&Html::fill(
                                     r#"<div class="message-content">
-                                        <div>Generated LaTeX document with {} sections</div>
+                                        <div>{}</div>
                                         <div class="message-meta">
                                             <span>Template: {}</span>
                                             <span>AI: {}</span>
                                             <span>Size: {}</span>
                                         </div>
                                     </div>"#,
-                                    content.matches(r"\section").count(),
-                                    template,
-                                    api_provider,
-                                    pdf_size
+                                    &[&summary, &template, &api_provider, &pdf_size]
                                 ));
+                                
+                                // What extraction did with the rest of the reply
+                                if let Some(summary) = extraction.summary() {
+                                    let note = document_rc.borrow().create_element("div").unwrap();
+                                    note.set_class_name("extraction-note");
+                                    note.set_text_content(Some(&summary));
+                                    last_message.first_element_child().unwrap().append_child(&note).unwrap();
+                                }
+                                
+                                if let Some(diff) = revised.filter(|d| !d.is_empty()) {
+                                    let changes = diff_details(&document_rc.borrow(), &diff);
+                                    last_message.first_element_child().unwrap().append_child(&changes).unwrap();
+                                }
                             }
                             
-                            // Add to chat history state
-                            let now = js_sys::Date::new_0();
-                            let date_str = now.to_locale_date_string("en-US", &JsValue::UNDEFINED);
-                            let time_str = now.to_locale_time_string("en-US");                            
-
-                            let mut history = chat_history_state.borrow_mut();
-                            history.push((date_str.as_string().unwrap(), time_str.as_string().unwrap(), topic.clone(), content.clone()));
+                            // Save to the history: a revision continues the
+                            // conversation it came from, a new document starts one
+                            if let Some(current) = generated_content.borrow_mut().as_mut() {
+                                record_revision(&chat_history_state, current, &topic, RevisionKind::Generation);
+                            }
+                            
+                            if max_fix_rounds > 0 {
+                                auto_fix(&document_rc, &generated_content, &chat_history_state, provider, &settings, messages, max_fix_rounds).await;
+                            }
                             
                             // Update history panel
-                            update_history_panel(&document_rc.borrow(), &history);
+                            update_history_panel(&document_rc.borrow(), &chat_history_state.borrow());
                             
                             // Enable download button
                             document_rc.borrow().get_element_by_id("download-btn").unwrap()
@@ -1068,16 +1974,20 @@ pub fn main() -> Result<(), JsValue> {
                         },
                         Err(err) => {
                             let error_msg = format!("Error: {}", err.as_string().unwrap_or_else(|| "Unknown error".to_string()));
-                            document_rc.borrow().get_element_by_id("preview-content").unwrap()
-                                .set_inner_html(&format!("<div class='error'>{}</div>", error_msg));
+                            // Put back the current document the partial reply was shown over
+                            match generated_content.borrow().as_ref() {
+                                Some(content) => render_latex_preview(&document_rc.borrow(), &content.latex, &content.diagnostics),
+                                None => document_rc.borrow().get_element_by_id("preview-content").unwrap()
+                                    .set_html(&Html::fill("<div class='error'>{}</div>", &[&error_msg])),
+                            }
                             
                             // Update AI message with error
                             let chat_history = document_rc.borrow().get_element_by_id("chat-history").unwrap();
                             if let Some(last_message) = chat_history.last_child() {
                                 let last_message = last_message.dyn_into::<Element>().unwrap();
-                                last_message.set_inner_html(&format!(
+                                last_message.set_html(&Html::fill(
                                     r#"<div class="message-content error">Failed to generate document: {}</div>"#,
-                                    error_msg
+                                    &[&error_msg]
                                 ));
                             }
                             
@@ -1120,75 +2030,48 @@ pub fn main() -> Result<(), JsValue> {
         let download_callback = Closure::wrap(Box::new(move || {
             let document = document_rc.borrow();
             
-            if let Some(content) = &*generated_content.borrow() {
+            if generated_content.borrow().is_some() {
                 // Show compilation in progress with a white background
                 document.get_element_by_id("preview-content").unwrap()
-                    .set_inner_html(r#"
+                    .set_html(&Html::from_static(r#"
                         <div class="pdf-message" style="background: white; height: 100%; display: flex; flex-direction: column; align-items: center; justify-content: center;">
                             <div class="loader-spinner"></div>
                             <p>Compiling PDF for download...</p>
                         </div>
-                    "#);
-                
-                // In a real implementation, you would:
-                // 1. Send the LaTeX content to a compilation service
-                // 2. Receive the PDF binary data
-                // 3. Create a blob and download link
+                    "#));
                 
-                // For now, we'll simulate this with a timeout
-                let window = web_sys::window().unwrap();
                 let document_rc = document_rc.clone();
-                let generated_content = Rc::clone(&generated_content); // Clone again for the closure
+                let generated_content = Rc::clone(&generated_content); // Clone again for the future
                 
-                let _ = window.set_timeout_with_callback_and_timeout_and_arguments_0(
-                    Closure::once_into_js(move || {
-                        // In a real implementation, replace this with actual PDF compilation
-                        let mut content = generated_content.borrow_mut();
-                        if let Some(content) = &mut *content {
-                            // Create a simple PDF with the text "Compiled PDF"
-                            let pdf_content = format!("%PDF-1.4\n1 0 obj\n<< /Type /Catalog /Pages 2 0 R >>\nendobj\n2 0 obj\n<< /Type /Pages /Kids [3 0 R] /Count 1 >>\nendobj\n3 0 obj\n<< /Type /Page /Parent 2 0 R /Resources << /Font << /F1 4 0 R >> >> /Contents 5 0 R >>\nendobj\n4 0 obj\n<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>\nendobj\n5 0 obj\n<< /Length 44 >>\nstream\nBT\n/F1 12 Tf\n100 700 Td\n(Compiled PDF) Tj\nET\nendstream\nendobj\nxref\n0 6\n0000000000 65535 f \n0000000010 00000 n \n0000000079 00000 n \n0000000173 00000 n \n0000000301 00000 n \n0000000380 00000 n \ntrailer\n<< /Size 6 /Root 1 0 R >>\nstartxref\n492\n%%EOF");
-                            
-                            // Convert to Uint8Array
-                            let mut pdf_bytes = Vec::with_capacity(pdf_content.len());
-                            pdf_bytes.extend_from_slice(pdf_content.as_bytes());
-                            let pdf_array = Uint8Array::from(&pdf_bytes[..]);
+                wasm_bindgen_futures::spawn_local(async move {
+                    match compile_current_pdf(&generated_content).await {
+                        Ok(pdf_url) => {
+                            // Create download link for PDF
+                            let doc = document_rc.borrow();
+                            let a = doc.create_element("a").unwrap();
+                            let a = a.dyn_into::<web_sys::HtmlElement>().unwrap();
+                            a.set_attribute("href", &pdf_url).unwrap();
+                            a.set_attribute("download", "document.pdf").unwrap();
+                            a.set_attribute("style", "display: none").unwrap();
                             
-                            let blob_parts = Array::new();
-                            blob_parts.push(&pdf_array);
+                            doc.body().unwrap().append_child(&a).unwrap();
+                            a.click();
+                            doc.body().unwrap().remove_child(&a).unwrap();
                             
-                            match Blob::new_with_u8_array_sequence(&blob_parts) {
-                                Ok(blob) => {
-                                    content.pdf_blob = Some(blob);
-                                    
-                                    match Url::create_object_url_with_blob(content.pdf_blob.as_ref().unwrap()) {
-                                        Ok(pdf_url) => {
-                                            content.pdf_url = Some(pdf_url.clone());
-                                            
-                                            // Create download link for PDF
-                                            let doc = document_rc.borrow();
-                                            let a = doc.create_element("a").unwrap();
-                                            let a = a.dyn_into::<web_sys::HtmlElement>().unwrap();
-                                            a.set_attribute("href", &pdf_url).unwrap();
-                                            a.set_attribute("download", "document.pdf").unwrap();
-                                            a.set_attribute("style", "display: none").unwrap();
-                                            
-                                            doc.body().unwrap().append_child(&a).unwrap();
-                                            a.click();
-                                            doc.body().unwrap().remove_child(&a).unwrap();
-                                        },
-                                        Err(e) => {
-                                            console::error_1(&JsString::from(format!("Failed to create object URL: {:?}", e)));
-                                        }
-                                    }
-                                },
-                                Err(e) => {
-                                    console::error_1(&JsString::from(format!("Failed to create blob: {:?}", e)));
-                                }
+                            // Go back to the LaTeX view
+                            if let Some(content) = &*generated_content.borrow() {
+                                render_latex_preview(&doc, &content.latex, &content.diagnostics);
                             }
+                            doc.get_element_by_id("latex-toggle").unwrap()
+                                .set_class_name("toggle-btn active");
+                            doc.get_element_by_id("pdf-toggle").unwrap()
+                                .set_class_name("toggle-btn");
+                        },
+                        Err(err) => {
+                            show_compile_error(&document_rc.borrow(), &err, generated_content.borrow().as_ref());
                         }
-                    }).unchecked_ref(),
-                    1000, // Simulate 1 second compilation time
-                );
+                    }
+                });
             } else {
                 alert("No content generated yet.");
             }
@@ -1207,8 +2090,7 @@ pub fn main() -> Result<(), JsValue> {
             let document = document_rc.borrow();
             
             if let Some(content) = &*generated_content.borrow() {
-                document.get_element_by_id("preview-content").unwrap()
-                    .set_inner_html(&format!("<pre class='latex-content'>{}</pre>", content.latex));
+                render_latex_preview(&document, &content.latex, &content.diagnostics);
             }
             
             document.get_element_by_id("latex-toggle").unwrap()
@@ -1221,84 +2103,268 @@ pub fn main() -> Result<(), JsValue> {
         latex_callback.forget();
     }
     
-    // PDF toggle callback
+    // Diagnostics click handler: jump to the offending line
     {
-        let document_rc = document_rc.clone();
-        let generated_content = generated_content.clone();
-        let window = web_sys::window().unwrap();
-    
-        let pdf_callback = Closure::wrap(Box::new(move || {
-            let document = document_rc.borrow();
-            let preview_content = document.get_element_by_id("preview-content").unwrap();
+        let preview_content = document.get_element_by_id("preview-content").unwrap();
         
-            if let Some(content) = &mut *generated_content.borrow_mut() {
-                // Clean up any existing URL
-                if let Some(url) = content.pdf_url.take() {
-                    Url::revoke_object_url(&url).ok();
-                }
-            
+        let diagnostic_click_callback = Closure::wrap(Box::new(move |event: web_sys::MouseEvent| {
+            let Some(target) = event.target().and_then(|t| t.dyn_into::<Element>().ok()) else {
+                return;
+            };
+            let Ok(Some(item)) = target.closest(".diagnostic[data-line]") else {
+                return;
+            };
+            show_source_line(&get_document(), &item.get_attribute("data-line").unwrap());
+        }) as Box<dyn FnMut(_)>);
+        
+        preview_content.add_event_listener_with_callback("click", diagnostic_click_callback.as_ref().unchecked_ref())?;
+        diagnostic_click_callback.forget();
+    }
+    
+    // Outline click handler: go to the entry's page in the PDF view, or its
+    // line in the LaTeX view
+    {
+        let preview_content = document.get_element_by_id("preview-content").unwrap();
+        
+        let outline_click_callback = Closure::wrap(Box::new(move |event: web_sys::MouseEvent| {
+            let Some(target) = event.target().and_then(|t| t.dyn_into::<Element>().ok()) else {
+                return;
+            };
+            let document = get_document();
+            if let Ok(Some(rewrite)) = target.closest(".outline-rewrite") {
+                event.prevent_default();
+                let index = rewrite.get_attribute("data-section").and_then(|i| i.parse().ok()).unwrap();
+                let title = rewrite.get_attribute("data-title").unwrap_or_default();
+                set_section_scope(&document, Some((index, &title)));
+                if let Some(input) = document.get_element_by_id("chat-input") {
+                    let _ = input.dyn_into::<HtmlElement>().unwrap().focus();
+                }
+                return;
+            }
+            let Ok(Some(entry)) = target.closest(".outline-entry") else {
+                return;
+            };
+            // Entries with children sit in a summary; don't fold them
+            event.prevent_default();
+            
+            let iframe = document.query_selector(".pdf-view iframe").ok().flatten();
+            match (iframe, entry.get_attribute("data-page")) {
+                (Some(iframe), Some(page)) => {
+                    let src = iframe.get_attribute("src").unwrap_or_default();
+                    let url = src.split('#').next().unwrap_or_default();
+                    iframe.set_attribute("src", &format!("{}#page={}", url, page)).unwrap();
+                },
+                _ => {
+                    if let Some(line) = entry.get_attribute("data-line") {
+                        show_source_line(&document, &line);
+                    }
+                },
+            }
+        }) as Box<dyn FnMut(_)>);
+        
+        preview_content.add_event_listener_with_callback("click", outline_click_callback.as_ref().unchecked_ref())?;
+        outline_click_callback.forget();
+    }
+    
+    // LaTeX editor: typing, undo and redo, and the bracket at the cursor
+    {
+        let source_editor = Rc::new(SourceEditor {
+            generated_content: generated_content.clone(),
+            chat_history_state: chat_history_state.clone(),
+            history: RefCell::new(EditHistory::new("")),
+            refresh_timer: Cell::new(None),
+        });
+        let preview_content = document.get_element_by_id("preview-content").unwrap();
+        
+        let editor_textarea = |event: &web_sys::Event| event.target()
+            .and_then(|t| t.dyn_into::<HtmlTextAreaElement>().ok())
+            .filter(|textarea| textarea.id() == "latex-editor");
+        
+        let input_callback = {
+            let source_editor = source_editor.clone();
+            Closure::wrap(Box::new(move |event: web_sys::Event| {
+                if let Some(textarea) = editor_textarea(&event) {
+                    source_editor.edited(&textarea);
+                }
+            }) as Box<dyn FnMut(_)>)
+        };
+        preview_content.add_event_listener_with_callback("input", input_callback.as_ref().unchecked_ref())?;
+        input_callback.forget();
+        
+        let keydown_callback = {
+            let source_editor = source_editor.clone();
+            Closure::wrap(Box::new(move |event: web_sys::KeyboardEvent| {
+                if editor_textarea(&event).is_none() || !(event.ctrl_key() || event.meta_key()) {
+                    return;
+                }
+                let redo = match event.key().to_lowercase().as_str() {
+                    "z" => event.shift_key(),
+                    "y" => true,
+                    _ => return,
+                };
+                event.prevent_default();
+                source_editor.step(redo);
+            }) as Box<dyn FnMut(_)>)
+        };
+        preview_content.add_event_listener_with_callback("keydown", keydown_callback.as_ref().unchecked_ref())?;
+        keydown_callback.forget();
+        
+        let click_callback = {
+            let source_editor = source_editor.clone();
+            Closure::wrap(Box::new(move |event: web_sys::MouseEvent| {
+                let Some(target) = event.target().and_then(|t| t.dyn_into::<Element>().ok()) else {
+                    return;
+                };
+                if target.closest(".editor-undo").ok().flatten().is_some() {
+                    source_editor.step(false);
+                } else if target.closest(".editor-redo").ok().flatten().is_some() {
+                    source_editor.step(true);
+                } else if target.closest(".editor-revisions").ok().flatten().is_some() {
+                    source_editor.toggle_revisions();
+                } else if let Ok(Some(restore)) = target.closest(".revision-restore") {
+                    let entry = restore.closest(".revision-entry").unwrap().unwrap();
+                    let index = entry.get_attribute("data-revision").and_then(|i| i.parse().ok()).unwrap();
+                    source_editor.restore(index);
+                }
+            }) as Box<dyn FnMut(_)>)
+        };
+        preview_content.add_event_listener_with_callback("click", click_callback.as_ref().unchecked_ref())?;
+        click_callback.forget();
+        
+        // Another pair of revisions or diff layout was picked
+        let revision_change_callback = Closure::wrap(Box::new(move |event: web_sys::Event| {
+            let Some(target) = event.target().and_then(|t| t.dyn_into::<Element>().ok()) else {
+                return;
+            };
+            if let Ok(Some(timeline)) = target.closest(".revision-timeline") {
+                source_editor.show_diff(&timeline);
+            }
+        }) as Box<dyn FnMut(_)>);
+        preview_content.add_event_listener_with_callback("change", revision_change_callback.as_ref().unchecked_ref())?;
+        revision_change_callback.forget();
+        
+        // The cursor moved
+        let bracket_callback = Closure::wrap(Box::new(move |event: web_sys::Event| {
+            if editor_textarea(&event).is_some() {
+                show_matching_bracket(&get_document());
+            }
+        }) as Box<dyn FnMut(_)>);
+        for event in ["keyup", "mouseup"] {
+            preview_content.add_event_listener_with_callback(event, bracket_callback.as_ref().unchecked_ref())?;
+        }
+        bracket_callback.forget();
+    }
+    
+    // Section scope clear button
+    {
+        let clear_callback = Closure::wrap(Box::new(move || {
+            set_section_scope(&get_document(), None);
+        }) as Box<dyn FnMut()>);
+        
+        section_scope_clear.add_event_listener_with_callback("click", clear_callback.as_ref().unchecked_ref())?;
+        clear_callback.forget();
+    }
+    
+    // Length notice: send the expand/condense request as a follow-up
+    {
+        let generated_content = generated_content.clone();
+        let pdf_size_select = pdf_size_select.clone();
+        let preview_content = document.get_element_by_id("preview-content").unwrap();
+        
+        let length_click_callback = Closure::wrap(Box::new(move |event: web_sys::MouseEvent| {
+            let Some(target) = event.target().and_then(|t| t.dyn_into::<Element>().ok()) else {
+                return;
+            };
+            let Ok(Some(notice)) = target.closest(".length-notice") else {
+                return;
+            };
+            if target.closest(".length-dismiss-btn").ok().flatten().is_some() {
+                notice.remove();
+                return;
+            }
+            if target.closest(".length-adjust-btn").ok().flatten().is_none() {
+                return;
+            }
+            
+            let request = match &*generated_content.borrow() {
+                Some(content) => {
+                    // Revise towards the size the document was written for
+                    pdf_size_select.set_value(&content.pdf_size);
+                    let size = PdfSize::from_label(&content.pdf_size).unwrap_or_default();
+                    content.page_count.and_then(|pages| size.target().adjustment_request(pages))
+                },
+                None => None,
+            };
+            let document = get_document();
+            let send_btn = document.get_element_by_id("send-btn").unwrap();
+            let Some(request) = request.filter(|_| !send_btn.has_attribute("disabled")) else {
+                return;
+            };
+            notice.remove();
+            
+            document.get_element_by_id("chat-input").unwrap()
+                .dyn_into::<HtmlTextAreaElement>().unwrap()
+                .set_value(&request);
+            send_btn.dyn_into::<HtmlElement>().unwrap().click();
+        }) as Box<dyn FnMut(_)>);
+        
+        preview_content.add_event_listener_with_callback("click", length_click_callback.as_ref().unchecked_ref())?;
+        length_click_callback.forget();
+    }
+    
+    // PDF toggle callback
+    {
+        let document_rc = document_rc.clone();
+        let generated_content = generated_content.clone();
+    
+        let pdf_callback = Closure::wrap(Box::new(move || {
+            let document = document_rc.borrow();
+            let preview_content = document.get_element_by_id("preview-content").unwrap();
+        
+            if generated_content.borrow().is_some() {
                 // Show compilation in progress with white background
-                preview_content.set_inner_html(r#"
+                preview_content.set_html(&Html::from_static(r#"
                     <div class="pdf-message" style="background: white; height: 100%; display: flex; flex-direction: column; align-items: center; justify-content: center;">
                         <div class="loader-spinner"></div>
                         <p>Compiling PDF preview...</p>
                     </div>
-                "#);
+                "#));
             
-                // Simulate PDF compilation (in a real app, you'd call a LaTeX compilation service)
                 let document_rc = document_rc.clone();
                 let content_clone = generated_content.clone();
             
-                let _ = window.set_timeout_with_callback_and_timeout_and_arguments_0(
-                    Closure::once_into_js(move || {
-                        let mut content = content_clone.borrow_mut();
-                        if let Some(content) = &mut *content {
-                            // Here you would normally send the LaTeX to a compilation service
-                            // For this example, we'll create a simple PDF with the LaTeX content rendered as text
-                        
-                            // Create a PDF with the LaTeX content
-                            let latex_content = &content.latex;
-                            let pdf_content = format!("%PDF-1.4\n1 0 obj\n<< /Type /Catalog /Pages 2 0 R >>\nendobj\n2 0 obj\n<< /Type /Pages /Kids [3 0 R] /Count 1 >>\nendobj\n3 0 obj\n<< /Type /Page /Parent 2 0 R /Resources << /Font << /F1 4 0 R >> >> /Contents 5 0 R >>\nendobj\n4 0 obj\n<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>\nendobj\n5 0 obj\n<< /Length {} >>\nstream\nBT\n/F1 12 Tf\n100 700 Td\n({}) Tj\nET\nendstream\nendobj\nxref\n0 6\n0000000000 65535 f \n0000000010 00000 n \n0000000079 00000 n \n0000000173 00000 n \n0000000301 00000 n \n0000000380 00000 n \ntrailer\n<< /Size 6 /Root 1 0 R >>\nstartxref\n492\n%%EOF", 
-                                latex_content.len() + 20, // Length placeholder
-                                latex_content.replace("(", "\\(").replace(")", "\\)") // Escape parentheses
-                            );
-                        
-                            // Convert to Uint8Array
-                            let mut pdf_bytes = Vec::with_capacity(pdf_content.len());
-                            pdf_bytes.extend_from_slice(pdf_content.as_bytes());
-                            let pdf_array = Uint8Array::from(&pdf_bytes[..]);
-                        
-                            let blob_parts = Array::new();
-                            blob_parts.push(&pdf_array);
-                        
-                            match Blob::new_with_u8_array_sequence(&blob_parts) {
-                                Ok(blob) => {
-                                    content.pdf_blob = Some(blob);
-                                
-                                    match Url::create_object_url_with_blob(content.pdf_blob.as_ref().unwrap()) {
-                                        Ok(pdf_url) => {
-                                            content.pdf_url = Some(pdf_url.clone());
-                                        
-                                            let doc = document_rc.borrow();
-                                            let preview_content = doc.get_element_by_id("preview-content").unwrap();
-                                            preview_content.set_inner_html(&format!(
-                                                r#"<iframe src="{}" style="width:100%;height:100%;border:none;background:white;"></iframe>"#,
-                                                pdf_url
-                                            ));
-                                        },
-                                        Err(e) => {
-                                            console::error_1(&JsString::from(format!("Failed to create object URL: {:?}", e)));
-                                        }
-                                    }
-                                },
-                                Err(e) => {
-                                    console::error_1(&JsString::from(format!("Failed to create blob: {:?}", e)));
+                wasm_bindgen_futures::spawn_local(async move {
+                    let result = compile_current_pdf(&content_clone).await;
+                    let doc = document_rc.borrow();
+                    
+                    // The user may have switched back to LaTeX while compiling
+                    if !doc.get_element_by_id("pdf-toggle").unwrap().class_name().contains("active") {
+                        return;
+                    }
+                    
+                    match result {
+                        Ok(pdf_url) => {
+                            let notice = content_clone.borrow().as_ref().map(length_notice).unwrap_or_default();
+                            let preview_content = doc.get_element_by_id("preview-content").unwrap();
+                            preview_content.set_html(&Html::fill(
+                                r#"<div class="pdf-view">{}<iframe src="{}" style="width:100%;height:100%;border:none;background:white;"></iframe></div>"#,
+                                &[&notice, &pdf_url]
+                            ));
+                            
+                            if let Some(content) = &*content_clone.borrow() {
+                                let outline = outline::outline(&content.latex);
+                                if !outline.is_empty() {
+                                    let iframe = preview_content.query_selector("iframe").unwrap().unwrap();
+                                    let panel = render_outline(&doc, &outline, content.page_count);
+                                    iframe.before_with_node_1(&panel).unwrap();
                                 }
                             }
+                        },
+                        Err(err) => {
+                            show_compile_error(&doc, &err, content_clone.borrow().as_ref());
                         }
-                    }).unchecked_ref(),
-                    1000, // Simulate 1 second compilation time
-                );
+                    }
+                });
             }
         
             document.get_element_by_id("latex-toggle").unwrap()
@@ -1311,370 +2377,1656 @@ pub fn main() -> Result<(), JsValue> {
         pdf_callback.forget();
     }
     
-    // History item click handler
+    // History search
+    {
+        let chat_history_state = chat_history_state.clone();
+        let history_search = document.get_element_by_id("history-search").unwrap();
+        
+        let search_callback = Closure::wrap(Box::new(move || {
+            update_history_panel(&get_document(), &chat_history_state.borrow());
+        }) as Box<dyn FnMut()>);
+        
+        history_search.add_event_listener_with_callback("input", search_callback.as_ref().unchecked_ref())?;
+        search_callback.forget();
+    }
+    
+    // Clear history button: delete every conversation that is not pinned
     {
-        let document_rc = document_rc.clone();
         let generated_content = generated_content.clone();
+        let chat_history_state = chat_history_state.clone();
+        let history_clear_btn = document.get_element_by_id("history-clear-btn").unwrap();
+        
+        let clear_callback = Closure::wrap(Box::new(move || {
+            let mut history = chat_history_state.borrow_mut();
+            let ids: Vec<String> = history.iter()
+                .filter(|conversation| !conversation.pinned)
+                .map(|conversation| conversation.id.clone())
+                .collect();
+            if ids.is_empty() {
+                return;
+            }
+            let message = match ids.len() {
+                1 => "Delete 1 conversation? Pinned conversations are kept.".to_string(),
+                count => format!("Delete {} conversations? Pinned conversations are kept.", count),
+            };
+            if !web_sys::window().unwrap().confirm_with_message(&message).unwrap_or(false) {
+                return;
+            }
+            
+            history.retain(|conversation| conversation.pinned);
+            forget_conversations(&generated_content, &ids);
+            history_store::delete_later(ids);
+            update_history_panel(&get_document(), &history);
+        }) as Box<dyn FnMut()>);
+        
+        history_clear_btn.add_event_listener_with_callback("click", clear_callback.as_ref().unchecked_ref())?;
+        clear_callback.forget();
+    }
+    
+    // History item click handler: restore the conversation with the
+    // settings it was generated with
+    {
+        let generated_content = generated_content.clone();
+        let chat_history_state = chat_history_state.clone();
         let history_list = document.get_element_by_id("history-list").unwrap();
         
         let history_click_callback = Closure::wrap(Box::new(move |event: web_sys::MouseEvent| {
-            if let Some(target) = event.target() {
-                if let Ok(element) = target.dyn_into::<Element>() {
-                    let parent = element.parent_element();
-                    let node_to_compare = parent.as_ref().map(|v| v as &web_sys::Node);
-                    
-                    if element.class_name().contains("history-entry") || 
-                       element.parent_element().map_or(false, |p| p.class_name().contains("history-entry")) {
-                        let document = document_rc.borrow();
-                        let history_list = document.get_element_by_id("history-list").unwrap();
-                        let entries = history_list.query_selector_all(".history-entry").unwrap();
-
-                        for i in 0..entries.length() {
-                            if let Ok(entry) = entries.get(i).unwrap().dyn_into::<Element>() {
-                                if entry.is_same_node(Some(&element)) || 
-                                   entry.is_same_node(node_to_compare) {
-                                    if let Some(data_index) = entry.get_attribute("data-index") {
-                                        let data_index = data_index.parse::<usize>().unwrap_or(0);
-                                        if let Ok(Some(storage)) = web_sys::window().unwrap().local_storage() {
-                                            if let Ok(Some(history)) = storage.get_item("chat_history_data") {
-                                                if let Ok(history_data) = JSON::parse(&history) {
-                                                    if let Ok(entry_data) = Reflect::get(&history_data, &JsValue::from_f64(data_index as f64)) {
-                                                        let topic = Reflect::get(&entry_data, &JsValue::from_str("topic"))
-                                                            .ok()
-                                                            .and_then(|v| v.as_string())
-                                                            .unwrap_or_default();
-                    
-                                                        let content = Reflect::get(&entry_data, &JsValue::from_str("content"))
-                                                            .ok()
-                                                            .and_then(|v| v.as_string())
-                                                            .unwrap_or_default();
-                                                        
-                                                        let template = Reflect::get(&entry_data, &JsValue::from_str("template"))
-                                                            .ok()
-                                                            .and_then(|v| v.as_string())
-                                                            .unwrap_or_else(|| "Article".to_string());
-                                                        
-                                                        let ai_provider = Reflect::get(&entry_data, &JsValue::from_str("ai_provider"))
-                                                            .ok()
-                                                            .and_then(|v| v.as_string())
-                                                            .unwrap_or_else(|| "Claude".to_string());
-                                                        
-                                                        let pdf_size = Reflect::get(&entry_data, &JsValue::from_str("pdf_size"))
-                                                            .ok()
-                                                            .and_then(|v| v.as_string())
-                                                            .unwrap_or_else(|| "Medium".to_string());
-                                                        
-                                                        // Update chat history display
-                                                        let chat_history = document.get_element_by_id("chat-history").unwrap();
-                                                        chat_history.set_inner_html("");
-                                                        
-                                                        let user_message = document.create_element("div").unwrap();
-                                                        user_message.set_class_name("chat-message user-message");
-                                                        user_message.set_inner_html(&format!(
-                                                            r#"<div class="message-content">{}</div>"#,
-                                                            topic.replace("\n", "<br>")
-                                                        ));
-                                                        chat_history.append_child(&user_message).unwrap();
-                                                        
-                                                        let ai_message = document.create_element("div").unwrap();
-                                                        ai_message.set_class_name("chat-message ai-message");
-                                                        ai_message.set_inner_html(&format!(
-                                                            r#"<div class="message-content">
-                                                                <div>Generated LaTeX document with {} sections</div>
-                                                                <div class="message-meta">
-                                                                    <span>Template: {}</span>
-                                                                    <span>AI: {}</span>
-                                                                    <span>Size: {}</span>
-                                                                </div>
-                                                            </div>"#,
-                                                            content.matches(r"\section").count(),
-                                                            template,
-                                                            ai_provider,
-                                                            pdf_size
-                                                        ));
-                                                        chat_history.append_child(&ai_message).unwrap();
-                                                        
-                                                        // Update preview with LaTeX content
-                                                        let preview_content = document.get_element_by_id("preview-content").unwrap();
-                                                        preview_content.set_inner_html(&format!("<pre class='latex-content'>{}</pre>", content));
-                                                        
-                                                        // Store the generated content
-                                                        let mut chat_history = Vec::new();
-                                                        chat_history.push(("user".to_string(), topic));
-                                                        chat_history.push(("ai".to_string(), content.clone()));
-                                                        
-                                                        *generated_content.borrow_mut() = Some(GeneratedContent {
-                                                            latex: content,
-                                                            pdf_blob: None,
-                                                            pdf_url: None,
-                                                            chat_history,
-                                                            pdf_size,
-                                                            template,
-                                                            ai_provider,
-                                                        });
-                                                        
-                                                        // Enable download button
-                                                        document.get_element_by_id("download-btn").unwrap()
-                                                            .remove_attribute("disabled").unwrap();
-                                                        
-                                                        // Switch to LaTeX view
-                                                        document.get_element_by_id("latex-toggle").unwrap()
-                                                            .set_class_name("toggle-btn active");
-                                                        document.get_element_by_id("pdf-toggle").unwrap()
-                                                            .set_class_name("toggle-btn");
-                                                        
-                                                        // Close history panel
-                                                        document.get_element_by_id("history-panel").unwrap()
-                                                            .set_class_name("history-panel");
-                                                        document.get_element_by_id("left-panel").unwrap()
-                                                            .set_class_name("left-panel");
-                                                        
-                                                        break;
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
+            let Some(target) = event.target().and_then(|t| t.dyn_into::<Element>().ok()) else {
+                return;
+            };
+            let Some(id) = target.closest(".history-entry[data-id]").ok().flatten()
+                .and_then(|entry| entry.get_attribute("data-id")) else {
+                return;
+            };
+            if target.closest(".history-actions").ok().flatten().is_some() {
+                manage_history_entry(&target, &id, &chat_history_state, &generated_content);
+                return;
+            }
+            let Some(conversation) = history::find(&chat_history_state.borrow(), &id).cloned() else {
+                return;
+            };
+            let document = get_document();
+            let content = conversation.latex().to_string();
+            
+            // Update chat history display
+            let chat_history = document.get_element_by_id("chat-history").unwrap();
+            chat_history.set_inner_html("");
+            
+            let user_message = document.create_element("div").unwrap();
+            user_message.set_class_name("chat-message user-message");
+            user_message.set_html(&Html::fill(
+                r#"<div class="message-content">{}</div>"#,
+                &[&Html::lines(&conversation.topic)]
+            ));
+            chat_history.append_child(&user_message).unwrap();
+            
+            // Named after what made the current document
+            let made_by = conversation.revisions.last().map_or(RevisionKind::Generation, |revision| revision.kind).label();
+            let ai_message = document.create_element("div").unwrap();
+            ai_message.set_class_name("chat-message ai-message");
+            ai_message.set_html(&Html::fill(
+                r#"<div class="message-content">
+                    <div>{} LaTeX document with {} sections</div>
+                    <div class="message-meta">
+                        <span>Template: {}</span>
+                        <span>AI: {}</span>
+                        <span>Size: {}</span>
+                    </div>
+                </div>"#,
+                &[&made_by, &section_count(&content), &conversation.template, &conversation.provider, &conversation.pdf_size]
+            ));
+            chat_history.append_child(&ai_message).unwrap();
+            
+            // Follow-up requests use the conversation's settings
+            for (select, value) in [
+                ("template-select", &conversation.template),
+                ("api-provider", &conversation.provider),
+                ("pdf-size-select", &conversation.pdf_size),
+            ] {
+                let select = document.get_element_by_id(select).unwrap().dyn_into::<HtmlSelectElement>().unwrap();
+                let selected = select.value();
+                select.set_value(value);
+                // Not an option any more; keep the current choice
+                if select.value() != *value {
+                    select.set_value(&selected);
                 }
             }
+            
+            // Update preview with LaTeX content
+            render_latex_preview(&document, &content, &[]);
+            
+            // Store the generated content, dropping the previous document's PDF
+            if let Some(url) = generated_content.borrow_mut().as_mut().and_then(|previous| previous.pdf_url.take()) {
+                Url::revoke_object_url(&url).ok();
+            }
+            *generated_content.borrow_mut() = Some(GeneratedContent {
+                latex: content,
+                pdf_blob: None,
+                pdf_url: None,
+                page_count: None,
+                assets: conversation.assets.clone(),
+                diagnostics: Vec::new(),
+                chat_history: conversation.chat_history(),
+                pdf_size: conversation.pdf_size,
+                template: conversation.template,
+                ai_provider: conversation.provider,
+                conversation_id: Some(conversation.id),
+            });
+            
+            // Enable download button
+            document.get_element_by_id("download-btn").unwrap()
+                .remove_attribute("disabled").unwrap();
+            
+            // Switch to LaTeX view
+            document.get_element_by_id("latex-toggle").unwrap()
+                .set_class_name("toggle-btn active");
+            document.get_element_by_id("pdf-toggle").unwrap()
+                .set_class_name("toggle-btn");
+            
+            // Close history panel
+            document.get_element_by_id("history-panel").unwrap()
+                .set_class_name("history-panel");
+            document.get_element_by_id("left-panel").unwrap()
+                .set_class_name("left-panel");
         }) as Box<dyn FnMut(_)>);
         
         history_list.add_event_listener_with_callback("click", history_click_callback.as_ref().unchecked_ref())?;
         history_click_callback.forget();
     }
     
-    // Add CSS styles
-    let style = document.create_element("style")?;
-    style.set_text_content(Some(get_css()));
-    document.query_selector("head")?.unwrap().append_child(&style)?;
-    
-    Ok(())
+    // Add CSS styles
+    let style = document.create_element("style")?;
+    style.set_text_content(Some(get_css()));
+    document.query_selector("head")?.unwrap().append_child(&style)?;
+    
+    Ok(())
+}
+
+// Update the history panel to show `history`, narrowed by the search box.
+// Entries and date headings already shown are kept and moved rather than
+// built again.
+fn update_history_panel(document: &Document, history: &[Conversation]) {
+    let history_list = document.get_element_by_id("history-list").unwrap();
+    let query = document.get_element_by_id("history-search").unwrap()
+        .dyn_into::<HtmlInputElement>().unwrap()
+        .value();
+    
+    let mut shown = HashMap::new();
+    let children = history_list.children();
+    for i in 0..children.length() {
+        let child = children.item(i).unwrap();
+        if let Some(key) = child.get_attribute("data-key") {
+            shown.insert(key, child);
+        }
+    }
+    
+    let groups = history::date_groups(history, &query, date_label);
+    let mut wanted = Vec::new();
+    if groups.is_empty() {
+        let key = if history.is_empty() { "empty" } else { "no-match" };
+        wanted.push(shown.remove(key).unwrap_or_else(|| history_empty_state(document, history.is_empty())));
+    }
+    for (date, entries) in groups {
+        let key = format!("date:{}", date);
+        wanted.push(shown.remove(&key).unwrap_or_else(|| history_date_element(document, &key, &date)));
+        for conversation in entries {
+            let entry = shown.remove(&format!("entry:{}", conversation.id))
+                .unwrap_or_else(|| history_entry_element(document, conversation));
+            update_history_entry(&entry, conversation);
+            wanted.push(entry);
+        }
+    }
+    
+    // Put the wanted nodes in order; whatever is left over ends up after them
+    let mut next = history_list.first_element_child();
+    for node in &wanted {
+        if next.as_ref().is_some_and(|next| next.is_same_node(Some(node))) {
+            next = node.next_element_sibling();
+        } else {
+            history_list.insert_before(node, next.as_ref().map(|next| next.as_ref())).unwrap();
+        }
+    }
+    while let Some(node) = next {
+        next = node.next_element_sibling();
+        node.remove();
+    }
+}
+
+fn history_empty_state(document: &Document, no_history: bool) -> Element {
+    let state = document.create_element("div").unwrap();
+    state.set_class_name("empty-state");
+    if no_history {
+        state.set_attribute("data-key", "empty").unwrap();
+        state.set_html(&Html::from_static(r#"
+            <svg xmlns="http://www.w3.org/2000/svg" width="24" height="24" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round">
+                <path d="M21 15a2 2 0 0 1-2 2H7l-4 4V5a2 2 0 0 1-2-2V5a2 2 0 0 0 2 2h14a2 2 0 0 0 2-2V5a2 2 0 0 1-2-2z"></path>
+            </svg>
+            <p>No chat history yet</p>
+        "#));
+    } else {
+        state.set_attribute("data-key", "no-match").unwrap();
+        state.set_html(&Html::from_static("<p>No conversations match your search</p>"));
+    }
+    state
+}
+
+fn history_date_element(document: &Document, key: &str, date: &str) -> Element {
+    let date_header = document.create_element("div").unwrap();
+    date_header.set_class_name("history-date");
+    date_header.set_attribute("data-key", key).unwrap();
+    date_header.set_text_content(Some(date));
+    date_header
+}
+
+fn history_entry_element(document: &Document, conversation: &Conversation) -> Element {
+    let entry = document.create_element("div").unwrap();
+    entry.set_class_name("history-entry");
+    entry.set_attribute("data-key", &format!("entry:{}", conversation.id)).unwrap();
+    entry.set_attribute("data-id", &conversation.id).unwrap();
+    
+    let time_span = document.create_element("span").unwrap();
+    time_span.set_class_name("history-time");
+    time_span.set_text_content(Some(&time_label(conversation.created)));
+    
+    let topic_span = document.create_element("span").unwrap();
+    topic_span.set_class_name("history-topic");
+    
+    let actions = document.create_element("div").unwrap();
+    actions.set_class_name("history-actions");
+    for (class, label) in [("history-pin", "Pin"), ("history-rename", "Rename"), ("history-delete", "Delete")] {
+        let button = document.create_element("button").unwrap();
+        button.set_class_name(class);
+        button.set_text_content(Some(label));
+        actions.append_child(&button).unwrap();
+    }
+    
+    entry.append_child(&time_span).unwrap();
+    entry.append_child(&topic_span).unwrap();
+    entry.append_child(&actions).unwrap();
+    entry
+}
+
+// Bring an entry's name and pin state up to date
+fn update_history_entry(entry: &Element, conversation: &Conversation) {
+    let topic = entry.query_selector(".history-topic").unwrap().unwrap();
+    if topic.text_content().as_deref() != Some(conversation.title()) {
+        topic.set_text_content(Some(conversation.title()));
+    }
+    entry.class_list().toggle_with_force("pinned", conversation.pinned).unwrap();
+    entry.query_selector(".history-pin").unwrap().unwrap()
+        .set_text_content(Some(if conversation.pinned { "Unpin" } else { "Pin" }));
+}
+
+fn date_label(time: f64) -> String {
+    js_sys::Date::new(&JsValue::from_f64(time))
+        .to_locale_date_string("en-US", &JsValue::UNDEFINED)
+        .as_string()
+        .unwrap_or_default()
+}
+
+fn time_label(time: f64) -> String {
+    js_sys::Date::new(&JsValue::from_f64(time))
+        .to_locale_time_string("en-US")
+        .as_string()
+        .unwrap_or_default()
+}
+
+// A new random conversation id
+fn new_conversation_id() -> String {
+    random_bytes::<16>().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Pin, rename or delete a conversation with the buttons on its entry
+fn manage_history_entry(
+    target: &Element,
+    id: &str,
+    chat_history_state: &RefCell<Vec<Conversation>>,
+    generated_content: &RefCell<Option<GeneratedContent>>,
+) {
+    let window = web_sys::window().unwrap();
+    let mut history = chat_history_state.borrow_mut();
+    let Some(position) = history.iter().position(|conversation| conversation.id == id) else {
+        return;
+    };
+    let clicked = |class: &str| target.closest(class).ok().flatten().is_some();
+    
+    if clicked(".history-pin") {
+        let conversation = &mut history[position];
+        conversation.pinned = !conversation.pinned;
+        history_store::save_later(conversation.clone());
+    } else if clicked(".history-rename") {
+        let conversation = &mut history[position];
+        let Ok(Some(title)) = window.prompt_with_message_and_default("Rename conversation", conversation.title()) else {
+            return;
+        };
+        conversation.rename(&title, js_sys::Date::now());
+        history_store::save_later(conversation.clone());
+    } else if clicked(".history-delete") {
+        let message = format!("Delete \"{}\"? This cannot be undone.", history[position].title());
+        if !window.confirm_with_message(&message).unwrap_or(false) {
+            return;
+        }
+        let ids = vec![history.remove(position).id];
+        forget_conversations(generated_content, &ids);
+        history_store::delete_later(ids);
+    } else {
+        return;
+    }
+    update_history_panel(&get_document(), &history);
+}
+
+// The current document stops being saved with a deleted conversation
+fn forget_conversations(generated_content: &RefCell<Option<GeneratedContent>>, ids: &[String]) {
+    if let Some(content) = generated_content.borrow_mut().as_mut() {
+        if content.conversation_id.as_ref().is_some_and(|id| ids.contains(id)) {
+            content.conversation_id = None;
+        }
+    }
+}
+
+// Keep the current document as a revision of its history conversation. A
+// document with none starts one, named after its first request or `topic`.
+fn record_revision(chat_history_state: &RefCell<Vec<Conversation>>, content: &mut GeneratedContent, topic: &str, kind: RevisionKind) {
+    let now = js_sys::Date::now();
+    let mut history = chat_history_state.borrow_mut();
+    let position = content.conversation_id.as_ref()
+        .and_then(|id| history.iter().position(|conversation| &conversation.id == id));
+    let conversation = match position {
+        Some(position) => &mut history[position],
+        None => {
+            let topic = content.chat_history.iter()
+                .find(|(role, _)| role == chat::USER)
+                .map_or(topic, |(_, request)| request);
+            history.push(Conversation::new(new_conversation_id(), now, topic, &content.ai_provider, &content.template, &content.pdf_size));
+            history.last_mut().unwrap()
+        },
+    };
+    conversation.provider = content.ai_provider.clone();
+    conversation.template = content.template.clone();
+    conversation.pdf_size = content.pdf_size.clone();
+    conversation.set_turns(&content.chat_history, now);
+    conversation.add_revision(kind, &content.latex, now);
+    conversation.assets = content.assets.clone();
+    content.conversation_id = Some(conversation.id.clone());
+    history_store::save_later(conversation.clone());
+}
+
+// Keep an edited document with its history conversation, both in the
+// panel's state and in the saved history
+fn save_history_content(chat_history_state: &RefCell<Vec<Conversation>>, id: &str, latex: &str) {
+    let mut history = chat_history_state.borrow_mut();
+    if let Some(conversation) = history.iter_mut().find(|conversation| conversation.id == id) {
+        conversation.edit_latex(latex, js_sys::Date::now());
+        history_store::save_later(conversation.clone());
+    }
+}
+
+// Keep the document's files with its history conversation
+fn save_history_assets(chat_history_state: &RefCell<Vec<Conversation>>, id: &str, assets: &[Asset]) {
+    let mut history = chat_history_state.borrow_mut();
+    if let Some(conversation) = history.iter_mut().find(|conversation| conversation.id == id) {
+        conversation.assets = assets.to_vec();
+        history_store::save_later(conversation.clone());
+    }
+}
+
+// The key vault saved in this browser, if any
+fn saved_vault() -> Option<Result<Vault, VaultError>> {
+    let storage = web_sys::window().unwrap().local_storage().ok().flatten()?;
+    let json = storage.get_item(VAULT_STORAGE_KEY).ok().flatten()?;
+    Some(Vault::from_json(&json))
+}
+
+// Random bytes from the browser's cryptographic generator
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    web_sys::window().unwrap().crypto().unwrap()
+        .get_random_values_with_u8_array(&mut bytes).unwrap();
+    bytes
+}
+
+// Put the session's keys in the profile form, or clear it when locked
+fn show_api_keys(document: &Document, api_keys: &ApiKeys) {
+    for provider in providers::PROVIDERS {
+        document.get_element_by_id(&format!("{}-key", provider.id())).unwrap()
+            .dyn_into::<HtmlInputElement>().unwrap()
+            .set_value(&api_keys.get(provider.id()));
+    }
+}
+
+// Say how the keys are stored and offer the vault actions that apply
+fn update_vault_status(document: &Document, api_keys: &ApiKeys) {
+    let has_vault = saved_vault().is_some();
+    let unlocked = api_keys.vault_passphrase.is_some();
+    let status = match (has_vault, unlocked) {
+        (false, _) => "Keys are saved unencrypted in this browser. Enter a passphrase and save to encrypt them.",
+        (true, false) => "Keys are encrypted. Unlock the vault to use them in this session.",
+        (true, true) => "The vault is unlocked for this session. Saving with a new passphrase changes it.",
+    };
+    document.get_element_by_id("vault-status").unwrap().set_text_content(Some(status));
+    
+    let show = |id: &str, visible: bool| {
+        let button = document.get_element_by_id(id).unwrap();
+        if visible {
+            button.remove_attribute("hidden").unwrap();
+        } else {
+            button.set_attribute("hidden", "").unwrap();
+        }
+    };
+    show("vault-unlock-btn", has_vault && !unlocked);
+    show("vault-lock-btn", has_vault && unlocked);
+}
+
+// The proxy to send model requests through, if one is configured
+fn llm_proxy_url() -> Option<String> {
+    web_sys::window().unwrap().local_storage().ok().flatten()
+        .and_then(|storage| storage.get_item(LLM_PROXY_STORAGE_KEY).ok().flatten())
+        .filter(|url| !url.trim().is_empty())
+}
+
+// The saved key, endpoint and generation settings for `provider`
+fn provider_settings(provider: &dyn LlmProvider, api_keys: &ApiKeys) -> Result<ProviderSettings, String> {
+    let storage = web_sys::window().unwrap().local_storage().ok().flatten();
+    ProviderSettings::parse(&api_keys.get(provider.id()), |name| {
+        storage.as_ref()
+            .and_then(|storage| storage.get_item(&format!("{}_{}", provider.id(), name)).ok().flatten())
+            .unwrap_or_default()
+    })
+}
+
+// Value of a settings input or textarea
+fn form_value(element: &Element) -> String {
+    match element.dyn_ref::<HtmlTextAreaElement>() {
+        Some(textarea) => textarea.value(),
+        None => element.dyn_ref::<HtmlInputElement>().map(HtmlInputElement::value).unwrap_or_default(),
+    }
+}
+
+fn set_form_value(element: &Element, value: &str) {
+    if let Some(textarea) = element.dyn_ref::<HtmlTextAreaElement>() {
+        textarea.set_value(value);
+    } else if let Some(input) = element.dyn_ref::<HtmlInputElement>() {
+        input.set_value(value);
+    }
+}
+
+// Compile the current document with the configured backend, reusing the
+// cached PDF when the LaTeX has not changed since the last compile
+async fn compile_current_pdf(generated_content: &Rc<RefCell<Option<GeneratedContent>>>) -> Result<String, CompileError> {
+    let (latex, assets) = match &*generated_content.borrow() {
+        Some(content) => {
+            if let Some(url) = &content.pdf_url {
+                return Ok(url.clone());
+            }
+            (content.latex.clone(), content.assets.clone())
+        },
+        None => return Err(CompileError::new("No content generated yet.")),
+    };
+    
+    let compiler = compiler_from_settings();
+    let result = compiler.compile(&latex, &assets).await;
+    
+    // Keep the parsed log around for the LaTeX view, warnings included
+    let log = match &result {
+        Ok(output) => &output.log,
+        Err(err) => &err.log,
+    };
+    let diagnostics = texlog::parse_log(log);
+    if let Some(content) = &mut *generated_content.borrow_mut() {
+        if content.latex == latex {
+            content.diagnostics = diagnostics;
+        }
+    }
+    
+    let output = result?;
+    
+    let pdf_array = Uint8Array::from(&output.pdf[..]);
+    let blob_parts = Array::new();
+    blob_parts.push(&pdf_array);
+    
+    let options = BlobPropertyBag::new();
+    options.set_type("application/pdf");
+    
+    let blob = Blob::new_with_u8_array_sequence_and_options(&blob_parts, &options)
+        .map_err(|e| CompileError::new(format!("Failed to create blob: {:?}", e)))?;
+    let pdf_url = Url::create_object_url_with_blob(&blob)
+        .map_err(|e| CompileError::new(format!("Failed to create object URL: {:?}", e)))?;
+    
+    match &mut *generated_content.borrow_mut() {
+        // Only cache the PDF if the document was not replaced while compiling
+        Some(content) if content.latex == latex => {
+            if let Some(old_url) = content.pdf_url.replace(pdf_url.clone()) {
+                Url::revoke_object_url(&old_url).ok();
+            }
+            content.pdf_blob = Some(blob);
+            // A compile service may answer with just the PDF and no log
+            content.page_count = texlog::page_count(&output.log).or_else(|| length::pdf_page_count(&output.pdf));
+        },
+        _ => {}
+    }
+    
+    Ok(pdf_url)
+}
+
+// Banner above the PDF when its page count misses the size target, with a
+// button asking for an expanded or condensed revision
+fn length_notice(content: &GeneratedContent) -> Html {
+    let (Some(pages), Some(size)) = (content.page_count, PdfSize::from_label(&content.pdf_size)) else {
+        return Html::default();
+    };
+    let target = size.target();
+    let action = match target.check(pages) {
+        PageCheck::OnTarget => return Html::default(),
+        PageCheck::TooShort => "Expand",
+        PageCheck::TooLong => "Condense",
+    };
+    
+    Html::fill(
+        r#"<div class="length-notice">
+            <span>This PDF has {} page{}; {} documents are {}.</span>
+            <button class="length-adjust-btn">{}</button>
+            <button class="length-dismiss-btn" title="Dismiss">&times;</button>
+        </div>"#,
+        &[&pages, &if pages == 1 { "" } else { "s" }, &size.label(), &target.pages(), &action]
+    )
+}
+
+// Show a failed compilation: the error on top of the LaTeX view with the
+// parsed diagnostics, or the raw log if nothing could be parsed from it
+fn show_compile_error(document: &Document, err: &CompileError, content: Option<&GeneratedContent>) {
+    console::error_1(&JsString::from(format!("Compilation failed: {}", err.message)));
+    
+    let preview_content = document.get_element_by_id("preview-content").unwrap();
+    match content {
+        Some(content) => render_latex_preview(document, &content.latex, &content.diagnostics),
+        None => preview_content.set_inner_html(""),
+    }
+    
+    let error = document.create_element("div").unwrap();
+    error.set_class_name("error");
+    error.set_text_content(Some(&err.message));
+    preview_content.prepend_with_node_1(&error).unwrap();
+    
+    if !err.log.is_empty() && content.is_none_or(|c| c.diagnostics.is_empty()) {
+        let log = document.create_element("pre").unwrap();
+        log.set_class_name("latex-content compile-log");
+        log.set_text_content(Some(&err.log));
+        preview_content.append_child(&log).unwrap();
+    }
+    
+    document.get_element_by_id("latex-toggle").unwrap()
+        .set_class_name("toggle-btn active");
+    document.get_element_by_id("pdf-toggle").unwrap()
+        .set_class_name("toggle-btn");
+}
+
+// Render the LaTeX source as an editor: a textarea over a highlighted copy
+// with line numbers and the lines with diagnostics marked, followed by a
+// clickable list of the diagnostics and lint issues
+fn render_latex_preview(document: &Document, latex: &str, diagnostics: &[Diagnostic]) {
+    let preview_content = document.get_element_by_id("preview-content").unwrap();
+    preview_content.set_inner_html("");
+    
+    let diagnostics: Vec<Diagnostic> = diagnostics.iter().cloned()
+        .chain(lint::lint(latex).iter().map(LintIssue::to_diagnostic))
+        .collect();
+    
+    let outline = outline::outline(latex);
+    if !outline.is_empty() {
+        preview_content.append_child(&render_outline(document, &outline, None)).unwrap();
+    }
+    
+    let toolbar = document.create_element("div").unwrap();
+    toolbar.set_class_name("editor-toolbar");
+    for (class, label, title) in [
+        ("editor-undo", "Undo", "Undo (Ctrl+Z)"),
+        ("editor-redo", "Redo", "Redo (Ctrl+Y)"),
+        ("editor-revisions", "Revisions", "Compare and restore earlier versions"),
+    ] {
+        let button = document.create_element("button").unwrap();
+        button.set_class_name(class);
+        button.set_attribute("title", title).unwrap();
+        button.set_text_content(Some(label));
+        toolbar.append_child(&button).unwrap();
+    }
+    preview_content.append_child(&toolbar).unwrap();
+    
+    let editor = document.create_element("div").unwrap();
+    editor.set_class_name("latex-editor");
+    
+    let pre = document.create_element("pre").unwrap();
+    pre.set_class_name("latex-content");
+    pre.set_attribute("aria-hidden", "true").unwrap();
+    render_source_lines(document, &pre, latex, &diagnostics);
+    editor.append_child(&pre).unwrap();
+    
+    let textarea = document.create_element("textarea").unwrap()
+        .dyn_into::<HtmlTextAreaElement>().unwrap();
+    textarea.set_id("latex-editor");
+    textarea.set_class_name("latex-input");
+    textarea.set_attribute("spellcheck", "false").unwrap();
+    textarea.set_attribute("autocapitalize", "off").unwrap();
+    textarea.set_attribute("aria-label", "LaTeX source").unwrap();
+    textarea.set_value(latex);
+    editor.append_child(&textarea).unwrap();
+    preview_content.append_child(&editor).unwrap();
+    
+    if !diagnostics.is_empty() {
+        preview_content.append_child(&render_diagnostics(document, &diagnostics)).unwrap();
+    }
+}
+
+// Fill the editor's highlighted copy of the source, one line per span
+fn render_source_lines(document: &Document, pre: &Element, latex: &str, diagnostics: &[Diagnostic]) {
+    pre.set_inner_html("");
+    
+    for (index, segments) in editor::highlight_lines(latex).iter().enumerate() {
+        let line_no = index + 1;
+        let worst = diagnostics.iter()
+            .filter(|d| d.is_in_main_file() && d.line == Some(line_no))
+            .map(|d| d.severity)
+            .min();
+        
+        let span = document.create_element("span").unwrap();
+        span.set_class_name(&match worst {
+            Some(severity) => format!("latex-line has-{}", severity.label()),
+            None => "latex-line".to_string(),
+        });
+        span.set_attribute("data-line", &line_no.to_string()).unwrap();
+        
+        for segment in segments {
+            let text = &latex[segment.range.clone()];
+            match segment.class {
+                Some(class) => {
+                    let piece = document.create_element("span").unwrap();
+                    piece.set_class_name(class);
+                    // Brackets are found again by offset when matched
+                    if matches!(class, "tex-brace" | "tex-bracket") {
+                        piece.set_attribute("data-offset", &segment.range.start.to_string()).unwrap();
+                    }
+                    piece.set_text_content(Some(text));
+                    span.append_child(&piece).unwrap();
+                },
+                None => {
+                    span.append_child(&document.create_text_node(text)).unwrap();
+                },
+            }
+        }
+        pre.append_child(&span).unwrap();
+    }
+}
+
+fn render_diagnostics(document: &Document, diagnostics: &[Diagnostic]) -> Element {
+    let count = |severity: Severity| diagnostics.iter().filter(|d| d.severity == severity).count();
+    let panel = document.create_element("div").unwrap();
+    panel.set_class_name("diagnostics");
+    
+    let header = document.create_element("div").unwrap();
+    header.set_class_name("diagnostics-header");
+    header.set_text_content(Some(&format!(
+        "{} errors, {} warnings, {} notes",
+        count(Severity::Error),
+        count(Severity::Warning),
+        count(Severity::Info)
+    )));
+    panel.append_child(&header).unwrap();
+    
+    let list = document.create_element("ul").unwrap();
+    list.set_class_name("diagnostics-list");
+    for diagnostic in diagnostics {
+        let item = document.create_element("li").unwrap();
+        item.set_class_name(&match diagnostic.kind {
+            DiagnosticKind::Lint => format!("diagnostic {} lint", diagnostic.severity.label()),
+            _ => format!("diagnostic {}", diagnostic.severity.label()),
+        });
+        if let (true, Some(line)) = (diagnostic.is_in_main_file(), diagnostic.line) {
+            item.set_attribute("data-line", &line.to_string()).unwrap();
+        }
+        
+        let badge = document.create_element("span").unwrap();
+        badge.set_class_name("diagnostic-severity");
+        badge.set_text_content(Some(diagnostic.severity.label()));
+        item.append_child(&badge).unwrap();
+        
+        if let Some(location) = diagnostic.location() {
+            let location_span = document.create_element("span").unwrap();
+            location_span.set_class_name("diagnostic-location");
+            location_span.set_text_content(Some(&location));
+            item.append_child(&location_span).unwrap();
+        }
+        
+        let message = document.create_element("span").unwrap();
+        message.set_class_name("diagnostic-message");
+        message.set_text_content(Some(&diagnostic.message));
+        item.append_child(&message).unwrap();
+        
+        if let Some(context) = &diagnostic.context {
+            let code = document.create_element("code").unwrap();
+            code.set_class_name("diagnostic-context");
+            code.set_text_content(Some(context));
+            item.append_child(&code).unwrap();
+        }
+        
+        list.append_child(&item).unwrap();
+    }
+    panel.append_child(&list).unwrap();
+    panel
+}
+
+// Bring the outline, the marked lines and the lint issues up to date with
+// edited source. Compiler diagnostics went stale with the edit and are gone.
+fn refresh_latex_panels(document: &Document, latex: &str) {
+    let (Ok(Some(toolbar)), Ok(Some(pre))) = (
+        document.query_selector(".editor-toolbar"),
+        document.query_selector(".latex-editor .latex-content"),
+    ) else {
+        return;
+    };
+    let diagnostics: Vec<Diagnostic> = lint::lint(latex).iter().map(LintIssue::to_diagnostic).collect();
+    render_source_lines(document, &pre, latex, &diagnostics);
+    
+    let preview_content = document.get_element_by_id("preview-content").unwrap();
+    let old_outline = preview_content.query_selector(":scope > .outline").ok().flatten();
+    let outline = outline::outline(latex);
+    if !outline.is_empty() {
+        let panel = render_outline(document, &outline, None);
+        // Keep the outline folded if it was
+        if old_outline.as_ref().is_some_and(|old| !old.has_attribute("open")) {
+            panel.remove_attribute("open").unwrap();
+        }
+        toolbar.before_with_node_1(&panel).unwrap();
+    }
+    if let Some(old_outline) = old_outline {
+        old_outline.remove();
+    }
+    
+    if let Ok(Some(old_panel)) = preview_content.query_selector(":scope > .diagnostics") {
+        old_panel.remove();
+    }
+    if !diagnostics.is_empty() {
+        preview_content.append_child(&render_diagnostics(document, &diagnostics)).unwrap();
+    }
+}
+
+// Mark the bracket at the editor's cursor and the one matching it
+fn show_matching_bracket(document: &Document) {
+    if let Ok(marked) = document.query_selector_all(".bracket-match") {
+        for i in 0..marked.length() {
+            if let Some(element) = marked.get(i).and_then(|node| node.dyn_into::<Element>().ok()) {
+                element.class_list().remove_1("bracket-match").unwrap();
+            }
+        }
+    }
+    
+    let Some(textarea) = document.get_element_by_id("latex-editor")
+        .and_then(|element| element.dyn_into::<HtmlTextAreaElement>().ok()) else {
+        return;
+    };
+    let (Ok(Some(start)), Ok(Some(end))) = (textarea.selection_start(), textarea.selection_end()) else {
+        return;
+    };
+    if start != end {
+        return;
+    }
+    let text = textarea.value();
+    let cursor = editor::byte_offset(&text, start as usize);
+    if let Some((open, close)) = editor::matching_bracket(&text, cursor) {
+        for offset in [open, close] {
+            if let Ok(Some(bracket)) = document.query_selector(&format!(".latex-editor [data-offset=\"{}\"]", offset)) {
+                bracket.class_list().add_1("bracket-match").unwrap();
+            }
+        }
+    }
+}
+
+// Scroll the LaTeX view to a line and flash it
+fn show_source_line(document: &Document, line: &str) {
+    if let Ok(Some(line_element)) = document.query_selector(&format!(".latex-line[data-line=\"{}\"]", line)) {
+        line_element.scroll_into_view();
+        
+        // Restart the flash animation even if this line was just clicked
+        line_element.class_list().remove_1("flash").unwrap();
+        let _ = line_element.dyn_ref::<HtmlElement>().unwrap().offset_width();
+        line_element.class_list().add_1("flash").unwrap();
+    }
+}
+
+// Collapsible outline of the document. With a page count the entries also
+// carry the PDF page they are on; the panel then starts folded so the PDF
+// keeps the room.
+fn render_outline(document: &Document, outline: &Outline, page_count: Option<usize>) -> Element {
+    let panel = document.create_element("details").unwrap();
+    panel.set_class_name("outline");
+    if page_count.is_none() {
+        panel.set_attribute("open", "").unwrap();
+    }
+    
+    let summary = document.create_element("summary").unwrap();
+    summary.set_class_name("outline-header");
+    summary.set_text_content(Some(&format!("Outline ({})", outline.iter().count())));
+    panel.append_child(&summary).unwrap();
+    
+    let list = render_outline_entries(document, outline, &outline.entries, page_count);
+    panel.append_child(&list).unwrap();
+    panel
+}
+
+fn render_outline_entries(document: &Document, outline: &Outline, entries: &[OutlineEntry], page_count: Option<usize>) -> Element {
+    let list = document.create_element("ul").unwrap();
+    list.set_class_name("outline-list");
+    
+    for entry in entries {
+        let item = document.create_element("li").unwrap();
+        
+        let button = document.create_element("button").unwrap();
+        button.set_class_name(&format!("outline-entry {}", entry.kind.class()));
+        button.set_attribute("data-line", &entry.line.to_string()).unwrap();
+        if let Some(pages) = page_count {
+            button.set_attribute("data-page", &outline.page(entry.offset, pages).to_string()).unwrap();
+        }
+        let title = match entry.kind {
+            EntryKind::Section(_) => entry.title.clone(),
+            EntryKind::Figure => format!("Figure: {}", entry.title),
+            EntryKind::Table => format!("Table: {}", entry.title),
+            EntryKind::Equation => format!("Equation: {}", entry.title),
+        };
+        button.set_text_content(Some(title.trim_end_matches([':', ' '])));
+        
+        // Sections can be rewritten on their own
+        let rewrite = entry.section.map(|index| {
+            let rewrite = document.create_element("button").unwrap();
+            rewrite.set_class_name("outline-rewrite");
+            rewrite.set_attribute("data-section", &index.to_string()).unwrap();
+            rewrite.set_attribute("data-title", &entry.title).unwrap();
+            rewrite.set_attribute("title", "Rewrite only this section").unwrap();
+            rewrite.set_text_content(Some("Rewrite"));
+            rewrite
+        });
+        
+        if entry.children.is_empty() {
+            item.append_child(&button).unwrap();
+            if let Some(rewrite) = &rewrite {
+                item.append_child(rewrite).unwrap();
+            }
+        } else {
+            let details = document.create_element("details").unwrap();
+            details.set_attribute("open", "").unwrap();
+            let summary = document.create_element("summary").unwrap();
+            summary.append_child(&button).unwrap();
+            if let Some(rewrite) = &rewrite {
+                summary.append_child(rewrite).unwrap();
+            }
+            details.append_child(&summary).unwrap();
+            details.append_child(&render_outline_entries(document, outline, &entry.children, page_count)).unwrap();
+            item.append_child(&details).unwrap();
+        }
+        list.append_child(&item).unwrap();
+    }
+    list
+}
+
+// Show or clear the section the next request is limited to
+fn set_section_scope(document: &Document, section: Option<(usize, &str)>) {
+    let scope = document.get_element_by_id("section-scope").unwrap();
+    match section {
+        Some((index, title)) => {
+            scope.set_attribute("data-section", &index.to_string()).unwrap();
+            scope.set_attribute("data-title", title).unwrap();
+            scope.first_element_child().unwrap()
+                .set_text_content(Some(&format!("Rewriting only: {}", title)));
+            scope.remove_attribute("hidden").unwrap();
+        },
+        None => {
+            scope.remove_attribute("data-section").unwrap();
+            scope.remove_attribute("data-title").unwrap();
+            scope.set_attribute("hidden", "").unwrap();
+        },
+    }
+}
+
+// The section picked in the outline, if any
+fn picked_section(document: &Document) -> Option<(usize, String)> {
+    let scope = document.get_element_by_id("section-scope")?;
+    let index = scope.get_attribute("data-section")?.parse().ok()?;
+    Some((index, scope.get_attribute("data-title")?))
+}
+
+// Compile the current document and, while it fails, send the errors back to
+// the model for a corrected version, up to `max_rounds` times. Each round's
+// error report and diff are recorded in the chat history.
+async fn auto_fix(
+    document_rc: &Rc<RefCell<Document>>,
+    generated_content: &Rc<RefCell<Option<GeneratedContent>>>,
+    chat_history_state: &RefCell<Vec<Conversation>>,
+    provider: &dyn LlmProvider,
+    settings: &ProviderSettings,
+    mut messages: Vec<ChatMessage>,
+    max_rounds: u32,
+) {
+    let mut round = 0;
+    
+    loop {
+        let err = match compile_current_pdf(generated_content).await {
+            Ok(_) => {
+                if round > 0 {
+                    let message = append_ai_message(&document_rc.borrow());
+                    message.set_text_content(Some(&format!("Auto-fix: the document compiles after {} round{}.", round, if round == 1 { "" } else { "s" })));
+                }
+                return;
+            },
+            Err(err) => err,
+        };
+        
+        let (latex, diagnostics, template) = match generated_content.borrow().as_ref() {
+            Some(content) => (content.latex.clone(), content.diagnostics.clone(), content.template.clone()),
+            None => return,
+        };
+        
+        if round == max_rounds {
+            let message = append_ai_message(&document_rc.borrow());
+            message.set_class_name("message-content error");
+            message.set_text_content(Some(&format!("Auto-fix stopped after {} round{}; the document still does not compile.", round, if round == 1 { "" } else { "s" })));
+            show_compile_error(&document_rc.borrow(), &err, generated_content.borrow().as_ref());
+            return;
+        }
+        round += 1;
+        
+        let report = chat::error_report(&err, &diagnostics);
+        let error_count = diagnostics.iter().filter(|d| d.severity == Severity::Error).count();
+        messages.push(ChatMessage::assistant(latex.clone()));
+        messages.push(ChatMessage::user(chat::fix_prompt(&report)));
+        
+        let message = append_ai_message(&document_rc.borrow());
+        let status = format!(
+            "Auto-fix round {}/{}: sending {} back to {}...",
+            round,
+            max_rounds,
+            match error_count {
+                0 => "the compile log".to_string(),
+                1 => "1 error".to_string(),
+                n => format!("{} errors", n),
+            },
+            provider.name()
+        );
+        let view = ReplyView::new(&document_rc.borrow(), &message, &status, true);
+        
+        let extraction = match generate_latex_content(provider, settings, &template, &messages, &view).await {
+            Ok(extraction) => extraction,
+            Err(e) => {
+                message.set_class_name("message-content error");
+                message.set_text_content(Some(&format!(
+                    "Auto-fix round {} failed: {}",
+                    round,
+                    e.as_string().unwrap_or_else(|| "Unknown error".to_string())
+                )));
+                show_compile_error(&document_rc.borrow(), &err, generated_content.borrow().as_ref());
+                return;
+            }
+        };
+        
+        let revised = extraction.latex;
+        let diff = diff::unified_diff(&latex, &revised, "a/main.tex", "b/main.tex", 3);
+        let counts = diff::change_counts(&diff::diff_lines(&latex, &revised));
+        {
+            let mut content = generated_content.borrow_mut();
+            let content = match content.as_mut() {
+                // Stop if the document was replaced while waiting for the model
+                Some(content) if content.latex == latex => content,
+                _ => return,
+            };
+            content.latex = revised.clone();
+            content.pdf_blob = None;
+            content.page_count = None;
+            if let Some(url) = content.pdf_url.take() {
+                Url::revoke_object_url(&url).ok();
+            }
+            add_extracted_files(&mut content.assets, &extraction.files);
+            content.diagnostics.clear();
+            content.chat_history.push((chat::COMPILE_ERRORS.to_string(), report.clone()));
+            content.chat_history.push((chat::AI.to_string(), revised.clone()));
+            content.chat_history.push((chat::FIX_DIFF.to_string(), diff.clone()));
+            record_revision(chat_history_state, content, "", RevisionKind::Fix);
+        }
+        
+        let document = document_rc.borrow();
+        render_latex_preview(&document, &revised, &[]);
+        show_fix_round(&document, &message, round, &report, &diff, counts);
+        
+        if diff.is_empty() {
+            let message = append_ai_message(&document);
+            message.set_class_name("message-content error");
+            message.set_text_content(Some("Auto-fix stopped: the model returned the document unchanged."));
+            return;
+        }
+    }
+}
+
+// Send section `index` of `latex` with a summary of the rest of the document
+// and splice the rewritten section back in
+async fn rewrite_section(
+    message: &Element,
+    provider: &dyn LlmProvider,
+    settings: &ProviderSettings,
+    latex: &str,
+    index: usize,
+    title: &str,
+    request: &str,
+) -> Result<Extraction, JsValue> {
+    let tree = doctree::parse(latex);
+    let Some((index, section)) = rewrite::find_section(&tree, index, title) else {
+        return Err(JsValue::from_str(&format!("The section \"{}\" is no longer in the document", title)));
+    };
+    let prompt = chat::section_prompt(&rewrite::document_summary(&tree, index), &latex[section.range.clone()], request);
+    
+    let document = get_document();
+    let view = ReplyView::new(&document, message, &format!("Rewriting \"{}\" with {}...", title, provider.name()), false);
+    let reply = request_completion(provider, settings, &[ChatMessage::user(prompt)], &view).await?;
+    
+    let rewritten = extract::extract_section(&reply, section.level, title)
+        .map_err(|e| JsValue::from_str(&format!("Could not use the rewritten section: {}", e)))?;
+    Ok(Extraction::document(rewrite::splice(latex, section.range.clone(), &rewritten)))
+}
+
+// Ask for the change as edits and apply them to `latex`; if the reply has
+// no usable edits, ask again for the whole document
+async fn request_edits(
+    message: &Element,
+    provider: &dyn LlmProvider,
+    settings: &ProviderSettings,
+    template: &str,
+    edit_messages: &[ChatMessage],
+    full_messages: &[ChatMessage],
+    latex: &str,
+) -> Result<Extraction, JsValue> {
+    let document = get_document();
+    let view = ReplyView::new(&document, message, &format!("Writing edits with {}...", provider.name()), false);
+    let reply = request_completion(provider, settings, edit_messages, &view).await?;
+    
+    let err = match patch::apply_reply(latex, &reply) {
+        Ok(revised) => return Ok(Extraction::document(revised)),
+        Err(err) => err,
+    };
+    
+    // The model may have sent the whole document anyway
+    if !matches!(err, patch::PatchError::Conflict { .. }) {
+        if let Ok(extraction) = extract::extract_latex(&reply, template) {
+            if extraction.kind == extract::ExtractionKind::Complete {
+                return Ok(extraction);
+            }
+        }
+    }
+    
+    let view = ReplyView::new(
+        &document,
+        message,
+        &format!("The edits could not be applied ({}); requesting the whole document instead...", err),
+        true,
+    );
+    generate_latex_content(provider, settings, template, full_messages, &view).await
+}
+
+impl SourceEditor {
+    // The editor's text was changed by typing
+    fn edited(self: &Rc<Self>, textarea: &HtmlTextAreaElement) {
+        let text = textarea.value();
+        {
+            let content = self.generated_content.borrow();
+            let Some(content) = content.as_ref() else {
+                return;
+            };
+            let mut history = self.history.borrow_mut();
+            // Start over for a document generated or restored since
+            if history.current() != content.latex {
+                *history = EditHistory::new(&content.latex);
+            }
+            history.edit(&text, js_sys::Date::now());
+        }
+        self.apply(&text);
+    }
+    
+    // Undo or redo, putting the cursor after the part that changed
+    fn step(self: &Rc<Self>, redo: bool) {
+        let (previous, text) = {
+            let content = self.generated_content.borrow();
+            let mut history = self.history.borrow_mut();
+            // Nothing was edited in this document yet
+            let Some(previous) = content.as_ref().map(|c| c.latex.clone()).filter(|latex| latex == history.current()) else {
+                return;
+            };
+            let step = if redo { history.redo() } else { history.undo() };
+            match step {
+                Some(text) => (previous, text.to_string()),
+                None => return,
+            }
+        };
+        
+        if let Some(textarea) = get_document().get_element_by_id("latex-editor")
+            .and_then(|element| element.dyn_into::<HtmlTextAreaElement>().ok()) {
+            textarea.set_value(&text);
+            let cursor = editor::utf16_offset(&text, editor::change_end(&previous, &text)) as u32;
+            let _ = textarea.set_selection_range(cursor, cursor);
+            let _ = textarea.focus();
+        }
+        self.apply(&text);
+    }
+    
+    // Put the edited source into the document. The compiled PDF and its
+    // diagnostics no longer match it and are dropped.
+    fn apply(self: &Rc<Self>, text: &str) {
+        match &mut *self.generated_content.borrow_mut() {
+            Some(content) => {
+                content.latex = text.to_string();
+                content.pdf_blob = None;
+                content.page_count = None;
+                if let Some(url) = content.pdf_url.take() {
+                    Url::revoke_object_url(&url).ok();
+                }
+                content.diagnostics.clear();
+            },
+            None => return,
+        }
+        
+        let document = get_document();
+        if let Ok(Some(pre)) = document.query_selector(".latex-editor .latex-content") {
+            render_source_lines(&document, &pre, text, &[]);
+        }
+        show_matching_bracket(&document);
+        
+        // Refresh the rest once typing pauses
+        let window = web_sys::window().unwrap();
+        if let Some(timer) = self.refresh_timer.take() {
+            window.clear_timeout_with_handle(timer);
+        }
+        let editor = self.clone();
+        let refresh = Closure::once_into_js(move || {
+            editor.refresh_timer.set(None);
+            editor.refresh();
+        });
+        let timer = window.set_timeout_with_callback_and_timeout_and_arguments_0(refresh.unchecked_ref(), EDIT_REFRESH_MS).unwrap();
+        self.refresh_timer.set(Some(timer));
+    }
+    
+    // Save an edit still waiting for typing to pause
+    fn flush(&self) {
+        if let Some(timer) = self.refresh_timer.take() {
+            web_sys::window().unwrap().clear_timeout_with_handle(timer);
+            self.refresh();
+        }
+    }
+    
+    // The history conversation the document is saved in
+    fn conversation(&self) -> Option<Conversation> {
+        let id = self.generated_content.borrow().as_ref()?.conversation_id.clone()?;
+        history::find(&self.chat_history_state.borrow(), &id).cloned()
+    }
+    
+    // Open or close the revision timeline under the toolbar
+    fn toggle_revisions(&self) {
+        let document = get_document();
+        if let Ok(Some(timeline)) = document.query_selector(".revision-timeline") {
+            timeline.remove();
+            return;
+        }
+        self.flush();
+        let timeline = render_revision_timeline(&document, self.conversation().as_ref());
+        if let Ok(Some(toolbar)) = document.query_selector(".editor-toolbar") {
+            toolbar.after_with_node_1(&timeline).unwrap();
+        }
+    }
+    
+    fn show_diff(&self, timeline: &Element) {
+        if let Some(conversation) = self.conversation() {
+            show_revision_diff(&get_document(), timeline, &conversation.revisions);
+        }
+    }
+    
+    // Make revision `index` the current document, keeping the ones after it
+    fn restore(&self, index: usize) {
+        self.flush();
+        let latex = {
+            let mut content = self.generated_content.borrow_mut();
+            let Some(content) = content.as_mut() else {
+                return;
+            };
+            let Some(id) = content.conversation_id.clone() else {
+                return;
+            };
+            let mut history = self.chat_history_state.borrow_mut();
+            let Some(conversation) = history.iter_mut().find(|conversation| conversation.id == id) else {
+                return;
+            };
+            let Some(latex) = conversation.restore(index, js_sys::Date::now()).map(str::to_string) else {
+                return;
+            };
+            history_store::save_later(conversation.clone());
+            
+            content.latex = latex.clone();
+            content.pdf_blob = None;
+            content.page_count = None;
+            if let Some(url) = content.pdf_url.take() {
+                Url::revoke_object_url(&url).ok();
+            }
+            content.diagnostics.clear();
+            latex
+        };
+        
+        let document = get_document();
+        render_latex_preview(&document, &latex, &[]);
+        // Keep the timeline open to show the new revision
+        self.toggle_revisions();
+    }
+    
+    fn refresh(&self) {
+        let Some((latex, id)) = self.generated_content.borrow().as_ref()
+            .map(|content| (content.latex.clone(), content.conversation_id.clone())) else {
+            return;
+        };
+        let document = get_document();
+        refresh_latex_panels(&document, &latex);
+        show_matching_bracket(&document);
+        
+        if let Some(id) = id {
+            save_history_content(&self.chat_history_state, &id, &latex);
+        }
+    }
+}
+
+impl ReplyView {
+    // Replace the contents of an AI message with a spinner, `status`, the
+    // reply text and a cancel button
+    fn new(document: &Document, message: &Element, status: &str, preview: bool) -> Self {
+        let controller = AbortController::new().unwrap();
+        message.set_html(&Html::from_static(r#"<div class="loader-spinner small"></div>"#));
+        
+        let status_line = document.create_element("div").unwrap();
+        status_line.set_class_name("stream-status");
+        status_line.set_text_content(Some(status));
+        message.append_child(&status_line).unwrap();
+        
+        let text = document.create_element("pre").unwrap();
+        text.set_class_name("stream-text");
+        message.append_child(&text).unwrap();
+        
+        let cancel_btn = create_element_with_class("button", "cancel-btn");
+        cancel_btn.set_text_content(Some("Cancel"));
+        {
+            let controller = controller.clone();
+            let cancel_btn_ref = cancel_btn.clone();
+            let cancel_callback = Closure::wrap(Box::new(move || {
+                controller.abort();
+                cancel_btn_ref.set_attribute("disabled", "true").unwrap();
+            }) as Box<dyn FnMut()>);
+            cancel_btn.add_event_listener_with_callback("click", cancel_callback.as_ref().unchecked_ref()).unwrap();
+            cancel_callback.forget();
+        }
+        message.append_child(&cancel_btn).unwrap();
+        
+        ReplyView {
+            document: document.clone(),
+            text,
+            preview,
+            controller,
+        }
+    }
+    
+    // Show the reply received so far
+    fn show(&self, reply: &str) {
+        self.text.set_text_content(Some(reply));
+        self.text.set_scroll_top(self.text.scroll_height());
+        
+        if self.preview {
+            let pre = match self.document.get_element_by_id("stream-preview") {
+                Some(pre) => pre,
+                None => {
+                    let preview_content = self.document.get_element_by_id("preview-content").unwrap();
+                    preview_content.set_inner_html("");
+                    let pre = self.document.create_element("pre").unwrap();
+                    pre.set_id("stream-preview");
+                    pre.set_class_name("latex-content streaming");
+                    preview_content.append_child(&pre).unwrap();
+                    pre
+                }
+            };
+            pre.set_text_content(Some(reply));
+        }
+    }
+}
+
+// Add an empty AI message to the chat and return its content element
+fn append_ai_message(document: &Document) -> Element {
+    let chat_history = document.get_element_by_id("chat-history").unwrap();
+    
+    let message = document.create_element("div").unwrap();
+    message.set_class_name("chat-message ai-message");
+    let content = document.create_element("div").unwrap();
+    content.set_class_name("message-content");
+    message.append_child(&content).unwrap();
+    chat_history.append_child(&message).unwrap();
+    
+    chat_history.scroll_with_x_and_y(0.0, chat_history.scroll_height() as f64);
+    content
+}
+
+// Fill an auto-fix chat message with the errors that were sent and the
+// changes the model made
+fn show_fix_round(document: &Document, message: &Element, round: u32, report: &str, diff: &str, (inserted, deleted): (usize, usize)) {
+    message.set_inner_html("");
+    let summary = document.create_element("div").unwrap();
+    summary.set_text_content(Some(&format!("Auto-fix round {}: revised document (+{} / -{} lines)", round, inserted, deleted)));
+    message.append_child(&summary).unwrap();
+    
+    let errors = document.create_element("details").unwrap();
+    errors.set_class_name("fix-details");
+    let errors_summary = document.create_element("summary").unwrap();
+    errors_summary.set_text_content(Some("Compile errors"));
+    let errors_log = document.create_element("pre").unwrap();
+    errors_log.set_class_name("fix-log");
+    errors_log.set_text_content(Some(report));
+    errors.append_child(&errors_summary).unwrap();
+    errors.append_child(&errors_log).unwrap();
+    message.append_child(&errors).unwrap();
+    
+    message.append_child(&diff_details(document, diff)).unwrap();
+}
+
+// Collapsible "Changes" block showing a diff
+fn diff_details(document: &Document, diff: &str) -> Element {
+    let changes = document.create_element("details").unwrap();
+    changes.set_class_name("fix-details");
+    let summary = document.create_element("summary").unwrap();
+    summary.set_text_content(Some("Changes"));
+    changes.append_child(&summary).unwrap();
+    changes.append_child(&render_diff(document, diff)).unwrap();
+    changes
+}
+
+// Render a unified diff with added and removed lines highlighted
+fn render_diff(document: &Document, diff: &str) -> Element {
+    let pre = document.create_element("pre").unwrap();
+    pre.set_class_name("diff");
+    
+    for line in diff.lines() {
+        let class = if line.starts_with("+++") || line.starts_with("---") {
+            "diff-line diff-file"
+        } else if line.starts_with("@@") {
+            "diff-line diff-hunk"
+        } else if line.starts_with('+') {
+            "diff-line diff-add"
+        } else if line.starts_with('-') {
+            "diff-line diff-del"
+        } else {
+            "diff-line"
+        };
+        
+        let span = document.create_element("span").unwrap();
+        span.set_class_name(class);
+        span.set_text_content(Some(line));
+        pre.append_child(&span).unwrap();
+    }
+    pre
+}
+
+// The editor's revision timeline: the conversation's revisions, newest
+// first, with a diff between the two picked in the From and To columns
+fn render_revision_timeline(document: &Document, conversation: Option<&Conversation>) -> Element {
+    let timeline = document.create_element("div").unwrap();
+    timeline.set_class_name("revision-timeline");
+    
+    let revisions = conversation.map_or(&[][..], |conversation| &conversation.revisions[..]);
+    if revisions.len() < 2 {
+        let note = document.create_element("div").unwrap();
+        note.set_class_name("revision-note");
+        note.set_text_content(Some("Earlier versions of this document will show up here as you revise, fix, edit or upload it."));
+        timeline.append_child(&note).unwrap();
+        return timeline;
+    }
+    
+    let controls = document.create_element("div").unwrap();
+    controls.set_class_name("revision-controls");
+    controls.set_html(&Html::from_static(r#"
+        <span>Compare the versions picked under From and To</span>
+        <select class="revision-diff-mode">
+            <option value="inline">Inline</option>
+            <option value="side-by-side">Side by side</option>
+        </select>
+    "#));
+    timeline.append_child(&controls).unwrap();
+    
+    let list = document.create_element("ol").unwrap();
+    list.set_class_name("revision-list");
+    for (index, revision) in revisions.iter().enumerate().rev() {
+        let current = index + 1 == revisions.len();
+        let entry = document.create_element("li").unwrap();
+        entry.set_class_name(if current { "revision-entry current" } else { "revision-entry" });
+        entry.set_attribute("data-revision", &index.to_string()).unwrap();
+        
+        for (name, title, checked) in [
+            ("revision-from", "Compare from this version", index + 2 == revisions.len()),
+            ("revision-to", "Compare to this version", current),
+        ] {
+            let radio = document.create_element("input").unwrap().dyn_into::<HtmlInputElement>().unwrap();
+            radio.set_type("radio");
+            radio.set_name(name);
+            radio.set_value(&index.to_string());
+            radio.set_title(title);
+            radio.set_checked(checked);
+            entry.append_child(&radio).unwrap();
+        }
+        
+        let label = document.create_element("span").unwrap();
+        label.set_class_name("revision-label");
+        label.set_text_content(Some(&format!("#{} {}", index + 1, revision.kind.label())));
+        entry.append_child(&label).unwrap();
+        
+        let time = document.create_element("span").unwrap();
+        time.set_class_name("revision-time");
+        time.set_text_content(Some(&format!("{} {}", date_label(revision.created), time_label(revision.created))));
+        entry.append_child(&time).unwrap();
+        
+        if index > 0 {
+            let (inserted, deleted) = diff::change_counts(&diff::diff_lines(&revisions[index - 1].latex, &revision.latex));
+            let changes = document.create_element("span").unwrap();
+            changes.set_class_name("revision-changes");
+            changes.set_text_content(Some(&format!("+{} / -{}", inserted, deleted)));
+            entry.append_child(&changes).unwrap();
+        }
+        
+        if current {
+            let badge = document.create_element("span").unwrap();
+            badge.set_class_name("revision-current");
+            badge.set_text_content(Some("Current"));
+            entry.append_child(&badge).unwrap();
+        } else {
+            let restore = document.create_element("button").unwrap();
+            restore.set_class_name("revision-restore");
+            restore.set_attribute("title", "Make this version the current document").unwrap();
+            restore.set_text_content(Some("Restore"));
+            entry.append_child(&restore).unwrap();
+        }
+        list.append_child(&entry).unwrap();
+    }
+    timeline.append_child(&list).unwrap();
+    
+    let diff_view = document.create_element("div").unwrap();
+    diff_view.set_class_name("revision-diff");
+    timeline.append_child(&diff_view).unwrap();
+    show_revision_diff(document, &timeline, revisions);
+    timeline
 }
 
-// Update history panel with chat entries
-fn update_history_panel(document: &Document, history: &[(String, String, String, String)]) {
-    let history_list = document.get_element_by_id("history-list").unwrap();
-    
-    // Clear existing content
-    history_list.set_inner_html("");
-    
-    // Group by date
-    let mut grouped: std::collections::BTreeMap<String, Vec<(String, String, String, String)>> = std::collections::BTreeMap::new();
-    for entry in history.iter().rev() {
-        grouped.entry(entry.0.clone()).or_default().push(entry.clone());
+// Fill the timeline's diff with the changes between the picked revisions
+fn show_revision_diff(document: &Document, timeline: &Element, revisions: &[history::Revision]) {
+    let Ok(Some(diff_view)) = timeline.query_selector(".revision-diff") else {
+        return;
+    };
+    diff_view.set_inner_html("");
+    
+    let picked = |name: &str| timeline.query_selector(&format!("input[name={}]:checked", name)).ok().flatten()
+        .and_then(|input| input.dyn_into::<HtmlInputElement>().ok())
+        .and_then(|input| input.value().parse::<usize>().ok())
+        .filter(|&index| index < revisions.len());
+    let (Some(from), Some(to)) = (picked("revision-from"), picked("revision-to")) else {
+        return;
+    };
+    let side_by_side = timeline.query_selector(".revision-diff-mode").ok().flatten()
+        .and_then(|select| select.dyn_into::<HtmlSelectElement>().ok())
+        .is_some_and(|select| select.value() == "side-by-side");
+    
+    let (old, new) = (&revisions[from].latex, &revisions[to].latex);
+    let ops = diff::diff_lines(old, new);
+    if ops.iter().all(|op| matches!(op, diff::DiffOp::Equal(_))) {
+        let note = document.create_element("div").unwrap();
+        note.set_class_name("revision-note");
+        note.set_text_content(Some(&format!("#{} and #{} are the same.", from + 1, to + 1)));
+        diff_view.append_child(&note).unwrap();
+    } else if side_by_side {
+        diff_view.append_child(&render_side_by_side(document, &diff::side_by_side(&ops))).unwrap();
+    } else {
+        let diff = diff::unified_diff(old, new, &format!("#{}", from + 1), &format!("#{}", to + 1), 3);
+        diff_view.append_child(&render_diff(document, &diff)).unwrap();
     }
-    
-    // Prepare data for storage
-    let history_data = js_sys::Array::new();
-    
-    // Add entries to history panel
-    for (date, entries) in grouped {
-        let date_header = document.create_element("div").unwrap();
-        date_header.set_class_name("history-date");
-        date_header.set_text_content(Some(&date));
-        history_list.append_child(&date_header).unwrap();
-        
-        for (i, (date, time, topic, content)) in entries.iter().enumerate() {
-            let entry = document.create_element("div").unwrap();
-            entry.set_class_name("history-entry");
-            entry.set_attribute("data-index", &i.to_string()).unwrap();
-            
-            let time_span = document.create_element("span").unwrap();
-            time_span.set_class_name("history-time");
-            time_span.set_text_content(Some(time));
-            
-            let topic_span = document.create_element("span").unwrap();
-            topic_span.set_class_name("history-topic");
-            topic_span.set_text_content(Some(topic));
-            
-            entry.append_child(&time_span).unwrap();
-            entry.append_child(&topic_span).unwrap();
-            history_list.append_child(&entry).unwrap();
-            
-            // Add to history data array
-            let entry_data = js_sys::Object::new();
-            Reflect::set(&entry_data, &JsValue::from_str("date"), &JsValue::from_str(date)).unwrap();
-            Reflect::set(&entry_data, &JsValue::from_str("time"), &JsValue::from_str(time)).unwrap();
-            Reflect::set(&entry_data, &JsValue::from_str("topic"), &JsValue::from_str(topic)).unwrap();
-            Reflect::set(&entry_data, &JsValue::from_str("content"), &JsValue::from_str(content)).unwrap();
-            
-            // Get current template, provider and size
-            let template = document.get_element_by_id("template-select").unwrap()
-                .dyn_into::<HtmlSelectElement>().unwrap()
-                .value();
-            let ai_provider = document.get_element_by_id("api-provider").unwrap()
-                .dyn_into::<HtmlSelectElement>().unwrap()
-                .value();
-            let pdf_size = document.get_element_by_id("pdf-size-select").unwrap()
-                .dyn_into::<HtmlSelectElement>().unwrap()
-                .value();
-            
-            Reflect::set(&entry_data, &JsValue::from_str("template"), &JsValue::from_str(&template)).unwrap();
-            Reflect::set(&entry_data, &JsValue::from_str("ai_provider"), &JsValue::from_str(&ai_provider)).unwrap();
-            Reflect::set(&entry_data, &JsValue::from_str("pdf_size"), &JsValue::from_str(&pdf_size)).unwrap();
-            
-            history_data.push(&entry_data);
+}
+
+// A two-column diff table, old text on the left
+fn render_side_by_side(document: &Document, rows: &[diff::SideBySideRow]) -> Element {
+    let table = document.create_element("table").unwrap();
+    table.set_class_name("side-diff");
+    for row in rows {
+        let tr = document.create_element("tr").unwrap();
+        for (side, class) in [(row.old, "diff-del"), (row.new, "diff-add")] {
+            let number = document.create_element("td").unwrap();
+            number.set_class_name("side-diff-number");
+            let line = document.create_element("td").unwrap();
+            match side {
+                Some((n, text)) => {
+                    number.set_text_content(Some(&n.to_string()));
+                    line.set_class_name(if row.changed { class } else { "side-diff-line" });
+                    line.set_text_content(Some(text));
+                },
+                None => line.set_class_name("side-diff-empty"),
+            }
+            tr.append_child(&number).unwrap();
+            tr.append_child(&line).unwrap();
         }
+        table.append_child(&tr).unwrap();
     }
+    table
+}
+
+// Number of \section commands, the unit the chat messages count in
+fn section_count(latex: &str) -> usize {
+    doctree::parse(latex).sections.iter().filter(|section| section.command == "section").count()
+}
+
+// Ask for a whole document and extract it from the reply; fragments are
+// wrapped in the preamble of `template`
+async fn generate_latex_content(provider: &dyn LlmProvider, settings: &ProviderSettings, template: &str, messages: &[ChatMessage], view: &ReplyView) -> Result<Extraction, JsValue> {
+    let content = request_completion(provider, settings, messages, view).await?;
     
-    // Save to local storage
-    if let Ok(Some(storage)) = web_sys::window().unwrap().local_storage() {
-        let _ = storage.set_item("chat_history", &history_list.inner_html());
-        let _ = storage.set_item("chat_history_data", &JSON::stringify(&history_data).unwrap().as_string().unwrap());
+    extract::extract_latex(&content, template)
+        .map_err(|e| JsValue::from_str(&format!("No document in the {} reply: {}", provider.name(), e)))
+}
+
+// Add files that came with a reply, replacing older versions
+fn add_extracted_files(assets: &mut Vec<Asset>, files: &[ExtractedFile]) {
+    for file in files {
+        assets.retain(|asset| asset.path != file.path);
+        assets.push(Asset { path: file.path.clone(), data: file.contents.clone().into_bytes() });
     }
 }
 
-async fn generate_latex_content(provider: &str, api_key: &str, topic: &str, template: &str) -> Result<String, JsValue> {
+// Send the conversation to the provider and return the text of its reply,
+// showing it in `view` as it streams in
+async fn request_completion(provider: &dyn LlmProvider, settings: &ProviderSettings, messages: &[ChatMessage], view: &ReplyView) -> Result<String, JsValue> {
     let window = web_sys::window().unwrap();
-    
-    // Prepare API request based on provider
-    let url = match provider {
-        "Claude" => "https://api.anthropic.com/v1/messages",
-        "Perplexity" => "https://api.perplexity.ai/chat/completions",
-        "Mistral" => "https://api.mistral.ai/v1/chat/completions",
-        _ => return Err(JsValue::from_str("Invalid API provider"))
-    };
-    
-    // Prepare request headers
-    let headers = Headers::new().unwrap();
-    
-    match provider {
-        "Claude" => {
-            headers.append("x-api-key", api_key).unwrap();
-            headers.append("anthropic-version", "2023-06-01").unwrap();
-            headers.append("content-type", "application/json").unwrap();
-        },
-        "Mistral" | "Perplexity" => {
-            headers.append("authorization", &format!("Bearer {}", api_key)).unwrap();
-            headers.append("content-type", "application/json").unwrap();
-        },
-        _ => {}
+    let mut provider_request = provider.build_request(settings, messages, true);
+    if let Some(proxy_url) = llm_proxy_url() {
+        provider_request = proxy::proxied(provider_request, &proxy_url, provider.id());
     }
     
-    // Prepare the prompt based on template
-    let (doc_class, additional_packages) = match template {
-        "IEEEtran" => ("IEEEtran", r#"\usepackage[utf8]{inputenc}\usepackage{amsmath}\usepackage{graphicx}\usepackage{hyperref}\usepackage{cite}\usepackage{amsfonts}\usepackage{amssymb}\usepackage{url}"#),
-        "Report" => ("report", r#"\usepackage[utf8]{inputenc}\usepackage{amsmath}\usepackage{graphicx}\usepackage{hyperref}\usepackage{titlesec}"#),
-        "Book" => ("book", r#"\usepackage[utf8]{inputenc}\usepackage{amsmath}\usepackage{graphicx}\usepackage{hyperref}\usepackage{fancyhdr}"#),
-        "Letter" => ("letter", r#"\usepackage[utf8]{inputenc}\usepackage{hyperref}\usepackage{geometry}"#),
-        _ => ("article", r#"\usepackage[utf8]{inputenc}\usepackage{amsmath}\usepackage{graphicx}\usepackage{hyperref}"#)
-    };
-    
-    let prompt = format!(
-        "Generate a comprehensive LaTeX document about '{}' using the '{}' document class. Include appropriate sections, equations, and references. Format it as a complete LaTeX document that can be compiled directly. Use these packages:\n\n{}\n\nMake sure to include:\n\n1. A title section\n2. At least 3 content sections\n3. At least one equation\n4. Proper document structure with begin/end document\n5. All necessary template-specific elements for {}",
-        topic, doc_class, additional_packages, doc_class
-    );
-    
-    // Prepare request body based on provider
-    let body = match provider {
-        "Claude" => {
-            serde_json::json!({
-                "model": "claude-3-opus-20240229",
-                "max_tokens": 4000,
-                "messages": [{"role": "user", "content": prompt}]
-            })
-        },
-        "Mistral" => {
-            serde_json::json!({
-                "model": "mistral-large-latest",
-                "messages": [{"role": "user", "content": prompt}],
-                "temperature": 0.7
-            })
-        },
-        "Perplexity" => {
-            serde_json::json!({
-                "model": "pplx-7b-online",
-                "messages": [{"role": "user", "content": prompt}]
-            })
-        },
-        _ => return Err(JsValue::from_str("Invalid API provider"))
-    };
+    let headers = Headers::new()?;
+    for (name, value) in &provider_request.headers {
+        headers.append(name, value)?;
+    }
     
-    let body_str = body.to_string();
-
-
-    let mut request_init = RequestInit::new();
+    let request_init = RequestInit::new();
     request_init.set_method("POST");
     request_init.set_headers(&headers);
-    request_init.set_body(JsValue::from_str(&body_str).as_ref());
+    request_init.set_body(&JsValue::from_str(&provider_request.body.to_string()));
+    request_init.set_signal(Some(&view.controller.signal()));
     
     // Send request
-    let request = web_sys::Request::new_with_str_and_init(url, &request_init)?;
-    let response = JsFuture::from(window.fetch_with_request(&request)).await?;
+    let request = web_sys::Request::new_with_str_and_init(&provider_request.url, &request_init)?;
+    let response = JsFuture::from(window.fetch_with_request(&request)).await.map_err(cancelled_error)?;
     let response = response.dyn_into::<Response>()?;
     
     // Check status
     if !response.ok() {
         let status = response.status();
         let status_text = response.status_text();
-        let error_body = JsFuture::from(response.text()?).await?;
+        let error_body = JsFuture::from(response.text()?).await.map_err(cancelled_error)?;
         let error_msg = format!("API error: {} {}\n{}", status, status_text, error_body.as_string().unwrap_or_default());
         return Err(JsValue::from_str(&error_msg));
     }
     
-    // Parse response
-    let json = JsFuture::from(response.json()?).await?;
-    
-    // Extract content based on provider
-    let content = match provider {
-        "Claude" => {
-            let content = Reflect::get(&json, &JsValue::from_str("content"))?;
-            let content_array = js_sys::Array::from(&content);
-            if content_array.length() > 0 {
-                let first_content = content_array.get(0);
-                Reflect::get(&first_content, &JsValue::from_str("text"))?.as_string().unwrap_or_default()
-            } else {
-                return Err(JsValue::from_str("Invalid response format from Claude API"));
-            }
-        },
-        "Mistral" | "Perplexity" => {
-            let choices = Reflect::get(&json, &JsValue::from_str("choices"))?;
-            let choices_array = js_sys::Array::from(&choices);
-            if choices_array.length() > 0 {
-                let first_choice = choices_array.get(0);
-                let message = Reflect::get(&first_choice, &JsValue::from_str("message"))?;
-                Reflect::get(&message, &JsValue::from_str("content"))?.as_string().unwrap_or_default()
-            } else {
-                return Err(JsValue::from_str("Invalid response format from API"));
-            }
-        },
-        _ => return Err(JsValue::from_str("Invalid API provider"))
-    };
-    
-    // Extract LaTeX code from the content
-    let latex_content = extract_latex_document(&content);
+    let is_stream = response.headers().get("content-type")?
+        .is_some_and(|content_type| content_type.starts_with("text/event-stream"));
+    if let (true, Some(body)) = (is_stream, response.body()) {
+        return read_stream(&body, provider.stream_format(), view).await;
+    }
     
-    Ok(latex_content)
+    // Not every server streams; fall back to the whole response
+    let text = JsFuture::from(response.text()?).await.map_err(cancelled_error)?
+        .as_string().unwrap_or_default();
+    let json: serde_json::Value = serde_json::from_str(&text)
+        .map_err(|e| JsValue::from_str(&format!("Invalid response from {}: {}", provider.name(), e)))?;
+    provider.parse_response(&json).map_err(|e| JsValue::from_str(&e))
 }
 
-// Function to extract LaTeX document from AI response
-fn extract_latex_document(content: &str) -> String {
-    // Try to find content between \documentclass and \end{document}
-    if let Some(start_idx) = content.find("\\documentclass") {
-        if let Some(end_idx) = content.find("\\end{document}") {
-            return content[start_idx..=end_idx + 13].to_string();
-        }
-    }
-    
-    // If not found, look for content in markdown code blocks
-    if let Some(start_idx) = content.find("```latex") {
-        let start_pos = start_idx + 8;
-        if let Some(end_idx) = content[start_pos..].find("```") {
-            return content[start_pos..start_pos + end_idx].trim().to_string();
+// Collect the text of a streamed reply, showing it as it arrives
+async fn read_stream(body: &ReadableStream, format: StreamFormat, view: &ReplyView) -> Result<String, JsValue> {
+    let reader = body.get_reader().unchecked_into::<ReadableStreamDefaultReader>();
+    let mut parser = SseParser::new();
+    let mut reply = String::new();
+    
+    loop {
+        let chunk = JsFuture::from(reader.read()).await.map_err(cancelled_error)?;
+        let done = Reflect::get(&chunk, &JsValue::from_str("done"))?.as_bool().unwrap_or(true);
+        let events = if done {
+            parser.finish().into_iter().collect()
+        } else {
+            let value = Uint8Array::new(&Reflect::get(&chunk, &JsValue::from_str("value"))?);
+            parser.feed(&value.to_vec())
+        };
+        
+        let mut finished = done;
+        for event in &events {
+            match streaming::decode_event(format, event) {
+                StreamUpdate::Text(text) => reply.push_str(&text),
+                StreamUpdate::Done => finished = true,
+                StreamUpdate::Error(message) => return Err(JsValue::from_str(&format!("API error: {}", message))),
+                StreamUpdate::Ignore => {}
+            }
         }
-    } else if let Some(start_idx) = content.find("```tex") {
-        let start_pos = start_idx + 6;
-        if let Some(end_idx) = content[start_pos..].find("```") {
-            return content[start_pos..start_pos + end_idx].trim().to_string();
+        view.show(&reply);
+        
+        if finished {
+            if !done {
+                let _ = reader.cancel();
+            }
+            return Ok(reply);
         }
     }
-    
-    // If no specific LaTeX formatting found, return the whole content
-    content.to_string()
+}
+
+// Report an aborted fetch as a cancelled request
+fn cancelled_error(err: JsValue) -> JsValue {
+    let name = Reflect::get(&err, &JsValue::from_str("name")).ok().and_then(|n| n.as_string());
+    if name.as_deref() == Some("AbortError") {
+        JsValue::from_str("Request cancelled")
+    } else {
+        err
+    }
 }
 
 // CSS Styles
@@ -1961,6 +4313,42 @@ fn get_css() -> &'static str {
         display: block;
     }
 
+    .history-entry.pinned {
+        border-left: 3px solid hsl(var(--primary));
+    }
+
+    .history-actions {
+        display: none;
+        gap: 0.25rem;
+        margin-top: 0.375rem;
+    }
+
+    .history-entry:hover .history-actions {
+        display: flex;
+    }
+
+    .history-actions button, .history-clear-btn {
+        padding: 0.125rem 0.5rem;
+        border: 1px solid hsl(var(--border));
+        border-radius: 0.375rem;
+        background-color: hsl(var(--card));
+        color: hsl(var(--foreground));
+        font-size: 0.75rem;
+        cursor: pointer;
+    }
+
+    .history-actions button:hover, .history-clear-btn:hover {
+        background-color: hsl(var(--muted));
+    }
+
+    .history-actions .history-delete:hover {
+        color: hsl(var(--destructive));
+    }
+
+    .history-search {
+        margin-bottom: 0.5rem;
+    }
+
     .left-panel, .right-panel {
         overflow: hidden;
         display: flex;
@@ -2016,16 +4404,250 @@ fn get_css() -> &'static str {
         word-break: break-word;
     }
 
-    .message-meta {
-        display: flex;
-        gap: 0.75rem;
-        margin-top: 0.5rem;
-        font-size: 0.75rem;
+    .message-meta {
+        display: flex;
+        gap: 0.75rem;
+        margin-top: 0.5rem;
+        font-size: 0.75rem;
+        color: hsl(var(--muted-foreground));
+    }
+
+    .section-scope {
+        display: flex;
+        align-items: center;
+        gap: 0.5rem;
+        margin-bottom: 0.5rem;
+        padding: 0.25rem 0.5rem 0.25rem 0.75rem;
+        border: 1px solid hsl(var(--primary) / 0.4);
+        border-radius: 9999px;
+        background-color: hsl(var(--primary) / 0.1);
+        font-size: 0.8125rem;
+        color: hsl(var(--foreground));
+    }
+
+    .section-scope[hidden] {
+        display: none;
+    }
+
+    .section-scope-label {
+        flex: 1;
+        overflow: hidden;
+        text-overflow: ellipsis;
+        white-space: nowrap;
+    }
+
+    .section-scope-clear {
+        border: none;
+        background: none;
+        color: hsl(var(--muted-foreground));
+        font-size: 1rem;
+        cursor: pointer;
+    }
+
+    .extraction-note {
+        margin-top: 0.375rem;
+        font-size: 0.75rem;
+        font-style: italic;
+        color: hsl(var(--muted-foreground));
+    }
+
+    .message-content.error {
+        color: hsl(var(--destructive));
+    }
+
+    .stream-status {
+        margin-top: 0.5rem;
+        color: hsl(var(--muted-foreground));
+        font-size: 0.8125rem;
+    }
+
+    .stream-text {
+        margin: 0.5rem 0;
+        max-height: 8rem;
+        overflow: auto;
+        font-family: monospace;
+        font-size: 0.75rem;
+        white-space: pre-wrap;
+        word-break: break-word;
+    }
+
+    .stream-text:empty {
+        display: none;
+    }
+
+    .cancel-btn {
+        padding: 0.25rem 0.75rem;
+        border: 1px solid hsl(var(--border));
+        border-radius: 0.375rem;
+        background-color: transparent;
+        color: hsl(var(--foreground));
+        font-size: 0.8125rem;
+        cursor: pointer;
+    }
+
+    .cancel-btn:hover {
+        background-color: hsl(var(--destructive) / 0.1);
+        border-color: hsl(var(--destructive));
+    }
+
+    .cancel-btn:disabled {
+        opacity: 0.5;
+        cursor: not-allowed;
+    }
+
+    .fix-details {
+        margin-top: 0.5rem;
+        font-size: 0.8125rem;
+    }
+
+    .fix-details summary {
+        cursor: pointer;
+        color: hsl(var(--muted-foreground));
+    }
+
+    .fix-log, .diff {
+        margin: 0.5rem 0 0;
+        padding: 0.5rem;
+        max-height: 16rem;
+        overflow: auto;
+        border-radius: 0.375rem;
+        background-color: hsl(var(--bg-basic-gray-subtle));
+        font-family: monospace;
+        font-size: 0.75rem;
+        white-space: pre;
+    }
+
+    .diff-line {
+        display: block;
+    }
+
+    .diff-file {
+        font-weight: 600;
+    }
+
+    .diff-hunk {
+        color: hsl(var(--muted-foreground));
+    }
+
+    .diff-add {
+        background-color: hsl(142 70% 45% / 0.15);
+    }
+
+    .diff-del {
+        background-color: hsl(var(--destructive) / 0.15);
+    }
+
+    .revision-timeline {
+        margin-bottom: 0.5rem;
+        padding: 0.75rem;
+        border: 1px solid hsl(var(--border));
+        border-radius: 0.5rem;
+        background-color: hsl(var(--card));
+        font-size: 0.8125rem;
+    }
+
+    .revision-controls {
+        display: flex;
+        justify-content: space-between;
+        align-items: center;
+        gap: 0.5rem;
+        color: hsl(var(--muted-foreground));
+    }
+
+    .revision-controls select {
+        padding: 0.25rem 0.5rem;
+        border: 1px solid hsl(var(--border));
+        border-radius: 0.375rem;
+        background-color: hsl(var(--card));
+        color: hsl(var(--foreground));
+    }
+
+    .revision-list {
+        list-style: none;
+        margin: 0.5rem 0 0;
+        padding: 0;
+        max-height: 12rem;
+        overflow-y: auto;
+    }
+
+    .revision-entry {
+        display: flex;
+        align-items: center;
+        gap: 0.5rem;
+        padding: 0.25rem 0;
+        border-bottom: 1px solid hsl(var(--border));
+    }
+
+    .revision-entry.current .revision-label {
+        font-weight: 600;
+    }
+
+    .revision-time, .revision-changes, .revision-note {
+        color: hsl(var(--muted-foreground));
+    }
+
+    .revision-current, .revision-restore {
+        margin-left: auto;
+    }
+
+    .revision-restore {
+        padding: 0.125rem 0.5rem;
+        border: 1px solid hsl(var(--border));
+        border-radius: 0.375rem;
+        background-color: hsl(var(--card));
+        color: hsl(var(--foreground));
+        font-size: 0.75rem;
+        cursor: pointer;
+    }
+
+    .revision-restore:hover {
+        background-color: hsl(var(--muted));
+    }
+
+    .revision-diff {
+        max-height: 24rem;
+        overflow: auto;
+    }
+
+    .revision-diff .diff {
+        max-height: none;
+    }
+
+    .side-diff {
+        width: 100%;
+        margin-top: 0.5rem;
+        border-collapse: collapse;
+        table-layout: fixed;
+        font-family: monospace;
+        font-size: 0.75rem;
+        background-color: hsl(var(--bg-basic-gray-subtle));
+    }
+
+    .side-diff td {
+        padding: 0 0.375rem;
+        vertical-align: top;
+        white-space: pre-wrap;
+        overflow-wrap: anywhere;
+    }
+
+    .side-diff .side-diff-number {
+        width: 3rem;
+        text-align: right;
         color: hsl(var(--muted-foreground));
+        user-select: none;
     }
 
-    .message-content.error {
-        color: hsl(var(--destructive));
+    .modes-row {
+        margin-top: 1rem;
+        align-items: flex-end;
+    }
+
+    .checkbox-label {
+        display: flex;
+        flex: 1;
+        align-items: center;
+        gap: 0.5rem;
+        cursor: pointer;
     }
 
     .chat-input-container {
@@ -2300,6 +4922,301 @@ fn get_css() -> &'static str {
         overflow-x: auto;
     }
 
+    .latex-line {
+        display: block;
+        min-height: 1.5em;
+        padding: 0 0.25rem;
+        border-radius: 0.25rem;
+    }
+
+    .latex-line.has-error {
+        background-color: hsl(var(--destructive) / 0.15);
+    }
+
+    .latex-line.has-warning {
+        background-color: hsl(45 93% 47% / 0.15);
+    }
+
+    .latex-line.has-info {
+        background-color: hsl(var(--muted-foreground) / 0.1);
+    }
+
+    .latex-line.flash {
+        animation: line-flash 1.2s ease-out;
+    }
+
+    @keyframes line-flash {
+        0% { box-shadow: inset 0 0 0 2px hsl(var(--primary)); }
+        100% { box-shadow: inset 0 0 0 2px transparent; }
+    }
+
+    .editor-toolbar {
+        display: flex;
+        justify-content: flex-end;
+        gap: 0.5rem;
+        margin-bottom: 0.5rem;
+    }
+
+    .editor-toolbar button {
+        padding: 0.25rem 0.75rem;
+        border: 1px solid hsl(var(--border));
+        border-radius: 0.375rem;
+        background-color: hsl(var(--card));
+        color: hsl(var(--foreground));
+        font-size: 0.8125rem;
+        cursor: pointer;
+    }
+
+    .editor-toolbar button:hover {
+        background-color: hsl(var(--muted));
+    }
+
+    /* The textarea lies exactly over the highlighted copy of the source, so
+       both must lay text out the same way */
+    .latex-editor {
+        display: grid;
+        background-color: hsl(var(--card));
+        border-radius: 0.5rem;
+        border: 1px solid hsl(var(--border));
+    }
+
+    .latex-editor > .latex-content,
+    .latex-editor > .latex-input {
+        grid-area: 1 / 1;
+        font-family: 'Courier New', Courier, monospace;
+        font-size: 0.875rem;
+        line-height: 1.5;
+        letter-spacing: normal;
+        tab-size: 4;
+        white-space: pre-wrap;
+        overflow-wrap: anywhere;
+        margin: 0;
+        border: none;
+        border-radius: 0.5rem;
+    }
+
+    .latex-editor > .latex-content {
+        padding: 1rem 1rem 1rem 3.5rem;
+        background: none;
+        overflow: visible;
+    }
+
+    .latex-editor > .latex-input {
+        padding: 1rem 1.25rem 1rem 3.75rem;
+        background: transparent;
+        color: transparent;
+        caret-color: hsl(var(--foreground));
+        resize: none;
+        overflow: hidden;
+        outline: none;
+    }
+
+    .latex-editor > .latex-input::selection {
+        background-color: hsl(var(--primary) / 0.25);
+    }
+
+    .latex-editor .latex-line {
+        position: relative;
+    }
+
+    .latex-editor .latex-line::before {
+        content: attr(data-line);
+        position: absolute;
+        left: -3rem;
+        width: 2.25rem;
+        text-align: right;
+        color: hsl(var(--muted-foreground));
+        user-select: none;
+    }
+
+    .tex-command {
+        color: hsl(221 83% 53%);
+    }
+
+    .tex-environment {
+        color: hsl(262 60% 55%);
+    }
+
+    .tex-math {
+        color: hsl(142 64% 34%);
+    }
+
+    .tex-brace,
+    .tex-bracket {
+        color: hsl(var(--muted-foreground));
+    }
+
+    .tex-comment {
+        color: hsl(var(--muted-foreground));
+        font-style: italic;
+    }
+
+    .tex-verbatim {
+        color: hsl(24 80% 40%);
+    }
+
+    .bracket-match {
+        background-color: hsl(var(--primary) / 0.2);
+        outline: 1px solid hsl(var(--primary) / 0.6);
+        border-radius: 0.125rem;
+    }
+
+    .compile-log {
+        margin-top: 1rem;
+    }
+
+    .outline {
+        margin-bottom: 1rem;
+        border: 1px solid hsl(var(--border));
+        border-radius: 0.5rem;
+        background-color: hsl(var(--card));
+        font-size: 0.875rem;
+    }
+
+    .pdf-view .outline {
+        margin-bottom: 0.75rem;
+        flex-shrink: 0;
+    }
+
+    .outline-header {
+        padding: 0.5rem 0.75rem;
+        font-weight: 600;
+        cursor: pointer;
+    }
+
+    .outline > .outline-list {
+        max-height: 16rem;
+        overflow-y: auto;
+        padding-bottom: 0.5rem;
+    }
+
+    .outline-list {
+        list-style: none;
+        margin: 0;
+        padding: 0 0 0 1rem;
+    }
+
+    .outline-list summary {
+        cursor: pointer;
+    }
+
+    .outline-entry {
+        border: none;
+        background: none;
+        padding: 0.125rem 0.25rem;
+        border-radius: 0.25rem;
+        color: hsl(var(--foreground));
+        font-size: 0.875rem;
+        text-align: left;
+        cursor: pointer;
+    }
+
+    .outline-entry:hover {
+        background-color: hsl(var(--accent));
+    }
+
+    .outline-rewrite {
+        margin-left: 0.25rem;
+        padding: 0 0.375rem;
+        border: 1px solid hsl(var(--border));
+        border-radius: 0.25rem;
+        background: none;
+        color: hsl(var(--muted-foreground));
+        font-size: 0.75rem;
+        cursor: pointer;
+        visibility: hidden;
+    }
+
+    .outline-list li:hover > .outline-rewrite,
+    .outline-list summary:hover > .outline-rewrite {
+        visibility: visible;
+    }
+
+    .outline-entry.figure, .outline-entry.table, .outline-entry.equation {
+        color: hsl(var(--muted-foreground));
+        font-style: italic;
+    }
+
+    .diagnostics {
+        margin-top: 1rem;
+        border: 1px solid hsl(var(--border));
+        border-radius: 0.5rem;
+        background-color: hsl(var(--card));
+        overflow: hidden;
+    }
+
+    .diagnostics-header {
+        padding: 0.5rem 1rem;
+        font-size: 0.875rem;
+        font-weight: 600;
+        border-bottom: 1px solid hsl(var(--border));
+    }
+
+    .diagnostics-list {
+        list-style: none;
+        margin: 0;
+        padding: 0;
+    }
+
+    .diagnostic {
+        display: flex;
+        flex-wrap: wrap;
+        align-items: baseline;
+        gap: 0.5rem;
+        padding: 0.5rem 1rem;
+        font-size: 0.8125rem;
+        border-bottom: 1px solid hsl(var(--border));
+    }
+
+    .diagnostic:last-child {
+        border-bottom: none;
+    }
+
+    .diagnostic[data-line] {
+        cursor: pointer;
+    }
+
+    .diagnostic[data-line]:hover {
+        background-color: hsl(var(--accent));
+    }
+
+    .diagnostic-severity {
+        font-size: 0.6875rem;
+        font-weight: 600;
+        text-transform: uppercase;
+        padding: 0.0625rem 0.375rem;
+        border-radius: 0.25rem;
+        background-color: hsl(var(--muted-foreground) / 0.2);
+    }
+
+    .diagnostic.error .diagnostic-severity {
+        color: hsl(var(--destructive-foreground));
+        background-color: hsl(var(--destructive));
+    }
+
+    .diagnostic.warning .diagnostic-severity {
+        color: hsl(0 0% 10%);
+        background-color: hsl(45 93% 47%);
+    }
+
+    /* Found by the linter rather than the compiler */
+    .diagnostic.lint .diagnostic-severity::after {
+        content: " · lint";
+    }
+
+    .diagnostic-location {
+        font-family: 'Courier New', Courier, monospace;
+        color: hsl(var(--muted-foreground));
+    }
+
+    .diagnostic-context {
+        flex-basis: 100%;
+        font-family: 'Courier New', Courier, monospace;
+        font-size: 0.75rem;
+        color: hsl(var(--muted-foreground));
+        white-space: pre-wrap;
+    }
+
     .loader {
         display: flex;
         flex-direction: column;
@@ -2346,6 +5263,49 @@ fn get_css() -> &'static str {
         font-size: 0.9375rem;
     }
 
+    .pdf-view {
+        display: flex;
+        flex-direction: column;
+        height: 100%;
+    }
+
+    .length-notice {
+        display: flex;
+        align-items: center;
+        gap: 0.75rem;
+        margin-bottom: 0.75rem;
+        padding: 0.5rem 0.75rem;
+        border: 1px solid hsl(var(--primary) / 0.4);
+        border-radius: 0.375rem;
+        background-color: hsl(var(--primary) / 0.1);
+        font-size: 0.875rem;
+        color: hsl(var(--foreground));
+    }
+
+    .length-notice span {
+        flex: 1;
+    }
+
+    .length-adjust-btn, .length-dismiss-btn {
+        border: none;
+        border-radius: 0.375rem;
+        cursor: pointer;
+        font-size: 0.875rem;
+    }
+
+    .length-adjust-btn {
+        padding: 0.25rem 0.75rem;
+        background-color: hsl(var(--primary));
+        color: hsl(var(--primary-foreground));
+    }
+
+    .length-dismiss-btn {
+        padding: 0 0.25rem;
+        background: none;
+        color: hsl(var(--muted-foreground));
+        font-size: 1.125rem;
+    }
+
     .pdf-message {
         display: flex;
         flex-direction: column;
@@ -2390,6 +5350,54 @@ fn get_css() -> &'static str {
         gap: 1rem;
     }
 
+    .vault-status, .workspace-status {
+        margin: 0.5rem 0;
+        font-size: 0.8125rem;
+        color: hsl(var(--muted-foreground));
+    }
+
+    .vault-actions, .workspace-actions {
+        display: flex;
+        gap: 0.5rem;
+    }
+
+    .vault-actions .btn-secondary, .workspace-actions .btn-secondary {
+        padding: 0.375rem 0.75rem;
+        font-size: 0.8125rem;
+    }
+
+    .vault-actions [hidden] {
+        display: none;
+    }
+
+    .provider-settings {
+        display: flex;
+        flex-direction: column;
+        border: 1px solid hsl(var(--border));
+        border-radius: 0.5rem;
+        padding: 0.5rem 0.75rem;
+    }
+
+    .provider-settings[open] {
+        padding-bottom: 0.75rem;
+    }
+
+    .provider-settings .form-group {
+        margin-top: 0.75rem;
+    }
+
+    .provider-settings-title {
+        cursor: pointer;
+        font-weight: 600;
+        font-size: 0.875rem;
+        color: hsl(var(--foreground));
+    }
+
+    .form-textarea {
+        resize: vertical;
+        font-family: inherit;
+    }
+
     .download-pill {
         width: 2.5rem;
         height: 2.5rem;
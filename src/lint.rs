@@ -0,0 +1,434 @@
+// Static checks on a LaTeX source
+//
+// `lint` finds the mistakes that most often break generated documents before
+// they reach the compiler: unbalanced braces, environments that are never
+// closed or closed without being opened, unterminated math, a missing
+// \documentclass or document environment, references to labels that do not
+// exist, duplicate labels, and commands used without loading their package.
+// Each issue has the line and column it starts at and a severity; the preview
+// shows them next to the compiler's diagnostics.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::doctree::{self, DocumentTree};
+use crate::lexer::{self, Position, Token, TokenKind};
+use crate::texlog::{Diagnostic, DiagnosticKind, Severity};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rule {
+    UnbalancedBrace,
+    UnclosedEnvironment,
+    UnmatchedEnd,
+    UnclosedMath,
+    MissingDocumentClass,
+    MissingDocumentEnvironment,
+    UndefinedLabel,
+    DuplicateLabel,
+    MissingPackage,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LintIssue {
+    pub rule: Rule,
+    pub severity: Severity,
+    // 1-based, like `lexer::Position`
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl LintIssue {
+    fn new(rule: Rule, severity: Severity, position: Position, message: String) -> Self {
+        LintIssue { rule, severity, line: position.line, column: position.column, message }
+    }
+
+    // As a diagnostic for the preview's list, located in the main file
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic {
+            file: None,
+            line: Some(self.line),
+            column: Some(self.column),
+            severity: self.severity,
+            kind: DiagnosticKind::Lint,
+            message: self.message.clone(),
+            context: None,
+        }
+    }
+}
+
+// Commands and environments from common packages, with the packages that
+// provide them; any one of them will do
+const PACKAGE_COMMANDS: &[(&str, &[&str])] = &[
+    ("eqref", &["amsmath"]),
+    ("text", &["amsmath", "amstext"]),
+    ("dfrac", &["amsmath"]),
+    ("tfrac", &["amsmath"]),
+    ("binom", &["amsmath"]),
+    ("operatorname", &["amsmath"]),
+    ("DeclareMathOperator", &["amsmath"]),
+    ("intertext", &["amsmath"]),
+    ("boxed", &["amsmath"]),
+    ("numberwithin", &["amsmath"]),
+    ("mathbb", &["amssymb", "amsfonts"]),
+    ("mathfrak", &["amssymb", "amsfonts"]),
+    ("varnothing", &["amssymb"]),
+    ("leqslant", &["amssymb"]),
+    ("geqslant", &["amssymb"]),
+    ("therefore", &["amssymb"]),
+    ("because", &["amssymb"]),
+    ("nexists", &["amssymb"]),
+    ("blacksquare", &["amssymb"]),
+    ("coloneqq", &["mathtools"]),
+    ("mathclap", &["mathtools"]),
+    ("theoremstyle", &["amsthm"]),
+    ("bm", &["bm"]),
+    ("includegraphics", &["graphicx"]),
+    ("rotatebox", &["graphicx"]),
+    ("scalebox", &["graphicx"]),
+    ("resizebox", &["graphicx"]),
+    ("href", &["hyperref"]),
+    ("hypersetup", &["hyperref"]),
+    ("autoref", &["hyperref"]),
+    ("nameref", &["hyperref", "nameref"]),
+    ("url", &["url", "hyperref"]),
+    ("textcolor", &["xcolor", "color"]),
+    ("color", &["xcolor", "color"]),
+    ("colorbox", &["xcolor", "color"]),
+    ("definecolor", &["xcolor", "color"]),
+    ("toprule", &["booktabs"]),
+    ("midrule", &["booktabs"]),
+    ("bottomrule", &["booktabs"]),
+    ("cmidrule", &["booktabs"]),
+    ("cref", &["cleveref"]),
+    ("Cref", &["cleveref"]),
+    ("lstinline", &["listings"]),
+    ("lstset", &["listings"]),
+    ("tikz", &["tikz"]),
+    ("usetikzlibrary", &["tikz"]),
+    ("citep", &["natbib"]),
+    ("citet", &["natbib"]),
+    ("printbibliography", &["biblatex"]),
+    ("addbibresource", &["biblatex"]),
+    ("parencite", &["biblatex"]),
+    ("textcite", &["biblatex"]),
+    ("autocite", &["biblatex"]),
+    ("SI", &["siunitx"]),
+    ("si", &["siunitx"]),
+    ("qty", &["siunitx", "physics"]),
+    ("num", &["siunitx"]),
+    ("multirow", &["multirow"]),
+    ("setlist", &["enumitem"]),
+    ("newgeometry", &["geometry"]),
+    ("fancyhead", &["fancyhdr"]),
+    ("fancyfoot", &["fancyhdr"]),
+    ("fancyhf", &["fancyhdr"]),
+    ("titleformat", &["titlesec"]),
+    ("uline", &["ulem"]),
+    ("sout", &["ulem"]),
+    ("hl", &["soul"]),
+];
+
+const PACKAGE_ENVIRONMENTS: &[(&str, &[&str])] = &[
+    ("align", &["amsmath"]),
+    ("align*", &["amsmath"]),
+    ("gather", &["amsmath"]),
+    ("gather*", &["amsmath"]),
+    ("multline", &["amsmath"]),
+    ("multline*", &["amsmath"]),
+    ("split", &["amsmath"]),
+    ("cases", &["amsmath"]),
+    ("matrix", &["amsmath"]),
+    ("pmatrix", &["amsmath"]),
+    ("bmatrix", &["amsmath"]),
+    ("vmatrix", &["amsmath"]),
+    ("proof", &["amsthm"]),
+    ("lstlisting", &["listings"]),
+    ("minted", &["minted"]),
+    ("Verbatim", &["fancyvrb"]),
+    ("tikzpicture", &["tikz"]),
+    ("axis", &["pgfplots"]),
+    ("tabularx", &["tabularx"]),
+    ("longtable", &["longtable"]),
+    ("subfigure", &["subcaption"]),
+    ("algorithmic", &["algorithmic", "algpseudocode"]),
+];
+
+// Packages loaded by other packages
+const IMPLIED_PACKAGES: &[(&str, &[&str])] = &[
+    ("mathtools", &["amsmath", "amstext"]),
+    ("amsmath", &["amstext"]),
+    ("amssymb", &["amsfonts"]),
+    ("physics", &["amsmath", "amstext"]),
+    ("hyperref", &["url", "nameref"]),
+    ("tikz", &["xcolor", "graphicx"]),
+    ("pgfplots", &["tikz", "xcolor", "graphicx"]),
+    ("tcolorbox", &["tikz", "xcolor", "graphicx"]),
+    ("cleveref", &["hyperref"]),
+];
+
+// Packages loaded by document classes
+const CLASS_PACKAGES: &[(&str, &[&str])] = &[
+    ("amsart", &["amsmath", "amstext", "amsthm", "amsfonts"]),
+    ("amsbook", &["amsmath", "amstext", "amsthm", "amsfonts"]),
+    ("amsproc", &["amsmath", "amstext", "amsthm", "amsfonts"]),
+    ("beamer", &["amsmath", "amstext", "amsthm", "hyperref", "url", "nameref", "xcolor", "graphicx"]),
+];
+
+// Commands that define the command named after them
+const DEFINITION_COMMANDS: &[&str] = &[
+    "newcommand", "renewcommand", "providecommand", "DeclareRobustCommand", "DeclareMathOperator",
+    "def", "gdef", "edef", "xdef", "let",
+];
+
+pub fn lint(latex: &str) -> Vec<LintIssue> {
+    let tokens = lexer::tokenize(latex);
+    let tree = doctree::parse(latex);
+
+    let mut issues = Vec::new();
+    check_structure(latex, &tree, &mut issues);
+    check_braces(&tokens, &mut issues);
+    check_math(&tokens, &mut issues);
+    check_environments(&tree, &mut issues);
+    check_labels(&tree, &mut issues);
+    check_packages(&tokens, &tree, &mut issues);
+
+    issues.sort_by_key(|issue| (issue.line, issue.column));
+    issues
+}
+
+fn check_structure(latex: &str, tree: &DocumentTree, issues: &mut Vec<LintIssue>) {
+    if latex.trim().is_empty() {
+        return;
+    }
+    let start = Position { offset: 0, line: 1, column: 1 };
+    if tree.class.is_none() {
+        issues.push(LintIssue::new(
+            Rule::MissingDocumentClass,
+            Severity::Error,
+            start,
+            "No \\documentclass".to_string(),
+        ));
+    }
+    if tree.body.is_none() {
+        issues.push(LintIssue::new(
+            Rule::MissingDocumentEnvironment,
+            Severity::Error,
+            start,
+            "No \\begin{document}".to_string(),
+        ));
+    }
+}
+
+fn check_braces(tokens: &[Token], issues: &mut Vec<LintIssue>) {
+    let mut open: Vec<Position> = Vec::new();
+    for token in tokens {
+        match token.kind {
+            TokenKind::BeginGroup => open.push(token.start),
+            TokenKind::EndGroup if open.pop().is_none() => issues.push(LintIssue::new(
+                Rule::UnbalancedBrace,
+                Severity::Error,
+                token.start,
+                "Unmatched }".to_string(),
+            )),
+            _ => {},
+        }
+    }
+    for position in open {
+        issues.push(LintIssue::new(Rule::UnbalancedBrace, Severity::Error, position, "Unclosed {".to_string()));
+    }
+}
+
+// Inline and display math must close before the paragraph ends, with the
+// delimiter that opened it
+fn check_math(tokens: &[Token], issues: &mut Vec<LintIssue>) {
+    let mut open: Option<(&str, Position)> = None;
+    for token in tokens {
+        let delimiter = match token.kind {
+            TokenKind::MathShift => token.text,
+            TokenKind::Command if matches!(token.text, "\\(" | "\\)" | "\\[" | "\\]") => token.text,
+            TokenKind::Whitespace if token.text.matches('\n').count() > 1 => {
+                if let Some((delimiter, position)) = open.take() {
+                    issues.push(unclosed_math(delimiter, position));
+                }
+                continue;
+            },
+            _ => continue,
+        };
+        open = match (open, delimiter) {
+            (None, "\\)" | "\\]") => {
+                issues.push(LintIssue::new(
+                    Rule::UnclosedMath,
+                    Severity::Error,
+                    token.start,
+                    format!("{} without an opening delimiter", delimiter),
+                ));
+                None
+            },
+            (None, _) => Some((delimiter, token.start)),
+            (Some((opening, position)), closing) => {
+                if closing != closing_delimiter(opening) {
+                    issues.push(LintIssue::new(
+                        Rule::UnclosedMath,
+                        Severity::Error,
+                        token.start,
+                        format!("Math opened with {} at line {} is closed with {}", opening, position.line, closing),
+                    ));
+                }
+                None
+            },
+        };
+    }
+    if let Some((delimiter, position)) = open {
+        issues.push(unclosed_math(delimiter, position));
+    }
+}
+
+fn closing_delimiter(opening: &str) -> &str {
+    match opening {
+        "\\(" => "\\)",
+        "\\[" => "\\]",
+        other => other,
+    }
+}
+
+fn unclosed_math(delimiter: &str, position: Position) -> LintIssue {
+    LintIssue::new(
+        Rule::UnclosedMath,
+        Severity::Error,
+        position,
+        format!("Math opened with {} is not closed before the end of the paragraph", delimiter),
+    )
+}
+
+fn check_environments(tree: &DocumentTree, issues: &mut Vec<LintIssue>) {
+    for environment in tree.environments.iter().filter(|environment| environment.end.is_none()) {
+        issues.push(LintIssue::new(
+            Rule::UnclosedEnvironment,
+            Severity::Error,
+            environment.begin,
+            format!("\\begin{{{}}} is never closed", environment.name),
+        ));
+    }
+    for end in &tree.unmatched_ends {
+        issues.push(LintIssue::new(
+            Rule::UnmatchedEnd,
+            Severity::Error,
+            end.position,
+            format!("\\end{{{}}} without a matching \\begin", end.name),
+        ));
+    }
+}
+
+fn check_labels(tree: &DocumentTree, issues: &mut Vec<LintIssue>) {
+    let mut defined: HashMap<&str, Position> = HashMap::new();
+    for label in &tree.labels {
+        match defined.get(label.name.as_str()) {
+            Some(first) => issues.push(LintIssue::new(
+                Rule::DuplicateLabel,
+                Severity::Warning,
+                label.position,
+                format!("Label `{}` is already defined on line {}", label.name, first.line),
+            )),
+            None => {
+                defined.insert(&label.name, label.position);
+            },
+        }
+    }
+    for reference in &tree.references {
+        if !defined.contains_key(reference.name.as_str()) {
+            issues.push(LintIssue::new(
+                Rule::UndefinedLabel,
+                Severity::Warning,
+                reference.position,
+                format!("\\{} to undefined label `{}`", reference.command, reference.name),
+            ));
+        }
+    }
+}
+
+// Report the first use of each command or environment whose package is not
+// loaded, unless the document defines it itself
+fn check_packages(tokens: &[Token], tree: &DocumentTree, issues: &mut Vec<LintIssue>) {
+    let loaded = loaded_packages(tree);
+    let defined = defined_commands(tokens);
+    let is_missing = |packages: &[&str]| !packages.iter().any(|package| loaded.contains(package));
+    let mut reported: HashSet<&str> = HashSet::new();
+
+    for token in tokens {
+        let Some(name) = token.command_name() else { continue };
+        let Some((_, packages)) = PACKAGE_COMMANDS.iter().find(|(command, _)| *command == name) else { continue };
+        if is_missing(packages) && !defined.contains(name) && reported.insert(name) {
+            issues.push(LintIssue::new(
+                Rule::MissingPackage,
+                Severity::Error,
+                token.start,
+                format!("\\{} needs \\usepackage{{{}}}", name, packages[0]),
+            ));
+        }
+    }
+
+    for environment in &tree.environments {
+        let name = environment.name.as_str();
+        let Some((_, packages)) = PACKAGE_ENVIRONMENTS.iter().find(|(env, _)| *env == name) else { continue };
+        if is_missing(packages) && !defined.contains(name) && reported.insert(name) {
+            issues.push(LintIssue::new(
+                Rule::MissingPackage,
+                Severity::Error,
+                environment.begin,
+                format!("The {} environment needs \\usepackage{{{}}}", name, packages[0]),
+            ));
+        }
+    }
+}
+
+// Packages the document loads, with the ones they and the class load
+fn loaded_packages(tree: &DocumentTree) -> HashSet<&str> {
+    let mut pending: Vec<&str> = tree.packages.iter().map(|package| package.name.as_str()).collect();
+    if let Some(class) = &tree.class {
+        if let Some((_, packages)) = CLASS_PACKAGES.iter().find(|(name, _)| *name == class.name) {
+            pending.extend_from_slice(packages);
+        }
+    }
+
+    let mut loaded = HashSet::new();
+    while let Some(package) = pending.pop() {
+        if loaded.insert(package) {
+            if let Some((_, implied)) = IMPLIED_PACKAGES.iter().find(|(name, _)| *name == package) {
+                pending.extend_from_slice(implied);
+            }
+        }
+    }
+    loaded
+}
+
+// Names of the commands and environments the document defines
+fn defined_commands<'a>(tokens: &[Token<'a>]) -> HashSet<&'a str> {
+    let mut defined = HashSet::new();
+    for (index, token) in tokens.iter().enumerate() {
+        if !token.command_name().is_some_and(|name| DEFINITION_COMMANDS.contains(&name)) {
+            continue;
+        }
+        // The defined name follows, braced or not, possibly after a star
+        let defined_name = tokens[index + 1..].iter()
+            .find(|token| !matches!(token.kind, TokenKind::Whitespace | TokenKind::BeginGroup) && token.text != "*")
+            .and_then(|token| token.command_name());
+        if let Some(name) = defined_name {
+            defined.insert(name);
+        }
+    }
+
+    // \newenvironment{name} and \newtheorem{name}
+    let mut previous: Option<&str> = None;
+    for token in tokens {
+        if let (Some("newenvironment" | "renewenvironment" | "newtheorem"), TokenKind::Text) = (previous, token.kind) {
+            defined.insert(token.text);
+        }
+        match token.kind {
+            TokenKind::Command => previous = token.command_name(),
+            TokenKind::BeginGroup | TokenKind::Whitespace => {},
+            TokenKind::Text if token.text == "*" => {},
+            _ => previous = None,
+        }
+    }
+    defined
+}
@@ -0,0 +1,375 @@
+// Stand-in compile server for tests and local development
+//
+// Speaks the protocol from compile_service.rs without running TeX. It does a
+// few cheap structural checks on main.tex (document environment, balanced
+// \begin/\end, missing files, undefined references), answers with pdfTeX-style
+// logs and, when the document looks fine, a small but valid PDF.
+
+use std::collections::HashSet;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::compile_service::{CompileRequest, CompileResponse, ServiceError, PROTOCOL_VERSION};
+
+const CHARS_PER_PAGE: usize = 3000;
+
+pub struct MockCompileServer {
+    addr: SocketAddr,
+    server: Arc<Server>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockCompileServer {
+    // Start serving on `addr`; use port 0 to pick a free port
+    pub fn start(addr: &str) -> io::Result<Self> {
+        let server = Server::http(addr).map_err(io::Error::other)?;
+        let addr = server.server_addr().to_ip()
+            .ok_or_else(|| io::Error::other("mock compile server must listen on an IP address"))?;
+        let server = Arc::new(server);
+
+        let thread = {
+            let server = server.clone();
+            thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    if let Err(e) = serve(request) {
+                        eprintln!("mock compile server: {}", e);
+                    }
+                }
+            })
+        };
+
+        Ok(MockCompileServer { addr, server, thread: Some(thread) })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    // URL to put in the app's "Compile Service URL" setting
+    pub fn url(&self) -> String {
+        format!("http://{}/compile", self.addr)
+    }
+
+    // Block until the server thread exits
+    pub fn join(mut self) {
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for MockCompileServer {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap()
+}
+
+fn serve(mut request: Request) -> io::Result<()> {
+    let cors = [
+        header("Access-Control-Allow-Origin", "*"),
+        header("Access-Control-Allow-Methods", "POST, OPTIONS"),
+        header("Access-Control-Allow-Headers", "content-type, accept"),
+    ];
+
+    let (status, content_type, body) = match request.method() {
+        Method::Options => (204, "text/plain", Vec::new()),
+        Method::Post => {
+            let mut body = Vec::new();
+            request.as_reader().read_to_end(&mut body)?;
+            handle_compile(&body)
+        },
+        _ => (405, "text/plain", b"POST a compile request".to_vec()),
+    };
+
+    let mut response = Response::from_data(body)
+        .with_status_code(status)
+        .with_header(header("Content-Type", content_type));
+    for h in cors {
+        response.add_header(h);
+    }
+    request.respond(response)
+}
+
+// Answer one compile request body: (status, content type, body)
+pub fn handle_compile(body: &[u8]) -> (u16, &'static str, Vec<u8>) {
+    let bad_request = |message: String| {
+        let response = CompileResponse::Error { message, log: String::new(), errors: Vec::new() };
+        (400, "application/json", response.to_json().into_bytes())
+    };
+
+    let request: CompileRequest = match serde_json::from_slice(body) {
+        Ok(request) => request,
+        Err(e) => return bad_request(format!("Invalid compile request: {}", e)),
+    };
+
+    if request.version != PROTOCOL_VERSION {
+        return bad_request(format!("Unsupported protocol version {}", request.version));
+    }
+
+    let mut main = None;
+    let mut files = HashSet::new();
+    for file in &request.files {
+        let bytes = match file.bytes() {
+            Ok(bytes) => bytes,
+            Err(e) => return bad_request(e),
+        };
+        if file.path == request.main {
+            main = Some(String::from_utf8_lossy(&bytes).into_owned());
+        }
+        files.insert(file.path.clone());
+    }
+
+    let main = match main {
+        Some(main) => main,
+        None => return bad_request(format!("Main file {} is not among the uploaded files", request.main)),
+    };
+
+    let outcome = check_document(&request.main, &main, &files);
+    if let Some(error) = outcome.error {
+        let response = CompileResponse::Error {
+            message: error.message.clone(),
+            log: outcome.log,
+            errors: vec![error],
+        };
+        return (422, "application/json", response.to_json().into_bytes());
+    }
+
+    let pdf = minimal_pdf(outcome.pages, &format!("{} compiled by the mock compile server", request.main));
+    (200, "application/json", CompileResponse::success(&pdf, &outcome.log).to_json().into_bytes())
+}
+
+struct Outcome {
+    log: String,
+    pages: usize,
+    error: Option<ServiceError>,
+}
+
+// Cheap structural checks standing in for a real TeX run
+fn check_document(main_name: &str, source: &str, files: &HashSet<String>) -> Outcome {
+    let mut log = format!(
+        "This is pdfTeX, Version 3.141592653-2.6-1.40.25 (mock compile server)\nentering extended mode\n(./{}\nLaTeX2e <2023-11-01>\n",
+        main_name
+    );
+    let mut environments: Vec<(String, usize)> = Vec::new();
+    let mut labels = HashSet::new();
+    let mut refs = Vec::new();
+    let mut saw_begin_document = false;
+    let mut saw_end_document = false;
+    let mut body_chars = 0;
+    let mut page_breaks = 0;
+
+    let fail = |mut log: String, line_no: usize, line: &str, message: String| {
+        log.push_str(&format!(
+            "\n! {}\n\nSee the LaTeX manual or LaTeX Companion for explanation.\nType  H <return>  for immediate help.\n ...                                              \n\nl.{} {}\n\n)\n",
+            message, line_no, line.trim_end()
+        ));
+        log.push_str("! ==> Fatal error occurred, no output PDF file produced!\n");
+        Outcome {
+            log,
+            pages: 0,
+            error: Some(ServiceError { file: Some(main_name.to_string()), line: Some(line_no), message }),
+        }
+    };
+
+    if !source.contains("\\documentclass") {
+        let line = source.lines().next().unwrap_or("");
+        return fail(log, 1, line, "LaTeX Error: Missing \\begin{document}.".to_string());
+    }
+
+    for (index, raw_line) in source.lines().enumerate() {
+        let line_no = index + 1;
+        let line = strip_comment(raw_line);
+
+        if saw_begin_document && !saw_end_document {
+            body_chars += line.len();
+        }
+        page_breaks += line.matches("\\newpage").count() + line.matches("\\clearpage").count();
+
+        for (command, argument) in commands_with_argument(line) {
+            match command {
+                "begin" => {
+                    if argument == "document" {
+                        saw_begin_document = true;
+                    }
+                    environments.push((argument.to_string(), line_no));
+                },
+                "end" => {
+                    match environments.pop() {
+                        Some((open, _)) if open == argument => {},
+                        Some((open, open_line)) => {
+                            let message = format!("LaTeX Error: \\begin{{{}}} on input line {} ended by \\end{{{}}}.", open, open_line, argument);
+                            return fail(log, line_no, raw_line, message);
+                        },
+                        None => {
+                            let message = format!("LaTeX Error: \\begin{{document}} ended by \\end{{{}}}.", argument);
+                            return fail(log, line_no, raw_line, message);
+                        }
+                    }
+                    if argument == "document" {
+                        saw_end_document = true;
+                    }
+                },
+                "includegraphics" | "input" | "include" | "bibliography" if !has_file(files, command, argument) => {
+                    let message = format!("LaTeX Error: File `{}' not found.", argument);
+                    return fail(log, line_no, raw_line, message);
+                },
+                "label" => {
+                    labels.insert(argument.to_string());
+                },
+                "ref" | "eqref" | "pageref" => {
+                    refs.push((argument.to_string(), line_no));
+                },
+                _ => {}
+            }
+        }
+
+        if saw_end_document {
+            break;
+        }
+    }
+
+    let last_line = source.lines().count();
+    if !saw_begin_document {
+        return fail(log, last_line, "", "LaTeX Error: Missing \\begin{document}.".to_string());
+    }
+    if !saw_end_document {
+        log.push_str("\n! Emergency stop.\n<*> main.tex\n\n*** (job aborted, no legal \\end found)\n\n");
+        return Outcome {
+            log,
+            pages: 0,
+            error: Some(ServiceError {
+                file: Some(main_name.to_string()),
+                line: Some(last_line),
+                message: "Emergency stop: no legal \\end found".to_string(),
+            }),
+        };
+    }
+
+    let mut undefined = false;
+    for (reference, line_no) in &refs {
+        if !labels.contains(reference) {
+            undefined = true;
+            log.push_str(&format!(
+                "\nLaTeX Warning: Reference `{}' on page 1 undefined on input line {}.\n",
+                reference, line_no
+            ));
+        }
+    }
+    if undefined {
+        log.push_str("\nLaTeX Warning: There were undefined references.\n");
+    }
+
+    let pages = 1 + page_breaks + body_chars / CHARS_PER_PAGE;
+    log.push_str(&format!(") )\nOutput written on main.pdf ({} page{}).\n", pages, if pages == 1 { "" } else { "s" }));
+
+    Outcome { log, pages, error: None }
+}
+
+fn strip_comment(line: &str) -> &str {
+    let bytes = line.as_bytes();
+    for (i, &b) in bytes.iter().enumerate() {
+        if b == b'%' && (i == 0 || bytes[i - 1] != b'\\') {
+            return &line[..i];
+        }
+    }
+    line
+}
+
+// Find `\command[...]{argument}` occurrences on a line
+fn commands_with_argument(line: &str) -> Vec<(&str, &str)> {
+    let mut found = Vec::new();
+    let mut rest = line;
+
+    while let Some(start) = rest.find('\\') {
+        rest = &rest[start + 1..];
+        let name_len = rest.bytes().take_while(|b| b.is_ascii_alphabetic()).count();
+        if name_len == 0 {
+            // Skip escaped characters such as \\ or \%
+            rest = rest.get(1..).unwrap_or("");
+            continue;
+        }
+        let name = &rest[..name_len];
+        let mut after = rest[name_len..].trim_start();
+        if after.starts_with('[') {
+            if let Some(close) = after.find(']') {
+                after = after[close + 1..].trim_start();
+            }
+        }
+        if let Some(arg) = after.strip_prefix('{') {
+            if let Some(close) = arg.find('}') {
+                found.push((name, arg[..close].trim()));
+            }
+        }
+        rest = &rest[name_len..];
+    }
+
+    found
+}
+
+fn has_file(files: &HashSet<String>, command: &str, name: &str) -> bool {
+    let extensions: &[&str] = match command {
+        "includegraphics" => &["", ".pdf", ".png", ".jpg", ".jpeg", ".eps"],
+        "bibliography" => &[".bib"],
+        _ => &["", ".tex"],
+    };
+    name.split(',').all(|name| {
+        extensions.iter().any(|ext| files.contains(&format!("{}{}", name.trim(), ext)))
+    })
+}
+
+// Build a valid PDF with one line of text per page
+pub fn minimal_pdf(pages: usize, text: &str) -> Vec<u8> {
+    let pages = pages.max(1);
+    let text = text.replace('\\', "\\\\").replace('(', "\\(").replace(')', "\\)");
+
+    // Object numbers: 1 catalog, 2 page tree, 3 font, then a page and a content
+    // stream per page
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            (0..pages).map(|i| format!("{} 0 R", 4 + i * 2)).collect::<Vec<_>>().join(" "),
+            pages
+        ),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string(),
+    ];
+    for i in 0..pages {
+        let stream = format!("BT\n/F1 12 Tf\n72 720 Td\n(Page {} of {}: {}) Tj\nET", i + 1, pages, text);
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+            5 + i * 2
+        ));
+        objects.push(format!("<< /Length {} >>\nstream\n{}\nendstream", stream.len(), stream));
+    }
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::new();
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).as_bytes());
+    }
+
+    let xref_offset = pdf.len();
+    pdf.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+    for offset in offsets {
+        pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    pdf.extend_from_slice(format!(
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref_offset
+    ).as_bytes());
+
+    pdf
+}
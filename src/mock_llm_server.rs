@@ -0,0 +1,223 @@
+// Stand-in OpenAI-compatible chat server for tests and local development
+//
+// Answers POST .../chat/completions with a short LaTeX document about the
+// last user message, as one JSON completion or, for `"stream": true`, as
+// server-sent events ending in `data: [DONE]`. It can require a bearer key
+// and keeps the requests it received for tests to inspect.
+
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+// Size of the text pieces sent per streamed event
+const STREAM_CHUNK_CHARS: usize = 24;
+
+pub struct MockLlmServer {
+    addr: SocketAddr,
+    server: Arc<Server>,
+    requests: Arc<Mutex<Vec<ReceivedRequest>>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+// A request as the server saw it
+#[derive(Clone, Debug)]
+pub struct ReceivedRequest {
+    pub path: String,
+    pub authorization: Option<String>,
+    pub body: Value,
+}
+
+impl MockLlmServer {
+    // Start serving on `addr` without authentication; use port 0 to pick a
+    // free port
+    pub fn start(addr: &str) -> io::Result<Self> {
+        Self::start_with_key(addr, None)
+    }
+
+    // Start serving on `addr`, rejecting requests without `Bearer <api_key>`
+    pub fn start_with_key(addr: &str, api_key: Option<&str>) -> io::Result<Self> {
+        let server = Server::http(addr).map_err(io::Error::other)?;
+        let addr = server.server_addr().to_ip()
+            .ok_or_else(|| io::Error::other("mock LLM server must listen on an IP address"))?;
+        let server = Arc::new(server);
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let thread = {
+            let server = server.clone();
+            let requests = requests.clone();
+            let api_key = api_key.map(str::to_string);
+            thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    if let Err(e) = serve(request, api_key.as_deref(), &requests) {
+                        eprintln!("mock LLM server: {}", e);
+                    }
+                }
+            })
+        };
+
+        Ok(MockLlmServer { addr, server, requests, thread: Some(thread) })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    // URL to put in the custom endpoint's "Base URL" setting
+    pub fn base_url(&self) -> String {
+        format!("http://{}/v1", self.addr)
+    }
+
+    // Requests received so far that reached the completions handler
+    pub fn requests(&self) -> Vec<ReceivedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    // Block until the server thread exits
+    pub fn join(mut self) {
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for MockLlmServer {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap()
+}
+
+fn serve(mut request: Request, api_key: Option<&str>, requests: &Mutex<Vec<ReceivedRequest>>) -> io::Result<()> {
+    let cors = [
+        header("Access-Control-Allow-Origin", "*"),
+        header("Access-Control-Allow-Methods", "POST, OPTIONS"),
+        header("Access-Control-Allow-Headers", "content-type, authorization"),
+    ];
+
+    let authorization = request.headers().iter()
+        .find(|h| h.field.equiv("Authorization"))
+        .map(|h| h.value.to_string());
+    let path = request.url().to_string();
+
+    let (status, content_type, body) = match request.method() {
+        Method::Options => (204, "text/plain", String::new()),
+        Method::Post if path.ends_with("/chat/completions") => {
+            let mut body = Vec::new();
+            request.as_reader().read_to_end(&mut body)?;
+            handle_completion(&path, authorization, &body, api_key, requests)
+        },
+        Method::Post => error_response(404, &format!("No route for {}", path)),
+        _ => error_response(405, "POST a chat completion request"),
+    };
+
+    let mut response = Response::from_data(body.into_bytes())
+        .with_status_code(status)
+        .with_header(header("Content-Type", content_type));
+    for h in cors {
+        response.add_header(h);
+    }
+    request.respond(response)
+}
+
+fn handle_completion(
+    path: &str,
+    authorization: Option<String>,
+    body: &[u8],
+    api_key: Option<&str>,
+    requests: &Mutex<Vec<ReceivedRequest>>,
+) -> (u16, &'static str, String) {
+    if let Some(key) = api_key {
+        if authorization.as_deref() != Some(format!("Bearer {}", key).as_str()) {
+            return error_response(401, "Invalid API key");
+        }
+    }
+
+    let body: Value = match serde_json::from_slice(body) {
+        Ok(body) => body,
+        Err(e) => return error_response(400, &format!("Invalid JSON body: {}", e)),
+    };
+    requests.lock().unwrap().push(ReceivedRequest {
+        path: path.to_string(),
+        authorization,
+        body: body.clone(),
+    });
+
+    let model = match body.get("model").and_then(Value::as_str) {
+        Some(model) if !model.is_empty() => model.to_string(),
+        _ => return error_response(400, "model is required"),
+    };
+    let last_user_message = body.get("messages").and_then(Value::as_array)
+        .and_then(|messages| messages.iter().rev().find(|m| m["role"] == "user"))
+        .and_then(|message| message["content"].as_str());
+    let Some(prompt) = last_user_message else {
+        return error_response(400, "messages must contain a user message");
+    };
+
+    let reply = reply_document(prompt);
+    if body.get("stream").and_then(Value::as_bool).unwrap_or(false) {
+        (200, "text/event-stream", stream_body(&model, &reply))
+    } else {
+        let completion = json!({
+            "id": "chatcmpl-mock",
+            "object": "chat.completion",
+            "model": model,
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": reply},
+                "finish_reason": "stop"
+            }]
+        });
+        (200, "application/json", completion.to_string())
+    }
+}
+
+fn error_response(status: u16, message: &str) -> (u16, &'static str, String) {
+    let body = json!({"error": {"message": message, "type": "invalid_request_error"}});
+    (status, "application/json", body.to_string())
+}
+
+// The reply as chat.completion.chunk events
+fn stream_body(model: &str, reply: &str) -> String {
+    let chars: Vec<char> = reply.chars().collect();
+    let mut body = String::new();
+
+    let role = json!({"model": model, "choices": [{"index": 0, "delta": {"role": "assistant"}}]});
+    body.push_str(&format!("data: {}\n\n", role));
+    for piece in chars.chunks(STREAM_CHUNK_CHARS) {
+        let chunk = json!({
+            "object": "chat.completion.chunk",
+            "model": model,
+            "choices": [{"index": 0, "delta": {"content": piece.iter().collect::<String>()}}]
+        });
+        body.push_str(&format!("data: {}\n\n", chunk));
+    }
+    let done = json!({"model": model, "choices": [{"index": 0, "delta": {}, "finish_reason": "stop"}]});
+    body.push_str(&format!("data: {}\n\n", done));
+    body.push_str("data: [DONE]\n\n");
+    body
+}
+
+// A small document titled after the first line of the prompt, wrapped in
+// prose and a code fence the way chat models answer
+pub fn reply_document(prompt: &str) -> String {
+    let title: String = prompt.lines().find(|line| !line.trim().is_empty()).unwrap_or("Untitled")
+        .chars()
+        .filter(|c| c.is_alphanumeric() || matches!(c, ' ' | ',' | '.' | '-' | '\''))
+        .take(60)
+        .collect();
+
+    format!(
+        "Here is the document:\n\n```latex\n\\documentclass{{article}}\n\\title{{{}}}\n\\begin{{document}}\n\\maketitle\n\\section{{Introduction}}\nGenerated by the mock LLM server.\n\\begin{{equation}}\n  E = mc^2\n\\end{{equation}}\n\\end{{document}}\n```\n",
+        title.trim()
+    )
+}
@@ -0,0 +1,199 @@
+// Document outline for the preview
+//
+// Built from `doctree`: the sectioning commands nested by level, with the
+// figures, tables and displayed equations listed under the section they
+// appear in. Each entry knows its line in the source, and `Outline::page`
+// places it in the compiled PDF.
+
+use std::ops::Range;
+
+use crate::doctree::{self, DocumentTree, Environment};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntryKind {
+    // `doctree::Section::level`
+    Section(u8),
+    Figure,
+    Table,
+    Equation,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutlineEntry {
+    pub kind: EntryKind,
+    pub title: String,
+    pub line: usize,
+    pub offset: usize,
+    // Index in `DocumentTree::sections` of a section entry
+    pub section: Option<usize>,
+    pub children: Vec<OutlineEntry>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Outline {
+    pub entries: Vec<OutlineEntry>,
+    // Where the typeset part of the source is, for estimating pages
+    body: Range<usize>,
+}
+
+const FIGURE_ENVIRONMENTS: &[&str] = &["figure", "figure*", "wrapfigure"];
+const TABLE_ENVIRONMENTS: &[&str] = &["table", "table*", "longtable"];
+const EQUATION_ENVIRONMENTS: &[&str] = &[
+    "equation", "equation*", "align", "align*", "gather", "gather*", "multline", "multline*", "displaymath",
+];
+
+// Longest equation preview used as a title
+const MAX_EQUATION_TITLE: usize = 40;
+
+impl EntryKind {
+    fn of(environment: &str) -> Option<EntryKind> {
+        if FIGURE_ENVIRONMENTS.contains(&environment) {
+            Some(EntryKind::Figure)
+        } else if TABLE_ENVIRONMENTS.contains(&environment) {
+            Some(EntryKind::Table)
+        } else if EQUATION_ENVIRONMENTS.contains(&environment) {
+            Some(EntryKind::Equation)
+        } else {
+            None
+        }
+    }
+
+    // Class name for the preview
+    pub fn class(self) -> &'static str {
+        match self {
+            EntryKind::Section(_) => "section",
+            EntryKind::Figure => "figure",
+            EntryKind::Table => "table",
+            EntryKind::Equation => "equation",
+        }
+    }
+}
+
+impl OutlineEntry {
+    // This entry and everything under it, depth first
+    pub fn walk(&self) -> Box<dyn Iterator<Item = &OutlineEntry> + '_> {
+        Box::new(std::iter::once(self).chain(self.children.iter().flat_map(OutlineEntry::walk)))
+    }
+}
+
+impl Outline {
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // All entries, depth first
+    pub fn iter(&self) -> impl Iterator<Item = &OutlineEntry> {
+        self.entries.iter().flat_map(OutlineEntry::walk)
+    }
+
+    // Page of a PDF with `page_count` pages that the source at `offset` is
+    // typeset on. The compiler gives no source map, so this is estimated from
+    // how far into the body the offset is.
+    pub fn page(&self, offset: usize, page_count: usize) -> usize {
+        if page_count == 0 || self.body.is_empty() {
+            return 1;
+        }
+        let into_body = offset.clamp(self.body.start, self.body.end) - self.body.start;
+        let page = into_body * page_count / self.body.len() + 1;
+        page.min(page_count)
+    }
+}
+
+pub fn outline(latex: &str) -> Outline {
+    let tree = doctree::parse(latex);
+    let body = tree.body.clone().unwrap_or(0..latex.len());
+
+    // Sections and the environments to list, in source order, with the level
+    // they nest at; environments are leaves
+    let mut items: Vec<(u8, OutlineEntry)> = tree.sections.iter().enumerate()
+        .map(|(index, section)| (section.level, OutlineEntry {
+            kind: EntryKind::Section(section.level),
+            title: section.title.clone(),
+            line: section.position.line,
+            offset: section.position.offset,
+            section: Some(index),
+            children: Vec::new(),
+        }))
+        .collect();
+    for (index, environment) in tree.environments.iter().enumerate() {
+        let Some(kind) = EntryKind::of(&environment.name) else { continue };
+        // Skip an equation inside a listed figure and the like
+        if listed_ancestor(&tree, index) {
+            continue;
+        }
+        items.push((u8::MAX, OutlineEntry {
+            kind,
+            title: environment_title(latex, &tree, environment, kind),
+            line: environment.begin.line,
+            offset: environment.begin.offset,
+            section: None,
+            children: Vec::new(),
+        }));
+    }
+    items.sort_by_key(|(_, entry)| entry.offset);
+
+    Outline { entries: nest(items), body }
+}
+
+fn listed_ancestor(tree: &DocumentTree, index: usize) -> bool {
+    let mut parent = tree.environments[index].parent;
+    while let Some(index) = parent {
+        if EntryKind::of(&tree.environments[index].name).is_some() {
+            return true;
+        }
+        parent = tree.environments[index].parent;
+    }
+    false
+}
+
+// A float's caption, or an equation's label or opening text
+fn environment_title(latex: &str, tree: &DocumentTree, environment: &Environment, kind: EntryKind) -> String {
+    let inside = |offset: usize| environment.range.contains(&offset);
+    let caption = tree.captions.iter().find(|caption| inside(caption.position.offset));
+    let label = tree.labels.iter().find(|label| inside(label.position.offset));
+
+    match (kind, caption, label) {
+        (EntryKind::Figure | EntryKind::Table, Some(caption), _) => collapse_whitespace(&caption.text),
+        (EntryKind::Equation, _, Some(label)) => label.name.clone(),
+        (EntryKind::Equation, _, None) => {
+            let begin = format!("\\begin{{{}}}", environment.name);
+            let start = environment.begin.offset + begin.len();
+            let end = latex[start..environment.range.end].find("\\end{").map_or(environment.range.end, |n| start + n);
+            let text = collapse_whitespace(&latex[start.min(end)..end]);
+            match text.char_indices().nth(MAX_EQUATION_TITLE) {
+                Some((cut, _)) => format!("{}…", &text[..cut]),
+                None => text,
+            }
+        },
+        _ => String::new(),
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Nest entries under the closest preceding entry with a lower level
+fn nest(items: Vec<(u8, OutlineEntry)>) -> Vec<OutlineEntry> {
+    let mut roots = Vec::new();
+    let mut open: Vec<(u8, OutlineEntry)> = Vec::new();
+
+    let close = |open: &mut Vec<(u8, OutlineEntry)>, roots: &mut Vec<OutlineEntry>| {
+        let (_, entry) = open.pop().unwrap();
+        match open.last_mut() {
+            Some((_, parent)) => parent.children.push(entry),
+            None => roots.push(entry),
+        }
+    };
+
+    for (level, entry) in items {
+        while open.last().is_some_and(|(open_level, _)| *open_level >= level) {
+            close(&mut open, &mut roots);
+        }
+        open.push((level, entry));
+    }
+    while !open.is_empty() {
+        close(&mut open, &mut roots);
+    }
+    roots
+}
@@ -0,0 +1,324 @@
+// Structured edits returned by the model
+//
+// In edit mode the model answers a follow-up with changes to the current
+// document instead of the whole file, either as search/replace blocks:
+//
+//     <<<<<<< SEARCH
+//     \section{Intro}
+//     =======
+//     \section{Introduction}
+//     >>>>>>> REPLACE
+//
+// or as a unified diff. Edits are applied strictly: a search text that is
+// missing or matches more than once, or a hunk whose lines are not in the
+// document, is a conflict and nothing is applied.
+
+use std::fmt;
+
+const SEARCH_MARKER: &str = "<<<<<<< SEARCH";
+const DIVIDER: &str = "=======";
+const REPLACE_MARKER: &str = ">>>>>>> REPLACE";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Patch {
+    SearchReplace(Vec<SearchReplace>),
+    Unified(Vec<Hunk>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchReplace {
+    pub search: String,
+    pub replace: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hunk {
+    // 1-based line the hunk starts at in the old text, as stated in its header
+    pub old_start: usize,
+    pub lines: Vec<HunkLine>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HunkLine {
+    Context(String),
+    Delete(String),
+    Insert(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PatchError {
+    // The reply holds neither search/replace blocks nor diff hunks
+    NotAPatch,
+    Malformed(String),
+    // Edit number `edit` (1-based) does not apply to the document
+    Conflict { edit: usize, message: String },
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::NotAPatch => write!(f, "the reply contains no edits"),
+            PatchError::Malformed(message) => write!(f, "malformed edits: {}", message),
+            PatchError::Conflict { edit, message } => write!(f, "edit {} does not apply: {}", edit, message),
+        }
+    }
+}
+
+// Find the edits in a model reply
+pub fn parse_patch(reply: &str) -> Result<Patch, PatchError> {
+    if reply.lines().any(|line| line.trim_end() == SEARCH_MARKER) {
+        parse_search_replace(reply).map(Patch::SearchReplace)
+    } else if reply.lines().any(|line| line.starts_with("@@ -")) {
+        parse_unified(reply).map(Patch::Unified)
+    } else {
+        Err(PatchError::NotAPatch)
+    }
+}
+
+// Parse and apply the edits in `reply` to `latex`
+pub fn apply_reply(latex: &str, reply: &str) -> Result<String, PatchError> {
+    match parse_patch(reply)? {
+        Patch::SearchReplace(blocks) => apply_search_replace(latex, &blocks),
+        Patch::Unified(hunks) => apply_unified(latex, &hunks),
+    }
+}
+
+fn parse_search_replace(reply: &str) -> Result<Vec<SearchReplace>, PatchError> {
+    let mut blocks = Vec::new();
+    let mut lines = reply.lines();
+
+    while let Some(line) = lines.next() {
+        if line.trim_end() != SEARCH_MARKER {
+            continue;
+        }
+
+        let mut search = Vec::new();
+        let mut replace = Vec::new();
+        let mut in_replace = false;
+        let mut closed = false;
+        for line in lines.by_ref() {
+            match line.trim_end() {
+                DIVIDER if !in_replace => in_replace = true,
+                REPLACE_MARKER if in_replace => {
+                    closed = true;
+                    break;
+                },
+                _ if in_replace => replace.push(line),
+                _ => search.push(line),
+            }
+        }
+
+        if !closed {
+            return Err(PatchError::Malformed(format!("block {} is not terminated by {}", blocks.len() + 1, REPLACE_MARKER)));
+        }
+        blocks.push(SearchReplace {
+            search: search.join("\n"),
+            replace: replace.join("\n"),
+        });
+    }
+
+    Ok(blocks)
+}
+
+fn parse_unified(reply: &str) -> Result<Vec<Hunk>, PatchError> {
+    let mut hunks = Vec::new();
+    let mut lines = reply.lines();
+
+    while let Some(line) = lines.next() {
+        let Some(header) = line.strip_prefix("@@ -") else {
+            continue;
+        };
+        let (old_start, old_count, new_count) = parse_hunk_header(header)
+            .ok_or_else(|| PatchError::Malformed(format!("bad hunk header: {}", line)))?;
+
+        // The header counts tell where the hunk ends, so lines such as
+        // "--- text" inside it are not mistaken for file headers
+        let mut hunk_lines = Vec::new();
+        let (mut old_seen, mut new_seen) = (0, 0);
+        while old_seen < old_count || new_seen < new_count {
+            let Some(line) = lines.next() else {
+                return Err(PatchError::Malformed(format!("hunk at line {} is truncated", old_start)));
+            };
+            if line.starts_with('\\') {
+                // "\ No newline at end of file"
+                continue;
+            }
+            let (kind, text) = match line.chars().next() {
+                Some('+') => ('+', &line[1..]),
+                Some('-') => ('-', &line[1..]),
+                Some(' ') => (' ', &line[1..]),
+                // Some models drop the space in front of empty context lines
+                None => (' ', ""),
+                Some(_) => return Err(PatchError::Malformed(format!("unexpected line in hunk: {}", line))),
+            };
+            match kind {
+                '+' => {
+                    new_seen += 1;
+                    hunk_lines.push(HunkLine::Insert(text.to_string()));
+                },
+                '-' => {
+                    old_seen += 1;
+                    hunk_lines.push(HunkLine::Delete(text.to_string()));
+                },
+                _ => {
+                    old_seen += 1;
+                    new_seen += 1;
+                    hunk_lines.push(HunkLine::Context(text.to_string()));
+                }
+            }
+        }
+
+        hunks.push(Hunk { old_start, lines: hunk_lines });
+    }
+
+    Ok(hunks)
+}
+
+// "12,5 +12,6 @@ ..." (after the leading "@@ -")
+fn parse_hunk_header(header: &str) -> Option<(usize, usize, usize)> {
+    let (ranges, _) = header.split_once(" @@")?;
+    let (old, new) = ranges.split_once(" +")?;
+    let range = |range: &str| -> Option<(usize, usize)> {
+        match range.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((range.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_count) = range(old)?;
+    let (_, new_count) = range(new)?;
+    Some((old_start, old_count, new_count))
+}
+
+// Apply search/replace blocks in order; each search text must occur exactly
+// once in the document as edited so far
+pub fn apply_search_replace(latex: &str, blocks: &[SearchReplace]) -> Result<String, PatchError> {
+    if blocks.is_empty() {
+        return Err(PatchError::NotAPatch);
+    }
+
+    let mut text = latex.to_string();
+    for (index, block) in blocks.iter().enumerate() {
+        let conflict = |message: String| PatchError::Conflict { edit: index + 1, message };
+
+        if block.search.trim().is_empty() {
+            return Err(conflict("empty search text".to_string()));
+        }
+
+        let range = match find_unique(&text, &block.search) {
+            Ok(range) => range,
+            // Models often get trailing whitespace wrong
+            Err(0) => find_lines_ignoring_trailing_space(&text, &block.search)
+                .map_err(|count| conflict(match count {
+                    0 => format!("search text not found: {}", first_line(&block.search)),
+                    n => format!("search text matches {} places: {}", n, first_line(&block.search)),
+                }))?,
+            Err(n) => return Err(conflict(format!("search text matches {} places: {}", n, first_line(&block.search)))),
+        };
+
+        text.replace_range(range, &block.replace);
+    }
+
+    Ok(text)
+}
+
+// Byte range of the only occurrence of `needle`, or the number of
+// occurrences if there is not exactly one
+fn find_unique(haystack: &str, needle: &str) -> Result<std::ops::Range<usize>, usize> {
+    let matches: Vec<usize> = haystack.match_indices(needle).map(|(index, _)| index).collect();
+    match matches[..] {
+        [start] => Ok(start..start + needle.len()),
+        _ => Err(matches.len()),
+    }
+}
+
+// Like `find_unique`, but compares whole lines without trailing whitespace
+fn find_lines_ignoring_trailing_space(haystack: &str, needle: &str) -> Result<std::ops::Range<usize>, usize> {
+    let needle: Vec<&str> = needle.lines().map(str::trim_end).collect();
+    let offsets = line_offsets(haystack);
+    let lines: Vec<&str> = haystack.lines().collect();
+
+    let starts: Vec<usize> = (0..lines.len().saturating_sub(needle.len() - 1))
+        .filter(|&start| lines[start..start + needle.len()].iter().map(|l| l.trim_end()).eq(needle.iter().copied()))
+        .collect();
+
+    match starts[..] {
+        [start] => {
+            let last = start + needle.len() - 1;
+            Ok(offsets[start]..offsets[last] + lines[last].len())
+        },
+        _ => Err(starts.len()),
+    }
+}
+
+// Byte offset of the start of each line
+fn line_offsets(text: &str) -> Vec<usize> {
+    let mut offsets = vec![0];
+    offsets.extend(text.match_indices('\n').map(|(index, _)| index + 1));
+    offsets
+}
+
+fn first_line(text: &str) -> &str {
+    text.lines().find(|line| !line.trim().is_empty()).unwrap_or("").trim()
+}
+
+// Apply unified diff hunks in order. A hunk whose old lines are not at the
+// stated line is applied at the nearest place they do occur, as long as that
+// is after the previous hunk.
+pub fn apply_unified(latex: &str, hunks: &[Hunk]) -> Result<String, PatchError> {
+    if hunks.is_empty() {
+        return Err(PatchError::NotAPatch);
+    }
+
+    let mut lines: Vec<String> = latex.lines().map(str::to_string).collect();
+    // Where the next hunk may start, and how far earlier hunks moved the text
+    let mut earliest = 0;
+    let mut shift: isize = 0;
+
+    for (index, hunk) in hunks.iter().enumerate() {
+        let old: Vec<&str> = hunk.lines.iter()
+            .filter_map(|line| match line {
+                HunkLine::Context(text) | HunkLine::Delete(text) => Some(text.trim_end()),
+                HunkLine::Insert(_) => None,
+            })
+            .collect();
+        let new: Vec<String> = hunk.lines.iter()
+            .filter_map(|line| match line {
+                HunkLine::Context(text) | HunkLine::Insert(text) => Some(text.clone()),
+                HunkLine::Delete(_) => None,
+            })
+            .collect();
+
+        // A hunk with no old lines inserts after line `old_start`
+        let stated = if old.is_empty() { hunk.old_start } else { hunk.old_start.saturating_sub(1) };
+        let expected = (stated as isize + shift).max(0) as usize;
+
+        let start = if old.is_empty() {
+            if expected > lines.len() || expected < earliest {
+                return Err(PatchError::Conflict { edit: index + 1, message: format!("insertion point {} is outside the document", hunk.old_start) });
+            }
+            expected
+        } else {
+            let candidates = (earliest..=lines.len().saturating_sub(old.len()))
+                .filter(|&start| start + old.len() <= lines.len())
+                .filter(|&start| lines[start..start + old.len()].iter().map(|l| l.trim_end()).eq(old.iter().copied()));
+            match candidates.min_by_key(|&start| start.abs_diff(expected)) {
+                Some(start) => start,
+                None => return Err(PatchError::Conflict {
+                    edit: index + 1,
+                    message: format!("lines near line {} do not match: {}", hunk.old_start, first_line(&old.join("\n"))),
+                }),
+            }
+        };
+
+        let new_len = new.len();
+        lines.splice(start..start + old.len(), new);
+        shift += new_len as isize - old.len() as isize;
+        earliest = start + new_len;
+    }
+
+    let mut text = lines.join("\n");
+    if latex.ends_with('\n') {
+        text.push('\n');
+    }
+    Ok(text)
+}
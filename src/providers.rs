@@ -0,0 +1,392 @@
+// AI providers
+//
+// Each vendor is an `LlmProvider`: it knows its endpoint and authentication,
+// how to shape the conversation into its native request body, and how to read
+// the reply, whole or streamed. `PROVIDERS` is the registry the provider
+// dropdown and the API key inputs are built from, so adding a vendor means
+// adding one impl (or one `OpenAiChat` for OpenAI-style APIs) to it.
+//
+// `CustomEndpoint` talks to any server with an OpenAI-compatible
+// `chat/completions` API (Ollama, llama.cpp, vLLM, LM Studio) at a base URL
+// and model the user configures; its key is optional.
+//
+// The model, max tokens, temperature, top-p and system prompt come from the
+// provider's `ProviderSettings`; values left empty there fall back to the
+// provider's defaults.
+
+use serde_json::{json, Value};
+
+use crate::chat::{self, ChatMessage};
+use crate::streaming::StreamFormat;
+
+// An HTTP request ready to be sent with fetch
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProviderRequest {
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Value,
+}
+
+// What the user configured for a provider; empty strings and `None` mean
+// the provider's default
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProviderSettings {
+    pub api_key: String,
+    // Only used by providers with a configurable endpoint
+    pub base_url: String,
+    pub model: String,
+    pub max_tokens: Option<u32>,
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub system_prompt: String,
+}
+
+impl ProviderSettings {
+    // Settings from their text form, as typed in the profile panel and kept
+    // in localStorage; `field` returns the text stored under a setting name
+    // ("base_url", "model", "max_tokens", "temperature", "top_p",
+    // "system_prompt")
+    pub fn parse(api_key: &str, field: impl Fn(&str) -> String) -> Result<Self, String> {
+        let number = |name: &str, label: &str, max: f64| -> Result<Option<f64>, String> {
+            let text = field(name);
+            if text.trim().is_empty() {
+                return Ok(None);
+            }
+            match text.trim().parse::<f64>() {
+                Ok(value) if (0.0..=max).contains(&value) => Ok(Some(value)),
+                _ => Err(format!("{} must be a number between 0 and {}", label, max)),
+            }
+        };
+
+        let max_tokens = field("max_tokens");
+        let max_tokens = match max_tokens.trim() {
+            "" => None,
+            text => match text.parse::<u32>() {
+                Ok(tokens) if tokens > 0 => Some(tokens),
+                _ => return Err("Max tokens must be a positive whole number".to_string()),
+            },
+        };
+
+        Ok(ProviderSettings {
+            api_key: api_key.trim().to_string(),
+            base_url: field("base_url").trim().to_string(),
+            model: field("model").trim().to_string(),
+            max_tokens,
+            temperature: number("temperature", "Temperature", 2.0)?,
+            top_p: number("top_p", "Top-p", 1.0)?,
+            system_prompt: field("system_prompt").trim().to_string(),
+        })
+    }
+
+    // The configured model, or `default` when none is set
+    pub fn model_or<'a>(&'a self, default: &'a str) -> &'a str {
+        if self.model.is_empty() { default } else { &self.model }
+    }
+
+    pub fn system_prompt(&self) -> &str {
+        if self.system_prompt.is_empty() { chat::SYSTEM_PROMPT } else { &self.system_prompt }
+    }
+}
+
+pub trait LlmProvider {
+    // Stable id, used in storage keys and element ids
+    fn id(&self) -> &'static str;
+    // Name shown in the dropdown and stored with generated documents
+    fn name(&self) -> &'static str;
+    // Placeholder for the API key input
+    fn key_placeholder(&self) -> &'static str;
+    fn requires_key(&self) -> bool {
+        true
+    }
+    // Whether the user sets the base URL and model
+    fn has_custom_endpoint(&self) -> bool {
+        false
+    }
+    // Model used when the settings name none; empty if the user must set one
+    fn default_model(&self) -> &'static str;
+    // Models offered as suggestions in the model input
+    fn suggested_models(&self) -> &'static [&'static str] {
+        &[]
+    }
+    // Generation parameters sent when the settings leave them empty
+    fn default_max_tokens(&self) -> Option<u32> {
+        None
+    }
+    fn default_temperature(&self) -> Option<f64> {
+        None
+    }
+    fn build_request(&self, settings: &ProviderSettings, messages: &[ChatMessage], stream: bool) -> ProviderRequest;
+    // The reply text of a non-streamed response
+    fn parse_response(&self, json: &Value) -> Result<String, String>;
+    fn stream_format(&self) -> StreamFormat;
+}
+
+pub const CLAUDE: Claude = Claude {
+    model: "claude-sonnet-4-5",
+    models: &["claude-sonnet-4-5", "claude-opus-4-1", "claude-haiku-4-5"],
+    max_tokens: 4000,
+};
+
+pub const PERPLEXITY: OpenAiChat = OpenAiChat {
+    id: "perplexity",
+    name: "Perplexity",
+    url: "https://api.perplexity.ai/chat/completions",
+    model: "sonar",
+    models: &["sonar", "sonar-pro", "sonar-reasoning"],
+    temperature: None,
+    key_placeholder: "pplx-...",
+};
+
+pub const MISTRAL: OpenAiChat = OpenAiChat {
+    id: "mistral",
+    name: "Mistral",
+    url: "https://api.mistral.ai/v1/chat/completions",
+    model: "mistral-large-latest",
+    models: &["mistral-large-latest", "mistral-medium-latest", "mistral-small-latest"],
+    temperature: Some(0.7),
+    key_placeholder: "sk-...",
+};
+
+pub const CUSTOM: CustomEndpoint = CustomEndpoint;
+
+// Settings saved per provider besides its key, as "{provider id}_{name}"
+pub const PROVIDER_SETTINGS: [&str; 6] = ["base_url", "model", "max_tokens", "temperature", "top_p", "system_prompt"];
+
+// All providers, in dropdown order
+pub const PROVIDERS: &[&dyn LlmProvider] = &[&CLAUDE, &PERPLEXITY, &MISTRAL, &CUSTOM];
+
+// Look up a provider by its dropdown name
+pub fn find(name: &str) -> Option<&'static dyn LlmProvider> {
+    PROVIDERS.iter().copied().find(|provider| provider.name() == name)
+}
+
+// Anthropic's messages API, which requires `max_tokens`
+pub struct Claude {
+    pub model: &'static str,
+    pub models: &'static [&'static str],
+    pub max_tokens: u32,
+}
+
+impl LlmProvider for Claude {
+    fn id(&self) -> &'static str {
+        "claude"
+    }
+
+    fn name(&self) -> &'static str {
+        "Claude"
+    }
+
+    fn key_placeholder(&self) -> &'static str {
+        "sk-ant-..."
+    }
+
+    fn default_model(&self) -> &'static str {
+        self.model
+    }
+
+    fn suggested_models(&self) -> &'static [&'static str] {
+        self.models
+    }
+
+    fn default_max_tokens(&self) -> Option<u32> {
+        Some(self.max_tokens)
+    }
+
+    fn build_request(&self, settings: &ProviderSettings, messages: &[ChatMessage], stream: bool) -> ProviderRequest {
+        // The system prompt goes in its own field rather than the messages
+        let mut body = json!({
+            "model": settings.model_or(self.model),
+            "max_tokens": settings.max_tokens.unwrap_or(self.max_tokens),
+            "system": settings.system_prompt(),
+            "messages": chat::alternating(messages),
+            "stream": stream
+        });
+        if let Some(temperature) = settings.temperature {
+            body["temperature"] = json!(temperature);
+        }
+        if let Some(top_p) = settings.top_p {
+            body["top_p"] = json!(top_p);
+        }
+
+        ProviderRequest {
+            url: "https://api.anthropic.com/v1/messages".to_string(),
+            headers: vec![
+                ("x-api-key".to_string(), settings.api_key.clone()),
+                ("anthropic-version".to_string(), "2023-06-01".to_string()),
+                ("content-type".to_string(), "application/json".to_string()),
+            ],
+            body,
+        }
+    }
+
+    fn parse_response(&self, json: &Value) -> Result<String, String> {
+        json.pointer("/content/0/text")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| "Invalid response format from Claude API".to_string())
+    }
+
+    fn stream_format(&self) -> StreamFormat {
+        StreamFormat::Anthropic
+    }
+}
+
+// An OpenAI-style `chat/completions` API with bearer authentication
+pub struct OpenAiChat {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub url: &'static str,
+    pub model: &'static str,
+    pub models: &'static [&'static str],
+    pub temperature: Option<f64>,
+    pub key_placeholder: &'static str,
+}
+
+impl LlmProvider for OpenAiChat {
+    fn id(&self) -> &'static str {
+        self.id
+    }
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn key_placeholder(&self) -> &'static str {
+        self.key_placeholder
+    }
+
+    fn default_model(&self) -> &'static str {
+        self.model
+    }
+
+    fn suggested_models(&self) -> &'static [&'static str] {
+        self.models
+    }
+
+    fn default_temperature(&self) -> Option<f64> {
+        self.temperature
+    }
+
+    fn build_request(&self, settings: &ProviderSettings, messages: &[ChatMessage], stream: bool) -> ProviderRequest {
+        openai_request(self.url, settings, settings.model_or(self.model), settings.temperature.or(self.temperature), messages, stream)
+    }
+
+    fn parse_response(&self, json: &Value) -> Result<String, String> {
+        parse_openai_response(json)
+    }
+
+    fn stream_format(&self) -> StreamFormat {
+        StreamFormat::OpenAi
+    }
+}
+
+// A self-hosted server with an OpenAI-compatible API
+pub struct CustomEndpoint;
+
+impl LlmProvider for CustomEndpoint {
+    fn id(&self) -> &'static str {
+        "custom"
+    }
+
+    fn name(&self) -> &'static str {
+        "Custom endpoint"
+    }
+
+    fn key_placeholder(&self) -> &'static str {
+        "Optional"
+    }
+
+    fn requires_key(&self) -> bool {
+        false
+    }
+
+    fn has_custom_endpoint(&self) -> bool {
+        true
+    }
+
+    // Local servers each have their own models, so the user names one
+    fn default_model(&self) -> &'static str {
+        ""
+    }
+
+    fn build_request(&self, settings: &ProviderSettings, messages: &[ChatMessage], stream: bool) -> ProviderRequest {
+        let url = chat_completions_url(&settings.base_url);
+        openai_request(&url, settings, &settings.model, settings.temperature, messages, stream)
+    }
+
+    fn parse_response(&self, json: &Value) -> Result<String, String> {
+        parse_openai_response(json)
+    }
+
+    fn stream_format(&self) -> StreamFormat {
+        StreamFormat::OpenAi
+    }
+}
+
+// The chat completions URL for a configured base URL. Accepts the server root
+// ("http://localhost:11434"), its versioned API root
+// ("http://localhost:1234/v1") or the full endpoint.
+pub fn chat_completions_url(base_url: &str) -> String {
+    let base = base_url.trim().trim_end_matches('/');
+    if base.ends_with("/chat/completions") {
+        return base.to_string();
+    }
+
+    let last_segment = base.rsplit('/').next().unwrap_or("");
+    let is_version = last_segment.len() > 1
+        && last_segment.starts_with('v')
+        && last_segment[1..].chars().all(|c| c.is_ascii_digit());
+    if is_version {
+        format!("{}/chat/completions", base)
+    } else {
+        format!("{}/v1/chat/completions", base)
+    }
+}
+
+// Request for an OpenAI-style API with the resolved model and temperature;
+// the system prompt is sent as a leading system message, and an empty key
+// sends no authorization header
+pub fn openai_request(
+    url: &str,
+    settings: &ProviderSettings,
+    model: &str,
+    temperature: Option<f64>,
+    messages: &[ChatMessage],
+    stream: bool,
+) -> ProviderRequest {
+    let mut headers = vec![("content-type".to_string(), "application/json".to_string())];
+    if !settings.api_key.is_empty() {
+        headers.insert(0, ("authorization".to_string(), format!("Bearer {}", settings.api_key)));
+    }
+
+    let mut chat_messages = vec![json!({"role": "system", "content": settings.system_prompt()})];
+    chat_messages.extend(chat::alternating(messages).iter().map(|message| json!(message)));
+
+    let mut body = json!({
+        "model": model,
+        "messages": chat_messages,
+        "stream": stream
+    });
+    if let Some(max_tokens) = settings.max_tokens {
+        body["max_tokens"] = json!(max_tokens);
+    }
+    if let Some(temperature) = temperature {
+        body["temperature"] = json!(temperature);
+    }
+    if let Some(top_p) = settings.top_p {
+        body["top_p"] = json!(top_p);
+    }
+
+    ProviderRequest {
+        url: url.to_string(),
+        headers,
+        body,
+    }
+}
+
+pub fn parse_openai_response(json: &Value) -> Result<String, String> {
+    json.pointer("/choices/0/message/content")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| "Invalid response format from API".to_string())
+}
@@ -0,0 +1,192 @@
+// Backend proxy mode
+//
+// Deployments that cannot hand vendor API keys to end users run the
+// `llm-proxy` binary next to the app. The browser then sends each provider
+// request to `{proxy_url}/{provider_id}` on its own origin without any
+// credentials; the proxy adds the key it holds for that provider, applies a
+// per-user rate limit and forwards the body to the vendor, passing the reply
+// (streamed or not) straight back.
+//
+// The proxy reads a JSON config:
+//
+//     { "listen": "127.0.0.1:8790",
+//       "path": "/api/llm",
+//       "user_header": "x-forwarded-user",
+//       "rate_limit": { "requests": 30, "window_secs": 60 },
+//       "providers": {
+//         "claude": { "api_key_env": "ANTHROPIC_API_KEY" },
+//         "custom": { "base_url": "http://127.0.0.1:11434/v1" } } }
+//
+// Without `user_header` users are told apart by their address. Only the
+// providers listed are forwarded.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::providers::{self, ProviderRequest, ProviderSettings};
+
+pub const DEFAULT_PATH: &str = "/api/llm";
+
+// Headers that carry vendor credentials; never sent to or accepted from the
+// browser in proxy mode
+pub const CREDENTIAL_HEADERS: &[&str] = &["authorization", "x-api-key"];
+
+// `request` sent to the proxy at `proxy_url` instead of to the vendor
+pub fn proxied(request: ProviderRequest, proxy_url: &str, provider_id: &str) -> ProviderRequest {
+    let headers = request.headers.into_iter()
+        .filter(|(name, _)| !is_credential(name))
+        .collect();
+    ProviderRequest {
+        url: format!("{}/{}", proxy_url.trim().trim_end_matches('/'), provider_id),
+        headers,
+        body: request.body,
+    }
+}
+
+pub fn is_credential(name: &str) -> bool {
+    CREDENTIAL_HEADERS.iter().any(|header| header.eq_ignore_ascii_case(name))
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ProxyConfig {
+    #[serde(default = "default_listen")]
+    pub listen: String,
+    #[serde(default = "default_path")]
+    pub path: String,
+    // Header set by the authenticating front end; requests without it are
+    // refused
+    #[serde(default)]
+    pub user_header: Option<String>,
+    #[serde(default)]
+    pub rate_limit: RateLimit,
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: usize,
+    pub providers: BTreeMap<String, UpstreamConfig>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub struct RateLimit {
+    pub requests: usize,
+    pub window_secs: u64,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        RateLimit { requests: 30, window_secs: 60 }
+    }
+}
+
+// Where and how to reach one provider
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct UpstreamConfig {
+    #[serde(default)]
+    pub api_key: Option<String>,
+    // Name of the environment variable holding the key, to keep it out of
+    // the config file
+    #[serde(default)]
+    pub api_key_env: Option<String>,
+    // For the custom OpenAI-compatible provider
+    #[serde(default)]
+    pub base_url: String,
+    // Replaces the vendor's endpoint, e.g. for a gateway or a mock
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+fn default_listen() -> String {
+    "127.0.0.1:8790".to_string()
+}
+
+fn default_path() -> String {
+    DEFAULT_PATH.to_string()
+}
+
+fn default_max_body_bytes() -> usize {
+    1024 * 1024
+}
+
+// The endpoint and headers, key included, for forwarding to a provider
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Upstream {
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl ProxyConfig {
+    pub fn from_json(json: &str) -> Result<ProxyConfig, String> {
+        let config: ProxyConfig = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        if !config.path.starts_with('/') {
+            return Err(format!("path must start with '/': {}", config.path));
+        }
+        if config.rate_limit.requests == 0 || config.rate_limit.window_secs == 0 {
+            return Err("rate_limit.requests and rate_limit.window_secs must be positive".to_string());
+        }
+        for id in config.providers.keys() {
+            if !providers::PROVIDERS.iter().any(|provider| provider.id() == id) {
+                return Err(format!("unknown provider '{}'", id));
+            }
+        }
+        Ok(config)
+    }
+
+    // Where to forward requests for `provider_id`, if it is configured
+    pub fn upstream(&self, provider_id: &str) -> Option<Upstream> {
+        let config = self.providers.get(provider_id)?;
+        let provider = providers::PROVIDERS.iter().find(|provider| provider.id() == provider_id)?;
+        let settings = ProviderSettings {
+            api_key: config.key(),
+            base_url: config.base_url.clone(),
+            ..ProviderSettings::default()
+        };
+        let request = provider.build_request(&settings, &[], false);
+        let headers = request.headers.into_iter()
+            .filter(|(name, value)| !(is_credential(name) && value.is_empty()))
+            .collect();
+        Some(Upstream { url: config.url.clone().unwrap_or(request.url), headers })
+    }
+}
+
+impl UpstreamConfig {
+    pub fn key(&self) -> String {
+        match (&self.api_key, &self.api_key_env) {
+            (Some(key), _) => key.clone(),
+            (None, Some(name)) => std::env::var(name).unwrap_or_default(),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+// At most `limit.requests` per user in any `limit.window_secs` window
+pub struct RateLimiter {
+    limit: RateLimit,
+    recent: HashMap<String, VecDeque<Instant>>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        RateLimiter { limit, recent: HashMap::new() }
+    }
+
+    // Count a request from `user` at `now`, or say how long until the
+    // oldest one in the window expires
+    pub fn check(&mut self, user: &str, now: Instant) -> Result<(), Duration> {
+        let window = Duration::from_secs(self.limit.window_secs);
+        // Forget users with nothing left in the window
+        self.recent.retain(|_, times| {
+            while times.front().is_some_and(|&time| now.saturating_duration_since(time) >= window) {
+                times.pop_front();
+            }
+            !times.is_empty()
+        });
+
+        let times = self.recent.entry(user.to_string()).or_default();
+        if times.len() >= self.limit.requests {
+            let oldest = times[0];
+            return Err(window.saturating_sub(now.saturating_duration_since(oldest)));
+        }
+        times.push_back(now);
+        Ok(())
+    }
+}
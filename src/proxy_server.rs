@@ -0,0 +1,203 @@
+// The LLM proxy server
+//
+// Serves POST {path}/{provider_id} as described in proxy.rs. Each request is
+// handled on its own thread, so a long streamed reply does not hold up other
+// users. Upstream replies are relayed as they arrive, chunk by chunk, with
+// the upstream status and content type.
+
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+
+use crate::proxy::{ProxyConfig, RateLimiter};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const RELAY_BUFFER_BYTES: usize = 8 * 1024;
+
+pub struct ProxyServer {
+    addr: SocketAddr,
+    path: String,
+    server: Arc<Server>,
+    thread: Option<JoinHandle<()>>,
+}
+
+struct Shared {
+    config: ProxyConfig,
+    limiter: Mutex<RateLimiter>,
+    agent: ureq::Agent,
+}
+
+impl ProxyServer {
+    // Start serving on `config.listen`; use port 0 to pick a free port
+    pub fn start(config: ProxyConfig) -> io::Result<Self> {
+        let server = Server::http(&config.listen).map_err(io::Error::other)?;
+        let addr = server.server_addr().to_ip()
+            .ok_or_else(|| io::Error::other("LLM proxy must listen on an IP address"))?;
+        let server = Arc::new(server);
+        let path = config.path.trim_end_matches('/').to_string();
+
+        let shared = Arc::new(Shared {
+            limiter: Mutex::new(RateLimiter::new(config.rate_limit)),
+            agent: ureq::AgentBuilder::new().timeout_connect(CONNECT_TIMEOUT).build(),
+            config,
+        });
+        let thread = {
+            let server = server.clone();
+            thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    let shared = shared.clone();
+                    thread::spawn(move || {
+                        if let Err(e) = serve(request, &shared) {
+                            eprintln!("LLM proxy: {}", e);
+                        }
+                    });
+                }
+            })
+        };
+
+        Ok(ProxyServer { addr, path, server, thread: Some(thread) })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    // URL to put in the "LLM Proxy URL" setting when the app is served
+    // from the same address
+    pub fn url(&self) -> String {
+        format!("http://{}{}", self.addr, self.path)
+    }
+
+    // Block until the server thread exits
+    pub fn join(mut self) {
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for ProxyServer {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap()
+}
+
+fn request_header(request: &Request, name: &str) -> Option<String> {
+    request.headers().iter()
+        .find(|h| h.field.as_str().as_str().eq_ignore_ascii_case(name))
+        .map(|h| h.value.to_string())
+}
+
+fn error(request: Request, status: u16, message: &str) -> io::Result<()> {
+    let body = json!({"error": {"message": message, "type": "proxy_error"}});
+    let response = Response::from_data(body.to_string().into_bytes())
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"));
+    request.respond(response)
+}
+
+fn serve(mut request: Request, shared: &Shared) -> io::Result<()> {
+    let config = &shared.config;
+    let url = request.url().split('?').next().unwrap_or_default().to_string();
+    let provider_id = url.strip_prefix(config.path.trim_end_matches('/'))
+        .and_then(|rest| rest.strip_prefix('/'))
+        .filter(|id| !id.is_empty() && !id.contains('/'));
+    let Some(provider_id) = provider_id.map(str::to_string) else {
+        return error(request, 404, &format!("No route for {}", url));
+    };
+    if request.method() != &Method::Post {
+        return error(request, 405, "POST a provider request");
+    }
+
+    let user = match &config.user_header {
+        Some(name) => match request_header(&request, name) {
+            Some(user) if !user.trim().is_empty() => user.trim().to_string(),
+            _ => return error(request, 401, "Not signed in"),
+        },
+        None => request.remote_addr().map(|addr| addr.ip().to_string()).unwrap_or_default(),
+    };
+
+    let Some(upstream) = config.upstream(&provider_id) else {
+        return error(request, 404, &format!("Provider '{}' is not available", provider_id));
+    };
+
+    let allowed = shared.limiter.lock().unwrap().check(&user, Instant::now());
+    if let Err(retry_after) = allowed {
+        let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+        let body = json!({"error": {"message": "Too many requests, try again later", "type": "rate_limited"}});
+        let response = Response::from_data(body.to_string().into_bytes())
+            .with_status_code(429)
+            .with_header(header("Content-Type", "application/json"))
+            .with_header(header("Retry-After", &seconds.to_string()));
+        return request.respond(response);
+    }
+
+    let mut body = Vec::new();
+    request.as_reader().take(config.max_body_bytes as u64 + 1).read_to_end(&mut body)?;
+    if body.len() > config.max_body_bytes {
+        return error(request, 413, "Request body is too large");
+    }
+    if let Err(e) = serde_json::from_slice::<serde_json::Value>(&body) {
+        return error(request, 400, &format!("Invalid JSON body: {}", e));
+    }
+
+    // Only the proxy's own headers go upstream; whatever the browser sent,
+    // credentials included, stays here
+    let mut call = shared.agent.post(&upstream.url);
+    for (name, value) in &upstream.headers {
+        call = call.set(name, value);
+    }
+    match call.send_bytes(&body) {
+        Ok(response) | Err(ureq::Error::Status(_, response)) => relay(request, response),
+        Err(e) => error(request, 502, &format!("Could not reach {}: {}", provider_id, e)),
+    }
+}
+
+// Pass the upstream reply on as it arrives. tiny_http's own chunked writer
+// buffers several kilobytes, which would hold back streamed tokens, so the
+// response is written to the socket directly.
+fn relay(request: Request, upstream: ureq::Response) -> io::Result<()> {
+    let status = upstream.status();
+    let content_type = upstream.header("content-type").unwrap_or("application/json").to_string();
+    let retry_after = upstream.header("retry-after").map(str::to_string);
+
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nCache-Control: no-cache\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n",
+        status, StatusCode(status).default_reason_phrase(), content_type,
+    );
+    if let Some(retry_after) = retry_after {
+        head.push_str(&format!("Retry-After: {}\r\n", retry_after));
+    }
+    head.push_str("\r\n");
+
+    let mut reader = upstream.into_reader();
+    let mut writer = request.into_writer();
+    writer.write_all(head.as_bytes())?;
+    writer.flush()?;
+
+    let mut buffer = [0u8; RELAY_BUFFER_BYTES];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        write!(writer, "{:x}\r\n", read)?;
+        writer.write_all(&buffer[..read])?;
+        writer.write_all(b"\r\n")?;
+        writer.flush()?;
+    }
+    writer.write_all(b"0\r\n\r\n")?;
+    writer.flush()
+}
@@ -0,0 +1,71 @@
+// Section-scoped rewrites
+//
+// Long reports and books are revised one section at a time: only the chosen
+// section is sent to the model, with a summary of the rest of the document so
+// that it keeps to the same structure, labels and packages. The rewritten
+// section replaces the old one in place and the rest of the source is left
+// byte for byte as it was.
+
+use std::ops::Range;
+
+use crate::doctree::{DocumentTree, Section};
+
+// Most labels listed in the summary
+const MAX_SUMMARY_LABELS: usize = 40;
+
+// The section at `index` in `tree`, or the first section with its title if
+// the sections have moved since it was picked
+pub fn find_section<'a>(tree: &'a DocumentTree, index: usize, title: &str) -> Option<(usize, &'a Section)> {
+    match tree.sections.get(index) {
+        Some(section) if section.title == title => Some((index, section)),
+        _ => tree.sections.iter().enumerate().find(|(_, section)| section.title == title),
+    }
+}
+
+// What the model needs to know about the document around section `index`:
+// its class, packages and title, the section headings with the one being
+// rewritten marked, and the labels defined outside that section
+pub fn document_summary(tree: &DocumentTree, index: usize) -> String {
+    let mut summary = String::new();
+    if let Some(class) = &tree.class {
+        summary.push_str(&format!("Document class: {}\n", class.name));
+    }
+    if !tree.packages.is_empty() {
+        let packages: Vec<&str> = tree.packages.iter().map(|package| package.name.as_str()).collect();
+        summary.push_str(&format!("Packages: {}\n", packages.join(", ")));
+    }
+    if let Some(title) = &tree.title {
+        summary.push_str(&format!("Title: {}\n", title));
+    }
+
+    summary.push_str("Structure:\n");
+    let top = tree.sections.iter().map(|section| section.level).min().unwrap_or(0);
+    for (i, section) in tree.sections.iter().enumerate() {
+        summary.push_str(&"  ".repeat(usize::from(section.level - top) + 1));
+        summary.push_str(&format!("\\{}{{{}}}", section.command, section.title));
+        if i == index {
+            summary.push_str("  <- the section to rewrite");
+        }
+        summary.push('\n');
+    }
+
+    let range = tree.sections.get(index).map_or(0..0, |section| section.range.clone());
+    let labels: Vec<&str> = tree.labels.iter()
+        .filter(|label| !range.contains(&label.position.offset))
+        .map(|label| label.name.as_str())
+        .take(MAX_SUMMARY_LABELS)
+        .collect();
+    if !labels.is_empty() {
+        summary.push_str(&format!("Labels defined elsewhere: {}\n", labels.join(", ")));
+    }
+    summary
+}
+
+// `latex` with the section at `range` replaced by `section`, keeping the
+// whitespace that separated the old section from what follows
+pub fn splice(latex: &str, range: Range<usize>, section: &str) -> String {
+    let old = &latex[range.clone()];
+    let trailing = &old[old.trim_end().len()..];
+    let trailing = if trailing.is_empty() { "\n" } else { trailing };
+    format!("{}{}{}{}", &latex[..range.start], section.trim_end(), trailing, &latex[range.end..])
+}
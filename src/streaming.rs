@@ -0,0 +1,156 @@
+// Server-sent event streams from the AI providers
+//
+// With `"stream": true` the providers answer with `text/event-stream`: blank
+// line separated events made of `event:` and `data:` fields. Claude's
+// messages API sends typed JSON events and the text arrives in
+// `content_block_delta` events; the OpenAI-style `chat/completions` APIs
+// (Mistral, Perplexity) send `choices[0].delta.content` chunks and finish
+// with `data: [DONE]`.
+
+use serde_json::Value;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamFormat {
+    // Claude messages API
+    Anthropic,
+    // OpenAI-style chat completions
+    OpenAi,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SseEvent {
+    pub event: Option<String>,
+    pub data: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StreamUpdate {
+    Text(String),
+    Done,
+    Error(String),
+    // Pings, metadata and other events without text
+    Ignore,
+}
+
+// Splits a byte stream into events; chunks may end anywhere, including
+// inside a UTF-8 character
+#[derive(Default)]
+pub struct SseParser {
+    bytes: Vec<u8>,
+    text: String,
+    event: SseEvent,
+    has_data: bool,
+}
+
+impl SseParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Feed the next chunk of the response body and return the events it
+    // completed
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.bytes.extend_from_slice(chunk);
+        // Keep an incomplete character at the end for the next chunk
+        let valid = match std::str::from_utf8(&self.bytes) {
+            Ok(text) => text.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => self.bytes.len(),
+        };
+        let text = String::from_utf8_lossy(&self.bytes[..valid]).into_owned();
+        self.bytes.drain(..valid);
+        self.text.push_str(&text);
+        self.take_events()
+    }
+
+    // The event left over when the stream ends without a final blank line
+    pub fn finish(&mut self) -> Option<SseEvent> {
+        let mut rest = std::mem::take(&mut self.text);
+        rest.push_str(&String::from_utf8_lossy(&std::mem::take(&mut self.bytes)));
+
+        let mut events = Vec::new();
+        self.process_line(rest.trim_end_matches('\r'), &mut events);
+        events.extend(self.dispatch());
+        events.pop()
+    }
+
+    fn take_events(&mut self) -> Vec<SseEvent> {
+        let mut events = Vec::new();
+        // Only complete lines; the rest waits for the next chunk
+        while let Some(end) = self.text.find('\n') {
+            let line: String = self.text.drain(..=end).collect();
+            self.process_line(line.trim_end_matches('\n').trim_end_matches('\r'), &mut events);
+        }
+        events
+    }
+
+    fn process_line(&mut self, line: &str, events: &mut Vec<SseEvent>) {
+        if line.is_empty() {
+            events.extend(self.dispatch());
+            return;
+        }
+        if line.starts_with(':') {
+            return;
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event.event = Some(value.to_string()),
+            "data" => {
+                if self.has_data {
+                    self.event.data.push('\n');
+                }
+                self.event.data.push_str(value);
+                self.has_data = true;
+            },
+            _ => {}
+        }
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = std::mem::take(&mut self.event);
+        let has_data = std::mem::take(&mut self.has_data);
+        has_data.then_some(event)
+    }
+}
+
+// What a provider event means for the reply
+pub fn decode_event(format: StreamFormat, event: &SseEvent) -> StreamUpdate {
+    let data = event.data.trim();
+    if format == StreamFormat::OpenAi && data == "[DONE]" {
+        return StreamUpdate::Done;
+    }
+
+    let json: Value = match serde_json::from_str(data) {
+        Ok(json) => json,
+        Err(_) => return StreamUpdate::Ignore,
+    };
+
+    if let Some(error) = json.get("error") {
+        let message = error.get("message").and_then(Value::as_str)
+            .map(str::to_string)
+            .unwrap_or_else(|| error.to_string());
+        return StreamUpdate::Error(message);
+    }
+
+    match format {
+        StreamFormat::Anthropic => match json.get("type").and_then(Value::as_str) {
+            Some("content_block_delta") => json.pointer("/delta/text")
+                .and_then(Value::as_str)
+                .map_or(StreamUpdate::Ignore, |text| StreamUpdate::Text(text.to_string())),
+            Some("message_stop") => StreamUpdate::Done,
+            _ => StreamUpdate::Ignore,
+        },
+        StreamFormat::OpenAi => {
+            let choice = json.pointer("/choices/0");
+            let text = choice.and_then(|c| c.pointer("/delta/content")).and_then(Value::as_str);
+            match text {
+                Some(text) if !text.is_empty() => StreamUpdate::Text(text.to_string()),
+                _ => StreamUpdate::Ignore,
+            }
+        },
+    }
+}
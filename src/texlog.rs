@@ -0,0 +1,398 @@
+// TeX log parser
+//
+// Pulls errors and warnings out of pdfTeX/LaTeX logs: `! ...` errors with
+// their `l.<n>` context, -file-line-error style `file:line: message` errors,
+// LaTeX and package warnings, undefined references and citations, missing
+// files and packages, and over/underfull boxes. It also reads the page count
+// of the output.
+
+use serde::{Deserialize, Serialize};
+
+// TeX wraps log lines at this many characters
+const MAX_LINE_LENGTH: usize = 79;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticKind {
+    Error,
+    Warning,
+    UndefinedReference,
+    UndefinedCitation,
+    MissingPackage,
+    MissingFile,
+    OverfullBox,
+    UnderfullBox,
+    // Found by `lint` in the source rather than by the compiler
+    Lint,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    // Only known for lint issues; TeX logs report lines
+    #[serde(default)]
+    pub column: Option<usize>,
+    pub severity: Severity,
+    pub kind: DiagnosticKind,
+    pub message: String,
+    pub context: Option<String>,
+}
+
+impl Severity {
+    pub fn label(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        }
+    }
+}
+
+impl Diagnostic {
+    // Whether this diagnostic points into the main document rather than a
+    // package or class file
+    pub fn is_in_main_file(&self) -> bool {
+        match &self.file {
+            None => true,
+            Some(file) => file.trim_start_matches("./") == "main.tex",
+        }
+    }
+
+    // "main.tex:12" style location, if known
+    pub fn location(&self) -> Option<String> {
+        let file = self.file.as_deref().map(|f| f.trim_start_matches("./"));
+        let line = self.line.map(|line| match self.column {
+            Some(column) => format!("{}:{}", line, column),
+            None => line.to_string(),
+        });
+        match (file, line) {
+            (Some(file), Some(line)) => Some(format!("{}:{}", file, line)),
+            (None, Some(line)) => Some(format!("line {}", line)),
+            (Some(file), None) => Some(file.to_string()),
+            (None, None) => None,
+        }
+    }
+}
+
+// Parse a complete TeX log into diagnostics, errors first
+pub fn parse_log(log: &str) -> Vec<Diagnostic> {
+    let lines = unwrap_lines(log);
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    let mut files: Vec<Option<String>> = Vec::new();
+
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i].as_str();
+        let current_file = files.iter().rev().flatten().next().cloned();
+
+        if let Some(message) = line.strip_prefix("! ") {
+            if !message.starts_with("==>") {
+                let (line_no, context, consumed) = find_error_context(&lines[i + 1..]);
+                let mut diagnostic = error_diagnostic(message, current_file.clone());
+                diagnostic.line = diagnostic.line.or(line_no);
+                diagnostic.context = context;
+
+                // A fatal error right after another one (e.g. a missing file in
+                // batch mode) shows the location of the first
+                if diagnostic.message == "Emergency stop." {
+                    if let Some(previous) = diagnostics.last_mut().filter(|d| d.line.is_none() && d.severity == Severity::Error) {
+                        previous.line = diagnostic.line;
+                        previous.context = diagnostic.context.clone();
+                    }
+                }
+
+                diagnostics.push(diagnostic);
+                i += 1 + consumed;
+                continue;
+            }
+        } else if let Some(diagnostic) = parse_file_line_error(line) {
+            diagnostics.push(diagnostic);
+        } else if let Some((diagnostic, consumed)) = parse_box(line, lines.get(i + 1).map(String::as_str), current_file.clone()) {
+            diagnostics.push(diagnostic);
+            i += consumed;
+            continue;
+        } else if let Some((diagnostic, consumed)) = parse_warning(&lines[i..], current_file.clone()) {
+            diagnostics.push(diagnostic);
+            i += consumed;
+            continue;
+        } else if line.starts_with("*** (job aborted") {
+            diagnostics.push(Diagnostic {
+                file: current_file.clone(),
+                line: None,
+                column: None,
+                severity: Severity::Error,
+                kind: DiagnosticKind::Error,
+                message: line.trim_start_matches("*** ").trim_matches(|c| c == '(' || c == ')').to_string(),
+                context: None,
+            });
+        } else {
+            track_files(line, &mut files);
+        }
+
+        i += 1;
+    }
+
+    let mut unique: Vec<Diagnostic> = Vec::new();
+    for diagnostic in diagnostics {
+        if !unique.contains(&diagnostic) {
+            unique.push(diagnostic);
+        }
+    }
+    unique.sort_by_key(|d| d.severity);
+    unique
+}
+
+// Rejoin lines TeX hard-wrapped at 79 characters
+fn unwrap_lines(log: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut continues = false;
+
+    for line in log.lines() {
+        if continues {
+            lines.last_mut().unwrap().push_str(line);
+        } else {
+            lines.push(line.to_string());
+        }
+        continues = line.chars().count() == MAX_LINE_LENGTH;
+    }
+
+    lines
+}
+
+// Follow TeX's "(./file.tex" ... ")" file nesting on an ordinary log line
+fn track_files(line: &str, files: &mut Vec<Option<String>>) {
+    let mut chars = line.char_indices();
+
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '(' => {
+                let rest = &line[index + 1..];
+                let end = rest.find(|c: char| c.is_whitespace() || c == '(' || c == ')').unwrap_or(rest.len());
+                let name = &rest[..end];
+                if looks_like_file(name) {
+                    files.push(Some(name.to_string()));
+                    for _ in 0..name.chars().count() {
+                        chars.next();
+                    }
+                } else {
+                    files.push(None);
+                }
+            },
+            ')' => {
+                files.pop();
+            },
+            _ => {}
+        }
+    }
+}
+
+fn looks_like_file(name: &str) -> bool {
+    let has_extension = name.rsplit_once('.')
+        .is_some_and(|(stem, ext)| !stem.is_empty() && !ext.is_empty() && ext.chars().all(|c| c.is_ascii_alphanumeric()));
+    has_extension && (name.starts_with("./") || name.starts_with('/') || name.starts_with("../") || !name.contains(':'))
+}
+
+// Look for the "l.<n> <context>" line that follows an error
+fn find_error_context(lines: &[String]) -> (Option<usize>, Option<String>, usize) {
+    for (offset, line) in lines.iter().enumerate().take(12) {
+        if line.starts_with("! ") {
+            break;
+        }
+        if let Some(rest) = line.strip_prefix("l.") {
+            let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+            if let Ok(line_no) = digits.parse::<usize>() {
+                let mut context = rest[digits.len()..].trim().to_string();
+                let mut consumed = offset + 1;
+                // TeX breaks the context line where the error was detected;
+                // keep the break, with the rest indented under the error point
+                if let Some(next) = lines.get(offset + 1) {
+                    let next = next.trim();
+                    if !next.is_empty() && !next.starts_with('!') {
+                        context = format!("{}\n{}{}", context, " ".repeat(context.chars().count()), next);
+                        consumed += 1;
+                    }
+                }
+                let context = if context.is_empty() { None } else { Some(context) };
+                return (Some(line_no), context, consumed);
+            }
+        }
+    }
+    (None, None, 0)
+}
+
+fn error_diagnostic(message: &str, file: Option<String>) -> Diagnostic {
+    let message = message.trim().to_string();
+    let kind = match quoted_name(&message) {
+        Some(name) if message.contains("not found") => {
+            if name.ends_with(".sty") || name.ends_with(".cls") {
+                DiagnosticKind::MissingPackage
+            } else {
+                DiagnosticKind::MissingFile
+            }
+        },
+        _ => DiagnosticKind::Error,
+    };
+
+    Diagnostic {
+        file,
+        line: input_line(&message),
+        column: None,
+        severity: Severity::Error,
+        kind,
+        message,
+        context: None,
+    }
+}
+
+// "./main.tex:12: Undefined control sequence."
+fn parse_file_line_error(line: &str) -> Option<Diagnostic> {
+    let mut parts = line.splitn(3, ':');
+    let file = parts.next()?;
+    let line_no = parts.next()?.parse::<usize>().ok()?;
+    let message = parts.next()?.trim();
+
+    if !looks_like_file(file) || message.is_empty() {
+        return None;
+    }
+
+    let mut diagnostic = error_diagnostic(message, Some(file.to_string()));
+    diagnostic.line = Some(line_no);
+    Some(diagnostic)
+}
+
+// "Overfull \hbox (12.0pt too wide) in paragraph at lines 10--12"; returns
+// the diagnostic and how many lines it used
+fn parse_box(line: &str, next: Option<&str>, file: Option<String>) -> Option<(Diagnostic, usize)> {
+    let (kind, severity) = if line.starts_with("Overfull \\") {
+        (DiagnosticKind::OverfullBox, Severity::Warning)
+    } else if line.starts_with("Underfull \\") {
+        (DiagnosticKind::UnderfullBox, Severity::Info)
+    } else {
+        return None;
+    };
+
+    let line_no = line.split_once(" at lines ")
+        .or_else(|| line.split_once(" at line "))
+        .and_then(|(_, rest)| rest.split("--").next())
+        .and_then(|n| n.trim().parse::<usize>().ok());
+
+    // The offending box content is printed on the following line
+    let context = next
+        .map(str::trim)
+        .filter(|n| {
+            let page_marker = n.starts_with('[') && n[1..].starts_with(|c: char| c.is_ascii_digit());
+            !n.is_empty() && !page_marker && !n.starts_with('(')
+        })
+        .map(str::to_string);
+    let consumed = if context.is_some() { 2 } else { 1 };
+
+    Some((Diagnostic {
+        file,
+        line: line_no,
+        column: None,
+        severity,
+        kind,
+        message: line.trim().to_string(),
+        context,
+    }, consumed))
+}
+
+// "LaTeX Warning: ..." and "Package foo Warning: ..." possibly spanning
+// several lines; returns the diagnostic and how many lines it used
+fn parse_warning(lines: &[String], file: Option<String>) -> Option<(Diagnostic, usize)> {
+    let first = lines[0].as_str();
+    let (prefix_end, package) = if let Some(index) = first.find("LaTeX Warning: ") {
+        (index + "LaTeX Warning: ".len(), None)
+    } else if first.starts_with("Package ") || first.starts_with("Class ") {
+        let index = first.find(" Warning: ")?;
+        let name = first.split_whitespace().nth(1)?.to_string();
+        (index + " Warning: ".len(), Some(name))
+    } else if let Some(index) = first.find("LaTeX Font Warning: ") {
+        (index + "LaTeX Font Warning: ".len(), None)
+    } else {
+        return None;
+    };
+
+    let mut message = first[prefix_end..].trim().to_string();
+    let mut consumed = 1;
+
+    // Continuation lines are indented, or prefixed with "(package)"
+    let continuation_prefix = package.as_ref().map(|p| format!("({})", p));
+    for line in &lines[1..] {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            break;
+        }
+        let continued = match &continuation_prefix {
+            Some(prefix) => line.starts_with(prefix.as_str()),
+            None => line.starts_with(' '),
+        };
+        if !continued {
+            break;
+        }
+        let text = match &continuation_prefix {
+            Some(prefix) => line[prefix.len()..].trim(),
+            None => trimmed,
+        };
+        message.push(' ');
+        message.push_str(text);
+        consumed += 1;
+    }
+
+    let kind = if message.starts_with("Reference `") || message.starts_with("There were undefined references") {
+        DiagnosticKind::UndefinedReference
+    } else if message.starts_with("Citation `") || message.starts_with("There were undefined citations") {
+        DiagnosticKind::UndefinedCitation
+    } else {
+        DiagnosticKind::Warning
+    };
+
+    let severity = if first.contains("Font Warning") { Severity::Info } else { Severity::Warning };
+    let message = match package {
+        Some(package) => format!("{}: {}", package, message),
+        None => message,
+    };
+
+    Some((Diagnostic {
+        file,
+        line: input_line(&message),
+        column: None,
+        severity,
+        kind,
+        message,
+        context: None,
+    }, consumed))
+}
+
+// Number of pages in the PDF, from "Output written on main.pdf (3 pages,
+// 12345 bytes)."
+pub fn page_count(log: &str) -> Option<usize> {
+    let start = log.rfind("Output written on ")?;
+    // The line may have been wrapped anywhere
+    let sentence: String = log[start..].chars().filter(|&c| c != '\n' && c != '\r').take_while(|&c| c != ')').collect();
+    let (_, counts) = sentence.rsplit_once('(')?;
+    let (pages, _) = counts.split_once(" page")?;
+    pages.trim().parse().ok()
+}
+
+// "... on input line 42."
+fn input_line(message: &str) -> Option<usize> {
+    let (_, rest) = message.rsplit_once("input line ")?;
+    rest.chars().take_while(|c| c.is_ascii_digit()).collect::<String>().parse().ok()
+}
+
+// The name in "File `foo.sty' not found"
+fn quoted_name(message: &str) -> Option<&str> {
+    let start = message.find('`')? + 1;
+    let end = message[start..].find('\'')? + start;
+    Some(&message[start..end])
+}
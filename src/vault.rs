@@ -0,0 +1,160 @@
+// Passphrase-protected API key vault
+//
+// Instead of one plaintext localStorage item per provider, the keys can be
+// kept as a single sealed record: a 256-bit key is derived from the
+// passphrase with PBKDF2-HMAC-SHA256 and the keys, as JSON, are encrypted
+// with AES-256-GCM. The salt, nonce and iteration count are stored with the
+// ciphertext. The caller supplies the random salt and nonce, which the
+// browser's crypto API provides.
+//
+// Stored as:
+//
+//     { "version": 1, "iterations": 600000, "salt": "<base64>",
+//       "nonce": "<base64>", "ciphertext": "<base64>" }
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+pub const VERSION: u32 = 1;
+// OWASP's recommendation for PBKDF2-HMAC-SHA256
+pub const ITERATIONS: u32 = 600_000;
+// The range a stored vault's iteration count must be in: fewer would make the
+// passphrase cheap to guess, more would hang the page while it derives a key
+pub const MIN_ITERATIONS: u32 = 100_000;
+pub const MAX_ITERATIONS: u32 = 10_000_000;
+pub const SALT_LEN: usize = 16;
+pub const NONCE_LEN: usize = 12;
+
+// Ties the ciphertext to this use, so a blob sealed for something else
+// never opens as a vault
+const ASSOCIATED_DATA: &[u8] = b"ladfx api key vault v1";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Vault {
+    pub version: u32,
+    pub iterations: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VaultError {
+    EmptyPassphrase,
+    // The passphrase is wrong or the vault was tampered with; AES-GCM
+    // cannot tell the two apart
+    WrongPassphrase,
+    UnsupportedVersion(u32),
+    // An iteration count outside `MIN_ITERATIONS..=MAX_ITERATIONS`
+    BadIterations(u32),
+    Malformed(String),
+}
+
+impl fmt::Display for VaultError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VaultError::EmptyPassphrase => write!(f, "the passphrase is empty"),
+            VaultError::WrongPassphrase => write!(f, "wrong passphrase"),
+            VaultError::UnsupportedVersion(version) => write!(f, "unsupported vault version {}", version),
+            VaultError::BadIterations(iterations) => write!(
+                f,
+                "{} iterations is outside the allowed {} to {}",
+                iterations, MIN_ITERATIONS, MAX_ITERATIONS
+            ),
+            VaultError::Malformed(reason) => write!(f, "the saved vault is damaged: {}", reason),
+        }
+    }
+}
+
+impl Vault {
+    // Encrypt `keys`, by provider id, under `passphrase`
+    pub fn seal(
+        keys: &BTreeMap<String, String>,
+        passphrase: &str,
+        salt: [u8; SALT_LEN],
+        nonce: [u8; NONCE_LEN],
+        iterations: u32,
+    ) -> Result<Vault, VaultError> {
+        if passphrase.is_empty() {
+            return Err(VaultError::EmptyPassphrase);
+        }
+        check_iterations(iterations)?;
+        let plaintext = serde_json::to_vec(keys).map_err(|e| VaultError::Malformed(e.to_string()))?;
+        let cipher = cipher(passphrase, &salt, iterations);
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: &plaintext, aad: ASSOCIATED_DATA })
+            .map_err(|_| VaultError::Malformed("encryption failed".to_string()))?;
+
+        Ok(Vault {
+            version: VERSION,
+            iterations,
+            salt: BASE64.encode(salt),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        })
+    }
+
+    // The keys, by provider id
+    pub fn open(&self, passphrase: &str) -> Result<BTreeMap<String, String>, VaultError> {
+        if self.version != VERSION {
+            return Err(VaultError::UnsupportedVersion(self.version));
+        }
+        if passphrase.is_empty() {
+            return Err(VaultError::EmptyPassphrase);
+        }
+        check_iterations(self.iterations)?;
+        let decode = |field: &str, value: &str| BASE64.decode(value)
+            .map_err(|e| VaultError::Malformed(format!("{}: {}", field, e)));
+        let salt = decode("salt", &self.salt)?;
+        let nonce = decode("nonce", &self.nonce)?;
+        let ciphertext = decode("ciphertext", &self.ciphertext)?;
+        if nonce.len() != NONCE_LEN {
+            return Err(VaultError::Malformed(format!("nonce: expected {} bytes", NONCE_LEN)));
+        }
+
+        let plaintext = cipher(passphrase, &salt, self.iterations)
+            .decrypt(Nonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: ASSOCIATED_DATA })
+            .map_err(|_| VaultError::WrongPassphrase)?;
+        serde_json::from_slice(&plaintext).map_err(|e| VaultError::Malformed(e.to_string()))
+    }
+
+    // The keys to seal when saving over this vault while it is locked: its
+    // own keys, opened with `passphrase`, with the non-empty `keys` typed
+    // since replacing them. Fails rather than dropping keys it cannot open.
+    pub fn merged_keys(&self, passphrase: &str, keys: &[(String, String)]) -> Result<BTreeMap<String, String>, VaultError> {
+        let mut merged = self.open(passphrase)?;
+        merged.extend(keys.iter().filter(|(_, key)| !key.is_empty()).cloned());
+        Ok(merged)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn from_json(json: &str) -> Result<Vault, VaultError> {
+        serde_json::from_str(json).map_err(|e| VaultError::Malformed(e.to_string()))
+    }
+}
+
+fn check_iterations(iterations: u32) -> Result<(), VaultError> {
+    if (MIN_ITERATIONS..=MAX_ITERATIONS).contains(&iterations) {
+        Ok(())
+    } else {
+        Err(VaultError::BadIterations(iterations))
+    }
+}
+
+fn cipher(passphrase: &str, salt: &[u8], iterations: u32) -> Aes256Gcm {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, iterations, &mut key);
+    let cipher = Aes256Gcm::new(&key.into());
+    key.fill(0);
+    cipher
+}
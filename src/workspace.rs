@@ -0,0 +1,151 @@
+// Workspace export and import
+//
+// The whole workspace goes in one JSON file, to back it up or move it to
+// another browser:
+//
+//     { "format": "ladfx-workspace", "version": 1, "exported": <ms>,
+//       "conversations": [<history::Conversation>, ...],
+//       "settings": { "<localStorage key>": "<value>", ... } }
+//
+// Conversations carry their turns, every revision and the template they are
+// written in; the templates themselves are built in. Settings are the
+// localStorage items `is_setting` accepts. API keys and the key vault never
+// go in a workspace file, and are dropped from one that has them.
+//
+// Importing merges by id: a conversation already here is replaced only by a
+// copy updated more recently, and imported settings replace the ones here.
+// Settings that say where requests are sent (`is_endpoint`) are applied only
+// once the user has seen the new addresses and agreed.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::history::Conversation;
+use crate::providers;
+
+pub const FORMAT: &str = "ladfx-workspace";
+// Bump when a change to the file would be misread by older versions
+pub const VERSION: u32 = 1;
+
+// Settings kept in localStorage under fixed names
+const SETTINGS: [&str; 9] = [
+    "api_provider",
+    "compiler_backend",
+    "compile_service_url",
+    "llm_proxy_url",
+    "edit_mode",
+    "auto_fix",
+    "auto_fix_rounds",
+    "lint_in_prompt",
+    "theme",
+];
+
+// Settings holding the address of a server the app sends documents or
+// prompts to
+const ENDPOINTS: [&str; 2] = ["compile_service_url", "llm_proxy_url"];
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Workspace {
+    pub format: String,
+    pub version: u32,
+    // Milliseconds since the Unix epoch
+    pub exported: f64,
+    pub conversations: Vec<Conversation>,
+    #[serde(default)]
+    pub settings: BTreeMap<String, String>,
+}
+
+// What importing changed
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Merged {
+    // Ids of the conversations that were new here, and of those replaced by
+    // a newer copy
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    // Conversations already here, as recent as the imported copy
+    pub unchanged: usize,
+}
+
+impl Workspace {
+    // A workspace of `conversations` and the settings among the localStorage
+    // `items`
+    pub fn new(exported: f64, conversations: Vec<Conversation>, items: impl IntoIterator<Item = (String, String)>) -> Self {
+        Workspace {
+            format: FORMAT.to_string(),
+            version: VERSION,
+            exported,
+            conversations,
+            settings: items.into_iter().filter(|(key, _)| is_setting(key)).collect(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let value: serde_json::Value = serde_json::from_str(json).map_err(|e| format!("Not a JSON file: {}", e))?;
+        if value.get("format").and_then(|format| format.as_str()) != Some(FORMAT) {
+            return Err("Not a LaDFX workspace file".to_string());
+        }
+        match value.get("version").and_then(|version| version.as_u64()) {
+            Some(version) if version > u64::from(VERSION) => {
+                return Err(format!("The workspace file is version {}, newer than this app reads ({})", version, VERSION));
+            },
+            Some(_) => {},
+            None => return Err("The workspace file has no version".to_string()),
+        }
+
+        let mut workspace: Workspace = serde_json::from_value(value).map_err(|e| format!("Invalid workspace file: {}", e))?;
+        workspace.settings.retain(|key, _| is_setting(key));
+        Ok(workspace)
+    }
+
+    // The endpoint settings this workspace would change, as (key, new value),
+    // given the `current` value of a setting here
+    pub fn changed_endpoints(&self, current: impl Fn(&str) -> Option<String>) -> Vec<(&str, &str)> {
+        self.settings.iter()
+            .filter(|(key, value)| is_endpoint(key) && current(key).as_deref() != Some(value.as_str()))
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect()
+    }
+}
+
+// Whether the localStorage item `key` is a setting that goes in a workspace
+// file. Keys, the vault and anything else stored on the page are not.
+pub fn is_setting(key: &str) -> bool {
+    SETTINGS.contains(&key) || providers::PROVIDERS.iter().any(|provider| {
+        key.strip_prefix(provider.id())
+            .and_then(|rest| rest.strip_prefix('_'))
+            .is_some_and(|name| providers::PROVIDER_SETTINGS.contains(&name))
+    })
+}
+
+// Whether the setting `key` is the address of a compile service, proxy or
+// provider API
+pub fn is_endpoint(key: &str) -> bool {
+    ENDPOINTS.contains(&key) || providers::PROVIDERS.iter().any(|provider| {
+        key.strip_prefix(provider.id()) == Some("_base_url")
+    })
+}
+
+// Merge `imported` into `history` by id, keeping it oldest first
+pub fn merge(history: &mut Vec<Conversation>, imported: Vec<Conversation>) -> Merged {
+    let mut merged = Merged::default();
+    for conversation in imported {
+        match history.iter_mut().find(|existing| existing.id == conversation.id) {
+            Some(existing) if conversation.updated > existing.updated => {
+                merged.updated.push(conversation.id.clone());
+                *existing = conversation;
+            },
+            Some(_) => merged.unchanged += 1,
+            None => {
+                merged.added.push(conversation.id.clone());
+                history.push(conversation);
+            },
+        }
+    }
+    history.sort_by(|a, b| a.created.total_cmp(&b.created));
+    merged
+}
@@ -0,0 +1,130 @@
+// Messages sent to the model: compile error reports and follow-up turns
+
+use wasm_pdf_generator::chat::{self, alternating, conversation, edit_prompt, error_report, fix_prompt, generation_prompt, section_prompt, ChatMessage, Role, SYSTEM_PROMPT};
+use wasm_pdf_generator::compiler::CompileError;
+use wasm_pdf_generator::length::PdfSize;
+use wasm_pdf_generator::texlog::parse_log;
+
+fn compile_error(log: &str) -> CompileError {
+    CompileError { message: "pdflatex exited with status 1".to_string(), log: log.to_string() }
+}
+
+#[test]
+fn reports_errors_with_location_and_context() {
+    let log = "\
+(./main.tex
+LaTeX Warning: Reference `fig:one' on page 1 undefined on input line 4.
+
+! Undefined control sequence.
+l.12 Some text \\foo
+                   bar.
+
+(./chapters/results.tex
+./chapters/results.tex:31: Missing $ inserted.
+)
+! Emergency stop.
+*** (job aborted, no legal \\end found)
+";
+    let err = compile_error(log);
+
+    assert_eq!(error_report(&err, &parse_log(log)), "\
+main.tex:12: Undefined control sequence.
+    Some text \\foo
+                  bar.
+chapters/results.tex:31: Missing $ inserted.
+main.tex: Emergency stop.
+main.tex: job aborted, no legal \\end found
+");
+}
+
+#[test]
+fn reports_at_most_ten_errors() {
+    let log: String = (1..=15).map(|line| format!("./main.tex:{}: Undefined control sequence.\n", line)).collect();
+    let report = error_report(&compile_error(&log), &parse_log(&log));
+
+    assert_eq!(report.lines().count(), 10);
+    assert!(report.ends_with("main.tex:10: Undefined control sequence.\n"));
+}
+
+#[test]
+fn falls_back_to_the_end_of_the_log() {
+    // Warnings only: the reason for the failure is somewhere in the log
+    let mut log = String::from("LaTeX Warning: Label(s) may have changed. Rerun to get cross-references right.\n\n");
+    log.extend((1..=40).map(|line| format!("log line {}\n", line)));
+    let err = compile_error(&log);
+    let report = error_report(&err, &parse_log(&log));
+
+    let tail: Vec<String> = (11..=40).map(|line| format!("log line {}", line)).collect();
+    assert_eq!(report, format!("pdflatex exited with status 1\n\n{}", tail.join("\n")));
+
+    // Nothing at all to go on
+    assert_eq!(error_report(&compile_error("\n  \n"), &[]), "pdflatex exited with status 1");
+}
+
+fn turn(role: &str, text: &str) -> (String, String) {
+    (role.to_string(), text.to_string())
+}
+
+#[test]
+fn sends_earlier_turns_with_documents_replaced() {
+    let length = PdfSize::Medium.target();
+    let history = [
+        turn(chat::USER, "Graph colouring"),
+        turn(chat::AI, "\\documentclass{article} first"),
+        turn(chat::USER, "Add a section on planar graphs"),
+        turn(chat::AI, "\\documentclass{article} second"),
+        turn(chat::FIX_DIFF, "--- before\n+++ after\n"),
+        turn(chat::COMPILE_ERRORS, "main.tex:12: Undefined control sequence."),
+        turn(chat::AI, "\\documentclass{article} third"),
+    ];
+    let messages = conversation(&history, "Article", &length, "Now shorten it".to_string());
+
+    let placeholder = |revision: usize| ChatMessage::assistant(format!(
+        "[Revision {} of the LaTeX document, superseded by the current document in the next message]",
+        revision
+    ));
+    assert_eq!(messages, [
+        ChatMessage::user(generation_prompt("Graph colouring", "Article", &length)),
+        placeholder(1),
+        ChatMessage::user("Add a section on planar graphs"),
+        placeholder(2),
+        ChatMessage::user(fix_prompt("main.tex:12: Undefined control sequence.")),
+        placeholder(3),
+        ChatMessage::user("Now shorten it"),
+    ]);
+    assert_eq!(alternating(&messages), messages);
+}
+
+#[test]
+fn merges_turns_so_roles_alternate_from_the_user() {
+    // An uploaded document starts the history with the model's side
+    let history = [
+        turn(chat::AI, "\\documentclass{article} uploaded"),
+        turn(chat::USER, "Fix the title"),
+    ];
+    let messages = alternating(&conversation(&history, "Article", &PdfSize::Small.target(), "Current document".to_string()));
+
+    assert_eq!(messages, [ChatMessage::user("Fix the title\n\nCurrent document")]);
+
+    let messages = alternating(&[
+        ChatMessage::assistant("a"),
+        ChatMessage::assistant("b"),
+        ChatMessage::user("x"),
+        ChatMessage::assistant("c"),
+        ChatMessage::assistant("d"),
+        ChatMessage::user("y"),
+    ]);
+    let roles: Vec<Role> = messages.iter().map(|m| m.role).collect();
+    assert_eq!(roles, [Role::User, Role::Assistant, Role::User]);
+    assert_eq!(messages[1].content, "c\n\nd");
+}
+
+#[test]
+fn leaves_the_reply_form_to_each_mode() {
+    // Sent with every request, so it must not contradict the edit and
+    // section prompts
+    assert!(!SYSTEM_PROMPT.contains("complete"));
+    assert!(generation_prompt("Knots", "Article", &PdfSize::Small.target()).contains("complete LaTeX document, from \\documentclass to \\end{document}"));
+    assert!(edit_prompt("\\documentclass{article}", "Fix the title").contains("Do not repeat the whole document"));
+    assert!(section_prompt("1. Intro", "\\section{Intro}", "Shorter").contains("Reply only with the rewritten section"));
+}
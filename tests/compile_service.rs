@@ -0,0 +1,133 @@
+// End-to-end checks of the compile service protocol against the mock server
+
+use std::io::Read;
+
+use wasm_pdf_generator::compile_service::{decode_response, CompileRequest, Encoding};
+use wasm_pdf_generator::compiler::{Asset, CompileError, CompileOutput};
+use wasm_pdf_generator::mock_compile_server::MockCompileServer;
+
+const DOCUMENT: &str = r"\documentclass{article}
+\begin{document}
+\section{Intro}\label{sec:intro}
+See Section~\ref{sec:intro}.
+\end{document}
+";
+
+// POST a body the way the browser client does and decode the answer
+fn post(server: &MockCompileServer, body: &str) -> Result<CompileOutput, CompileError> {
+    let response = match ureq::post(&server.url())
+        .set("Content-Type", "application/json")
+        .send_string(body)
+    {
+        Ok(response) => response,
+        Err(ureq::Error::Status(_, response)) => response,
+        Err(e) => panic!("request failed: {}", e),
+    };
+
+    let status = response.status();
+    let content_type = response.content_type().to_string();
+    let mut bytes = Vec::new();
+    response.into_reader().read_to_end(&mut bytes).unwrap();
+
+    decode_response(status, &content_type, &bytes)
+}
+
+fn compile(server: &MockCompileServer, latex: &str, assets: &[Asset]) -> Result<CompileOutput, CompileError> {
+    post(server, &CompileRequest::new(latex, assets).to_json())
+}
+
+#[test]
+fn compiles_valid_document_to_pdf() {
+    let server = MockCompileServer::start("127.0.0.1:0").unwrap();
+
+    let output = compile(&server, DOCUMENT, &[]).unwrap();
+
+    assert!(output.pdf.starts_with(b"%PDF-1.4"));
+    assert!(output.pdf.ends_with(b"%%EOF\n"));
+    assert!(output.log.contains("Output written on main.pdf (1 page)"));
+}
+
+#[test]
+fn reports_unbalanced_environment_with_log() {
+    let server = MockCompileServer::start("127.0.0.1:0").unwrap();
+    let latex = "\\documentclass{article}\n\\begin{document}\n\\begin{itemize}\n\\item one\n\\end{document}\n";
+
+    let err = compile(&server, latex, &[]).err().unwrap();
+
+    assert!(err.message.contains("\\begin{itemize} on input line 3 ended by \\end{document}"));
+    assert!(err.log.contains("l.5 \\end{document}"));
+}
+
+#[test]
+fn reports_missing_end_document() {
+    let server = MockCompileServer::start("127.0.0.1:0").unwrap();
+    let latex = "\\documentclass{article}\n\\begin{document}\nHello\n";
+
+    let err = compile(&server, latex, &[]).err().unwrap();
+
+    assert!(err.log.contains("no legal \\end found"));
+}
+
+#[test]
+fn sends_assets_alongside_main_file() {
+    let server = MockCompileServer::start("127.0.0.1:0").unwrap();
+    let latex = "\\documentclass{article}\n\\usepackage{graphicx}\n\\begin{document}\n\\includegraphics[width=3cm]{figure}\n\\end{document}\n";
+
+    let err = compile(&server, latex, &[]).err().unwrap();
+    assert!(err.message.contains("File `figure' not found"));
+
+    let figure = Asset { path: "figure.png".to_string(), data: vec![0x89, b'P', b'N', b'G', 0, 255] };
+    let request = CompileRequest::new(latex, std::slice::from_ref(&figure));
+    assert_eq!(request.files[1].encoding, Encoding::Base64);
+    assert_eq!(request.files[1].bytes().unwrap(), figure.data);
+
+    assert!(compile(&server, latex, &[figure]).is_ok());
+}
+
+#[test]
+fn returns_undefined_reference_warnings_on_success() {
+    let server = MockCompileServer::start("127.0.0.1:0").unwrap();
+    let latex = "\\documentclass{article}\n\\begin{document}\nSee~\\ref{fig:missing}.\n\\end{document}\n";
+
+    let output = compile(&server, latex, &[]).unwrap();
+
+    assert!(output.log.contains("Reference `fig:missing' on page 1 undefined on input line 3"));
+}
+
+#[test]
+fn rejects_malformed_requests() {
+    let server = MockCompileServer::start("127.0.0.1:0").unwrap();
+
+    let err = post(&server, "{\"version\": 1}").err().unwrap();
+    assert!(err.message.starts_with("Invalid compile request"));
+
+    let mut request = CompileRequest::new(DOCUMENT, &[]);
+    request.main = "other.tex".to_string();
+    let err = post(&server, &request.to_json()).err().unwrap();
+    assert!(err.message.contains("other.tex is not among the uploaded files"));
+
+    request = CompileRequest::new(DOCUMENT, &[]);
+    request.version = 99;
+    let err = post(&server, &request.to_json()).err().unwrap();
+    assert!(err.message.contains("Unsupported protocol version 99"));
+}
+
+#[test]
+fn answers_cors_preflight() {
+    let server = MockCompileServer::start("127.0.0.1:0").unwrap();
+
+    let response = ureq::request("OPTIONS", &server.url()).call().unwrap();
+
+    assert_eq!(response.status(), 204);
+    assert_eq!(response.header("Access-Control-Allow-Origin"), Some("*"));
+}
+
+#[test]
+fn accepts_raw_pdf_responses() {
+    let output = decode_response(200, "application/pdf", b"%PDF-1.7 raw").unwrap();
+    assert_eq!(output.pdf, b"%PDF-1.7 raw");
+
+    let err = decode_response(502, "text/html", b"<h1>Bad Gateway</h1>").err().unwrap();
+    assert!(err.message.contains("502"));
+    assert_eq!(err.log, "<h1>Bad Gateway</h1>");
+}
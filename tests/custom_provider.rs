@@ -0,0 +1,189 @@
+// The custom OpenAI-compatible provider against the mock model server
+
+use std::io::Read;
+
+use wasm_pdf_generator::chat::{ChatMessage, SYSTEM_PROMPT};
+use wasm_pdf_generator::mock_llm_server::MockLlmServer;
+use wasm_pdf_generator::providers::{chat_completions_url, LlmProvider, ProviderSettings, CLAUDE, CUSTOM, MISTRAL};
+use wasm_pdf_generator::streaming::{decode_event, SseParser, StreamUpdate};
+
+fn settings(server: &MockLlmServer, api_key: &str) -> ProviderSettings {
+    ProviderSettings {
+        api_key: api_key.to_string(),
+        base_url: server.base_url(),
+        model: "llama3.1".to_string(),
+        ..ProviderSettings::default()
+    }
+}
+
+// Send the request the way the browser client does: (status, content type, body)
+fn send(settings: &ProviderSettings, messages: &[ChatMessage], stream: bool) -> (u16, String, Vec<u8>) {
+    let request = CUSTOM.build_request(settings, messages, stream);
+    let mut call = ureq::post(&request.url);
+    for (name, value) in &request.headers {
+        call = call.set(name, value);
+    }
+
+    let response = match call.send_string(&request.body.to_string()) {
+        Ok(response) => response,
+        Err(ureq::Error::Status(_, response)) => response,
+        Err(e) => panic!("request failed: {}", e),
+    };
+
+    let status = response.status();
+    let content_type = response.content_type().to_string();
+    let mut body = Vec::new();
+    response.into_reader().read_to_end(&mut body).unwrap();
+    (status, content_type, body)
+}
+
+#[test]
+fn completes_with_configured_model() {
+    let server = MockLlmServer::start("127.0.0.1:0").unwrap();
+    let messages = [ChatMessage::user("Quantum tunnelling")];
+
+    let (status, content_type, body) = send(&settings(&server, ""), &messages, false);
+    assert_eq!(status, 200);
+    assert_eq!(content_type, "application/json");
+
+    let reply = CUSTOM.parse_response(&serde_json::from_slice(&body).unwrap()).unwrap();
+    assert!(reply.contains("\\title{Quantum tunnelling}"));
+    assert!(reply.contains("\\end{document}"));
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].path, "/v1/chat/completions");
+    assert_eq!(requests[0].authorization, None);
+    assert_eq!(requests[0].body["model"], "llama3.1");
+    assert_eq!(requests[0].body["messages"][0]["role"], "system");
+    assert_eq!(requests[0].body["messages"][0]["content"], SYSTEM_PROMPT);
+    assert_eq!(requests[0].body["messages"][1]["content"], "Quantum tunnelling");
+}
+
+#[test]
+fn streams_server_sent_events() {
+    let server = MockLlmServer::start("127.0.0.1:0").unwrap();
+    let messages = [ChatMessage::user("Graph colouring")];
+
+    let (status, content_type, body) = send(&settings(&server, ""), &messages, true);
+    assert_eq!(status, 200);
+    assert_eq!(content_type, "text/event-stream");
+
+    // Feed the body in small pieces, as fetch delivers it
+    let mut parser = SseParser::new();
+    let mut events = Vec::new();
+    for chunk in body.chunks(17) {
+        events.extend(parser.feed(chunk));
+    }
+    events.extend(parser.finish());
+
+    let mut reply = String::new();
+    let mut done = false;
+    for event in &events {
+        match decode_event(CUSTOM.stream_format(), event) {
+            StreamUpdate::Text(text) => reply.push_str(&text),
+            StreamUpdate::Done => done = true,
+            StreamUpdate::Error(message) => panic!("stream error: {}", message),
+            StreamUpdate::Ignore => {}
+        }
+    }
+
+    assert!(done);
+    assert!(reply.contains("\\title{Graph colouring}"));
+    assert_eq!(server.requests()[0].body["stream"], true);
+}
+
+#[test]
+fn sends_bearer_key_when_configured() {
+    let server = MockLlmServer::start_with_key("127.0.0.1:0", Some("secret")).unwrap();
+    let messages = [ChatMessage::user("Sorting networks")];
+
+    let (status, _, body) = send(&settings(&server, ""), &messages, false);
+    assert_eq!(status, 401);
+    let error: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(error["error"]["message"], "Invalid API key");
+
+    let (status, _, _) = send(&settings(&server, "secret"), &messages, false);
+    assert_eq!(status, 200);
+    assert_eq!(server.requests()[0].authorization.as_deref(), Some("Bearer secret"));
+}
+
+#[test]
+fn rejects_requests_without_model() {
+    let server = MockLlmServer::start("127.0.0.1:0").unwrap();
+    let mut settings = settings(&server, "");
+    settings.model.clear();
+
+    let (status, _, _) = send(&settings, &[ChatMessage::user("Anything")], false);
+
+    assert_eq!(status, 400);
+}
+
+#[test]
+fn sends_configured_generation_settings() {
+    let server = MockLlmServer::start("127.0.0.1:0").unwrap();
+    let settings = ProviderSettings {
+        max_tokens: Some(2048),
+        temperature: Some(0.2),
+        top_p: Some(0.9),
+        system_prompt: "Answer in LaTeX only.".to_string(),
+        ..settings(&server, "")
+    };
+
+    let (status, _, _) = send(&settings, &[ChatMessage::user("Fourier series")], false);
+    assert_eq!(status, 200);
+
+    let body = &server.requests()[0].body;
+    assert_eq!(body["max_tokens"], 2048);
+    assert_eq!(body["temperature"], 0.2);
+    assert_eq!(body["top_p"], 0.9);
+    assert_eq!(body["messages"][0]["content"], "Answer in LaTeX only.");
+}
+
+#[test]
+fn falls_back_to_provider_defaults() {
+    let messages = [ChatMessage::user("Topology")];
+
+    let claude = CLAUDE.build_request(&ProviderSettings::default(), &messages, false);
+    assert_eq!(claude.body["model"], CLAUDE.default_model());
+    assert_eq!(claude.body["max_tokens"], 4000);
+    assert_eq!(claude.body["system"], SYSTEM_PROMPT);
+    assert!(claude.body.get("temperature").is_none());
+
+    let mistral = MISTRAL.build_request(&ProviderSettings::default(), &messages, false);
+    assert_eq!(mistral.body["model"], "mistral-large-latest");
+    assert_eq!(mistral.body["temperature"], 0.7);
+    assert!(mistral.body.get("max_tokens").is_none());
+
+    let settings = ProviderSettings { model: "mistral-small-latest".to_string(), temperature: Some(1.0), ..ProviderSettings::default() };
+    let mistral = MISTRAL.build_request(&settings, &messages, false);
+    assert_eq!(mistral.body["model"], "mistral-small-latest");
+    assert_eq!(mistral.body["temperature"], 1.0);
+}
+
+#[test]
+fn parses_stored_settings() {
+    let stored = |values: &'static [(&'static str, &'static str)]| {
+        move |name: &str| values.iter().find(|(n, _)| *n == name).map_or(String::new(), |(_, v)| v.to_string())
+    };
+
+    let settings = ProviderSettings::parse(" key ", stored(&[("model", " sonar-pro "), ("max_tokens", "1500"), ("top_p", "0.5")])).unwrap();
+    assert_eq!(settings.api_key, "key");
+    assert_eq!(settings.model, "sonar-pro");
+    assert_eq!(settings.max_tokens, Some(1500));
+    assert_eq!(settings.temperature, None);
+    assert_eq!(settings.top_p, Some(0.5));
+
+    assert!(ProviderSettings::parse("", stored(&[("max_tokens", "0")])).is_err());
+    assert!(ProviderSettings::parse("", stored(&[("max_tokens", "1.5")])).is_err());
+    assert!(ProviderSettings::parse("", stored(&[("temperature", "3")])).is_err());
+    assert!(ProviderSettings::parse("", stored(&[("top_p", "high")])).is_err());
+}
+
+#[test]
+fn normalizes_base_urls() {
+    assert_eq!(chat_completions_url("http://localhost:11434"), "http://localhost:11434/v1/chat/completions");
+    assert_eq!(chat_completions_url("http://localhost:1234/v1/"), "http://localhost:1234/v1/chat/completions");
+    assert_eq!(chat_completions_url(" http://gpu:8000/v1/chat/completions "), "http://gpu:8000/v1/chat/completions");
+    assert_eq!(chat_completions_url("https://llm.example.com/openai"), "https://llm.example.com/openai/v1/chat/completions");
+}
@@ -0,0 +1,54 @@
+// Line diffs between revisions, unified and side by side
+
+use wasm_pdf_generator::diff::{diff_lines, side_by_side, unified_diff, SideBySideRow};
+
+#[test]
+fn pairs_changed_lines_side_by_side() {
+    let old = "\\section{A}\nold one\nold two\nkept\ndropped\n";
+    let new = "\\section{A}\nnew one\nkept\nadded\n";
+    let rows = side_by_side(&diff_lines(old, new));
+
+    assert_eq!(rows, [
+        SideBySideRow { old: Some((1, "\\section{A}")), new: Some((1, "\\section{A}")), changed: false },
+        SideBySideRow { old: Some((2, "old one")), new: Some((2, "new one")), changed: true },
+        SideBySideRow { old: Some((3, "old two")), new: None, changed: true },
+        SideBySideRow { old: Some((4, "kept")), new: Some((3, "kept")), changed: false },
+        SideBySideRow { old: Some((5, "dropped")), new: Some((4, "added")), changed: true },
+    ]);
+
+    assert!(side_by_side(&diff_lines("same\n", "same\n")).iter().all(|row| !row.changed));
+    assert_eq!(side_by_side(&diff_lines("", "")), []);
+}
+
+#[test]
+fn names_the_revisions_in_unified_diffs() {
+    let diff = unified_diff("a\nb\n", "a\nc\n", "#1", "#3", 3);
+    assert_eq!(diff, "--- #1\n+++ #3\n@@ -1,2 +1,2 @@\n a\n-b\n+c\n");
+    assert_eq!(unified_diff("a\n", "a\n", "#1", "#2", 3), "");
+}
+
+#[test]
+fn numbers_hunk_headers_like_diff() {
+    let old: String = (1..=10).map(|line| format!("l{}\n", line)).collect();
+    let new = old.replace("l2\n", "X\n").replace("l9\n", "");
+    assert_eq!(unified_diff(&old, &new, "a", "b", 1), "\
+--- a
++++ b
+@@ -1,3 +1,3 @@
+ l1
+-l2
++X
+ l3
+@@ -8,3 +8,2 @@
+ l8
+-l9
+ l10
+");
+
+    // Changes closer than twice the context share a hunk
+    assert_eq!(unified_diff(&old, &new, "a", "b", 3).matches("@@ -").count(), 1);
+
+    // Empty ranges name the line before them; one-line ranges have no count
+    assert_eq!(unified_diff("a\nb\n", "x\na\nb\n", "a", "b", 0), "--- a\n+++ b\n@@ -0,0 +1 @@\n+x\n");
+    assert_eq!(unified_diff("a\nb\nc\n", "a\nc\n", "a", "b", 0), "--- a\n+++ b\n@@ -2 +1,0 @@\n-b\n");
+}
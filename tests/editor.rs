@@ -0,0 +1,112 @@
+// Highlighting, bracket matching and undo history of the LaTeX editor
+
+use wasm_pdf_generator::editor::{byte_offset, change_end, highlight_lines, matching_bracket, utf16_offset, EditHistory};
+
+// Each line as (class, text) pairs
+fn classes(text: &str) -> Vec<Vec<(Option<&'static str>, &str)>> {
+    highlight_lines(text).into_iter()
+        .map(|line| line.into_iter().map(|segment| (segment.class, &text[segment.range])).collect())
+        .collect()
+}
+
+#[test]
+fn highlights_commands_math_and_comments() {
+    let lines = classes("\\begin{itemize}\n\\item $x^2$ % square\n\\end{itemize}\n");
+
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0], [
+        (Some("tex-command"), "\\begin"),
+        (Some("tex-brace"), "{"),
+        (Some("tex-environment"), "itemize"),
+        (Some("tex-brace"), "}"),
+    ]);
+    assert_eq!(lines[1], [
+        (Some("tex-command"), "\\item"),
+        (None, " "),
+        (Some("tex-math"), "$"),
+        (Some("tex-math"), "x^2"),
+        (Some("tex-math"), "$"),
+        (None, " "),
+        (Some("tex-comment"), "% square"),
+    ]);
+    assert_eq!(lines[2][2], (Some("tex-environment"), "itemize"));
+    // The trailing newline leaves an empty last line, as in the textarea
+    assert!(lines[3].is_empty());
+}
+
+#[test]
+fn splits_multiline_pieces_into_lines() {
+    let text = "a\n\n\\[\nx\n\\]\nb";
+    let lines = classes(text);
+
+    assert_eq!(lines.len(), 6);
+    assert_eq!(lines[0], [(None, "a")]);
+    assert!(lines[1].is_empty());
+    assert_eq!(lines[3], [(Some("tex-math"), "x")]);
+    assert_eq!(lines[5], [(None, "b")]);
+}
+
+#[test]
+fn matches_brackets_of_the_same_kind() {
+    let text = "\\frac{a[1]}{b} % {\n[{]}";
+
+    // Cursor before and after the opening brace
+    assert_eq!(matching_bracket(text, 5), Some((5, 10)));
+    assert_eq!(matching_bracket(text, 6), Some((5, 10)));
+    // From the closing side
+    assert_eq!(matching_bracket(text, 10), Some((5, 10)));
+    assert_eq!(matching_bracket(text, 7), Some((7, 9)));
+    // The brace in the comment is not a bracket
+    assert_eq!(matching_bracket(text, 17), None);
+    // Braces and square brackets nest separately
+    let start = text.find('[').unwrap();
+    let last = text.rfind('[').unwrap();
+    assert_eq!(matching_bracket(text, start), Some((start, start + 2)));
+    assert_eq!(matching_bracket(text, last), Some((last, last + 2)));
+    assert_eq!(matching_bracket(text, last + 1), Some((last + 1, last + 3)));
+    // Unbalanced and away from brackets
+    assert_eq!(matching_bracket("{a", 0), None);
+    assert_eq!(matching_bracket(text, 2), None);
+}
+
+#[test]
+fn converts_textarea_offsets() {
+    let text = "é𝔸x";
+
+    assert_eq!(byte_offset(text, 0), 0);
+    assert_eq!(byte_offset(text, 1), 2);
+    assert_eq!(byte_offset(text, 3), 6);
+    assert_eq!(byte_offset(text, 4), 7);
+    assert_eq!(byte_offset(text, 10), 7);
+    assert_eq!(utf16_offset(text, 6), 3);
+    assert_eq!(utf16_offset(text, 7), 4);
+}
+
+#[test]
+fn undoes_typing_in_steps() {
+    let mut history = EditHistory::new("a");
+
+    history.edit("ab", 0.0);
+    history.edit("abc", 300.0);
+    // A pause starts a new step
+    history.edit("abc d", 2000.0);
+
+    assert_eq!(history.undo(), Some("abc"));
+    assert_eq!(history.undo(), Some("a"));
+    assert_eq!(history.undo(), None);
+    assert_eq!(history.redo(), Some("abc"));
+
+    // Editing drops what could be redone
+    history.edit("abcx", 2100.0);
+    assert_eq!(history.redo(), None);
+    assert_eq!(history.current(), "abcx");
+    assert_eq!(history.undo(), Some("abc"));
+}
+
+#[test]
+fn places_cursor_after_change() {
+    assert_eq!(change_end("abc", "abXc"), 3);
+    assert_eq!(change_end("abXc", "abc"), 2);
+    assert_eq!(change_end("aaa", "aaaa"), 4);
+    assert_eq!(change_end("é", "éé"), 4);
+}
@@ -0,0 +1,165 @@
+// LaTeX extraction against a corpus of provider replies in tests/replies
+
+use wasm_pdf_generator::extract::{extract_latex, find_document_end, Discarded, ExtractError, Extraction, ExtractionKind};
+
+fn extract(reply: &str) -> Extraction {
+    extract_latex(reply, "Article").unwrap()
+}
+
+fn prose(extraction: &Extraction) -> Vec<&str> {
+    extraction.discarded.iter()
+        .filter_map(|discarded| match discarded {
+            Discarded::Prose(text) => Some(text.as_str()),
+            Discarded::CodeBlock { .. } => None,
+        })
+        .collect()
+}
+
+fn code_blocks(extraction: &Extraction) -> Vec<&str> {
+    extraction.discarded.iter()
+        .filter_map(|discarded| match discarded {
+            Discarded::CodeBlock { language, .. } => Some(language.as_str()),
+            Discarded::Prose(_) => None,
+        })
+        .collect()
+}
+
+#[test]
+fn takes_fenced_document_and_drops_explanation() {
+    let extraction = extract(include_str!("replies/claude_fenced.md"));
+
+    assert_eq!(extraction.kind, ExtractionKind::Complete);
+    assert!(extraction.latex.starts_with("\\documentclass{article}"));
+    assert!(extraction.latex.ends_with("\\end{document}\n"));
+    assert_eq!(extraction.latex.matches("\\section").count(), 3);
+
+    let prose = prose(&extraction);
+    assert_eq!(prose.len(), 2);
+    assert!(prose[0].starts_with("I'll create"));
+    assert!(prose[1].contains("ignored by LaTeX"));
+    assert_eq!(extraction.summary().as_deref(), Some("Dropped 6 lines of explanation"));
+}
+
+#[test]
+fn skips_end_document_in_verbatim_and_comments() {
+    let extraction = extract(include_str!("replies/verbatim_tutorial.md"));
+
+    assert_eq!(extraction.kind, ExtractionKind::Complete);
+    assert!(extraction.latex.contains("\\section{Next steps}"));
+    assert!(extraction.latex.trim_end().ends_with("\\verb!\\begin{equation}!.\n\\end{document}"));
+}
+
+#[test]
+fn joins_document_split_over_fences() {
+    let extraction = extract(include_str!("replies/split_over_fences.md"));
+
+    assert_eq!(extraction.kind, ExtractionKind::Complete);
+    assert!(extraction.latex.starts_with("\\documentclass{report}"));
+    assert!(extraction.latex.contains("\\chapter{Introduction}"));
+    assert!(extraction.latex.contains("\\chapter{Conclusion}"));
+    assert_eq!(extraction.latex.matches("\\end{document}").count(), 1);
+    assert!(code_blocks(&extraction).is_empty());
+}
+
+#[test]
+fn saves_bibtex_fence_under_bibliography_name() {
+    let extraction = extract(include_str!("replies/bibtex_fence.md"));
+
+    assert_eq!(extraction.files.len(), 1);
+    assert_eq!(extraction.files[0].path, "refs.bib");
+    assert!(extraction.files[0].contents.starts_with("@inproceedings{batcher1968,"));
+    assert_eq!(code_blocks(&extraction), ["bash"]);
+    assert_eq!(
+        extraction.summary().as_deref(),
+        Some("Added refs.bib; dropped 1 line of explanation; dropped 1 other code block")
+    );
+}
+
+#[test]
+fn moves_filecontents_into_files() {
+    let extraction = extract(include_str!("replies/filecontents.md"));
+
+    assert_eq!(extraction.kind, ExtractionKind::Complete);
+    assert!(extraction.latex.starts_with("% main.tex\n\\documentclass{article}"));
+    assert!(!extraction.latex.contains("filecontents"));
+    assert_eq!(extraction.files.len(), 1);
+    assert_eq!(extraction.files[0].path, "sources.bib");
+    assert!(extraction.files[0].contents.starts_with("@book{knuth1984,"));
+    assert!(extraction.files[0].contents.ends_with("}\n"));
+}
+
+#[test]
+fn trims_prose_around_unfenced_document() {
+    let extraction = extract(include_str!("replies/mistral_unfenced.md"));
+
+    assert_eq!(extraction.kind, ExtractionKind::Complete);
+    assert!(extraction.latex.starts_with("\\documentclass{article}"));
+    assert!(extraction.latex.ends_with("\\end{document}\n"));
+    assert_eq!(prose(&extraction), [
+        "Sure! Below is a LaTeX document on quantum tunnelling.",
+        "I hope this helps! Feel free to ask for changes, such as adding figures.",
+    ]);
+}
+
+#[test]
+fn closes_truncated_document() {
+    let extraction = extract(include_str!("replies/truncated.md"));
+
+    assert_eq!(extraction.kind, ExtractionKind::Unterminated);
+    assert!(extraction.latex.ends_with("  \\item The prime number theorem states that\n\\end{itemize}\n\\end{document}\n"));
+    assert_eq!(extraction.summary().as_deref(), Some("Closed the cut-off document"));
+}
+
+#[test]
+fn wraps_body_in_template_preamble() {
+    let extraction = extract_latex(include_str!("replies/body_only.md"), "Report").unwrap();
+
+    assert_eq!(extraction.kind, ExtractionKind::Wrapped);
+    assert!(extraction.latex.starts_with("\\documentclass{report}\n\\usepackage[utf8]{inputenc}\n"));
+    let (preamble, body) = extraction.latex.split_once("\\begin{document}").unwrap();
+    assert!(preamble.contains("\\usepackage{titlesec}"));
+    assert!(preamble.contains("\\usepackage{booktabs}"));
+    assert!(body.trim_start().starts_with("\\section{Method}"));
+    assert!(body.trim_end().ends_with("CG is fastest.\n\n\\end{document}"));
+}
+
+#[test]
+fn drops_leading_prose_of_unfenced_fragment() {
+    let extraction = extract(include_str!("replies/unfenced_fragment.md"));
+
+    assert_eq!(extraction.kind, ExtractionKind::Wrapped);
+    assert!(extraction.latex.contains("\\begin{document}\n\n\\section{Topological Insulators}"));
+    assert_eq!(prose(&extraction), ["Based on current literature [1][2], here is a section you can use:"]);
+}
+
+#[test]
+fn prefers_largest_complete_document() {
+    let extraction = extract(include_str!("replies/two_documents.md"));
+
+    assert!(extraction.latex.contains("\\section{Full}"));
+    assert!(!extraction.latex.contains("Short."));
+    assert_eq!(code_blocks(&extraction), ["latex"]);
+}
+
+#[test]
+fn adds_missing_document_class() {
+    let extraction = extract_latex(include_str!("replies/missing_class.md"), "Book").unwrap();
+
+    assert_eq!(extraction.kind, ExtractionKind::Wrapped);
+    assert!(extraction.latex.starts_with("\\documentclass{book}\n\\usepackage{amsmath}\n\\title{Entropy}"));
+    assert!(extraction.latex.ends_with("\\end{document}\n"));
+}
+
+#[test]
+fn rejects_replies_without_latex() {
+    assert_eq!(extract_latex(include_str!("replies/refusal.md"), "Article"), Err(ExtractError::NoLatex));
+    assert_eq!(extract_latex(" \n\n", "Article"), Err(ExtractError::Empty));
+    assert_eq!(extract_latex("```python\nprint('hi')\n```\n", "Article"), Err(ExtractError::NoLatex));
+}
+
+#[test]
+fn finds_end_of_document() {
+    let latex = "\\begin{document}\n\\begin{lstlisting}\n\\end{document}\n\\end{lstlisting}\n\\end{document}\ntrailing";
+    assert_eq!(&latex[..find_document_end(latex).unwrap()], latex.strip_suffix("\ntrailing").unwrap());
+    assert_eq!(find_document_end("% \\end{document}\n\\verb|\\end{document}|"), None);
+}
@@ -0,0 +1,250 @@
+// Conversation records and the move from the old localStorage history
+
+use wasm_pdf_generator::chat;
+use wasm_pdf_generator::compiler::Asset;
+use wasm_pdf_generator::history::{date_groups, find, migrate_legacy, Conversation, Revision, RevisionKind, Turn, PINNED};
+
+const LEGACY: &str = r#"[
+    {"date": "10/16/2026", "time": "9:15:00 AM", "topic": "Graph colouring",
+     "content": "\\documentclass{article}", "template": "Report", "ai_provider": "Mistral", "pdf_size": "Large"},
+    {"date": "garbled", "time": "", "topic": "Knot theory", "content": "\\section{Knots}"}
+]"#;
+
+fn timestamp(date: &str, time: &str) -> Option<f64> {
+    (date == "10/16/2026" && time == "9:15:00 AM").then_some(1_792_142_100_000.0)
+}
+
+#[test]
+fn migrates_local_storage_entries() {
+    let conversations = migrate_legacy(LEGACY, 2e12, timestamp).unwrap();
+
+    assert_eq!(conversations.len(), 2);
+    let first = &conversations[0];
+    // Ids look like new ones and differ between entries
+    assert_eq!(first.id.len(), 32);
+    assert!(first.id.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_eq!(first.created, 1_792_142_100_000.0);
+    assert_eq!((first.provider.as_str(), first.template.as_str(), first.pdf_size.as_str()), ("Mistral", "Report", "Large"));
+    assert_eq!(first.turns, [
+        Turn { role: chat::USER.to_string(), content: "Graph colouring".to_string() },
+        Turn { role: chat::AI.to_string(), content: "\\documentclass{article}".to_string() },
+    ]);
+    assert_eq!(first.latex(), "\\documentclass{article}");
+
+    // Missing fields get what restoring used to assume, unreadable times `now`
+    let second = &conversations[1];
+    assert_ne!(second.id, first.id);
+    assert_eq!(second.created, 2e12);
+    assert_eq!((second.provider.as_str(), second.template.as_str(), second.pdf_size.as_str()), ("Claude", "Article", "Medium"));
+
+    // Migrating again gives the same records
+    assert_eq!(migrate_legacy(LEGACY, 2e12, timestamp).unwrap(), conversations);
+    assert!(migrate_legacy("{\"date\": 1}", 0.0, timestamp).is_err());
+
+    // Another browser's first entry is not the same conversation
+    let other = migrate_legacy(r#"[{"date": "10/16/2026", "time": "9:15:00 AM", "topic": "Sorting", "content": "\\section{Sorting}"}]"#, 2e12, timestamp).unwrap();
+    assert_ne!(other[0].id, first.id);
+}
+
+#[test]
+fn keeps_every_revision() {
+    let mut conversation = Conversation::new("c1".to_string(), 10.0, "Topology", "Claude", "Article", "Small");
+    assert_eq!(conversation.latex(), "");
+
+    conversation.add_revision(RevisionKind::Generation, "v1", 20.0);
+    conversation.add_revision(RevisionKind::Generation, "v1", 30.0);
+    conversation.add_revision(RevisionKind::Fix, "v2", 40.0);
+    assert_eq!(conversation.revisions.len(), 2);
+    assert_eq!(conversation.latex(), "v2");
+    assert_eq!(conversation.updated, 40.0);
+
+    // A run of typing is one revision, kept apart from the fix before it
+    conversation.edit_latex("v2 edited", 50.0);
+    conversation.edit_latex("v2 edited twice", 55.0);
+    assert_eq!(conversation.revisions.len(), 3);
+    assert_eq!(conversation.revisions[1].latex, "v2");
+    assert_eq!(conversation.revisions[2].kind, RevisionKind::Edit);
+    assert_eq!(conversation.latex(), "v2 edited twice");
+    assert_eq!(conversation.created, 10.0);
+    assert_eq!(conversation.updated, 55.0);
+}
+
+#[test]
+fn restores_a_revision_as_a_new_one() {
+    let mut conversation = Conversation::new("c1".to_string(), 10.0, "Topology", "Claude", "Article", "Small");
+    conversation.add_revision(RevisionKind::Upload, "v1", 20.0);
+    conversation.add_revision(RevisionKind::Generation, "v2", 30.0);
+    conversation.edit_latex("v2 edited", 40.0);
+
+    assert_eq!(conversation.restore(0, 50.0), Some("v1"));
+    let kinds: Vec<RevisionKind> = conversation.revisions.iter().map(|revision| revision.kind).collect();
+    assert_eq!(kinds, [RevisionKind::Upload, RevisionKind::Generation, RevisionKind::Edit, RevisionKind::Restore]);
+    assert_eq!(conversation.latex(), "v1");
+    assert_eq!(conversation.updated, 50.0);
+
+    // Editing after a restore starts a new edit revision
+    conversation.edit_latex("v1 edited", 60.0);
+    assert_eq!(conversation.revisions.len(), 5);
+    assert_eq!(conversation.restore(9, 70.0), None);
+
+    // Revisions saved before kinds were recorded came from generating
+    let json = serde_json::json!({ "created": 1.0, "latex": "old" });
+    let revision: Revision = serde_json::from_value(json).unwrap();
+    assert_eq!(revision.kind, RevisionKind::Generation);
+    assert_eq!(serde_json::to_value(&conversation.revisions[3]).unwrap()["kind"], "restore");
+}
+
+#[test]
+fn stores_turns_as_records() {
+    let mut conversation = Conversation::new("c2".to_string(), 1.0, "Topology", "Claude", "Article", "Small");
+    let turns = vec![
+        (chat::USER.to_string(), "Topology".to_string()),
+        (chat::AI.to_string(), "\\begin{document}".to_string()),
+    ];
+    conversation.set_turns(&turns, 2.0);
+
+    assert_eq!(conversation.chat_history(), turns);
+    let json = serde_json::to_value(&conversation).unwrap();
+    assert_eq!(json["turns"][1]["role"], chat::AI);
+    assert_eq!(json["revisions"], serde_json::json!([]));
+}
+
+#[test]
+fn keeps_the_document_files() {
+    let mut conversation = Conversation::new("c3".to_string(), 1.0, "Topology", "Claude", "Article", "Small");
+    conversation.assets.push(Asset { path: "figures/torus.png".to_string(), data: vec![0x89, b'P', b'N', b'G', 0, 255] });
+
+    // Saved as base64 and read back byte for byte
+    let json = serde_json::to_value(&conversation).unwrap();
+    assert_eq!(json["assets"][0], serde_json::json!({ "path": "figures/torus.png", "data": "iVBORwD/" }));
+    assert_eq!(serde_json::from_value::<Conversation>(json.clone()).unwrap(), conversation);
+
+    // Conversations saved before files were kept have none
+    let mut old = json;
+    old.as_object_mut().unwrap().remove("assets");
+    assert_eq!(serde_json::from_value::<Conversation>(old).unwrap().assets, []);
+}
+
+const DAY: f64 = 86_400_000.0;
+
+fn day_label(time: f64) -> String {
+    format!("day {}", (time / DAY).floor())
+}
+
+fn conversation(id: &str, created: f64, provider: &str, template: &str, size: &str) -> Conversation {
+    let mut conversation = Conversation::new(id.to_string(), created, &format!("Topic {}", id), provider, template, size);
+    conversation.set_turns(&[
+        (chat::USER.to_string(), conversation.topic.clone()),
+        (chat::AI.to_string(), format!("latex {}", id)),
+    ], created);
+    conversation.add_revision(RevisionKind::Generation, &format!("latex {}", id), created);
+    conversation
+}
+
+#[test]
+fn groups_by_date_newest_first() {
+    let history = [
+        conversation("a", 10.0 * DAY + 1.0, "Claude", "Article", "Small"),
+        conversation("b", 10.0 * DAY + 2.0, "Mistral", "Report", "Large"),
+        conversation("c", 11.0 * DAY + 1.0, "Perplexity", "Letter", "Medium"),
+    ];
+
+    let groups = date_groups(&history, "", day_label);
+    let ids: Vec<(&str, Vec<&str>)> = groups.iter()
+        .map(|(date, entries)| (date.as_str(), entries.iter().map(|c| c.id.as_str()).collect()))
+        .collect();
+    assert_eq!(ids, [("day 11", vec!["c"]), ("day 10", vec!["b", "a"])]);
+}
+
+#[test]
+fn restores_the_entry_clicked_in_any_group() {
+    let history = [
+        conversation("a", 10.0 * DAY + 1.0, "Claude", "Article", "Small"),
+        conversation("b", 10.0 * DAY + 2.0, "Mistral", "Report", "Large"),
+        conversation("c", 11.0 * DAY + 1.0, "Perplexity", "Letter", "Medium"),
+    ];
+
+    // Every entry, by the id the panel renders, leads back to itself with
+    // the settings it was generated with
+    for (_, entries) in date_groups(&history, "", day_label) {
+        for entry in entries {
+            let restored = find(&history, &entry.id).unwrap();
+            assert_eq!(restored, entry);
+            assert_eq!(restored.latex(), format!("latex {}", entry.id));
+        }
+    }
+    let b = find(&history, "b").unwrap();
+    assert_eq!((b.provider.as_str(), b.template.as_str(), b.pdf_size.as_str()), ("Mistral", "Report", "Large"));
+    assert_eq!(find(&history, "z"), None);
+}
+
+#[test]
+fn round_trips_through_storage() {
+    let mut saved = conversation("a", 1_792_142_100_000.5, "Mistral", "Report", "Large");
+    saved.set_turns(&[
+        (chat::USER.to_string(), "Quantum <tunnelling> & \"barriers\"".to_string()),
+        (chat::AI.to_string(), "\\section{Ünïcode 𝔸}".to_string()),
+    ], 2e12);
+    saved.add_revision(RevisionKind::Generation, "second", 2e12);
+    saved.edit_latex("second, edited", 2e12 + 1.0);
+
+    let json = serde_json::to_string(&saved).unwrap();
+    let loaded: Conversation = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded, saved);
+    assert_eq!(loaded.chat_history(), saved.chat_history());
+    assert_eq!(loaded.revisions.len(), 3);
+
+    // Migrated entries keep their own settings through a save and load
+    let migrated = migrate_legacy(LEGACY, 2e12, timestamp).unwrap();
+    let json = serde_json::to_string(&migrated).unwrap();
+    assert_eq!(serde_json::from_str::<Vec<Conversation>>(&json).unwrap(), migrated);
+}
+
+#[test]
+fn searches_names_topics_and_documents() {
+    let mut history = [
+        conversation("a", 10.0 * DAY, "Claude", "Article", "Small"),
+        conversation("b", 10.0 * DAY + 1.0, "Claude", "Article", "Small"),
+    ];
+    history[0].add_revision(RevisionKind::Generation, "\\section{Hamiltonian Cycles}", 10.0 * DAY + 2.0);
+    history[1].rename("Exam notes", 10.0 * DAY + 3.0);
+
+    let ids = |query: &str| date_groups(&history, query, day_label).into_iter()
+        .flat_map(|(_, entries)| entries.into_iter().map(|c| c.id.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(ids(""), ["b", "a"]);
+    assert_eq!(ids("hamiltonian"), ["a"]);
+    assert_eq!(ids("EXAM notes"), ["b"]);
+    // Every word has to match, each anywhere
+    assert_eq!(ids("topic cycles"), ["a"]);
+    assert_eq!(ids("exam cycles"), Vec::<String>::new());
+    // The topic still matches after renaming
+    assert_eq!(ids("topic b"), ["b"]);
+}
+
+#[test]
+fn renames_and_pins() {
+    let mut history = [
+        conversation("a", 10.0 * DAY, "Claude", "Article", "Small"),
+        conversation("b", 11.0 * DAY, "Claude", "Article", "Small"),
+    ];
+    history[0].rename("  Old notes ", 12.0 * DAY);
+    assert_eq!(history[0].title(), "Old notes");
+    assert_eq!(history[0].topic, "Topic a");
+    history[0].rename("", 12.0 * DAY);
+    assert_eq!(history[0].title(), "Topic a");
+    assert_eq!(history[0].title, None);
+
+    history[0].pinned = true;
+    let groups = date_groups(&history, "", day_label);
+    assert_eq!(groups[0].0, PINNED);
+    assert_eq!(groups[0].1[0].id, "a");
+    assert_eq!(groups[1].0, "day 11");
+
+    // Records saved before names and pins existed still load
+    let mut json = serde_json::to_value(&history[1]).unwrap();
+    json.as_object_mut().unwrap().remove("title");
+    json.as_object_mut().unwrap().remove("pinned");
+    let loaded: Conversation = serde_json::from_value(json).unwrap();
+    assert_eq!(loaded, history[1]);
+}
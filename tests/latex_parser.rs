@@ -0,0 +1,151 @@
+// LaTeX tokenizer and document tree
+
+use wasm_pdf_generator::doctree;
+use wasm_pdf_generator::lexer::{tokenize, TokenKind};
+
+const PAPER: &str = r#"% A sample paper
+\documentclass[11pt, twocolumn]{article}
+\usepackage[utf8]{inputenc}
+\usepackage{amsmath,amssymb}
+\usepackage[colorlinks]{hyperref}
+\title{On {Nested} Braces}
+
+\begin{document}
+\maketitle
+
+\section{Introduction}\label{sec:intro}
+As shown in Section~\ref{sec:method} and Eq.~\eqref{eq:main}, see \cite{knuth1984, lamport1994}.
+
+\section*{Method}
+\label{sec:method}
+\begin{equation}
+  a^2 + b^2 = c^2 \label{eq:main}
+\end{equation}
+
+\subsection[Short]{A longer subsection title}
+\begin{itemize}
+  \item Results \citep[p.~3]{knuth1984}
+  \begin{enumerate}
+    \item Nested
+  \end{enumerate}
+\end{itemize}
+\begin{verbatim}
+\section{Not a section} \end{document} }
+\end{verbatim}
+
+\section{Conclusion}
+Done, with \verb|\label{fake}| in verbatim.
+\end{document}
+"#;
+
+#[test]
+fn tokenizes_with_positions() {
+    let tokens = tokenize("\\section{Intro} % note\n$x$ \\\\ \\%");
+    let kinds: Vec<TokenKind> = tokens.iter().map(|token| token.kind).collect();
+    assert_eq!(kinds, [
+        TokenKind::Command, TokenKind::BeginGroup, TokenKind::Text, TokenKind::EndGroup, TokenKind::Whitespace,
+        TokenKind::Comment, TokenKind::Whitespace, TokenKind::MathShift, TokenKind::Text, TokenKind::MathShift,
+        TokenKind::Whitespace, TokenKind::Command, TokenKind::Whitespace, TokenKind::Command,
+    ]);
+    assert_eq!(tokens[0].command_name(), Some("section"));
+    assert_eq!(tokens[5].text, "% note");
+    assert_eq!((tokens[7].start.line, tokens[7].start.column), (2, 1));
+    assert_eq!(tokens[11].command_name(), Some("\\"));
+    assert_eq!(tokens[13].command_name(), Some("%"));
+}
+
+#[test]
+fn keeps_verbatim_bodies_whole() {
+    let tokens = tokenize("\\begin{lstlisting}\n{ \\end{document}\n\\end{lstlisting} \\verb!}!x");
+    let verbatim: Vec<&str> = tokens.iter().filter(|t| t.kind == TokenKind::Verbatim).map(|t| t.text).collect();
+    assert_eq!(verbatim, ["\n{ \\end{document}\n", "\\verb!}!"]);
+
+    let end = tokens.iter().find(|t| t.command_name() == Some("end")).unwrap();
+    assert_eq!((end.start.line, end.start.column), (3, 1));
+}
+
+#[test]
+fn parses_preamble() {
+    let tree = doctree::parse(PAPER);
+
+    let class = tree.class.as_ref().unwrap();
+    assert_eq!(class.name, "article");
+    assert_eq!(class.options, ["11pt", "twocolumn"]);
+    assert_eq!(class.position.line, 2);
+
+    let packages: Vec<&str> = tree.packages.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(packages, ["inputenc", "amsmath", "amssymb", "hyperref"]);
+    assert_eq!(tree.packages[3].options, ["colorlinks"]);
+    assert!(tree.has_package("amssymb"));
+    assert_eq!(tree.title.as_deref(), Some("On {Nested} Braces"));
+
+    assert!(PAPER[tree.preamble.clone()].ends_with("Braces}\n\n"));
+    let body = &PAPER[tree.body.clone().unwrap()];
+    assert!(body.starts_with("\n\\maketitle"));
+    assert!(body.ends_with("in verbatim.\n"));
+}
+
+#[test]
+fn parses_sections_with_ranges() {
+    let tree = doctree::parse(PAPER);
+
+    let sections: Vec<(u8, &str, bool)> = tree.sections.iter().map(|s| (s.level, s.title.as_str(), s.starred)).collect();
+    assert_eq!(sections, [
+        (2, "Introduction", false),
+        (2, "Method", true),
+        (3, "A longer subsection title", false),
+        (2, "Conclusion", false),
+    ]);
+
+    let method = &PAPER[tree.sections[1].range.clone()];
+    assert!(method.starts_with("\\section*{Method}"));
+    assert!(method.contains("\\subsection[Short]"));
+    assert!(method.ends_with("\\end{verbatim}\n\n"));
+    assert_eq!(tree.subsections(1).count(), 1);
+
+    let conclusion = &PAPER[tree.sections[3].range.clone()];
+    assert!(conclusion.ends_with("in verbatim.\n"));
+}
+
+#[test]
+fn parses_environments_with_nesting() {
+    let tree = doctree::parse(PAPER);
+
+    let names: Vec<&str> = tree.environments.iter().map(|e| e.name.as_str()).collect();
+    assert_eq!(names, ["document", "equation", "itemize", "enumerate", "verbatim"]);
+    assert!(tree.environments.iter().all(|e| e.end.is_some()));
+
+    let enumerate = tree.environments_named("enumerate").next().unwrap();
+    assert_eq!(tree.environments[enumerate.parent.unwrap()].name, "itemize");
+    assert_eq!(tree.environments[2].parent, Some(0));
+    assert!(PAPER[enumerate.range.clone()].ends_with("\\end{enumerate}"));
+}
+
+#[test]
+fn parses_labels_refs_and_citations() {
+    let tree = doctree::parse(PAPER);
+
+    let labels: Vec<(&str, usize)> = tree.labels.iter().map(|l| (l.name.as_str(), l.position.line)).collect();
+    assert_eq!(labels, [("sec:intro", 11), ("sec:method", 15), ("eq:main", 17)]);
+
+    let references: Vec<(&str, &str)> = tree.references.iter().map(|r| (r.command.as_str(), r.name.as_str())).collect();
+    assert_eq!(references, [("ref", "sec:method"), ("eqref", "eq:main")]);
+
+    assert_eq!(tree.citations.len(), 2);
+    assert_eq!(tree.citations[0].keys, ["knuth1984", "lamport1994"]);
+    assert_eq!(tree.citations[1].command, "citep");
+    assert_eq!(tree.citations[1].keys, ["knuth1984"]);
+}
+
+#[test]
+fn tolerates_broken_input() {
+    let tree = doctree::parse("\\documentclass{article}\n\\begin{document}\n\\section{Open {brace}\n\\begin{itemize}\n\\item x\n\\end{document}");
+
+    // The unclosed title swallows nothing past the source, and the list
+    // left open is closed along with the document
+    assert_eq!(tree.sections.len(), 0);
+    let itemize = tree.environments_named("itemize").next().unwrap();
+    assert_eq!(itemize.end, None);
+    assert!(tree.environments[0].end.is_some());
+    assert_eq!(doctree::parse("").sections.len(), 0);
+}
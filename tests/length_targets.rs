@@ -0,0 +1,69 @@
+// PDF size targets and the page count check that follows a compile
+
+use wasm_pdf_generator::chat;
+use wasm_pdf_generator::length::{pdf_page_count, PageCheck, PdfSize};
+use wasm_pdf_generator::texlog;
+
+#[test]
+fn sizes_grow_monotonically() {
+    let targets: Vec<_> = PdfSize::ALL.iter().map(|size| size.target()).collect();
+    for pair in targets.windows(2) {
+        assert!(pair[0].max_pages < pair[1].min_pages);
+        assert!(pair[0].sections < pair[1].sections);
+        assert!(pair[0].words < pair[1].words);
+        assert!(pair[0].max_tokens < pair[1].max_tokens);
+    }
+    assert_eq!(PdfSize::from_label("Extra Large"), Some(PdfSize::ExtraLarge));
+    assert_eq!(PdfSize::from_label("Huge"), None);
+}
+
+#[test]
+fn prompt_carries_length_targets() {
+    let target = PdfSize::Large.target();
+    let prompt = chat::generation_prompt("Group theory", "Article", &target);
+
+    assert!(prompt.contains("7 content sections"));
+    assert!(prompt.contains("6-10 pages"));
+    assert!(prompt.contains("3500 words"));
+}
+
+#[test]
+fn reads_page_count_from_log() {
+    let log = "(./main.aux) )\nOutput written on main.pdf (12 pages, 184233 bytes).\nTranscript written on main.log.\n";
+    assert_eq!(texlog::page_count(log), Some(12));
+
+    let wrapped = "Output written on /tmp/build-3f9c2a7e/some/long/directory/name/main.pdf (1 pa\nge, 20012 bytes).\n";
+    assert_eq!(texlog::page_count(wrapped), Some(1));
+
+    assert_eq!(texlog::page_count("No pages of output.\n"), None);
+}
+
+#[test]
+fn counts_pages_in_a_pdf_without_a_log() {
+    let pdf = b"%PDF-1.5
+1 0 obj << /Type /Catalog /Pages 2 0 R >> endobj
+2 0 obj << /Type /Pages /Kids [3 0 R 4 0 R 5 0 R] /Count 3 >> endobj
+3 0 obj << /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] >> endobj
+4 0 obj << /Type/Page/Parent 2 0 R >> endobj
+5 0 obj <</Parent 2 0 R/Type
+/Page>> endobj
+6 0 obj << /Type /PageLabel /S /D >> endobj
+%%EOF
+";
+    assert_eq!(pdf_page_count(pdf), Some(3));
+    assert_eq!(pdf_page_count(b"%PDF-1.5\n1 0 obj << /Type /ObjStm /N 12 >> stream\nx\x9c\nendstream\n%%EOF\n"), None);
+}
+
+#[test]
+fn offers_to_expand_or_condense() {
+    let target = PdfSize::Medium.target();
+
+    assert_eq!(target.check(4), PageCheck::OnTarget);
+    assert_eq!(target.adjustment_request(4), None);
+
+    assert_eq!(target.check(1), PageCheck::TooShort);
+    assert!(target.adjustment_request(1).unwrap().starts_with("The document compiles to 1 page but should be 3-5 pages long. Expand"));
+
+    assert_eq!(target.check(9), PageCheck::TooLong);
+    assert!(target.adjustment_request(9).unwrap().contains("Condense"));
+}
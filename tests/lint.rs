@@ -0,0 +1,120 @@
+// Static checks on LaTeX sources
+
+use wasm_pdf_generator::chat::lint_report;
+use wasm_pdf_generator::lint::{lint, LintIssue, Rule};
+use wasm_pdf_generator::texlog::{DiagnosticKind, Severity};
+
+fn rules(issues: &[LintIssue]) -> Vec<Rule> {
+    issues.iter().map(|issue| issue.rule).collect()
+}
+
+fn document(preamble: &str, body: &str) -> String {
+    format!("\\documentclass{{article}}\n{}\\begin{{document}}\n{}\\end{{document}}\n", preamble, body)
+}
+
+#[test]
+fn clean_document_has_no_issues() {
+    let latex = document(
+        "\\usepackage{amsmath}\n\\usepackage{graphicx}\n",
+        "\\section{Intro}\\label{sec:intro}\nSee Section~\\ref{sec:intro} and $x^2$.\n\\begin{align}\na &= b\n\\end{align}\n\\includegraphics{plot}\n",
+    );
+    assert_eq!(lint(&latex), []);
+}
+
+#[test]
+fn reports_unbalanced_braces_with_position() {
+    let latex = document("", "\\textbf{bold\nand } extra }\n");
+    let issues = lint(&latex);
+
+    assert_eq!(rules(&issues), [Rule::UnbalancedBrace]);
+    assert_eq!((issues[0].line, issues[0].column), (4, 13));
+    assert_eq!(issues[0].message, "Unmatched }");
+
+    let issues = lint(&document("", "\\emph{open\n"));
+    assert_eq!(rules(&issues), [Rule::UnbalancedBrace]);
+    assert_eq!((issues[0].line, issues[0].column, issues[0].severity), (3, 6, Severity::Error));
+}
+
+#[test]
+fn reports_environment_mismatches() {
+    let latex = document("", "\\begin{itemize}\n\\item One\n\\end{enumerate}\n\\begin{center}\nText\n");
+    let issues = lint(&latex);
+
+    assert_eq!(rules(&issues), [Rule::UnclosedEnvironment, Rule::UnmatchedEnd, Rule::UnclosedEnvironment]);
+    assert_eq!(issues[0].message, "\\begin{itemize} is never closed");
+    assert_eq!(issues[0].line, 3);
+    assert_eq!(issues[1].message, "\\end{enumerate} without a matching \\begin");
+    assert_eq!(issues[1].line, 5);
+    assert_eq!(issues[2].message, "\\begin{center} is never closed");
+}
+
+#[test]
+fn reports_unclosed_math() {
+    let issues = lint(&document("", "Energy $E = mc^2.\n\nNext paragraph $x$.\n"));
+    assert_eq!(rules(&issues), [Rule::UnclosedMath]);
+    assert_eq!((issues[0].line, issues[0].column), (3, 8));
+
+    let issues = lint(&document("", "\\[ x \\)\n"));
+    assert_eq!(rules(&issues), [Rule::UnclosedMath]);
+    assert!(issues[0].message.contains("closed with \\)"));
+
+    // Escaped dollars and verbatim are not math
+    assert_eq!(lint(&document("", "Costs \\$5 and \\verb|$|.\n")), []);
+}
+
+#[test]
+fn reports_label_problems() {
+    let latex = document(
+        "",
+        "\\section{A}\\label{sec:a}\n\\section{B}\\label{sec:a}\nSee \\ref{sec:b}.\n",
+    );
+    let issues = lint(&latex);
+
+    assert_eq!(rules(&issues), [Rule::DuplicateLabel, Rule::UndefinedLabel]);
+    assert_eq!(issues[0].message, "Label `sec:a` is already defined on line 3");
+    assert_eq!(issues[1].message, "\\ref to undefined label `sec:b`");
+    assert!(issues.iter().all(|issue| issue.severity == Severity::Warning));
+}
+
+#[test]
+fn reports_first_use_of_commands_without_their_package() {
+    let latex = document(
+        "\\usepackage{mathtools}\n\\newcommand{\\R}{\\mathbb{R}}\n\\def\\toprule{\\hline}\n",
+        "$\\text{a} \\in \\R$ and $\\mathbb{Z}$\n\\toprule\n\\begin{tikzpicture}\n\\end{tikzpicture}\n\\textcolor{red}{x}\n",
+    );
+    let issues = lint(&latex);
+
+    assert_eq!(rules(&issues), [Rule::MissingPackage; 3]);
+    let messages: Vec<&str> = issues.iter().map(|issue| issue.message.as_str()).collect();
+    assert_eq!(messages, [
+        "\\mathbb needs \\usepackage{amssymb}",
+        "The tikzpicture environment needs \\usepackage{tikz}",
+        "\\textcolor needs \\usepackage{xcolor}",
+    ]);
+    assert_eq!((issues[0].line, issues[0].column), (3, 17));
+
+    // Classes load packages too
+    let beamer = "\\documentclass{beamer}\n\\begin{document}\n\\href{https://example.com}{x} $\\text{y}$\n\\end{document}\n";
+    assert_eq!(lint(beamer), []);
+}
+
+#[test]
+fn reports_missing_document_structure() {
+    let issues = lint("\\section{Loose}\nText.\n");
+    assert_eq!(rules(&issues), [Rule::MissingDocumentClass, Rule::MissingDocumentEnvironment]);
+    assert_eq!(lint(" \n"), []);
+}
+
+#[test]
+fn converts_issues_for_preview_and_prompt() {
+    let issues = lint(&document("", "\\begin{itemize}\n"));
+    let diagnostic = issues[0].to_diagnostic();
+
+    assert_eq!(diagnostic.kind, DiagnosticKind::Lint);
+    assert_eq!(diagnostic.location().as_deref(), Some("line 3:1"));
+    assert!(diagnostic.is_in_main_file());
+
+    let report = lint_report(&issues).unwrap();
+    assert!(report.contains("- line 3:1: \\begin{itemize} is never closed\n"));
+    assert_eq!(lint_report(&[]), None);
+}
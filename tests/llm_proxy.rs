@@ -0,0 +1,171 @@
+// The LLM proxy against the mock model server as upstream
+
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+use wasm_pdf_generator::chat::ChatMessage;
+use wasm_pdf_generator::mock_llm_server::MockLlmServer;
+use wasm_pdf_generator::providers::{LlmProvider, ProviderSettings, CLAUDE, CUSTOM};
+use wasm_pdf_generator::proxy::{proxied, ProxyConfig, RateLimit, RateLimiter};
+use wasm_pdf_generator::proxy_server::ProxyServer;
+use wasm_pdf_generator::streaming::{decode_event, SseParser, StreamUpdate};
+
+const SERVER_KEY: &str = "server-held-key";
+
+fn start(upstream: &MockLlmServer, requests: usize) -> ProxyServer {
+    let config = format!(
+        r#"{{ "listen": "127.0.0.1:0", "user_header": "x-forwarded-user",
+             "rate_limit": {{ "requests": {}, "window_secs": 60 }},
+             "providers": {{ "custom": {{ "api_key": "{}", "base_url": "{}" }} }} }}"#,
+        requests, SERVER_KEY, upstream.base_url(),
+    );
+    ProxyServer::start(ProxyConfig::from_json(&config).unwrap()).unwrap()
+}
+
+// Send `messages` through the proxy the way the browser client does, with a
+// key of the user's own that must not get through: (status, headers, body)
+fn send(proxy: &ProxyServer, provider: &str, user: Option<&str>, stream: bool) -> (u16, Vec<(String, String)>, Vec<u8>) {
+    let settings = ProviderSettings {
+        api_key: "browser-key".to_string(),
+        model: "llama3.1".to_string(),
+        ..ProviderSettings::default()
+    };
+    let request = CUSTOM.build_request(&settings, &[ChatMessage::user("Knot theory")], stream);
+    let request = proxied(request, &proxy.url(), provider);
+
+    let mut call = ureq::post(&request.url).set("authorization", "Bearer browser-key");
+    for (name, value) in &request.headers {
+        call = call.set(name, value);
+    }
+    if let Some(user) = user {
+        call = call.set("x-forwarded-user", user);
+    }
+    let response = match call.send_string(&request.body.to_string()) {
+        Ok(response) => response,
+        Err(ureq::Error::Status(_, response)) => response,
+        Err(e) => panic!("request failed: {}", e),
+    };
+
+    let status = response.status();
+    let headers = response.headers_names().into_iter()
+        .map(|name| {
+            let value = response.header(&name).unwrap_or_default().to_string();
+            (name, value)
+        })
+        .collect();
+    let mut body = Vec::new();
+    response.into_reader().read_to_end(&mut body).unwrap();
+    (status, headers, body)
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+}
+
+#[test]
+fn sends_to_the_proxy_without_credentials() {
+    let settings = ProviderSettings { api_key: "sk-ant-secret".to_string(), ..ProviderSettings::default() };
+    let request = CLAUDE.build_request(&settings, &[ChatMessage::user("Topology")], true);
+    let body = request.body.clone();
+
+    let request = proxied(request, "/api/llm/", "claude");
+    assert_eq!(request.url, "/api/llm/claude");
+    assert_eq!(request.body, body);
+    assert!(request.headers.iter().all(|(_, value)| !value.contains("secret")));
+    assert!(request.headers.iter().any(|(name, _)| name == "anthropic-version"));
+}
+
+#[test]
+fn adds_the_server_key_upstream() {
+    let config = ProxyConfig::from_json(r#"{ "providers": {
+        "claude": { "api_key": "sk-ant-server" },
+        "custom": { "base_url": "http://127.0.0.1:11434/v1", "url": "http://gateway.internal/chat" } } }"#).unwrap();
+
+    let claude = config.upstream("claude").unwrap();
+    assert_eq!(claude.url, "https://api.anthropic.com/v1/messages");
+    assert!(claude.headers.contains(&("x-api-key".to_string(), "sk-ant-server".to_string())));
+    // A keyless local server gets no credential header at all
+    let custom = config.upstream("custom").unwrap();
+    assert_eq!(custom.url, "http://gateway.internal/chat");
+    assert!(custom.headers.iter().all(|(name, _)| name != "authorization"));
+    assert_eq!(config.upstream("mistral"), None);
+
+    assert!(ProxyConfig::from_json(r#"{ "providers": { "gpt-9": {} } }"#).unwrap_err().contains("gpt-9"));
+}
+
+#[test]
+fn limits_each_user_within_a_sliding_window() {
+    let mut limiter = RateLimiter::new(RateLimit { requests: 2, window_secs: 10 });
+    let start = Instant::now();
+
+    assert_eq!(limiter.check("ada", start), Ok(()));
+    assert_eq!(limiter.check("ada", start + Duration::from_secs(4)), Ok(()));
+    assert_eq!(limiter.check("ada", start + Duration::from_secs(6)), Err(Duration::from_secs(4)));
+    // Other users have their own allowance
+    assert_eq!(limiter.check("grace", start + Duration::from_secs(6)), Ok(()));
+    // The first request leaves the window
+    assert_eq!(limiter.check("ada", start + Duration::from_secs(10)), Ok(()));
+    assert_eq!(limiter.check("ada", start + Duration::from_secs(11)), Err(Duration::from_secs(3)));
+}
+
+#[test]
+fn forwards_with_the_server_key() {
+    let upstream = MockLlmServer::start_with_key("127.0.0.1:0", Some(SERVER_KEY)).unwrap();
+    let proxy = start(&upstream, 10);
+
+    let (status, headers, body) = send(&proxy, "custom", Some("ada"), false);
+    assert_eq!(status, 200);
+    assert_eq!(header(&headers, "content-type"), Some("application/json"));
+    let reply = CUSTOM.parse_response(&serde_json::from_slice(&body).unwrap()).unwrap();
+    assert!(reply.contains("\\title{Knot theory}"));
+
+    let requests = upstream.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].path, "/v1/chat/completions");
+    assert_eq!(requests[0].authorization.as_deref(), Some("Bearer server-held-key"));
+    assert_eq!(requests[0].body["model"], "llama3.1");
+}
+
+#[test]
+fn relays_streamed_replies() {
+    let upstream = MockLlmServer::start_with_key("127.0.0.1:0", Some(SERVER_KEY)).unwrap();
+    let proxy = start(&upstream, 10);
+
+    let (status, headers, body) = send(&proxy, "custom", Some("ada"), true);
+    assert_eq!(status, 200);
+    assert_eq!(header(&headers, "content-type"), Some("text/event-stream"));
+
+    let mut parser = SseParser::new();
+    let mut events = parser.feed(&body);
+    events.extend(parser.finish());
+    let mut reply = String::new();
+    let mut done = false;
+    for event in &events {
+        match decode_event(CUSTOM.stream_format(), event) {
+            StreamUpdate::Text(text) => reply.push_str(&text),
+            StreamUpdate::Done => done = true,
+            StreamUpdate::Error(e) => panic!("stream error: {}", e),
+            StreamUpdate::Ignore => {},
+        }
+    }
+    assert!(done);
+    assert!(reply.contains("\\title{Knot theory}"));
+}
+
+#[test]
+fn refuses_over_the_limit_unknown_and_anonymous() {
+    let upstream = MockLlmServer::start_with_key("127.0.0.1:0", Some(SERVER_KEY)).unwrap();
+    let proxy = start(&upstream, 2);
+
+    assert_eq!(send(&proxy, "custom", Some("ada"), false).0, 200);
+    assert_eq!(send(&proxy, "custom", Some("ada"), false).0, 200);
+    let (status, headers, _) = send(&proxy, "custom", Some("ada"), false);
+    assert_eq!(status, 429);
+    let retry_after: u64 = header(&headers, "retry-after").unwrap().parse().unwrap();
+    assert!((1..=60).contains(&retry_after));
+    assert_eq!(send(&proxy, "custom", Some("grace"), false).0, 200);
+
+    assert_eq!(send(&proxy, "claude", Some("grace"), false).0, 404);
+    assert_eq!(send(&proxy, "custom", None, false).0, 401);
+    assert_eq!(upstream.requests().len(), 3);
+}
@@ -0,0 +1,91 @@
+// Document outline built from the parsed structure
+
+use wasm_pdf_generator::outline::{outline, EntryKind, OutlineEntry};
+
+const BOOK: &str = r"\documentclass{book}
+\usepackage{amsmath}
+\begin{document}
+\part{Foundations}
+\chapter{Introduction}
+\section{Motivation}
+\begin{figure}
+  \centering
+  \caption{Growth of   the field}
+  \label{fig:growth}
+\end{figure}
+\section{Outline}
+\chapter{Theory}
+\begin{equation}
+  E = mc^2
+  \label{eq:energy}
+\end{equation}
+\subsection*{Aside}
+\begin{align*}
+  a &= b + c \\ d &= e
+\end{align*}
+\begin{table}
+  \begin{equation} x \end{equation}
+\end{table}
+\end{document}
+";
+
+fn titles(entries: &[OutlineEntry]) -> Vec<&str> {
+    entries.iter().map(|entry| entry.title.as_str()).collect()
+}
+
+#[test]
+fn nests_sections_by_level() {
+    let outline = outline(BOOK);
+
+    assert_eq!(titles(&outline.entries), ["Foundations"]);
+    let part = &outline.entries[0];
+    assert_eq!(part.kind, EntryKind::Section(0));
+    assert_eq!(part.line, 4);
+    assert_eq!(titles(&part.children), ["Introduction", "Theory"]);
+    assert_eq!(titles(&part.children[0].children), ["Motivation", "Outline"]);
+}
+
+#[test]
+fn lists_floats_and_equations_under_their_section() {
+    let outline = outline(BOOK);
+    let chapters = &outline.entries[0].children;
+
+    let motivation = &chapters[0].children[0];
+    assert_eq!(motivation.children.len(), 1);
+    assert_eq!(motivation.children[0].kind, EntryKind::Figure);
+    assert_eq!(motivation.children[0].title, "Growth of the field");
+    assert_eq!(motivation.children[0].line, 7);
+
+    let theory = &chapters[1];
+    assert_eq!(theory.children[0].kind, EntryKind::Equation);
+    assert_eq!(theory.children[0].title, "eq:energy");
+
+    let aside = &theory.children[1];
+    assert_eq!(aside.kind, EntryKind::Section(3));
+    let kinds: Vec<EntryKind> = aside.children.iter().map(|entry| entry.kind).collect();
+    // The equation inside the table is not listed separately
+    assert_eq!(kinds, [EntryKind::Equation, EntryKind::Table]);
+    assert_eq!(aside.children[0].title, "a &= b + c \\\\ d &= e");
+    assert_eq!(aside.children[1].title, "");
+
+    assert_eq!(outline.iter().count(), 10);
+}
+
+#[test]
+fn estimates_pages_from_position_in_body() {
+    let latex = "\\documentclass{article}\n\\begin{document}\n\\section{A}\naaaa\n\\section{B}\nbbbb\n\\end{document}\n";
+    let outline = outline(latex);
+    let offsets: Vec<usize> = outline.iter().map(|entry| entry.offset).collect();
+
+    assert_eq!(outline.page(offsets[0], 4), 1);
+    assert_eq!(outline.page(offsets[1], 4), 3);
+    assert_eq!(outline.page(latex.len(), 4), 4);
+    assert_eq!(outline.page(0, 4), 1);
+    assert_eq!(outline.page(offsets[1], 0), 1);
+}
+
+#[test]
+fn empty_without_structure() {
+    assert!(outline("\\documentclass{article}\n\\begin{document}\nJust text.\n\\end{document}\n").is_empty());
+    assert!(outline("").is_empty());
+}
@@ -0,0 +1,88 @@
+// Parsing and applying the edits the model returns in edit mode
+
+use wasm_pdf_generator::patch::{apply_reply, apply_search_replace, parse_patch, Hunk, HunkLine, Patch, PatchError, SearchReplace};
+
+const DOCUMENT: &str = "\\documentclass{article}\n\\begin{document}\n\\section{Intro}\nHello.\n\\section{Method}\nHello.\n\\end{document}\n";
+
+#[test]
+fn replaces_a_unique_search_text() {
+    let reply = "Renamed the section.\n\n<<<<<<< SEARCH\n\\section{Intro}\n=======\n\\section{Introduction}\n>>>>>>> REPLACE\n";
+
+    assert_eq!(parse_patch(reply), Ok(Patch::SearchReplace(vec![SearchReplace {
+        search: "\\section{Intro}".to_string(),
+        replace: "\\section{Introduction}".to_string(),
+    }])));
+    assert_eq!(apply_reply(DOCUMENT, reply).unwrap(), DOCUMENT.replace("{Intro}", "{Introduction}"));
+}
+
+#[test]
+fn applies_nothing_when_a_search_text_matches_twice() {
+    let blocks = [
+        SearchReplace { search: "\\section{Intro}".to_string(), replace: "\\section{Introduction}".to_string() },
+        SearchReplace { search: "Hello.".to_string(), replace: "Hi.".to_string() },
+    ];
+
+    assert_eq!(apply_search_replace(DOCUMENT, &blocks), Err(PatchError::Conflict {
+        edit: 2,
+        message: "search text matches 2 places: Hello.".to_string(),
+    }));
+}
+
+#[test]
+fn reports_a_missing_search_text() {
+    let blocks = [SearchReplace { search: "\\section{Results}".to_string(), replace: String::new() }];
+
+    assert_eq!(apply_search_replace(DOCUMENT, &blocks), Err(PatchError::Conflict {
+        edit: 1,
+        message: "search text not found: \\section{Results}".to_string(),
+    }));
+}
+
+#[test]
+fn matches_lines_ignoring_trailing_whitespace() {
+    let latex = "\\title{Draft}   \n\\author{Me}\t\n\\date{}\n";
+    let blocks = [SearchReplace { search: "\\title{Draft}\n\\author{Me}".to_string(), replace: "\\title{Final}\n\\author{Me}".to_string() }];
+
+    assert_eq!(apply_search_replace(latex, &blocks).unwrap(), "\\title{Final}\n\\author{Me}\n\\date{}\n");
+}
+
+#[test]
+fn places_a_shifted_hunk_at_the_nearest_match() {
+    let latex = "a\nx\ny\nb\nx\ny\nc\n";
+    // The header is off by a few lines; the second "x, y" is closer to it
+    let reply = "@@ -7,2 +7,2 @@\n x\n-y\n+z\n";
+
+    assert_eq!(parse_patch(reply), Ok(Patch::Unified(vec![Hunk {
+        old_start: 7,
+        lines: vec![HunkLine::Context("x".to_string()), HunkLine::Delete("y".to_string()), HunkLine::Insert("z".to_string())],
+    }])));
+    assert_eq!(apply_reply(latex, reply).unwrap(), "a\nx\ny\nb\nx\nz\nc\n");
+
+    let reply = "@@ -1,2 +1,2 @@\n x\n-y\n+z\n";
+    assert_eq!(apply_reply(latex, reply).unwrap(), "a\nx\nz\nb\nx\ny\nc\n");
+}
+
+#[test]
+fn inserts_a_hunk_with_no_old_lines() {
+    let reply = "--- a/main.tex\n+++ b/main.tex\n@@ -0,0 +1,2 @@\n+% !TEX program = pdflatex\n+% Generated draft\n";
+
+    assert_eq!(
+        apply_reply(DOCUMENT, reply).unwrap(),
+        format!("% !TEX program = pdflatex\n% Generated draft\n{}", DOCUMENT),
+    );
+}
+
+#[test]
+fn rejects_a_truncated_hunk() {
+    let reply = "@@ -3,3 +3,3 @@\n \\section{Intro}\n-Hello.\n";
+
+    assert_eq!(parse_patch(reply), Err(PatchError::Malformed("hunk at line 3 is truncated".to_string())));
+}
+
+#[test]
+fn reports_a_reply_without_edits() {
+    let reply = "Here is the whole document again:\n\n\\documentclass{article}\n\\begin{document}\n\\end{document}\n";
+
+    assert_eq!(parse_patch(reply), Err(PatchError::NotAPatch));
+    assert_eq!(apply_reply(DOCUMENT, reply), Err(PatchError::NotAPatch));
+}
@@ -0,0 +1,106 @@
+// Request bodies and replies of the built-in providers
+
+use serde_json::json;
+use wasm_pdf_generator::chat::{ChatMessage, SYSTEM_PROMPT};
+use wasm_pdf_generator::providers::{find, LlmProvider, ProviderSettings, CLAUDE, MISTRAL, PERPLEXITY};
+
+fn messages() -> Vec<ChatMessage> {
+    vec![
+        ChatMessage::assistant("[Revision 0]"),
+        ChatMessage::user("Write about knots"),
+        ChatMessage::assistant("[Revision 1]"),
+        ChatMessage::user("main.tex:3: Undefined control sequence."),
+        ChatMessage::user("Fix it"),
+    ]
+}
+
+#[test]
+fn builds_claude_messages_requests() {
+    let settings = ProviderSettings {
+        api_key: "sk-ant-test".to_string(),
+        max_tokens: Some(8000),
+        top_p: Some(0.9),
+        ..ProviderSettings::default()
+    };
+    let request = CLAUDE.build_request(&settings, &messages(), true);
+
+    assert_eq!(request.url, "https://api.anthropic.com/v1/messages");
+    assert_eq!(request.headers, [
+        ("x-api-key".to_string(), "sk-ant-test".to_string()),
+        ("anthropic-version".to_string(), "2023-06-01".to_string()),
+        ("content-type".to_string(), "application/json".to_string()),
+    ]);
+    // The system prompt has its own field and turns alternate from the user
+    assert_eq!(request.body, json!({
+        "model": "claude-sonnet-4-5",
+        "max_tokens": 8000,
+        "system": SYSTEM_PROMPT,
+        "messages": [
+            {"role": "user", "content": "Write about knots"},
+            {"role": "assistant", "content": "[Revision 1]"},
+            {"role": "user", "content": "main.tex:3: Undefined control sequence.\n\nFix it"},
+        ],
+        "stream": true,
+        "top_p": 0.9,
+    }));
+}
+
+#[test]
+fn builds_openai_style_requests() {
+    let settings = ProviderSettings {
+        api_key: "pplx-test".to_string(),
+        max_tokens: Some(2000),
+        system_prompt: "Answer in LaTeX only.".to_string(),
+        ..ProviderSettings::default()
+    };
+    let request = PERPLEXITY.build_request(&settings, &messages(), false);
+
+    assert_eq!(request.url, "https://api.perplexity.ai/chat/completions");
+    assert_eq!(request.headers, [
+        ("authorization".to_string(), "Bearer pplx-test".to_string()),
+        ("content-type".to_string(), "application/json".to_string()),
+    ]);
+    // The system prompt leads the messages
+    assert_eq!(request.body, json!({
+        "model": "sonar",
+        "messages": [
+            {"role": "system", "content": "Answer in LaTeX only."},
+            {"role": "user", "content": "Write about knots"},
+            {"role": "assistant", "content": "[Revision 1]"},
+            {"role": "user", "content": "main.tex:3: Undefined control sequence.\n\nFix it"},
+        ],
+        "stream": false,
+        "max_tokens": 2000,
+    }));
+
+    let request = MISTRAL.build_request(&settings, &messages(), true);
+    assert_eq!(request.url, "https://api.mistral.ai/v1/chat/completions");
+    assert_eq!(request.body["temperature"], 0.7);
+    assert_eq!(request.body["stream"], true);
+}
+
+#[test]
+fn parses_whole_replies() {
+    let claude = json!({
+        "id": "msg_01",
+        "type": "message",
+        "role": "assistant",
+        "content": [{"type": "text", "text": "\\documentclass{article}"}],
+        "stop_reason": "end_turn",
+    });
+    assert_eq!(CLAUDE.parse_response(&claude), Ok("\\documentclass{article}".to_string()));
+
+    let openai = json!({
+        "id": "cmpl-1",
+        "object": "chat.completion",
+        "choices": [{"index": 0, "message": {"role": "assistant", "content": "\\documentclass{report}"}, "finish_reason": "stop"}],
+    });
+    assert_eq!(MISTRAL.parse_response(&openai), Ok("\\documentclass{report}".to_string()));
+
+    // Each reads only its own shape, and error bodies are not replies
+    assert!(CLAUDE.parse_response(&openai).is_err());
+    assert!(PERPLEXITY.parse_response(&claude).is_err());
+    let error = json!({"type": "error", "error": {"type": "invalid_request_error", "message": "max_tokens: too large"}});
+    assert_eq!(CLAUDE.parse_response(&error), Err("Invalid response format from Claude API".to_string()));
+    assert_eq!(find("Perplexity").unwrap().parse_response(&json!({"choices": []})), Err("Invalid response format from API".to_string()));
+}
@@ -0,0 +1,91 @@
+// Rewriting one section of a document
+
+use wasm_pdf_generator::chat::{section_prompt, section_request};
+use wasm_pdf_generator::doctree::parse;
+use wasm_pdf_generator::extract::{extract_section, ExtractError};
+use wasm_pdf_generator::rewrite::{document_summary, find_section, splice};
+
+const REPORT: &str = r"\documentclass{report}
+\usepackage{amsmath}
+\title{Sorting Networks}
+\begin{document}
+\maketitle
+\chapter{Introduction}
+Networks are introduced here, see Section~\ref{sec:methods}.
+\section{Methods}
+\label{sec:methods}
+We use Batcher's construction.
+\subsection{Depth}
+Depth is logarithmic squared.
+\section{Results}
+\label{sec:results}
+Results follow.
+\end{document}
+";
+
+#[test]
+fn finds_section_by_index_or_title() {
+    let tree = parse(REPORT);
+
+    let (index, section) = find_section(&tree, 1, "Methods").unwrap();
+    assert_eq!((index, section.command.as_str()), (1, "section"));
+    assert!(REPORT[section.range.clone()].starts_with("\\section{Methods}"));
+    assert!(REPORT[section.range.clone()].ends_with("squared.\n"));
+
+    // The sections moved since the one to rewrite was picked
+    assert_eq!(find_section(&tree, 3, "Results").map(|(index, _)| index), Some(3));
+    assert_eq!(find_section(&tree, 0, "Results").map(|(index, _)| index), Some(3));
+    assert!(find_section(&tree, 1, "Discussion").is_none());
+}
+
+#[test]
+fn summarises_rest_of_document() {
+    let summary = document_summary(&parse(REPORT), 1);
+
+    assert_eq!(summary, "Document class: report\nPackages: amsmath\nTitle: Sorting Networks\nStructure:\n  \\chapter{Introduction}\n    \\section{Methods}  <- the section to rewrite\n      \\subsection{Depth}\n    \\section{Results}\nLabels defined elsewhere: sec:results\n");
+
+    let prompt = section_prompt(&summary, "\\section{Methods}\nOld.\n", "make it more rigorous");
+    assert!(prompt.contains("<- the section to rewrite"));
+    assert!(prompt.contains("```latex\n\\section{Methods}\nOld.\n```"));
+    assert!(prompt.contains("Rewrite it as follows: make it more rigorous"));
+    assert_eq!(section_request("Methods", "shorter"), "Rewrite only the section \"Methods\": shorter");
+}
+
+#[test]
+fn splices_section_leaving_the_rest_untouched() {
+    let tree = parse(REPORT);
+    let (_, section) = find_section(&tree, 1, "Methods").unwrap();
+    let revised = splice(REPORT, section.range.clone(), "\\section{Methods}\n\\label{sec:methods}\nNew text.");
+
+    let before = &REPORT[..section.range.start];
+    let after = &REPORT[section.range.end..];
+    assert_eq!(revised, format!("{}\\section{{Methods}}\n\\label{{sec:methods}}\nNew text.\n{}", before, after));
+}
+
+#[test]
+fn extracts_section_from_fenced_reply() {
+    let reply = "Here is the rewritten section:\n\n```latex\n\\section{Methods}\n\\label{sec:methods}\nBetter text.\n\\subsection{Depth}\nStill logarithmic.\n```\n\nLet me know if you need more.";
+    assert_eq!(
+        extract_section(reply, 2, "Methods").unwrap(),
+        "\\section{Methods}\n\\label{sec:methods}\nBetter text.\n\\subsection{Depth}\nStill logarithmic.\n"
+    );
+}
+
+#[test]
+fn extracts_section_from_whole_document_reply() {
+    // The model sent the whole document back, or ran on into the next section
+    let section = extract_section(&format!("```latex\n{}```", REPORT), 2, "Methods").unwrap();
+    assert_eq!(section, "\\section{Methods}\n\\label{sec:methods}\nWe use Batcher's construction.\n\\subsection{Depth}\nDepth is logarithmic squared.\n");
+    let section = extract_section(&format!("```latex\n{}```", REPORT), 2, "Results").unwrap();
+    assert_eq!(section, "\\section{Results}\n\\label{sec:results}\nResults follow.\n");
+
+    let reply = "\\section{Results}\nOnly results.\n\\end{document}\n";
+    assert_eq!(extract_section(reply, 2, "Findings").unwrap(), "\\section{Results}\nOnly results.\n");
+}
+
+#[test]
+fn rejects_replies_without_the_section() {
+    assert_eq!(extract_section("  ", 2, "Depth"), Err(ExtractError::Empty));
+    assert_eq!(extract_section("I can't help with that.", 2, "Depth"), Err(ExtractError::NoLatex));
+    assert_eq!(extract_section("```latex\n\\subsection{Depth}\nText.\n```", 2, "Depth"), Err(ExtractError::NoSection));
+}
@@ -0,0 +1,95 @@
+// Server-sent event streams from the AI providers
+
+use wasm_pdf_generator::streaming::{decode_event, SseEvent, SseParser, StreamFormat, StreamUpdate};
+
+// Feed `chunks` through a parser and decode every event
+fn decode(format: StreamFormat, chunks: &[&[u8]]) -> Vec<StreamUpdate> {
+    let mut parser = SseParser::new();
+    let mut events: Vec<SseEvent> = chunks.iter().flat_map(|chunk| parser.feed(chunk)).collect();
+    events.extend(parser.finish());
+    events.iter().map(|event| decode_event(format, event)).collect()
+}
+
+const CLAUDE_STREAM: &str = "\
+event: message_start
+data: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_01\",\"type\":\"message\",\"role\":\"assistant\",\"content\":[],\"model\":\"claude-3-5-sonnet-20241022\"}}
+
+event: content_block_start
+data: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}
+
+event: ping
+data: {\"type\": \"ping\"}
+
+event: content_block_delta
+data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"\\\\documentclass\"}}
+
+event: content_block_delta
+data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"{article}\"}}
+
+event: content_block_stop
+data: {\"type\":\"content_block_stop\",\"index\":0}
+
+event: message_delta
+data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\"},\"usage\":{\"output_tokens\":6}}
+
+event: message_stop
+data: {\"type\":\"message_stop\"}
+
+";
+
+#[test]
+fn decodes_claude_events() {
+    let updates = decode(StreamFormat::Anthropic, &[CLAUDE_STREAM.as_bytes()]);
+
+    assert_eq!(updates, [
+        StreamUpdate::Ignore,
+        StreamUpdate::Ignore,
+        StreamUpdate::Ignore,
+        StreamUpdate::Text("\\documentclass".to_string()),
+        StreamUpdate::Text("{article}".to_string()),
+        StreamUpdate::Ignore,
+        StreamUpdate::Ignore,
+        StreamUpdate::Done,
+    ]);
+}
+
+#[test]
+fn reports_claude_error_events() {
+    let stream = "event: error\ndata: {\"type\":\"error\",\"error\":{\"type\":\"overloaded_error\",\"message\":\"Overloaded\"}}\n\n";
+
+    assert_eq!(decode(StreamFormat::Anthropic, &[stream.as_bytes()]), [StreamUpdate::Error("Overloaded".to_string())]);
+}
+
+#[test]
+fn joins_lines_split_across_chunks() {
+    // Every split point, including inside "data:" and between "\r" and "\n"
+    let stream = "data: {\"choices\":[{\"delta\":{\"content\":\"Hello\"}}]}\r\n\r\ndata: [DONE]\r\n\r\n";
+    for split in 1..stream.len() {
+        let (first, second) = stream.as_bytes().split_at(split);
+        assert_eq!(
+            decode(StreamFormat::OpenAi, &[first, second]),
+            [StreamUpdate::Text("Hello".to_string()), StreamUpdate::Done],
+            "split at byte {}",
+            split,
+        );
+    }
+
+    // A last event without its blank line still counts
+    assert_eq!(decode(StreamFormat::OpenAi, &[b"data: [DO", b"NE]"]), [StreamUpdate::Done]);
+}
+
+#[test]
+fn keeps_characters_split_across_chunks() {
+    let stream = "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"delta\":{\"type\":\"text_delta\",\"text\":\"Schrödinger — ∂ψ 😀\"}}\n\n";
+    let bytes = stream.as_bytes();
+    // One-byte chunks in the middle cut every multi-byte character
+    for split in 1..bytes.len() - 1 {
+        let chunks = [&bytes[..split], &bytes[split..split + 1], &bytes[split + 1..]];
+        assert_eq!(
+            decode(StreamFormat::Anthropic, &chunks),
+            [StreamUpdate::Text("Schrödinger — ∂ψ 😀".to_string())],
+            "split at byte {}",
+            split,
+        );
+    }
+}
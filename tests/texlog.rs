@@ -0,0 +1,209 @@
+// Diagnostics read from pdfTeX logs
+
+use wasm_pdf_generator::texlog::{page_count, parse_log, DiagnosticKind, Severity};
+
+const PREAMBLE: &str = "\
+This is pdfTeX, Version 3.141592653-2.6-1.40.25 (TeX Live 2023) (preloaded format=pdflatex)
+ restricted \\write18 enabled.
+entering extended mode
+(./main.tex
+LaTeX2e <2022-11-01> patch level 1
+L3 programming layer <2023-02-22>
+(/usr/share/texlive/texmf-dist/tex/latex/base/article.cls
+Document Class: article 2022/07/02 v1.4n Standard LaTeX document class
+(/usr/share/texlive/texmf-dist/tex/latex/base/size10.clo))
+(./main.aux)
+";
+
+#[test]
+fn reads_errors_with_their_line_context() {
+    let log = format!("{}{}", PREAMBLE, "\
+! Undefined control sequence.
+l.12 Some text \\foo
+                   bar.
+The control sequence at the end of the top line
+of your error message was never \\def'ed. If you have
+misspelled it (e.g., `\\hobx'), type `I' and the correct
+spelling (e.g., `I\\hbox'). Otherwise just continue,
+and I'll forget about whatever was undefined.
+
+[1{/usr/share/texlive/texmf-dist/fonts/map/pdftex/updmap/pdftex.map}] (./main.aux) )
+Output written on main.pdf (1 page, 21874 bytes).
+");
+    let diagnostics = parse_log(&log);
+
+    assert_eq!(diagnostics.len(), 1);
+    let error = &diagnostics[0];
+    assert_eq!(error.severity, Severity::Error);
+    assert_eq!(error.kind, DiagnosticKind::Error);
+    assert_eq!(error.message, "Undefined control sequence.");
+    assert_eq!(error.location().as_deref(), Some("main.tex:12"));
+    assert!(error.is_in_main_file());
+    // TeX's two context lines stay apart, the second under the error point
+    assert_eq!(error.context.as_deref(), Some("Some text \\foo\n              bar."));
+    assert_eq!(page_count(&log), Some(1));
+}
+
+#[test]
+fn reads_file_line_errors() {
+    let log = format!("{}{}", PREAMBLE, "\
+./main.tex:7: Undefined control sequence.
+l.7 \\sectoin
+            {Introduction}
+./chapters/results.tex:31: Missing $ inserted.
+<inserted text>
+                $
+l.31 x^
+       2
+");
+    let diagnostics = parse_log(&log);
+
+    let locations: Vec<Option<String>> = diagnostics.iter().map(|d| d.location()).collect();
+    assert_eq!(locations, [Some("main.tex:7".to_string()), Some("chapters/results.tex:31".to_string())]);
+    assert_eq!(diagnostics[1].message, "Missing $ inserted.");
+    assert!(!diagnostics[1].is_in_main_file());
+    assert!(diagnostics.iter().all(|d| d.severity == Severity::Error));
+}
+
+#[test]
+fn reports_a_missing_package_at_its_usepackage_line() {
+    let log = "\
+(./main.tex
+LaTeX2e <2022-11-01> patch level 1
+(/usr/share/texlive/texmf-dist/tex/latex/base/article.cls
+Document Class: article 2022/07/02 v1.4n Standard LaTeX document class
+(/usr/share/texlive/texmf-dist/tex/latex/base/size10.clo))
+
+! LaTeX Error: File `tikzz.sty' not found.
+
+Type X to quit or <RETURN> to proceed,
+or enter new name. (Default extension: sty)
+
+Enter file name:
+! Emergency stop.
+<read *>
+
+l.3 \\usepackage{tikzz}^^M
+
+*** (cannot \\read from terminal in nonstop modes)
+";
+    let diagnostics = parse_log(log);
+
+    let missing = &diagnostics[0];
+    assert_eq!(missing.kind, DiagnosticKind::MissingPackage);
+    assert_eq!(missing.message, "LaTeX Error: File `tikzz.sty' not found.");
+    assert_eq!(missing.location().as_deref(), Some("main.tex:3"));
+    assert_eq!(missing.context.as_deref(), Some("\\usepackage{tikzz}^^M"));
+    assert_eq!(diagnostics[1].message, "Emergency stop.");
+    assert_eq!(page_count(log), None);
+}
+
+#[test]
+fn reads_undefined_references_and_citations() {
+    let log = format!("{}{}", PREAMBLE, "\n\
+LaTeX Warning: Citation `knuth84' on page 1 undefined on input line 17.
+
+[1{/usr/share/texlive/texmf-dist/fonts/map/pdftex/updmap/pdftex.map}]
+
+LaTeX Warning: Reference `fig:results' on page 2 undefined on input line 42.
+
+[2] (./main.aux)
+
+LaTeX Warning: There were undefined references.
+
+ )
+");
+    let diagnostics = parse_log(&log);
+
+    let kinds: Vec<(DiagnosticKind, Option<usize>)> = diagnostics.iter().map(|d| (d.kind, d.line)).collect();
+    assert_eq!(kinds, [
+        (DiagnosticKind::UndefinedCitation, Some(17)),
+        (DiagnosticKind::UndefinedReference, Some(42)),
+        (DiagnosticKind::UndefinedReference, None),
+    ]);
+    assert_eq!(diagnostics[1].message, "Reference `fig:results' on page 2 undefined on input line 42.");
+    assert!(diagnostics.iter().all(|d| d.severity == Severity::Warning && d.file.as_deref() == Some("./main.tex")));
+}
+
+#[test]
+fn rejoins_lines_wrapped_at_79_columns() {
+    let log = format!("{}{}", PREAMBLE, "\n\
+LaTeX Warning: Reference `sec:evaluation-of-the-proposed-method' on page 3 unde
+fined on input line 88.
+
+[3] (./main.aux) )
+Output written on /home/writer/projects/quarterly-report/build/main.pdf (12 pag
+es, 183764 bytes).
+");
+    let diagnostics = parse_log(&log);
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].message, "Reference `sec:evaluation-of-the-proposed-method' on page 3 undefined on input line 88.");
+    assert_eq!(diagnostics[0].line, Some(88));
+    assert_eq!(page_count(&log), Some(12));
+}
+
+#[test]
+fn reads_overfull_and_underfull_boxes() {
+    let log = format!("{}{}", PREAMBLE, "\
+Overfull \\hbox (15.46pt too wide) in paragraph at lines 20--21
+[]\\OT1/cmr/m/n/10 See https://example.org/a/very/long/unbreakable/address/for/the/data|
+ []
+
+Underfull \\hbox (badness 10000) in paragraph at lines 30--31
+[]\\OT1/cmr/m/n/10 Short
+
+[1{/usr/share/texlive/texmf-dist/fonts/map/pdftex/updmap/pdftex.map}]
+Underfull \\vbox (badness 10000) has occurred while \\output is active
+[2] (./main.aux) )
+");
+    let diagnostics = parse_log(&log);
+
+    assert_eq!(diagnostics.len(), 3);
+    let overfull = &diagnostics[0];
+    assert_eq!((overfull.kind, overfull.severity, overfull.line), (DiagnosticKind::OverfullBox, Severity::Warning, Some(20)));
+    assert_eq!(overfull.message, "Overfull \\hbox (15.46pt too wide) in paragraph at lines 20--21");
+    assert_eq!(overfull.context.as_deref(), Some("[]\\OT1/cmr/m/n/10 See https://example.org/a/very/long/unbreakable/address/for/the/data|"));
+
+    let underfull = &diagnostics[1];
+    assert_eq!((underfull.kind, underfull.severity, underfull.line), (DiagnosticKind::UnderfullBox, Severity::Info, Some(30)));
+    assert_eq!(underfull.context.as_deref(), Some("[]\\OT1/cmr/m/n/10 Short"));
+
+    // Nothing to show after a box built by the output routine
+    let output = &diagnostics[2];
+    assert_eq!((output.kind, output.line, output.context.as_deref()), (DiagnosticKind::UnderfullBox, None, None));
+}
+
+#[test]
+fn removes_duplicates_and_puts_errors_first() {
+    let log = format!("{}{}", PREAMBLE, "\
+Underfull \\hbox (badness 10000) in paragraph at lines 9--9
+
+LaTeX Warning: Reference `tab:totals' on page 1 undefined on input line 14.
+
+! Undefined control sequence.
+l.15 \\tabel
+           {tab:totals}
+
+LaTeX Warning: Reference `tab:totals' on page 1 undefined on input line 14.
+
+Package hyperref Warning: Token not allowed in a PDF string (Unicode):
+(hyperref)                removing `math shift' on input line 20.
+
+! Undefined control sequence.
+l.15 \\tabel
+           {tab:totals}
+
+[1] (./main.aux) )
+");
+    let diagnostics = parse_log(&log);
+
+    let summary: Vec<(Severity, &str)> = diagnostics.iter().map(|d| (d.severity, d.message.as_str())).collect();
+    assert_eq!(summary, [
+        (Severity::Error, "Undefined control sequence."),
+        (Severity::Warning, "Reference `tab:totals' on page 1 undefined on input line 14."),
+        (Severity::Warning, "hyperref: Token not allowed in a PDF string (Unicode): removing `math shift' on input line 20."),
+        (Severity::Info, "Underfull \\hbox (badness 10000) in paragraph at lines 9--9"),
+    ]);
+    assert_eq!(diagnostics[2].line, Some(20));
+}
@@ -0,0 +1,106 @@
+// Passphrase-protected API key vault
+
+use std::collections::BTreeMap;
+
+use wasm_pdf_generator::vault::{Vault, VaultError, MAX_ITERATIONS, MIN_ITERATIONS, VERSION};
+
+// The fewest a vault may use, to keep the tests quick
+const ITERATIONS: u32 = MIN_ITERATIONS;
+const SALT: [u8; 16] = [7; 16];
+const NONCE: [u8; 12] = [3; 12];
+
+fn keys() -> BTreeMap<String, String> {
+    BTreeMap::from([
+        ("claude".to_string(), "sk-ant-secret".to_string()),
+        ("mistral".to_string(), "mistral-secret".to_string()),
+    ])
+}
+
+#[test]
+fn opens_with_the_passphrase_it_was_sealed_with() {
+    let vault = Vault::seal(&keys(), "correct horse", SALT, NONCE, ITERATIONS).unwrap();
+
+    assert_eq!(vault.version, VERSION);
+    assert_eq!(vault.open("correct horse").unwrap(), keys());
+    // The stored form round-trips and does not contain the keys
+    let json = vault.to_json();
+    assert!(!json.contains("secret"));
+    assert_eq!(Vault::from_json(&json).unwrap().open("correct horse").unwrap(), keys());
+}
+
+#[test]
+fn rejects_wrong_or_empty_passphrases() {
+    let vault = Vault::seal(&keys(), "correct horse", SALT, NONCE, ITERATIONS).unwrap();
+
+    assert_eq!(vault.open("correct hors"), Err(VaultError::WrongPassphrase));
+    assert_eq!(vault.open(""), Err(VaultError::EmptyPassphrase));
+    assert_eq!(Vault::seal(&keys(), "", SALT, NONCE, ITERATIONS), Err(VaultError::EmptyPassphrase));
+}
+
+#[test]
+fn salt_and_nonce_change_the_ciphertext() {
+    let a = Vault::seal(&keys(), "pass", SALT, NONCE, ITERATIONS).unwrap();
+    let b = Vault::seal(&keys(), "pass", [8; 16], NONCE, ITERATIONS).unwrap();
+    let c = Vault::seal(&keys(), "pass", SALT, [4; 12], ITERATIONS).unwrap();
+
+    assert_ne!(a.to_json(), b.to_json());
+    assert_ne!(a.to_json(), c.to_json());
+    assert_eq!(b.open("pass").unwrap(), c.open("pass").unwrap());
+}
+
+#[test]
+fn detects_tampering() {
+    let vault = Vault::seal(&keys(), "pass", SALT, NONCE, ITERATIONS).unwrap();
+    let mut json: serde_json::Value = serde_json::from_str(&vault.to_json()).unwrap();
+
+    // More iterations derive a different key
+    json["iterations"] = (ITERATIONS + 1).into();
+    assert_eq!(Vault::from_json(&json.to_string()).unwrap().open("pass"), Err(VaultError::WrongPassphrase));
+    json["iterations"] = ITERATIONS.into();
+
+    let ciphertext = json["ciphertext"].as_str().unwrap().to_string();
+    let flipped = if ciphertext.starts_with('A') { "B" } else { "A" };
+    json["ciphertext"] = format!("{}{}", flipped, &ciphertext[1..]).into();
+    assert_eq!(Vault::from_json(&json.to_string()).unwrap().open("pass"), Err(VaultError::WrongPassphrase));
+}
+
+#[test]
+fn reports_damaged_or_newer_vaults() {
+    assert!(matches!(Vault::from_json("{\"version\":1}"), Err(VaultError::Malformed(_))));
+
+    let vault = Vault::seal(&keys(), "pass", SALT, NONCE, ITERATIONS).unwrap();
+    let mut json: serde_json::Value = serde_json::from_str(&vault.to_json()).unwrap();
+    json["nonce"] = "not base64!".into();
+    assert!(matches!(Vault::from_json(&json.to_string()).unwrap().open("pass"), Err(VaultError::Malformed(_))));
+
+    // Iteration counts outside the allowed range are refused before any
+    // key is derived, when opening and when sealing
+    for iterations in [0, 1_000, MIN_ITERATIONS - 1, MAX_ITERATIONS + 1, u32::MAX] {
+        let mut json: serde_json::Value = serde_json::from_str(&vault.to_json()).unwrap();
+        json["iterations"] = iterations.into();
+        assert_eq!(Vault::from_json(&json.to_string()).unwrap().open("pass"), Err(VaultError::BadIterations(iterations)));
+        assert_eq!(Vault::seal(&keys(), "pass", SALT, NONCE, iterations), Err(VaultError::BadIterations(iterations)));
+    }
+
+    json["version"] = 2.into();
+    assert_eq!(Vault::from_json(&json.to_string()).unwrap().open("pass"), Err(VaultError::UnsupportedVersion(2)));
+}
+
+#[test]
+fn keeps_saved_keys_when_saving_over_a_locked_vault() {
+    let vault = Vault::seal(&keys(), "pass", SALT, NONCE, ITERATIONS).unwrap();
+    // The key inputs of a locked vault are empty except for what was typed
+    let typed = [
+        ("claude".to_string(), String::new()),
+        ("mistral".to_string(), "mistral-new".to_string()),
+        ("perplexity".to_string(), "pplx-new".to_string()),
+    ];
+
+    assert_eq!(vault.merged_keys("pass", &typed).unwrap(), BTreeMap::from([
+        ("claude".to_string(), "sk-ant-secret".to_string()),
+        ("mistral".to_string(), "mistral-new".to_string()),
+        ("perplexity".to_string(), "pplx-new".to_string()),
+    ]));
+    // Another passphrase does not replace the vault and its keys
+    assert_eq!(vault.merged_keys("other", &typed), Err(VaultError::WrongPassphrase));
+}
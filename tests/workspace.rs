@@ -0,0 +1,125 @@
+// Workspace export and import
+
+use wasm_pdf_generator::chat;
+use wasm_pdf_generator::history::{Conversation, RevisionKind};
+use wasm_pdf_generator::workspace::{is_endpoint, is_setting, merge, Workspace, FORMAT, VERSION};
+
+fn conversation(id: &str, created: f64, latex: &str) -> Conversation {
+    let mut conversation = Conversation::new(id.to_string(), created, &format!("Topic {}", id), "Mistral", "Report", "Large");
+    conversation.set_turns(&[
+        (chat::USER.to_string(), conversation.topic.clone()),
+        (chat::AI.to_string(), latex.to_string()),
+    ], created);
+    conversation.add_revision(RevisionKind::Generation, latex, created);
+    conversation
+}
+
+fn items(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+}
+
+#[test]
+fn exports_conversations_and_settings_without_keys() {
+    let mut edited = conversation("a", 10.0, "\\section{One}");
+    edited.edit_latex("\\section{One, edited}", 20.0);
+    let workspace = Workspace::new(30.0, vec![edited.clone()], items(&[
+        ("theme", "dark"),
+        ("api_provider", "Mistral"),
+        ("mistral_model", "mistral-small-latest"),
+        ("custom_base_url", "http://localhost:11434/v1"),
+        ("mistral_api_key", "secret-key"),
+        ("api_key_vault", "{\"sealed\": \"secret\"}"),
+        ("other_app_token", "secret"),
+        ("chat_history_data", "[]"),
+    ]));
+
+    let json = workspace.to_json();
+    assert!(!json.contains("secret"));
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(value["format"], FORMAT);
+    assert_eq!(value["version"], VERSION);
+    assert_eq!(value["settings"].as_object().unwrap().len(), 4);
+
+    // Every revision comes back
+    let imported = Workspace::from_json(&json).unwrap();
+    assert_eq!(imported, workspace);
+    assert_eq!(imported.conversations[0].revisions.len(), 2);
+    assert_eq!(imported.conversations[0].revisions[1].kind, RevisionKind::Edit);
+}
+
+#[test]
+fn recognises_settings() {
+    assert!(is_setting("auto_fix_rounds"));
+    assert!(is_setting("claude_system_prompt"));
+    assert!(!is_setting("claude_api_key"));
+    assert!(!is_setting("claude_"));
+    assert!(!is_setting("gpt_model"));
+    assert!(!is_setting("api_key_vault"));
+}
+
+#[test]
+fn lists_the_endpoints_an_import_would_change() {
+    let workspace = Workspace::new(0.0, Vec::new(), items(&[
+        ("theme", "dark"),
+        ("compile_service_url", "https://compile.example.org"),
+        ("llm_proxy_url", "http://localhost:8788"),
+        ("custom_base_url", "https://llm.example.org/v1"),
+        ("custom_model", "llama3"),
+    ]));
+    let here = items(&[("llm_proxy_url", "http://localhost:8788"), ("custom_base_url", "http://localhost:11434/v1")]);
+    let current = |key: &str| here.iter().find(|(k, _)| k == key).map(|(_, value)| value.clone());
+
+    assert_eq!(workspace.changed_endpoints(current), [
+        ("compile_service_url", "https://compile.example.org"),
+        ("custom_base_url", "https://llm.example.org/v1"),
+    ]);
+
+    assert!(is_endpoint("claude_base_url"));
+    assert!(is_endpoint("compile_service_url"));
+    assert!(!is_endpoint("claude_model"));
+    assert!(!is_endpoint("theme"));
+}
+
+#[test]
+fn refuses_other_files() {
+    assert!(Workspace::from_json("not json").is_err());
+    assert!(Workspace::from_json(r#"{ "version": 1, "conversations": [] }"#).unwrap_err().contains("Not a LaDFX workspace"));
+    let newer = format!(r#"{{ "format": "{}", "version": {}, "exported": 0, "conversations": [] }}"#, FORMAT, VERSION + 1);
+    assert!(Workspace::from_json(&newer).unwrap_err().contains("newer"));
+
+    // Keys put in the file by hand are left out
+    let json = format!(
+        r#"{{ "format": "{}", "version": 1, "exported": 0, "conversations": [],
+             "settings": {{ "theme": "light", "claude_api_key": "sk-ant" }} }}"#,
+        FORMAT,
+    );
+    let workspace = Workspace::from_json(&json).unwrap();
+    assert_eq!(workspace.settings.keys().collect::<Vec<_>>(), ["theme"]);
+}
+
+#[test]
+fn merges_by_id_keeping_the_newer_copy() {
+    let mut history = vec![
+        conversation("a", 10.0, "a here"),
+        conversation("b", 20.0, "b here"),
+    ];
+    let mut newer_b = conversation("b", 20.0, "b here");
+    newer_b.add_revision(RevisionKind::Fix, "b fixed elsewhere", 40.0);
+    let mut older_a = conversation("a", 10.0, "a here");
+    older_a.rename("Stale name", 5.0);
+    let imported = vec![conversation("c", 5.0, "c"), newer_b.clone(), older_a];
+
+    let merged = merge(&mut history, imported);
+    assert_eq!(merged.added, ["c"]);
+    assert_eq!(merged.updated, ["b"]);
+    assert_eq!(merged.unchanged, 1);
+
+    let ids: Vec<&str> = history.iter().map(|c| c.id.as_str()).collect();
+    assert_eq!(ids, ["c", "a", "b"]);
+    assert_eq!(history[1].title(), "Topic a");
+    assert_eq!(history[2], newer_b);
+
+    // Importing the same file again changes nothing
+    let again = merge(&mut history, vec![newer_b]);
+    assert_eq!((again.added.len(), again.updated.len(), again.unchanged), (0, 0, 1));
+}